@@ -4,6 +4,7 @@ use crate::database::{
     attributes::{Attribute, Attributes},
     connection::Connection as ConnectionInterface,
     error::Error,
+    events::ChangeBuffer,
 };
 use base64::{Engine as _, engine::general_purpose::STANDARD as b64};
 use log::{debug, error};
@@ -16,11 +17,13 @@ use rusqlite::{
 use std::cell::Cell;
 use std::fmt::Display;
 
-/// A pooled SQLite connection together with the current depth of transaction nesting open on it.
-/// Owns the pooled handle and returns it to the pool when dropped.
+/// A pooled SQLite connection together with the current depth of transaction nesting open on it,
+/// and the change events published under that nesting. Owns the pooled handle and returns it to the
+/// pool when dropped.
 pub struct Connection {
     handle: PooledConnection<SqliteConnectionManager>,
     depth: Cell<usize>,
+    changes: ChangeBuffer,
 }
 
 impl Connection {
@@ -28,6 +31,7 @@ impl Connection {
         Self {
             handle,
             depth: Cell::new(0),
+            changes: ChangeBuffer::default(),
         }
     }
 
@@ -46,10 +50,12 @@ impl Connection {
         };
         self.execute_batch(&sql)?;
         self.depth.update(|level| level + 1);
+        self.changes.begin();
         Ok(())
     }
 
     /// Closes the current transaction level successfully: `COMMIT` at the outermost, `RELEASE` below.
+    /// The outermost commit delivers the events published under it.
     fn commit_transaction(&self) -> Result<(), Error> {
         let sql = match self.depth.get() - 1 {
             0 => "COMMIT".to_string(),
@@ -57,11 +63,12 @@ impl Connection {
         };
         self.execute_batch(&sql)?;
         self.depth.update(|level| level - 1);
+        self.changes.commit();
         Ok(())
     }
 
     /// Discards the current transaction level: `ROLLBACK` at the outermost, `ROLLBACK TO`/`RELEASE`
-    /// below.
+    /// below. The events published under it are dropped.
     fn rollback_transaction(&self) -> Result<(), Error> {
         let sql = match self.depth.get() - 1 {
             0 => "ROLLBACK".to_string(),
@@ -69,6 +76,7 @@ impl Connection {
         };
         self.execute_batch(&sql)?;
         self.depth.update(|level| level - 1);
+        self.changes.rollback();
         Ok(())
     }
 }
@@ -78,13 +86,13 @@ impl Drop for Connection {
     /// `transaction` ran — so the connection rejoins the pool clean instead of poisoning the next
     /// checkout.
     fn drop(&mut self) {
-        if self.depth.get() > 0 {
-            if let Err(error) = self.execute_batch("ROLLBACK") {
-                error!(
-                    "Failed to roll back a dangling transaction before returning the \
-                     connection to the pool: {error}"
-                );
-            }
+        if self.depth.get() > 0
+            && let Err(error) = self.execute_batch("ROLLBACK")
+        {
+            error!(
+                "Failed to roll back a dangling transaction before returning the \
+                 connection to the pool: {error}"
+            );
         }
     }
 }
@@ -106,6 +114,9 @@ impl ToSql for Attribute {
             Attribute::DateTime(value) => {
                 Ok(ToSqlOutput::Owned(DatabaseValue::Text(value.to_rfc3339())))
             }
+            Attribute::Json(value) => {
+                Ok(ToSqlOutput::Owned(DatabaseValue::Text(value.to_string())))
+            }
         }
     }
 }
@@ -138,8 +149,6 @@ fn materialise_attributes<'sch>(
         .enumerate()
         .map(|(index, column)| -> Result<_, Error> {
             let name = column.name();
-            let value = row.get_ref_unwrap(index);
-
             let column = schema
                 .column(name)
                 .ok_or_else(|| Error::InconsistentSchema {
@@ -147,48 +156,8 @@ fn materialise_attributes<'sch>(
                     attribute: name.to_string(),
                     message: "Database returned an unknown column".to_string(),
                 })?;
-            let attribute_type = column.kind;
-
-            let value = match value {
-                ValueRef::Null => Attribute::Null,
-                ValueRef::Integer(value) => match attribute_type {
-                    AttributeType::Integer => Attribute::Integer(value),
-                    AttributeType::DateTime => {
-                        Attribute::DateTime(date_time_from_millis(value, name)?)
-                    }
-                    AttributeType::Boolean => Attribute::Boolean(match value {
-                        0 => false,
-                        1 => true,
-                        _ => Err(Error::InconsistentSchema {
-                            schema: schema.name().to_string(),
-                            attribute: name.to_string(),
-                            message: format!(
-                                "Integer value '{}' cannot be converted to Boolean",
-                                value
-                            ),
-                        })?,
-                    }),
-                    kind => inconsistent_schema_error(schema, name, "Integer", kind)?,
-                },
-                ValueRef::Real(value) => match attribute_type {
-                    AttributeType::Float => Attribute::Float(value),
-                    kind => inconsistent_schema_error(schema, name, "Float", kind)?,
-                },
-                ValueRef::Text(value) => {
-                    let text = String::from_utf8_lossy(value);
-                    match attribute_type {
-                        AttributeType::Text => Attribute::Text(text.to_string()),
-                        AttributeType::DateTime => {
-                            Attribute::DateTime(date_time_from_rfc3339(text.as_ref(), name)?)
-                        }
-                        kind => inconsistent_schema_error(schema, name, "Text", kind)?,
-                    }
-                }
-                ValueRef::Blob(value) => match attribute_type {
-                    AttributeType::Text => Attribute::Text(b64.encode(value)),
-                    kind => inconsistent_schema_error(schema, name, "Blob", kind)?,
-                },
-            };
+
+            let value = materialise_value(schema, name, column.kind, row.get_ref_unwrap(index))?;
             Ok((column.name, value))
         })
         .collect::<Result<Vec<_>, Error>>()?;
@@ -196,6 +165,86 @@ fn materialise_attributes<'sch>(
     Ok(Attributes::from_iter(entries))
 }
 
+/// Materialises a row positionally, each column as the type at its position in `kinds`.
+fn materialise_values(
+    schema: &Schema,
+    kinds: &[AttributeType],
+    row: &Row,
+) -> Result<Vec<Attribute>, Error> {
+    let columns = row.as_ref().columns();
+    if columns.len() != kinds.len() {
+        return Err(Error::InconsistentSchema {
+            schema: schema.name().to_string(),
+            attribute: schema.name().to_string(),
+            message: format!(
+                "Database returned {} columns where {} were expected",
+                columns.len(),
+                kinds.len()
+            ),
+        });
+    }
+
+    columns
+        .iter()
+        .zip(kinds)
+        .enumerate()
+        .map(|(index, (column, &kind))| {
+            materialise_value(schema, column.name(), kind, row.get_ref_unwrap(index))
+        })
+        .collect()
+}
+
+/// Converts a stored value into an attribute of type `attribute_type`, rejecting a storage class
+/// the type cannot be read from.
+fn materialise_value(
+    schema: &Schema,
+    name: &str,
+    attribute_type: AttributeType,
+    value: ValueRef,
+) -> Result<Attribute, Error> {
+    let value = match value {
+        ValueRef::Null => Attribute::Null,
+        ValueRef::Integer(value) => match attribute_type {
+            AttributeType::Integer => Attribute::Integer(value),
+            AttributeType::DateTime => Attribute::DateTime(date_time_from_millis(value, name)?),
+            AttributeType::Boolean => Attribute::Boolean(match value {
+                0 => false,
+                1 => true,
+                _ => Err(Error::InconsistentSchema {
+                    schema: schema.name().to_string(),
+                    attribute: name.to_string(),
+                    message: format!("Integer value '{}' cannot be converted to Boolean", value),
+                })?,
+            }),
+            kind => inconsistent_schema_error(schema, name, "Integer", kind)?,
+        },
+        ValueRef::Real(value) => match attribute_type {
+            AttributeType::Float => Attribute::Float(value),
+            kind => inconsistent_schema_error(schema, name, "Float", kind)?,
+        },
+        ValueRef::Text(value) => {
+            let text = String::from_utf8_lossy(value);
+            match attribute_type {
+                AttributeType::Text => Attribute::Text(text.to_string()),
+                AttributeType::DateTime => {
+                    Attribute::DateTime(date_time_from_rfc3339(text.as_ref(), name)?)
+                }
+                AttributeType::Json => match serde_json::from_str(text.as_ref()) {
+                    Ok(value) => Attribute::Json(value),
+                    Err(_) => inconsistent_schema_error(schema, name, "Text", attribute_type)?,
+                },
+                kind => inconsistent_schema_error(schema, name, "Text", kind)?,
+            }
+        }
+        ValueRef::Blob(value) => match attribute_type {
+            AttributeType::Text => Attribute::Text(b64.encode(value)),
+            kind => inconsistent_schema_error(schema, name, "Blob", kind)?,
+        },
+    };
+
+    Ok(value)
+}
+
 fn build_bindings(bindings: &[Attribute]) -> Vec<&dyn ToSql> {
     bindings.iter().map(|b| b as &dyn ToSql).collect()
 }
@@ -221,6 +270,31 @@ impl ConnectionInterface for Connection {
         Ok(rows)
     }
 
+    fn query_values(
+        &self,
+        query: String,
+        bindings: Vec<Attribute>,
+        schema: &Schema,
+        kinds: &[AttributeType],
+    ) -> Result<Vec<Vec<Attribute>>, Error> {
+        debug!("{}, {:?}", query, bindings);
+
+        let bindings = build_bindings(&bindings);
+        let mut statement = self.handle.prepare(&query)?;
+        let rows = statement
+            .query_and_then(bindings.as_slice(), |row| {
+                materialise_values(schema, kinds, row)
+            })?
+            .collect::<Result<Vec<_>, _>>()?;
+
+        debug!("Returned {} rows", rows.len());
+        Ok(rows)
+    }
+
+    fn changes(&self) -> &ChangeBuffer {
+        &self.changes
+    }
+
     fn execute(&self, query: String, bindings: Vec<Attribute>) -> Result<usize, Error> {
         debug!("{}, {:?}", query, bindings);
 
@@ -235,7 +309,10 @@ impl ConnectionInterface for Connection {
     /// Runs `operation` inside a transaction level: commits it on `Ok`, rolls it back on `Err`. The
     /// level nests — the outermost is a real transaction, inner ones savepoints — so composing store
     /// calls stays atomic. A panic inside `operation` leaves the level open; `Drop` clears it.
-    fn transaction<R>(&self, operation: impl FnOnce() -> Result<R, Error>) -> Result<R, Error> {
+    fn transaction_with<R, E: From<Error>>(
+        &self,
+        operation: impl FnOnce() -> Result<R, E>,
+    ) -> Result<R, E> {
         self.begin_transaction()?;
 
         match operation() {
@@ -1,8 +1,13 @@
 use super::Connection;
 use crate::database::{error::Error, pool::Pool as PoolInterface};
 use r2d2_sqlite::SqliteConnectionManager;
+use rusqlite::functions::FunctionFlags;
 use std::path::Path;
 
+/// The SQL function the query builder folds case with, as SQLite's own `lower` and `NOCASE` fold
+/// ASCII only. Registered on every pooled connection, ahead of and independently from any preamble.
+pub(crate) const CASEFOLD_FUNCTION: &str = "casefold";
+
 /// Statements run on every new connection before it enters the pool: enables write-ahead logging,
 /// foreign-key enforcement and a busy timeout. Runs against the raw connection, before it is
 /// wrapped, since it configures the connection rather than issuing application queries.
@@ -14,6 +19,21 @@ pub fn default_preamble(connection: &mut rusqlite::Connection) -> rusqlite::Resu
     )
 }
 
+/// Folds text to lower case across all of Unicode, the way the query builder folds bound values, so
+/// both sides of a case-insensitive comparison agree. `NULL` folds to `NULL`.
+fn register_functions(connection: &rusqlite::Connection) -> rusqlite::Result<()> {
+    connection.create_scalar_function(
+        CASEFOLD_FUNCTION,
+        1,
+        FunctionFlags::SQLITE_UTF8 | FunctionFlags::SQLITE_DETERMINISTIC,
+        |context| {
+            Ok(context
+                .get::<Option<String>>(0)?
+                .map(|value| value.to_lowercase()))
+        },
+    )
+}
+
 /// An r2d2-backed connection pool over SQLite.
 pub struct Pool {
     inner: r2d2::Pool<SqliteConnectionManager>,
@@ -22,10 +42,7 @@ pub struct Pool {
 impl Pool {
     /// Opens a file-backed pool, running [`default_preamble`] on each connection.
     pub fn open(path: impl AsRef<Path>) -> Result<Self, Error> {
-        Self::from_manager(
-            SqliteConnectionManager::file(path).with_init(default_preamble),
-            None,
-        )
+        Self::from_manager(SqliteConnectionManager::file(path), default_preamble, None)
     }
 
     /// Opens a file-backed pool, running `preamble` on each connection as it is created.
@@ -33,25 +50,24 @@ impl Pool {
         path: impl AsRef<Path>,
         preamble: impl Fn(&mut rusqlite::Connection) -> rusqlite::Result<()> + Send + Sync + 'static,
     ) -> Result<Self, Error> {
-        Self::from_manager(
-            SqliteConnectionManager::file(path).with_init(preamble),
-            None,
-        )
+        Self::from_manager(SqliteConnectionManager::file(path), preamble, None)
     }
 
     /// Opens a single-connection in-memory pool, running [`default_preamble`]. Suited to tests and
     /// single-process embedding, where one connection keeps the in-memory database alive.
     pub fn memory() -> Result<Self, Error> {
-        Self::from_manager(
-            SqliteConnectionManager::memory().with_init(default_preamble),
-            Some(1),
-        )
+        Self::from_manager(SqliteConnectionManager::memory(), default_preamble, Some(1))
     }
 
     fn from_manager(
         manager: SqliteConnectionManager,
+        preamble: impl Fn(&mut rusqlite::Connection) -> rusqlite::Result<()> + Send + Sync + 'static,
         max_size: Option<u32>,
     ) -> Result<Self, Error> {
+        let manager = manager.with_init(move |connection| {
+            register_functions(connection)?;
+            preamble(connection)
+        });
         let mut builder = r2d2::Pool::builder();
 
         if let Some(size) = max_size {
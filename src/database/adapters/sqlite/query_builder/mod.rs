@@ -1,18 +1,25 @@
 #[cfg(test)]
 mod tests;
 
+use super::pool::CASEFOLD_FUNCTION;
 use crate::database::attributes::Identifier;
 use crate::database::{
     attributes::{Attribute, Attributes},
     error::Error,
-    query_builder::QueryBuilder as QueryBuilderInterface,
+    query_builder::{Binder, EXCERPT_CLOSE, EXCERPT_OPEN, QueryBuilder as QueryBuilderInterface},
     query_parameters::{
-        FieldsParameters, FilterParameters, FilterValue, PageParameters, QueryParameters,
-        SearchParameters, SortDirection, SortParameters, SortingAttribute,
+        AS_OF_FILTER, Aggregate, AggregateFunction, AggregateParameters, DateBucket,
+        ExcerptParameters, FieldsParameters, FilterExpression, FilterParameters, FilterValue,
+        PageParameters, QueryParameters, RelationshipFilter, RelationshipFilterParameters,
+        RelationshipFilterValue, SearchParameters, SearchTerm, SortDirection, SortDirective,
+        SortParameters, SortingAttribute, require_text,
+    },
+    schema::{
+        AttributeType, DateTime, History, INDEX_KEY, INDEX_ROWID, RelationshipDescriptor,
+        RelationshipKind, Schema, TextIndex, VALID_FROM, VALID_TO,
     },
-    schema::{AttributeType, Schema},
 };
-use indexmap::IndexSet;
+use indexmap::{IndexMap, IndexSet};
 use itertools::Itertools;
 
 struct ExtractedAttributes<'sch> {
@@ -24,20 +31,19 @@ pub type Bindings = Vec<Attribute>;
 
 /// Binds values for a parameterised query, returning the positional placeholder (`?N`) for each so
 /// numbering stays consistent across every clause that contributes parameters.
-trait Binder {
-    fn bind(&mut self, value: Attribute) -> String;
-    fn bind_all(&mut self, values: impl IntoIterator<Item = Attribute>) -> Vec<String>;
-}
-
 impl Binder for Bindings {
     fn bind(&mut self, value: Attribute) -> String {
         self.push(value);
         format!("?{}", self.len())
     }
+}
 
-    fn bind_all(&mut self, values: impl IntoIterator<Item = Attribute>) -> Vec<String> {
-        values.into_iter().map(|value| self.bind(value)).collect()
-    }
+/// A rendered filter condition, or the constant it folds to: an empty `IN` set matches `Never`, an
+/// empty `NOT IN` set matches `Always`.
+enum Predicate {
+    Condition(String),
+    Always,
+    Never,
 }
 
 /// Whether any row can satisfy a built `WHERE` clause. An empty `IN` set makes a match
@@ -47,6 +53,15 @@ enum Match {
     Impossible,
 }
 
+/// What `snippet()` puts where it cuts a value short, and the number of tokens a snippet spans.
+const SNIPPET_ELLIPSIS: &str = "…";
+const SNIPPET_TOKENS: usize = 16;
+/// The column a ranked query numbers each row's position within its group in.
+const RANK_COLUMN: &str = "yajac_rank";
+/// The form a history's validity instants are stored and compared in: UTC RFC 3339 to the
+/// millisecond, which orders as text the way the instants do.
+const INSTANT_FORMAT: &str = "%Y-%m-%dT%H:%M:%f+00:00";
+
 pub struct QueryBuilder<'sch> {
     schema: &'sch Schema<'sch>,
 }
@@ -127,11 +142,70 @@ impl<'sch> QueryBuilder<'sch> {
         query.extend(["SET".to_string(), assignments]);
     }
 
-    fn build_from_clause(&self, query: &mut Vec<String>) {
-        query.extend(["FROM".to_string(), self.schema.name().to_string()]);
+    /// Renders the `FROM` clause: the table itself or, read `as_of` an instant, the versions its
+    /// history held then, under the table's name so every other clause addresses them alike.
+    fn build_from_clause(
+        &self,
+        as_of: &Option<DateTime>,
+        query: &mut Vec<String>,
+        bindings: &mut Bindings,
+    ) -> Result<(), Error> {
+        let table = self.schema.name();
+        let Some(as_of) = as_of else {
+            query.extend(["FROM".to_string(), table.to_string()]);
+            return Ok(());
+        };
+
+        let history = self.history()?;
+        let instant = instant(&bindings.bind(Attribute::DateTime(*as_of)));
+        query.push(format!(
+            "FROM (SELECT {} FROM {} WHERE {VALID_FROM} <= {instant} \
+             AND ({VALID_TO} IS NULL OR {VALID_TO} > {instant})) AS {table}",
+            history.columns.iter().join(", "),
+            history.name(),
+        ));
+
+        Ok(())
     }
 
+    /// Renders the joins a query needs: the full-text index when searching, and a `LEFT JOIN` per
+    /// distinct relationship path a sort crosses, aliased as `sort_N` so `ORDER BY` can address it.
+    /// The joins are `LEFT` so records missing a related record still list, sorted as `NULL`.
     fn build_join_clause(
+        &self,
+        search: &Option<SearchParameters>,
+        sort: &Option<SortParameters>,
+        query: &mut Vec<String>,
+    ) -> Result<(), Error> {
+        self.build_search_join_clause(search, query)?;
+
+        let paths = sort_paths(sort);
+        for (path, (alias, descriptor)) in &paths {
+            let parent = match paths.get(&path[..path.len() - 1]) {
+                Some((alias, _)) => format!("sort_{alias}"),
+                None => self.schema.name().to_string(),
+            };
+            let keys = &descriptor.related.keys;
+            // Nothing holds a to-one foreign key unique, so a `HasOne` target is joined through
+            // one row per key; SQLite reads every bare column of a group from the same row.
+            let target = match descriptor.kind {
+                RelationshipKind::HasOne => format!(
+                    "(SELECT * FROM {} GROUP BY {})",
+                    descriptor.related.resource, keys.related
+                ),
+                _ => descriptor.related.resource.to_string(),
+            };
+
+            query.push(format!(
+                "LEFT JOIN {target} AS sort_{alias} ON sort_{alias}.{} = {parent}.{}",
+                keys.related, keys.own
+            ));
+        }
+
+        Ok(())
+    }
+
+    fn build_search_join_clause(
         &self,
         search: &Option<SearchParameters>,
         query: &mut Vec<String>,
@@ -140,116 +214,164 @@ impl<'sch> QueryBuilder<'sch> {
             return Ok(());
         }
 
-        if !self.schema.text_index() {
-            return Err(Error::QueryValidationFailure {
+        let index = self.text_index()?;
+        let (name, table, primary_key) = (index.name(), index.table, index.primary_key.name);
+        match index.keys() {
+            Some(keys) => query.push(format!(
+                "JOIN {keys} fts_keys ON fts_keys.{INDEX_KEY} = {table}.{primary_key} \
+                 JOIN {name} fts ON fts_keys.{INDEX_ROWID} = fts.rowid"
+            )),
+            None => query.push(format!(
+                "JOIN {name} fts ON {table}.{primary_key} = fts.rowid"
+            )),
+        }
+
+        Ok(())
+    }
+
+    fn text_index(&self) -> Result<&'sch TextIndex<'sch>, Error> {
+        self.schema
+            .text_index()
+            .ok_or_else(|| Error::QueryValidationFailure {
                 schema: self.schema.name().to_string(),
                 attribute: "search".to_string(),
                 message: "This resource does not support full-text search".to_string(),
-            });
+            })
+    }
+
+    fn history(&self) -> Result<&'sch History<'sch>, Error> {
+        self.schema
+            .history()
+            .ok_or_else(|| Error::QueryValidationFailure {
+                schema: self.schema.name().to_string(),
+                attribute: AS_OF_FILTER.to_string(),
+                message: "This resource does not keep a history".to_string(),
+            })
+    }
+
+    /// Refuses a write scoped `as_of` an instant: only the current state can be written.
+    fn writes_current_state(&self, parameters: &QueryParameters) -> Result<(), Error> {
+        match parameters.as_of {
+            Some(_) => Err(Error::QueryValidationFailure {
+                schema: self.schema.name().to_string(),
+                attribute: AS_OF_FILTER.to_string(),
+                message: "Past states cannot be written".to_string(),
+            }),
+            None => Ok(()),
         }
+    }
 
+    /// Renders the `WHERE` clause of a batch write. A search needs the full-text index joined, which
+    /// an `UPDATE` or `DELETE` cannot do, so a searched write is scoped to the keys a joined `SELECT`
+    /// matches instead.
+    fn build_write_scope(
+        &self,
+        parameters: &QueryParameters,
+        query: &mut Vec<String>,
+        bindings: &mut Bindings,
+    ) -> Result<Match, Error> {
+        if parameters.search.is_none() {
+            return self.build_where_clause(
+                &parameters.filter,
+                &parameters.relationship_filter,
+                &parameters.filter_expression,
+                &None,
+                query,
+                bindings,
+            );
+        }
+
+        let (table, primary_key) = (self.schema.name(), self.schema.primary_key().name);
         query.push(format!(
-            "JOIN {}_fts fts ON {}.id = fts.rowid",
-            self.schema.name(),
-            self.schema.name()
+            "WHERE {table}.{primary_key} IN (SELECT {table}.{primary_key} FROM {table}"
         ));
+        self.build_search_join_clause(&parameters.search, query)?;
+        if let Match::Impossible = self.build_where_clause(
+            &parameters.filter,
+            &parameters.relationship_filter,
+            &parameters.filter_expression,
+            &parameters.search,
+            query,
+            bindings,
+        )? {
+            return Ok(Match::Impossible);
+        }
+        if let Some(last) = query.last_mut() {
+            last.push(')');
+        }
 
-        Ok(())
+        Ok(Match::Possible)
     }
 
     /// Renders the `WHERE` clause, reporting whether any row can match so the caller can skip an
     /// impossible query. An empty `IN` set matches no row (`x IN ()` is always false), making the
     /// whole query `Impossible`; an empty `NOT IN` set matches every row (always true) and is
-    /// dropped. The `WHERE` keyword is emitted only when at least one predicate remains.
+    /// dropped. A filter expression is ANDed in as a single parenthesised predicate. The `WHERE`
+    /// keyword is emitted only when at least one predicate remains.
     fn build_where_clause(
         &self,
         filter: &Option<FilterParameters>,
+        relationship_filter: &Option<RelationshipFilterParameters>,
+        expression: &Option<FilterExpression>,
         search: &Option<SearchParameters>,
         query: &mut Vec<String>,
         bindings: &mut Bindings,
     ) -> Result<Match, Error> {
-        use FilterValue::*;
-
-        if filter.is_none() && search.is_none() {
+        if filter.is_none()
+            && relationship_filter.is_none()
+            && expression.is_none()
+            && search.is_none()
+        {
             return Ok(Match::Possible);
         }
 
         let mut filter_query = Vec::new();
 
-        if let Some(values) = search {
-            for value in values {
+        if let Some(terms) = search {
+            let index = self.text_index()?.name();
+            for term in terms {
                 filter_query.push(format!(
-                    "{}_fts MATCH {}",
-                    self.schema.name(),
-                    bindings.bind(Attribute::Text(value.to_string()))
+                    "{index} MATCH {}",
+                    bindings.bind(Attribute::Text(match_expression(term)))
                 ));
             }
         }
 
         if let Some(filter) = filter {
             for (field, filters) in filter {
-                let table = self.schema.name();
-                let kind = self
-                    .schema
-                    .column(field)
-                    .ok_or_else(|| Error::InvalidAttributeAccess {
-                        schema: self.schema.name().to_string(),
-                        attribute: field.to_string(),
-                    })?
-                    .kind;
-
                 for filter in filters {
-                    match filter {
-                        In(values) => {
-                            if values.is_empty() {
-                                return Ok(Match::Impossible);
-                            }
-                            let placeholders = bindings.bind_all(values.iter().cloned()).join(",");
-                            filter_query.push(format!("{table}.{field} IN ({placeholders})"));
-                        }
-                        NotIn(values) => {
-                            if values.is_empty() {
-                                continue;
-                            }
-                            let placeholders = bindings.bind_all(values.iter().cloned()).join(",");
-                            filter_query.push(format!("{table}.{field} NOT IN ({placeholders})"));
-                        }
-                        Like(value) => {
-                            let binding = if matches!(kind, AttributeType::Text) {
-                                Attribute::Text(format!("%{}%", value))
-                            } else {
-                                return Err(Error::QueryValidationFailure {
-                                    schema: self.schema.name().to_string(),
-                                    attribute: field.to_string(),
-                                    message:
-                                        "The 'LIKE' operator can only be applied to text attributes"
-                                            .to_string(),
-                                });
-                            };
-                            filter_query
-                                .push(format!("{table}.{field} LIKE {}", bindings.bind(binding)));
-                        }
-                        filter => {
-                            let (operator, binding) = match filter {
-                                Equal(value) => ("=", value),
-                                NotEqual(value) => ("!=", value),
-                                GreaterThan(value) => (">", value),
-                                GreaterThanOrEqual(value) => (">=", value),
-                                LessThan(value) => ("<", value),
-                                LessThanOrEqual(value) => ("<=", value),
-                                _ => unreachable!(),
-                            };
-
-                            filter_query.push(format!(
-                                "{table}.{field} {operator} {}",
-                                bindings.bind(binding.clone())
-                            ));
-                        }
+                    match self.build_predicate(field, filter, bindings)? {
+                        Predicate::Condition(condition) => filter_query.push(condition),
+                        Predicate::Always => continue,
+                        Predicate::Never => return Ok(Match::Impossible),
                     }
                 }
             }
         }
 
+        if let Some(relationship_filter) = relationship_filter {
+            for (index, filter) in relationship_filter.values().enumerate() {
+                for value in &filter.values {
+                    match self.build_relationship_predicate(index + 1, filter, value, bindings)? {
+                        Predicate::Condition(condition) => filter_query.push(condition),
+                        Predicate::Always => continue,
+                        Predicate::Never => return Ok(Match::Impossible),
+                    }
+                }
+            }
+        }
+
+        if let Some(expression) = expression {
+            let mut position = relationship_filter
+                .as_ref()
+                .map_or(0, |filter| filter.len());
+            match self.build_expression(expression, &mut position, bindings)? {
+                Predicate::Condition(condition) => filter_query.push(condition),
+                Predicate::Always => {}
+                Predicate::Never => return Ok(Match::Impossible),
+            }
+        }
+
         if !filter_query.is_empty() {
             query.push("WHERE".to_string());
             query.push(filter_query.join(" AND "));
@@ -258,25 +380,326 @@ impl<'sch> QueryBuilder<'sch> {
         Ok(Match::Possible)
     }
 
-    fn build_order_by_clause(&self, sort: &Option<SortParameters>, query: &mut Vec<String>) {
+    /// Renders a filter expression, folding away constant operands: a conjunction with a `Never`
+    /// operand is `Never`, a disjunction with an `Always` one is `Always`, and neutral operands
+    /// are dropped. Conjunctions and disjunctions of several operands render parenthesised.
+    /// `position` numbers the relationship subqueries, continuing after the `filter[...]` ones.
+    fn build_expression(
+        &self,
+        expression: &FilterExpression,
+        position: &mut usize,
+        bindings: &mut Bindings,
+    ) -> Result<Predicate, Error> {
+        let (operands, operator, absorbing) = match expression {
+            FilterExpression::Condition { attribute, value } => {
+                return self.build_predicate(attribute, value, bindings);
+            }
+            FilterExpression::Relationship(filter) => {
+                let mut conditions = Vec::new();
+                for value in &filter.values {
+                    *position += 1;
+                    match self.build_relationship_predicate(*position, filter, value, bindings)? {
+                        Predicate::Condition(condition) => conditions.push(condition),
+                        Predicate::Always => {}
+                        Predicate::Never => return Ok(Predicate::Never),
+                    }
+                }
+
+                return Ok(match conditions.len() {
+                    0 => Predicate::Always,
+                    _ => Predicate::Condition(conditions.join(" AND ")),
+                });
+            }
+            FilterExpression::Not(operand) => {
+                return Ok(match self.build_expression(operand, position, bindings)? {
+                    Predicate::Condition(condition) => {
+                        Predicate::Condition(format!("NOT {condition}"))
+                    }
+                    Predicate::Always => Predicate::Never,
+                    Predicate::Never => Predicate::Always,
+                });
+            }
+            FilterExpression::And(operands) => (operands, "AND", Predicate::Never),
+            FilterExpression::Or(operands) => (operands, "OR", Predicate::Always),
+        };
+
+        let mut conditions = Vec::new();
+        for operand in operands {
+            match (
+                self.build_expression(operand, position, bindings)?,
+                &absorbing,
+            ) {
+                (Predicate::Condition(condition), _) => conditions.push(condition),
+                (Predicate::Never, Predicate::Never) | (Predicate::Always, Predicate::Always) => {
+                    return Ok(absorbing);
+                }
+                _ => {}
+            }
+        }
+
+        Ok(match conditions.len() {
+            0 => match absorbing {
+                Predicate::Never => Predicate::Always,
+                _ => Predicate::Never,
+            },
+            1 => Predicate::Condition(conditions.remove(0)),
+            _ => Predicate::Condition(format!("({})", conditions.join(&format!(" {operator} ")))),
+        })
+    }
+
+    /// Renders a single filter condition on `field`, or the constant it folds to.
+    fn build_predicate(
+        &self,
+        field: &str,
+        filter: &FilterValue,
+        bindings: &mut Bindings,
+    ) -> Result<Predicate, Error> {
+        use FilterValue::*;
+
+        let table = self.schema.name();
+        let kind = self
+            .schema
+            .column(field)
+            .ok_or_else(|| Error::InvalidAttributeAccess {
+                schema: self.schema.name().to_string(),
+                attribute: field.to_string(),
+            })?
+            .kind;
+
+        let condition = match filter {
+            In(values) => {
+                if values.is_empty() {
+                    return Ok(Predicate::Never);
+                }
+                let placeholders = bindings.bind_all(values.iter().cloned()).join(",");
+                format!("{table}.{field} IN ({placeholders})")
+            }
+            NotIn(values) => {
+                if values.is_empty() {
+                    return Ok(Predicate::Always);
+                }
+                let placeholders = bindings.bind_all(values.iter().cloned()).join(",");
+                format!("{table}.{field} NOT IN ({placeholders})")
+            }
+            Like(value) => {
+                let value = self.text_operand(field, kind, filter, value)?;
+                let pattern = format!("%{}%", escape_like(value));
+                format!(
+                    "{table}.{field} LIKE {} ESCAPE '\\'",
+                    bindings.bind(Attribute::Text(pattern))
+                )
+            }
+            StartsWith(value) | EndsWith(value) => {
+                let value = self.text_operand(field, kind, filter, value)?;
+                let pattern = match filter {
+                    StartsWith(_) => format!("{}*", escape_glob(value)),
+                    _ => format!("*{}", escape_glob(value)),
+                };
+                format!(
+                    "{table}.{field} GLOB {}",
+                    bindings.bind(Attribute::Text(pattern))
+                )
+            }
+            InsensitiveLike(value) => {
+                let value = self.text_operand(field, kind, filter, value)?;
+                let pattern = format!("%{}%", escape_like(&value.to_lowercase()));
+                format!(
+                    "{CASEFOLD_FUNCTION}({table}.{field}) LIKE {} ESCAPE '\\'",
+                    bindings.bind(Attribute::Text(pattern))
+                )
+            }
+            InsensitiveEqual(value) => {
+                let value = self.text_operand(field, kind, filter, value)?;
+                format!(
+                    "{CASEFOLD_FUNCTION}({table}.{field}) = {}",
+                    bindings.bind(Attribute::Text(value.to_lowercase()))
+                )
+            }
+            Custom(custom) => {
+                if !custom.operator.applies_to(kind) {
+                    return Err(Error::QueryValidationFailure {
+                        schema: table.to_string(),
+                        attribute: field.to_string(),
+                        message: format!(
+                            "The '{}' operator cannot be applied to {kind} attributes",
+                            custom.name()
+                        ),
+                    });
+                }
+                let column = format!("{table}.{field}");
+                format!(
+                    "({})",
+                    custom.operator.render(&column, &custom.values, bindings)
+                )
+            }
+            filter => {
+                let (operator, binding) = match filter {
+                    Equal(value) => ("=", value),
+                    NotEqual(value) => ("!=", value),
+                    GreaterThan(value) => (">", value),
+                    GreaterThanOrEqual(value) => (">=", value),
+                    LessThan(value) => ("<", value),
+                    LessThanOrEqual(value) => ("<=", value),
+                    _ => unreachable!(),
+                };
+
+                format!(
+                    "{table}.{field} {operator} {}",
+                    bindings.bind(binding.clone())
+                )
+            }
+        };
+
+        Ok(Predicate::Condition(condition))
+    }
+
+    /// Renders a filter on a relationship as a correlated subquery over the related table, aliased
+    /// `filter_N` after the relationship's position among the filters so a relationship related to
+    /// its own table stays unambiguous. The same shape serves every relationship kind, since a join
+    /// is always `related.keys.related = self.keys.own`. Identity filters and `exists` / `none`
+    /// test whether a (matching) related record exists; `count-` comparisons count them.
+    fn build_relationship_predicate(
+        &self,
+        position: usize,
+        filter: &RelationshipFilter,
+        value: &RelationshipFilterValue,
+        bindings: &mut Bindings,
+    ) -> Result<Predicate, Error> {
+        use FilterValue::*;
+
+        let table = self.schema.name();
+        let related = filter.descriptor.related.resource;
+        let keys = &filter.descriptor.related.keys;
+        let alias = format!("filter_{position}");
+        let correlation = format!(
+            "FROM {related} AS {alias} WHERE {alias}.{} = {table}.{}",
+            keys.related, keys.own
+        );
+
+        let (negated, condition) = match value {
+            RelationshipFilterValue::Exists => (false, String::new()),
+            RelationshipFilterValue::NotExists => (true, String::new()),
+            RelationshipFilterValue::Identity(value) => match value {
+                Equal(operand) | NotEqual(operand) => (
+                    matches!(value, NotEqual(_)),
+                    format!(
+                        " AND {alias}.{} = {}",
+                        filter.primary_key,
+                        bindings.bind(operand.clone())
+                    ),
+                ),
+                In(operands) | NotIn(operands) => {
+                    if operands.is_empty() {
+                        return Ok(match value {
+                            In(_) => Predicate::Never,
+                            _ => Predicate::Always,
+                        });
+                    }
+                    let placeholders = bindings.bind_all(operands.iter().cloned()).join(",");
+                    (
+                        matches!(value, NotIn(_)),
+                        format!(" AND {alias}.{} IN ({placeholders})", filter.primary_key),
+                    )
+                }
+                _ => return Err(Self::invalid_relationship_filter(table, filter)),
+            },
+            RelationshipFilterValue::Count(value) => {
+                let (operator, operand) = match value {
+                    Equal(operand) => ("=", operand),
+                    NotEqual(operand) => ("<>", operand),
+                    GreaterThan(operand) => (">", operand),
+                    GreaterThanOrEqual(operand) => (">=", operand),
+                    LessThan(operand) => ("<", operand),
+                    LessThanOrEqual(operand) => ("<=", operand),
+                    _ => return Err(Self::invalid_relationship_filter(table, filter)),
+                };
+
+                return Ok(Predicate::Condition(format!(
+                    "(SELECT COUNT(*) {correlation}) {operator} {}",
+                    bindings.bind(operand.clone())
+                )));
+            }
+        };
+
+        Ok(Predicate::Condition(format!(
+            "{}EXISTS (SELECT 1 {correlation}{condition})",
+            if negated { "NOT " } else { "" }
+        )))
+    }
+
+    fn invalid_relationship_filter(table: &str, filter: &RelationshipFilter) -> Error {
+        Error::QueryValidationFailure {
+            schema: table.to_string(),
+            attribute: filter.descriptor.name.to_string(),
+            message: "Relationships can only be filtered with 'eq', 'neq', 'in', 'nin', 'exists', \
+                      'none' or a 'count-' comparison"
+                .to_string(),
+        }
+    }
+
+    /// Borrows the text a text matching operation compares against. Parsing already rejects the
+    /// operation on anything but a text attribute; parameters built by hand are checked again.
+    fn text_operand<'a>(
+        &self,
+        field: &str,
+        kind: AttributeType,
+        filter: &FilterValue,
+        value: &'a Attribute,
+    ) -> Result<&'a str, Error> {
+        require_text(self.schema, field, kind, filter)?;
+
+        match value {
+            Attribute::Text(value) => Ok(value),
+            _ => Err(Error::InvalidAttributeConversion {
+                kind: AttributeType::Text.to_string(),
+            }),
+        }
+    }
+
+    fn build_order_by_clause(
+        &self,
+        sort: &Option<SortParameters>,
+        query: &mut Vec<String>,
+    ) -> Result<(), Error> {
         if let Some(fields) = sort {
             query.push("ORDER BY".to_string());
+            let paths = sort_paths(sort);
             let mut sort_query = Vec::new();
 
-            for SortingAttribute {
-                attribute: field,
-                direction,
-            } in fields
-            {
+            for directive in fields {
+                let SortingAttribute {
+                    path,
+                    attribute: field,
+                    direction,
+                } = match directive {
+                    SortDirective::Attribute(attribute) => attribute,
+                    // `bm25` scores better matches lower, so the most relevant come first when
+                    // sorting by descending relevance.
+                    SortDirective::Relevance(direction) => {
+                        let direction = match direction {
+                            SortDirection::Ascending => "DESC",
+                            SortDirection::Descending => "ASC",
+                        };
+                        sort_query.push(format!("bm25({}) {direction}", self.text_index()?.name()));
+                        continue;
+                    }
+                };
                 let direction = match direction {
                     SortDirection::Ascending => "ASC",
                     SortDirection::Descending => "DESC",
                 };
-                sort_query.push(format!("{}.{} {}", self.schema.name(), field, direction));
+                let names = path.iter().map(|descriptor| descriptor.name).collect_vec();
+                let table = match paths.get(names.as_slice()) {
+                    Some((alias, _)) => format!("sort_{alias}"),
+                    None => self.schema.name().to_string(),
+                };
+                sort_query.push(format!("{table}.{field} {direction}"));
             }
 
             query.push(sort_query.join(", ").to_string());
         }
+
+        Ok(())
     }
 
     fn build_limit_offset_clauses(&self, page: &Option<PageParameters>, query: &mut Vec<String>) {
@@ -288,6 +711,36 @@ impl<'sch> QueryBuilder<'sch> {
         }
     }
 
+    /// Renders a grouping column, truncated to its calendar period when bucketed. Date-times are
+    /// stored either as RFC 3339 text or as Unix milliseconds, so both forms are formatted.
+    fn group_expression(&self, column: &str, bucket: Option<DateBucket>) -> String {
+        let column = format!("{}.{column}", self.schema.name());
+        let Some(bucket) = bucket else {
+            return column;
+        };
+
+        let format = match bucket {
+            DateBucket::Year => "%Y",
+            DateBucket::Month => "%Y-%m",
+            DateBucket::Day => "%Y-%m-%d",
+        };
+        format!(
+            "CASE typeof({column}) WHEN 'integer' THEN strftime('{format}', {column} / 1000, \
+             'unixepoch') ELSE strftime('{format}', {column}) END"
+        )
+    }
+
+    fn aggregate_expression(&self, aggregate: &Aggregate) -> String {
+        let function = match aggregate.function {
+            AggregateFunction::Count => "COUNT",
+            AggregateFunction::Sum => "SUM",
+            AggregateFunction::Average => "AVG",
+            AggregateFunction::Minimum => "MIN",
+            AggregateFunction::Maximum => "MAX",
+        };
+        format!("{function}({}.{})", self.schema.name(), aggregate.column)
+    }
+
     fn build_returning_clause(&self, fields: &FieldsParameters, query: &mut Vec<String>) {
         query.extend([
             "RETURNING".to_string(),
@@ -349,6 +802,74 @@ impl<'sch> QueryBuilder<'sch> {
     }
 }
 
+/// Escapes the `GLOB` wildcards in `value` — `*`, `?` and `[` — so it matches literally. Each is
+/// wrapped in a single-character class; a lone `]` is already literal.
+fn escape_glob(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for character in value.chars() {
+        match character {
+            '*' | '?' | '[' => escaped.extend(['[', character, ']']),
+            character => escaped.push(character),
+        }
+    }
+    escaped
+}
+
+/// Escapes the `LIKE` wildcards in `value` — `%` and `_` — and the `\` escape character itself, for
+/// a pattern compared with `ESCAPE '\'`.
+fn escape_like(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for character in value.chars() {
+        if matches!(character, '%' | '_' | '\\') {
+            escaped.push('\\');
+        }
+        escaped.push(character);
+    }
+    escaped
+}
+
+/// Renders a search term as an FTS5 `MATCH` expression: the phrase as a string, its `"` doubled so
+/// no FTS5 syntax in it is interpreted, suffixed with `*` to match as a prefix, and scoped to an
+/// indexed column as `"column" : ...`. The expression is bound, never spliced into the query.
+fn match_expression(term: &SearchTerm) -> String {
+    let mut expression = String::new();
+    if let Some(attribute) = term.attribute {
+        expression.push_str(&format!("\"{attribute}\" : "));
+    }
+    expression.push_str(&format!("\"{}\"", term.phrase.replace('"', "\"\"")));
+    if term.prefix {
+        expression.push('*');
+    }
+    expression
+}
+
+/// Renders `value`, a text instant or `'now'`, in `INSTANT_FORMAT`.
+fn instant(value: &str) -> String {
+    format!("strftime('{INSTANT_FORMAT}', {value})")
+}
+
+/// Numbers every distinct relationship path the sort crosses, prefixes included, in first-seen
+/// order, pairing each with the relationship that ends it. Shared prefixes share one join.
+fn sort_paths<'a>(
+    sort: &Option<SortParameters<'a>>,
+) -> IndexMap<Vec<&'a str>, (usize, &'a RelationshipDescriptor<'a>)> {
+    let mut paths = IndexMap::new();
+
+    for directive in sort.iter().flatten() {
+        let SortDirective::Attribute(SortingAttribute { path, .. }) = directive else {
+            continue;
+        };
+        let mut names = Vec::with_capacity(path.len());
+        for descriptor in path {
+            names.push(descriptor.name);
+            let alias = paths.len() + 1;
+            paths.entry(names.clone()).or_insert((alias, *descriptor));
+        }
+    }
+
+    paths
+}
+
 impl<'sch> QueryBuilderInterface<'sch> for QueryBuilder<'sch> {
     fn new(schema: &'sch Schema<'sch>) -> Self {
         Self { schema }
@@ -359,17 +880,19 @@ impl<'sch> QueryBuilderInterface<'sch> for QueryBuilder<'sch> {
         let mut bindings = Bindings::new();
 
         self.build_select_clause(&parameters.fields, &mut query);
-        self.build_from_clause(&mut query);
-        self.build_join_clause(&parameters.search, &mut query)?;
+        self.build_from_clause(&parameters.as_of, &mut query, &mut bindings)?;
+        self.build_join_clause(&parameters.search, &parameters.sort, &mut query)?;
         if let Match::Impossible = self.build_where_clause(
             &parameters.filter,
+            &parameters.relationship_filter,
+            &parameters.filter_expression,
             &parameters.search,
             &mut query,
             &mut bindings,
         )? {
             return Ok(None);
         }
-        self.build_order_by_clause(&parameters.sort, &mut query);
+        self.build_order_by_clause(&parameters.sort, &mut query)?;
         self.build_limit_offset_clauses(&parameters.page, &mut query);
 
         Ok(Some((query.join(" "), bindings)))
@@ -381,12 +904,15 @@ impl<'sch> QueryBuilderInterface<'sch> for QueryBuilder<'sch> {
         parameters: &QueryParameters,
     ) -> Result<(String, Bindings), Error> {
         let mut query = Vec::new();
+        let mut bindings = Bindings::new();
 
         self.build_select_clause(&parameters.fields, &mut query);
-        self.build_from_clause(&mut query);
-        query.push("WHERE id = ?1".to_string());
-
-        let bindings = Bindings::from([Attribute::from(id)]);
+        self.build_from_clause(&parameters.as_of, &mut query, &mut bindings)?;
+        query.push(format!(
+            "WHERE {} = {}",
+            self.schema.primary_key().name,
+            bindings.bind(Attribute::from(id))
+        ));
 
         Ok((query.join(" ").to_string(), bindings))
     }
@@ -419,6 +945,49 @@ impl<'sch> QueryBuilderInterface<'sch> for QueryBuilder<'sch> {
         Ok((query.join(" "), bindings))
     }
 
+    fn upsert(
+        &self,
+        attributes: Attributes<'sch>,
+        parameters: &QueryParameters,
+    ) -> Result<(String, Bindings), Error> {
+        let primary_key = self.schema.primary_key().name;
+        if !attributes.contains_key(primary_key) {
+            return Err(Error::InvalidOperation {
+                schema: self.schema.name().to_string(),
+                operation: "UPSERT".to_string(),
+                message: "cannot upsert without a primary key".to_string(),
+            });
+        }
+
+        let mut query = Vec::new();
+        let mut bindings = Bindings::new();
+        // A conflicting row is replaced outright: every column is reassigned from the inserted one,
+        // so a column the row omits falls back to its default. With no column but the key, the key
+        // is reassigned to itself so a conflicting row is still returned.
+        let assignments = match self
+            .schema
+            .attributes()
+            .chain(self.schema.foreign_keys())
+            .map(|(column, _)| column)
+            .filter(|&column| column != primary_key)
+            .map(|column| format!("{column} = excluded.{column}"))
+            .join(", ")
+        {
+            assignments if assignments.is_empty() => {
+                format!("{primary_key} = excluded.{primary_key}")
+            }
+            assignments => assignments,
+        };
+
+        self.build_insert_clause(vec![attributes], &mut query, &mut bindings)?;
+        query.push(format!(
+            "ON CONFLICT({primary_key}) DO UPDATE SET {assignments}"
+        ));
+        self.build_returning_clause(&parameters.fields, &mut query);
+
+        Ok((query.join(" "), bindings))
+    }
+
     fn update(
         &self,
         id: Identifier,
@@ -429,7 +998,11 @@ impl<'sch> QueryBuilderInterface<'sch> for QueryBuilder<'sch> {
         let mut bindings = Bindings::new();
 
         self.build_update_clause(attributes, &mut query, &mut bindings);
-        query.push(format!("WHERE id = {}", bindings.bind(Attribute::from(id))));
+        query.push(format!(
+            "WHERE {} = {}",
+            self.schema.primary_key().name,
+            bindings.bind(Attribute::from(id))
+        ));
         self.build_returning_clause(&parameters.fields, &mut query);
 
         Ok((query.join(" "), bindings))
@@ -440,13 +1013,12 @@ impl<'sch> QueryBuilderInterface<'sch> for QueryBuilder<'sch> {
         attributes: Attributes<'sch>,
         parameters: &QueryParameters,
     ) -> Result<Option<(String, Bindings)>, Error> {
+        self.writes_current_state(parameters)?;
         let mut query = Vec::new();
         let mut bindings = Bindings::new();
 
         self.build_update_clause(attributes, &mut query, &mut bindings);
-        if let Match::Impossible =
-            self.build_where_clause(&parameters.filter, &None, &mut query, &mut bindings)?
-        {
+        if let Match::Impossible = self.build_write_scope(parameters, &mut query, &mut bindings)? {
             return Ok(None);
         }
         self.build_returning_clause(&parameters.fields, &mut query);
@@ -456,7 +1028,11 @@ impl<'sch> QueryBuilderInterface<'sch> for QueryBuilder<'sch> {
 
     fn delete(&self, id: Identifier) -> (String, Bindings) {
         (
-            format!("DELETE FROM {} WHERE id = ?1", self.schema.name()),
+            format!(
+                "DELETE FROM {} WHERE {} = ?1",
+                self.schema.name(),
+                self.schema.primary_key().name
+            ),
             [Attribute::from(id)].into(),
         )
     }
@@ -465,15 +1041,315 @@ impl<'sch> QueryBuilderInterface<'sch> for QueryBuilder<'sch> {
         &self,
         parameters: &QueryParameters,
     ) -> Result<Option<(String, Bindings)>, Error> {
+        self.writes_current_state(parameters)?;
         let mut query = vec!["DELETE FROM".to_string(), self.schema.name().to_string()];
         let mut bindings = Bindings::new();
 
-        if let Match::Impossible =
-            self.build_where_clause(&parameters.filter, &None, &mut query, &mut bindings)?
-        {
+        if let Match::Impossible = self.build_write_scope(parameters, &mut query, &mut bindings)? {
+            return Ok(None);
+        }
+
+        Ok(Some((query.join(" "), bindings)))
+    }
+
+    fn count_by(&self, column: &'sch str, values: IndexSet<Attribute>) -> (String, Bindings) {
+        let table = self.schema.name();
+        let mut bindings = Bindings::new();
+        let placeholders = bindings.bind_all(values).join(",");
+
+        (
+            format!(
+                "SELECT {table}.{column}, COUNT(*) FROM {table} \
+                 WHERE {table}.{column} IN ({placeholders}) GROUP BY {table}.{column}"
+            ),
+            bindings,
+        )
+    }
+
+    fn query_ranked(
+        &self,
+        column: &'sch str,
+        values: IndexSet<Attribute>,
+        sort: &Option<SortParameters>,
+        limit: Option<u32>,
+        fields: &FieldsParameters,
+    ) -> Result<(String, Bindings), Error> {
+        let table = self.schema.name();
+        let mut bindings = Bindings::new();
+        let mut order = Vec::new();
+
+        for directive in sort.iter().flatten() {
+            match directive {
+                SortDirective::Attribute(SortingAttribute {
+                    path,
+                    attribute,
+                    direction,
+                }) if path.is_empty() => {
+                    let direction = match direction {
+                        SortDirection::Ascending => "ASC",
+                        SortDirection::Descending => "DESC",
+                    };
+                    order.push(format!("{table}.{attribute} {direction}"));
+                }
+                _ => {
+                    return Err(Error::QueryValidationFailure {
+                        schema: table.to_string(),
+                        attribute: column.to_string(),
+                        message: "Ranked rows can only be sorted by their own attributes"
+                            .to_string(),
+                    });
+                }
+            }
+        }
+        // The primary key breaks ties, so a limit always keeps the same rows.
+        order.push(format!("{table}.{} ASC", self.schema.primary_key().name));
+
+        let placeholders = bindings.bind_all(values).join(",");
+        let mut query = vec![
+            "SELECT".to_string(),
+            self.fields_for_model(fields, true),
+            format!(
+                "FROM (SELECT *, ROW_NUMBER() OVER (PARTITION BY {table}.{column} ORDER BY {}) \
+                 AS {RANK_COLUMN} FROM {table} WHERE {table}.{column} IN ({placeholders})) AS {table}",
+                order.join(", ")
+            ),
+        ];
+
+        if let Some(limit) = limit {
+            let limit = bindings.bind(Attribute::Integer(limit.into()));
+            query.push(format!("WHERE {RANK_COLUMN} <= {limit}"));
+        }
+
+        query.push(format!("ORDER BY {table}.{column}, {RANK_COLUMN}"));
+
+        Ok((query.join(" "), bindings))
+    }
+
+    fn aggregate(
+        &self,
+        aggregate: &AggregateParameters,
+        parameters: &QueryParameters,
+    ) -> Result<Option<(String, Bindings)>, Error> {
+        let mut query = Vec::new();
+        let mut bindings = Bindings::new();
+
+        let group = aggregate
+            .group
+            .iter()
+            .map(|key| self.group_expression(key.column, key.bucket))
+            .collect_vec();
+        let columns = group
+            .iter()
+            .cloned()
+            .chain(
+                aggregate
+                    .aggregates
+                    .iter()
+                    .map(|aggregate| self.aggregate_expression(aggregate)),
+            )
+            .join(", ");
+
+        query.extend(["SELECT".to_string(), columns]);
+        self.build_from_clause(&parameters.as_of, &mut query, &mut bindings)?;
+        self.build_join_clause(&parameters.search, &None, &mut query)?;
+        if let Match::Impossible = self.build_where_clause(
+            &parameters.filter,
+            &parameters.relationship_filter,
+            &parameters.filter_expression,
+            &parameters.search,
+            &mut query,
+            &mut bindings,
+        )? {
             return Ok(None);
         }
+        if !group.is_empty() {
+            let group = group.join(", ");
+            query.extend([format!("GROUP BY {group}"), format!("ORDER BY {group}")]);
+        }
 
         Ok(Some((query.join(" "), bindings)))
     }
+
+    fn excerpts(
+        &self,
+        ids: IndexSet<Attribute>,
+        search: &SearchParameters,
+        excerpts: &ExcerptParameters,
+    ) -> Result<(String, Bindings), Error> {
+        let table = self.schema.name();
+        let primary_key = self.schema.primary_key().name;
+        let text_index = self.text_index()?;
+        let index = text_index.name();
+        let column = |attribute: &str| {
+            text_index
+                .column(attribute)
+                .ok_or_else(|| Error::InconsistentSchema {
+                    schema: table.to_string(),
+                    attribute: attribute.to_string(),
+                    message: "Attribute is not held by the full-text index".to_string(),
+                })
+        };
+
+        // The markers go in as `char()` calls, so no control character reaches the query text.
+        let (open, close) = (EXCERPT_OPEN as u32, EXCERPT_CLOSE as u32);
+        let mut columns = vec![format!("{table}.{primary_key}")];
+        for &attribute in &excerpts.snippets {
+            columns.push(format!(
+                "snippet({index}, {}, char({open}), char({close}), '{SNIPPET_ELLIPSIS}', \
+                 {SNIPPET_TOKENS})",
+                column(attribute)?
+            ));
+        }
+        for &attribute in &excerpts.highlights {
+            columns.push(format!(
+                "highlight({index}, {}, char({open}), char({close}))",
+                column(attribute)?
+            ));
+        }
+
+        let mut query = vec!["SELECT".to_string(), columns.join(", ")];
+        let mut bindings = Bindings::new();
+        self.build_from_clause(&None, &mut query, &mut bindings)?;
+        self.build_search_join_clause(&Some(search.clone()), &mut query)?;
+
+        let mut conditions = search
+            .iter()
+            .map(|term| {
+                format!(
+                    "{index} MATCH {}",
+                    bindings.bind(Attribute::Text(match_expression(term)))
+                )
+            })
+            .collect_vec();
+        let placeholders = bindings.bind_all(ids).join(",");
+        conditions.push(format!("{table}.{primary_key} IN ({placeholders})"));
+        query.extend(["WHERE".to_string(), conditions.join(" AND ")]);
+
+        Ok((query.join(" "), bindings))
+    }
+
+    /// Renders an external-content FTS5 table over the schema's content, its `rowid` mirroring the
+    /// index's `content_rowid`, with the triggers that mirror every write into it. Changing a row
+    /// removes its old values from the index before adding the new ones, as external content
+    /// requires. A text-keyed table first gets the side table numbering its keys, kept by the same
+    /// triggers so a renamed key keeps its number, and the view the index reads through. A
+    /// `rebuild` indexes the rows the table already holds.
+    fn create_text_index(&self) -> Result<Vec<String>, Error> {
+        let index = self.text_index()?;
+        let (name, table, key) = (index.name(), index.table, index.primary_key.name);
+        let (content, content_rowid) = (index.content(), index.content_rowid());
+        let columns = index.attributes.iter().join(", ");
+        let rowid = |row: &str| match index.keys() {
+            Some(keys) => {
+                format!("(SELECT {INDEX_ROWID} FROM {keys} WHERE {INDEX_KEY} = {row}.{key})")
+            }
+            None => format!("{row}.{key}"),
+        };
+        let values = |row: &str| {
+            std::iter::once(rowid(row))
+                .chain(
+                    index
+                        .attributes
+                        .iter()
+                        .map(|column| format!("{row}.{column}")),
+                )
+                .join(", ")
+        };
+        let add = format!(
+            "INSERT INTO {name}(rowid, {columns}) VALUES ({});",
+            values("new")
+        );
+        let remove = format!(
+            "INSERT INTO {name}({name}, rowid, {columns}) VALUES ('delete', {});",
+            values("old")
+        );
+
+        let mut statements = Vec::new();
+        let (insert, delete, update) = match index.keys() {
+            None => (add.clone(), remove.clone(), format!("{remove} {add}")),
+            Some(keys) => {
+                let view_columns = index
+                    .attributes
+                    .iter()
+                    .map(|column| format!("{table}.{column}"))
+                    .join(", ");
+                statements.extend([
+                    format!(
+                        "CREATE TABLE {keys} ({INDEX_ROWID} INTEGER PRIMARY KEY, \
+                         {INDEX_KEY} TEXT NOT NULL UNIQUE)"
+                    ),
+                    format!("INSERT INTO {keys}({INDEX_KEY}) SELECT {key} FROM {table}"),
+                    format!(
+                        "CREATE VIEW {content} AS SELECT {keys}.{INDEX_ROWID} AS {INDEX_ROWID}, \
+                         {view_columns} FROM {table} \
+                         JOIN {keys} ON {keys}.{INDEX_KEY} = {table}.{key}"
+                    ),
+                ]);
+
+                (
+                    format!("INSERT INTO {keys}({INDEX_KEY}) VALUES (new.{key}); {add}"),
+                    format!("{remove} DELETE FROM {keys} WHERE {INDEX_KEY} = old.{key};"),
+                    format!(
+                        "{remove} UPDATE {keys} SET {INDEX_KEY} = new.{key} \
+                         WHERE {INDEX_KEY} = old.{key}; {add}"
+                    ),
+                )
+            }
+        };
+
+        statements.extend([
+            format!(
+                "CREATE VIRTUAL TABLE {name} USING fts5({columns}, content='{content}', \
+                 content_rowid='{content_rowid}')"
+            ),
+            format!("CREATE TRIGGER {name}_insert AFTER INSERT ON {table} BEGIN {insert} END"),
+            format!("CREATE TRIGGER {name}_delete AFTER DELETE ON {table} BEGIN {delete} END"),
+            format!("CREATE TRIGGER {name}_update AFTER UPDATE ON {table} BEGIN {update} END"),
+            format!("INSERT INTO {name}({name}) VALUES ('rebuild')"),
+        ]);
+
+        Ok(statements)
+    }
+
+    /// Renders the history table as a copy of the table's columns, seeding each row the table
+    /// already has as an open version valid from the moment the history is created, so a read as
+    /// of any earlier instant finds nothing; indexed for finding a record's open version; and the triggers versioning each write: an insert opens a version, an update
+    /// changing any column closes the open one and opens the next, and a delete closes it. Every
+    /// version a statement closes or opens shares the statement's instant.
+    fn create_history(&self) -> Result<Vec<String>, Error> {
+        let history = self.history()?;
+        let (name, table, key) = (history.name(), history.table, history.primary_key);
+        let columns = history.columns.iter().join(", ");
+        let now = instant("'now'");
+        let open = format!(
+            "INSERT INTO {name}({columns}, {VALID_FROM}, {VALID_TO}) VALUES ({}, {now}, NULL);",
+            history
+                .columns
+                .iter()
+                .map(|column| format!("new.{column}"))
+                .join(", ")
+        );
+        let close = format!(
+            "UPDATE {name} SET {VALID_TO} = {now} WHERE {key} = old.{key} AND {VALID_TO} IS NULL;"
+        );
+        let changed = history
+            .columns
+            .iter()
+            .map(|column| format!("old.{column} IS NOT new.{column}"))
+            .join(" OR ");
+
+        Ok(vec![
+            format!(
+                "CREATE TABLE {name} AS SELECT {columns}, {now} AS {VALID_FROM}, \
+                 NULL AS {VALID_TO} FROM {table}"
+            ),
+            format!("CREATE INDEX {name}_versions ON {name}({key}, {VALID_TO})"),
+            format!("CREATE TRIGGER {name}_insert AFTER INSERT ON {table} BEGIN {open} END"),
+            format!(
+                "CREATE TRIGGER {name}_update AFTER UPDATE ON {table} WHEN {changed} \
+                 BEGIN {close} {open} END"
+            ),
+            format!("CREATE TRIGGER {name}_delete AFTER DELETE ON {table} BEGIN {close} END"),
+        ])
+    }
 }
@@ -1,8 +1,9 @@
 use super::*;
+use crate::database::filter_operator::FilterOperator;
 use crate::database::registry::Registry as DatabaseRegistry;
-use crate::database::schema::SchemaBuilder;
+use crate::database::schema::{IdentifierType, Related, SchemaBuilder};
 use crate::http_wrappers::Uri;
-use indexmap::IndexSet;
+use indexmap::{IndexMap, IndexSet};
 use std::error::Error as StdError;
 
 type Registry = DatabaseRegistry<'static>;
@@ -14,7 +15,7 @@ fn my_schema(text_index: bool) -> SchemaBuilder<'static> {
         .attribute("col3", AttributeType::Integer);
 
     if text_index {
-        builder.text_index()
+        builder.indexed(["col1", "col2"])
     } else {
         builder
     }
@@ -24,6 +25,46 @@ fn registry(text_index: bool) -> Registry {
     DatabaseRegistry::try_new([my_schema(text_index)]).expect("schema set is consistent")
 }
 
+/// `my_table` belonging to an owner, who in turn belongs to a team, for relationship paths.
+fn related_registry() -> Registry {
+    DatabaseRegistry::try_new([
+        my_schema(false)
+            .foreign_key("owner_id", AttributeType::Integer)
+            .belongs_to(
+                "owner",
+                Related::to("owners")
+                    .pointing_own("owner_id")
+                    .to_related("id"),
+            )
+            .has_one(
+                "detail",
+                Related::to("details")
+                    .pointing_related("item_id")
+                    .to_own("id"),
+            ),
+        SchemaBuilder::table("details")
+            .attribute("label", AttributeType::Text)
+            .foreign_key("item_id", AttributeType::Integer),
+        SchemaBuilder::table("owners")
+            .attribute("name", AttributeType::Text)
+            .foreign_key("team_id", AttributeType::Integer)
+            .belongs_to(
+                "team",
+                Related::to("teams")
+                    .pointing_own("team_id")
+                    .to_related("id"),
+            )
+            .has_many(
+                "items",
+                Related::to("my_table")
+                    .pointing_related("owner_id")
+                    .to_own("id"),
+            ),
+        SchemaBuilder::table("teams").attribute("position", AttributeType::Integer),
+    ])
+    .expect("schema set is consistent")
+}
+
 fn schema(registry: &Registry) -> &Schema<'_> {
     registry.schema("my_table").expect("my_table is registered")
 }
@@ -132,12 +173,320 @@ fn test_filter_with_like_operator() -> Result<(), Box<dyn StdError>> {
 
     assert_eq!(
         query,
-        "SELECT my_table.id, my_table.col1, my_table.col2, my_table.col3 FROM my_table WHERE my_table.col1 LIKE ?1"
+        "SELECT my_table.id, my_table.col1, my_table.col2, my_table.col3 FROM my_table WHERE my_table.col1 LIKE ?1 ESCAPE '\\'"
     );
     assert_eq!(bindings, vec![Attribute::Text("%keyword%".to_string())]);
     Ok(())
 }
 
+#[test]
+fn test_filter_with_anchored_operators_escapes_wildcards() -> Result<(), Box<dyn StdError>> {
+    let registry = registry(true);
+    let uri =
+        mock_uri("fields[my_table]=col1&filter[col1]=starts:a*b,ends:%5Bc%5D?,like:50%25_off");
+    let (query, bindings) = QueryBuilder::new(schema(&registry))
+        .query(&parse(&registry, &uri))?
+        .ok_or("query should be satisfiable")?;
+
+    assert_eq!(
+        query,
+        "SELECT my_table.id, my_table.col1 FROM my_table \
+        WHERE my_table.col1 GLOB ?1 AND my_table.col1 GLOB ?2 AND my_table.col1 LIKE ?3 ESCAPE '\\'"
+    );
+    assert_eq!(
+        bindings,
+        vec![
+            Attribute::Text("a[*]b*".to_string()),
+            Attribute::Text("*[[]c][?]".to_string()),
+            Attribute::Text("%50\\%\\_off%".to_string()),
+        ]
+    );
+    Ok(())
+}
+
+#[test]
+fn test_filter_with_case_insensitive_operators() -> Result<(), Box<dyn StdError>> {
+    let registry = registry(true);
+    let uri = mock_uri(
+        "fields[my_table]=col1&filter[col1]=ilike:50%25_%C3%89t%5C&filter[col2]=ieq:Caf%C3%89",
+    );
+    let (query, bindings) = QueryBuilder::new(schema(&registry))
+        .query(&parse(&registry, &uri))?
+        .ok_or("query should be satisfiable")?;
+
+    assert_eq!(
+        query,
+        "SELECT my_table.id, my_table.col1 FROM my_table \
+        WHERE casefold(my_table.col1) LIKE ?1 ESCAPE '\\' AND casefold(my_table.col2) = ?2"
+    );
+    assert_eq!(
+        bindings,
+        vec![
+            Attribute::Text("%50\\%\\_ét\\\\%".to_string()),
+            Attribute::Text("café".to_string()),
+        ]
+    );
+    Ok(())
+}
+
+#[test]
+fn test_filter_expression_disjunction() -> Result<(), Box<dyn StdError>> {
+    let registry = registry(true);
+    let uri =
+        mock_uri("fields[my_table]=col1&filter[col3]=gt:1&filter=or(eq(col1,a),not(eq(col2,b)))");
+    let (query, bindings) = QueryBuilder::new(schema(&registry))
+        .query(&parse(&registry, &uri))?
+        .ok_or("query should be satisfiable")?;
+
+    assert_eq!(
+        query,
+        "SELECT my_table.id, my_table.col1 FROM my_table \
+        WHERE my_table.col3 > ?1 AND (my_table.col1 = ?2 OR NOT my_table.col2 = ?3)"
+    );
+    assert_eq!(
+        bindings,
+        vec![
+            Attribute::Integer(1),
+            Attribute::Text("a".to_string()),
+            Attribute::Text("b".to_string()),
+        ]
+    );
+    Ok(())
+}
+
+#[test]
+fn test_filter_expression_nested_groups() -> Result<(), Box<dyn StdError>> {
+    let registry = registry(true);
+    let uri = mock_uri("fields[my_table]=col1&filter=and(or(eq(col1,a),eq(col2,b)),lt(col3,5))");
+    let (query, bindings) = QueryBuilder::new(schema(&registry))
+        .query(&parse(&registry, &uri))?
+        .ok_or("query should be satisfiable")?;
+
+    assert_eq!(
+        query,
+        "SELECT my_table.id, my_table.col1 FROM my_table \
+        WHERE ((my_table.col1 = ?1 OR my_table.col2 = ?2) AND my_table.col3 < ?3)"
+    );
+    assert_eq!(bindings.len(), 3);
+    Ok(())
+}
+
+#[test]
+fn test_filter_expression_folds_empty_sets() -> Result<(), Box<dyn StdError>> {
+    let registry = registry(true);
+    let mut parameters = QueryParameters::new(schema(&registry));
+    parameters.filter_expression = Some(FilterExpression::Or(vec![
+        FilterExpression::Condition {
+            attribute: "col3",
+            value: FilterValue::In(IndexSet::new()),
+        },
+        FilterExpression::Condition {
+            attribute: "col1",
+            value: FilterValue::Equal(Attribute::Text("a".to_string())),
+        },
+    ]));
+    let (query, _) = QueryBuilder::new(schema(&registry))
+        .query(&parameters)?
+        .ok_or("query should be satisfiable")?;
+
+    assert_eq!(
+        query,
+        "SELECT my_table.id, my_table.col1, my_table.col2, my_table.col3 FROM my_table \
+        WHERE my_table.col1 = ?1"
+    );
+
+    parameters.filter_expression = Some(FilterExpression::And(vec![
+        FilterExpression::Condition {
+            attribute: "col3",
+            value: FilterValue::In(IndexSet::new()),
+        },
+        FilterExpression::Condition {
+            attribute: "col1",
+            value: FilterValue::Equal(Attribute::Text("a".to_string())),
+        },
+    ]));
+    assert!(
+        QueryBuilder::new(schema(&registry))
+            .query(&parameters)?
+            .is_none()
+    );
+    Ok(())
+}
+
+/// A registered operator matching numbers within inclusive bounds, as `between:1,5`.
+struct Between;
+
+impl FilterOperator for Between {
+    fn name(&self) -> &str {
+        "between"
+    }
+
+    fn applies_to(&self, kind: AttributeType) -> bool {
+        kind == AttributeType::Integer
+    }
+
+    fn parse(&self, value: &str, kind: AttributeType) -> Result<Vec<Attribute>, String> {
+        value
+            .split(",")
+            .map(|bound| Attribute::parse(bound, kind).map_err(|error| error.to_string()))
+            .collect()
+    }
+
+    fn render(&self, column: &str, values: &[Attribute], binder: &mut dyn Binder) -> String {
+        let low = binder.bind(values[0].clone());
+        let high = binder.bind(values[1].clone());
+        format!("{column} BETWEEN {low} AND {high}")
+    }
+}
+
+#[test]
+fn test_filter_with_custom_operator() -> Result<(), Box<dyn StdError>> {
+    let registry = registry(false).with_filter_operator(Between)?;
+    let uri = mock_uri("fields[my_table]=col1&filter[col3]=between:1,5&filter[col1]=eq:x");
+    let (query, bindings) = QueryBuilder::new(schema(&registry))
+        .query(&parse(&registry, &uri))?
+        .ok_or("query should be satisfiable")?;
+
+    assert_eq!(
+        query,
+        "SELECT my_table.id, my_table.col1 FROM my_table \
+        WHERE (my_table.col3 BETWEEN ?1 AND ?2) AND my_table.col1 = ?3"
+    );
+    assert_eq!(
+        bindings,
+        vec![
+            Attribute::Integer(1),
+            Attribute::Integer(5),
+            Attribute::Text("x".to_string())
+        ]
+    );
+    Ok(())
+}
+
+#[test]
+fn test_filter_on_primary_and_foreign_keys() -> Result<(), Box<dyn StdError>> {
+    let registry = related_registry();
+    let uri = mock_uri("fields[my_table]=col1&filter[id]=in:1,2&filter[owner_id]=eq:3");
+    let (query, bindings) = QueryBuilder::new(schema(&registry))
+        .query(&parse(&registry, &uri))?
+        .ok_or("query should be satisfiable")?;
+
+    assert_eq!(
+        query,
+        "SELECT my_table.id, my_table.col1 FROM my_table \
+        WHERE my_table.id IN (?1,?2) AND my_table.owner_id = ?3"
+    );
+    assert_eq!(
+        bindings,
+        vec![
+            Attribute::Integer(1),
+            Attribute::Integer(2),
+            Attribute::Integer(3)
+        ]
+    );
+    Ok(())
+}
+
+#[test]
+fn test_filter_on_to_one_relationship() -> Result<(), Box<dyn StdError>> {
+    let registry = related_registry();
+    let uri = mock_uri("fields[my_table]=col1&filter[owner]=in:3,4");
+    let (query, bindings) = QueryBuilder::new(schema(&registry))
+        .query(&parse(&registry, &uri))?
+        .ok_or("query should be satisfiable")?;
+
+    assert_eq!(
+        query,
+        "SELECT my_table.id, my_table.col1 FROM my_table \
+        WHERE EXISTS (SELECT 1 FROM owners AS filter_1 \
+        WHERE filter_1.id = my_table.owner_id AND filter_1.id IN (?1,?2))"
+    );
+    assert_eq!(bindings, vec![Attribute::Integer(3), Attribute::Integer(4)]);
+    Ok(())
+}
+
+#[test]
+fn test_filter_expression_on_relationships() -> Result<(), Box<dyn StdError>> {
+    let registry = related_registry();
+    let uri =
+        mock_uri("fields[my_table]=col1&filter[owner]=exists&filter=or(eq(owner,3),eq(col1,a))");
+    let (query, bindings) = QueryBuilder::new(schema(&registry))
+        .query(&parse(&registry, &uri))?
+        .ok_or("query should be satisfiable")?;
+
+    assert_eq!(
+        query,
+        "SELECT my_table.id, my_table.col1 FROM my_table \
+        WHERE EXISTS (SELECT 1 FROM owners AS filter_1 WHERE filter_1.id = my_table.owner_id) \
+        AND (EXISTS (SELECT 1 FROM owners AS filter_2 \
+        WHERE filter_2.id = my_table.owner_id AND filter_2.id = ?1) OR my_table.col1 = ?2)"
+    );
+    assert_eq!(
+        bindings,
+        vec![Attribute::Integer(3), Attribute::Text("a".to_string())]
+    );
+    Ok(())
+}
+
+#[test]
+fn test_filter_on_to_many_relationship() -> Result<(), Box<dyn StdError>> {
+    let registry = related_registry();
+    let owners = registry.schema("owners")?;
+    let uri = mock_uri("fields[owners]=name&filter[items]=eq:5&filter[items]=nin:6");
+    let (query, bindings) = QueryBuilder::new(owners)
+        .query(&QueryParameters::parse(&uri, owners, &registry)?)?
+        .ok_or("query should be satisfiable")?;
+
+    assert_eq!(
+        query,
+        "SELECT owners.id, owners.name FROM owners \
+        WHERE EXISTS (SELECT 1 FROM my_table AS filter_1 \
+        WHERE filter_1.owner_id = owners.id AND filter_1.id = ?1) \
+        AND NOT EXISTS (SELECT 1 FROM my_table AS filter_1 \
+        WHERE filter_1.owner_id = owners.id AND filter_1.id IN (?2))"
+    );
+    assert_eq!(bindings, vec![Attribute::Integer(5), Attribute::Integer(6)]);
+    Ok(())
+}
+
+#[test]
+fn test_filter_on_relationship_existence() -> Result<(), Box<dyn StdError>> {
+    let registry = related_registry();
+    let owners = registry.schema("owners")?;
+    let uri = mock_uri("fields[owners]=name&filter[items]=exists&filter[team]=none");
+    let (query, bindings) = QueryBuilder::new(owners)
+        .query(&QueryParameters::parse(&uri, owners, &registry)?)?
+        .ok_or("query should be satisfiable")?;
+
+    assert_eq!(
+        query,
+        "SELECT owners.id, owners.name FROM owners \
+        WHERE EXISTS (SELECT 1 FROM my_table AS filter_1 WHERE filter_1.owner_id = owners.id) \
+        AND NOT EXISTS (SELECT 1 FROM teams AS filter_2 WHERE filter_2.id = owners.team_id)"
+    );
+    assert!(bindings.is_empty());
+    Ok(())
+}
+
+#[test]
+fn test_filter_on_relationship_count() -> Result<(), Box<dyn StdError>> {
+    let registry = related_registry();
+    let owners = registry.schema("owners")?;
+    let uri = mock_uri("fields[owners]=name&filter[items]=count-gt:1,count-lte:5");
+    let (query, bindings) = QueryBuilder::new(owners)
+        .query(&QueryParameters::parse(&uri, owners, &registry)?)?
+        .ok_or("query should be satisfiable")?;
+
+    assert_eq!(
+        query,
+        "SELECT owners.id, owners.name FROM owners \
+        WHERE (SELECT COUNT(*) FROM my_table AS filter_1 WHERE filter_1.owner_id = owners.id) > ?1 \
+        AND (SELECT COUNT(*) FROM my_table AS filter_1 WHERE filter_1.owner_id = owners.id) <= ?2"
+    );
+    assert_eq!(bindings, vec![Attribute::Integer(1), Attribute::Integer(5)]);
+    Ok(())
+}
+
 #[test]
 fn test_sort_single_field() -> Result<(), Box<dyn StdError>> {
     let registry = registry(true);
@@ -170,6 +519,62 @@ fn test_sort_multiple_fields() -> Result<(), Box<dyn StdError>> {
     Ok(())
 }
 
+#[test]
+fn test_sort_by_related_attribute() -> Result<(), Box<dyn StdError>> {
+    let registry = related_registry();
+    let uri = mock_uri("fields[my_table]=col1&sort=-owner.name,col1");
+    let (query, bindings) = QueryBuilder::new(schema(&registry))
+        .query(&parse(&registry, &uri))?
+        .ok_or("query should be satisfiable")?;
+
+    assert_eq!(
+        query,
+        "SELECT my_table.id, my_table.col1 FROM my_table \
+        LEFT JOIN owners AS sort_1 ON sort_1.id = my_table.owner_id \
+        ORDER BY sort_1.name DESC, my_table.col1 ASC"
+    );
+    assert!(bindings.is_empty());
+    Ok(())
+}
+
+#[test]
+fn test_sort_by_nested_related_attributes_shares_joins() -> Result<(), Box<dyn StdError>> {
+    let registry = related_registry();
+    let uri = mock_uri("fields[my_table]=col1&sort=owner.team.position,owner.name");
+    let (query, bindings) = QueryBuilder::new(schema(&registry))
+        .query(&parse(&registry, &uri))?
+        .ok_or("query should be satisfiable")?;
+
+    assert_eq!(
+        query,
+        "SELECT my_table.id, my_table.col1 FROM my_table \
+        LEFT JOIN owners AS sort_1 ON sort_1.id = my_table.owner_id \
+        LEFT JOIN teams AS sort_2 ON sort_2.id = sort_1.team_id \
+        ORDER BY sort_2.position ASC, sort_1.name ASC"
+    );
+    assert!(bindings.is_empty());
+    Ok(())
+}
+
+#[test]
+fn test_sort_through_has_one_joins_one_row_per_key() -> Result<(), Box<dyn StdError>> {
+    let registry = related_registry();
+    let uri = mock_uri("fields[my_table]=col1&sort=detail.label");
+    let (query, bindings) = QueryBuilder::new(schema(&registry))
+        .query(&parse(&registry, &uri))?
+        .ok_or("query should be satisfiable")?;
+
+    assert_eq!(
+        query,
+        "SELECT my_table.id, my_table.col1 FROM my_table \
+        LEFT JOIN (SELECT * FROM details GROUP BY item_id) AS sort_1 \
+        ON sort_1.item_id = my_table.id \
+        ORDER BY sort_1.label ASC"
+    );
+    assert!(bindings.is_empty());
+    Ok(())
+}
+
 #[test]
 fn test_pagination() -> Result<(), Box<dyn StdError>> {
     let registry = registry(true);
@@ -216,7 +621,7 @@ fn test_complex_query_with_all_features() -> Result<(), Box<dyn StdError>> {
     assert_eq!(
         bindings,
         vec![
-            Attribute::Text("find-me".to_string()),
+            Attribute::Text("\"find-me\"".to_string()),
             Attribute::Text("value1".to_string())
         ]
     );
@@ -325,6 +730,64 @@ fn test_insert_with_empty_attributes() -> Result<(), Box<dyn StdError>> {
     Ok(())
 }
 
+#[test]
+fn test_upsert_updates_on_primary_key_conflict() -> Result<(), Box<dyn StdError>> {
+    let registry = registry(true);
+    let uri = mock_uri("");
+    let attributes = Attributes::from_iter([
+        ("id", Attribute::Integer(7)),
+        ("col1", Attribute::Text("value1".to_string())),
+        ("col2", Attribute::Integer(42)),
+    ]);
+    let (query, bindings) =
+        QueryBuilder::new(schema(&registry)).upsert(attributes, &parse(&registry, &uri))?;
+
+    assert_eq!(
+        query,
+        "INSERT INTO my_table(id, col1, col2) VALUES (?1, ?2, ?3) \
+         ON CONFLICT(id) DO UPDATE SET col1 = excluded.col1, col2 = excluded.col2, \
+         col3 = excluded.col3 \
+         RETURNING id, col1, col2, col3"
+    );
+    assert_eq!(
+        bindings,
+        vec![
+            Attribute::Integer(7),
+            Attribute::Text("value1".to_string()),
+            Attribute::Integer(42)
+        ]
+    );
+    Ok(())
+}
+
+#[test]
+fn test_upsert_of_a_bare_key_resets_every_column() -> Result<(), Box<dyn StdError>> {
+    let registry = registry(true);
+    let uri = mock_uri("");
+    let attributes = Attributes::from_iter([("id", Attribute::Integer(7))]);
+    let (query, _) =
+        QueryBuilder::new(schema(&registry)).upsert(attributes, &parse(&registry, &uri))?;
+
+    assert_eq!(
+        query,
+        "INSERT INTO my_table(id) VALUES (?1) \
+         ON CONFLICT(id) DO UPDATE SET col1 = excluded.col1, col2 = excluded.col2, \
+         col3 = excluded.col3 \
+         RETURNING id, col1, col2, col3"
+    );
+    Ok(())
+}
+
+#[test]
+fn test_upsert_without_primary_key() {
+    let registry = registry(true);
+    let uri = mock_uri("");
+    let attributes = Attributes::from_iter([("col1", Attribute::Text("value1".to_string()))]);
+    let result = QueryBuilder::new(schema(&registry)).upsert(attributes, &parse(&registry, &uri));
+
+    assert!(matches!(result, Err(Error::InvalidOperation { .. })));
+}
+
 #[test]
 fn test_update_single_field() -> Result<(), Box<dyn StdError>> {
     let registry = registry(true);
@@ -480,6 +943,181 @@ fn test_delete() {
     assert_eq!(bindings, vec![Attribute::Integer(1)]);
 }
 
+#[test]
+fn test_count_by() {
+    let registry = related_registry();
+    let (query, bindings) = QueryBuilder::new(schema(&registry)).count_by(
+        "owner_id",
+        IndexSet::from([Attribute::Integer(1), Attribute::Integer(2)]),
+    );
+
+    assert_eq!(
+        query,
+        "SELECT my_table.owner_id, COUNT(*) FROM my_table \
+         WHERE my_table.owner_id IN (?1,?2) GROUP BY my_table.owner_id"
+    );
+    assert_eq!(bindings, vec![Attribute::Integer(1), Attribute::Integer(2)]);
+}
+
+#[test]
+fn test_query_ranked() -> Result<(), Box<dyn StdError>> {
+    let registry = related_registry();
+    let uri = mock_uri("fields[my_table]=col1&sort=-col3");
+    let parameters = parse(&registry, &uri);
+    let (query, bindings) = QueryBuilder::new(schema(&registry)).query_ranked(
+        "owner_id",
+        IndexSet::from([Attribute::Integer(1), Attribute::Integer(2)]),
+        &parameters.sort,
+        Some(3),
+        &parameters.fields,
+    )?;
+
+    assert_eq!(
+        query,
+        "SELECT my_table.id, my_table.col1 FROM (SELECT *, \
+         ROW_NUMBER() OVER (PARTITION BY my_table.owner_id ORDER BY my_table.col3 DESC, \
+         my_table.id ASC) AS yajac_rank FROM my_table WHERE my_table.owner_id IN (?1,?2)) \
+         AS my_table WHERE yajac_rank <= ?3 ORDER BY my_table.owner_id, yajac_rank"
+    );
+    assert_eq!(
+        bindings,
+        vec![
+            Attribute::Integer(1),
+            Attribute::Integer(2),
+            Attribute::Integer(3)
+        ]
+    );
+    Ok(())
+}
+
+#[test]
+fn test_query_ranked_rejects_relationship_sort() -> Result<(), Box<dyn StdError>> {
+    let registry = related_registry();
+    let uri = mock_uri("sort=owner.name");
+    let parameters = parse(&registry, &uri);
+    let result = QueryBuilder::new(schema(&registry)).query_ranked(
+        "owner_id",
+        IndexSet::from([Attribute::Integer(1)]),
+        &parameters.sort,
+        None,
+        &parameters.fields,
+    );
+
+    assert_eq!(
+        result,
+        Err(Error::QueryValidationFailure {
+            schema: "my_table".to_string(),
+            attribute: "owner_id".to_string(),
+            message: "Ranked rows can only be sorted by their own attributes".to_string(),
+        })
+    );
+    Ok(())
+}
+
+#[test]
+fn test_sort_by_relevance() -> Result<(), Box<dyn StdError>> {
+    let registry = registry(true);
+    let uri = mock_uri("fields[my_table]=col1&search=fox&sort=-relevance,col1");
+    let (query, _) = QueryBuilder::new(schema(&registry))
+        .query(&parse(&registry, &uri))?
+        .expect("query is possible");
+
+    assert_eq!(
+        query,
+        "SELECT my_table.id, my_table.col1 FROM my_table \
+         JOIN my_table_fts fts ON my_table.id = fts.rowid \
+         WHERE my_table_fts MATCH ?1 ORDER BY bm25(my_table_fts) ASC, my_table.col1 ASC"
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_excerpts() -> Result<(), Box<dyn StdError>> {
+    let registry = registry(true);
+    let uri = mock_uri("search=fox&meta[snippets]=col2&meta[highlights]=col1");
+    let parameters = parse(&registry, &uri);
+    let (query, bindings) = QueryBuilder::new(schema(&registry)).excerpts(
+        IndexSet::from([Attribute::Integer(1), Attribute::Integer(2)]),
+        parameters.search.as_ref().expect("search is requested"),
+        &parameters.excerpts,
+    )?;
+
+    assert_eq!(
+        query,
+        "SELECT my_table.id, snippet(my_table_fts, 1, char(2), char(3), '…', 16), \
+         highlight(my_table_fts, 0, char(2), char(3)) FROM my_table \
+         JOIN my_table_fts fts ON my_table.id = fts.rowid \
+         WHERE my_table_fts MATCH ?1 AND my_table.id IN (?2,?3)"
+    );
+    assert_eq!(
+        bindings,
+        vec![
+            Attribute::Text("\"fox\"".to_string()),
+            Attribute::Integer(1),
+            Attribute::Integer(2)
+        ]
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_aggregate_grouped_and_filtered() -> Result<(), Box<dyn StdError>> {
+    let registry = related_registry();
+    let uri = mock_uri("aggregate[sum]=col3&aggregate[count]=id&group=owner_id&filter[col1]=eq:a");
+    let parameters = parse(&registry, &uri);
+    let aggregate = parameters
+        .aggregate
+        .as_ref()
+        .expect("aggregation is requested");
+    let (query, bindings) = QueryBuilder::new(schema(&registry))
+        .aggregate(aggregate, &parameters)?
+        .expect("query is possible");
+
+    assert_eq!(
+        query,
+        "SELECT my_table.owner_id, SUM(my_table.col3), COUNT(my_table.id) FROM my_table \
+         WHERE my_table.col1 = ?1 GROUP BY my_table.owner_id ORDER BY my_table.owner_id"
+    );
+    assert_eq!(bindings, vec![Attribute::Text("a".to_string())]);
+
+    Ok(())
+}
+
+#[test]
+fn test_aggregate_ungrouped_skips_impossible_filter() -> Result<(), Box<dyn StdError>> {
+    let registry = registry(false);
+    let uri = mock_uri("aggregate[avg]=col3");
+    let parameters = parse(&registry, &uri);
+    let aggregate = parameters
+        .aggregate
+        .as_ref()
+        .expect("aggregation is requested");
+    let builder = QueryBuilder::new(schema(&registry));
+
+    assert_eq!(
+        builder.aggregate(aggregate, &parameters)?,
+        Some((
+            "SELECT AVG(my_table.col3) FROM my_table".to_string(),
+            vec![]
+        ))
+    );
+
+    let mut parameters = parse(&registry, &uri);
+    parameters.filter = Some(FilterParameters::from([(
+        "col3",
+        vec![FilterValue::In(IndexSet::new())],
+    )]));
+    let aggregate = parameters
+        .aggregate
+        .as_ref()
+        .expect("aggregation is requested");
+    assert_eq!(builder.aggregate(aggregate, &parameters)?, None);
+
+    Ok(())
+}
+
 #[test]
 fn test_insert_batch_multiple_rows() -> Result<(), Box<dyn StdError>> {
     let registry = registry(true);
@@ -539,6 +1177,32 @@ fn test_delete_batch_scoped_by_filter() -> Result<(), Box<dyn StdError>> {
     Ok(())
 }
 
+#[test]
+fn test_delete_batch_scoped_by_search() -> Result<(), Box<dyn StdError>> {
+    let registry = registry(true);
+    let uri = mock_uri("search=fox");
+    let (query, bindings) = QueryBuilder::new(schema(&registry))
+        .delete_batch(&scoped_to_id(&registry, &uri, 5))?
+        .ok_or("delete should be satisfiable")?;
+
+    assert_eq!(
+        query,
+        "\
+        DELETE FROM my_table WHERE my_table.id IN (SELECT my_table.id FROM my_table \
+        JOIN my_table_fts fts ON my_table.id = fts.rowid \
+        WHERE my_table_fts MATCH ?1 AND my_table.id = ?2)\
+        "
+    );
+    assert_eq!(
+        bindings,
+        vec![
+            Attribute::Text("\"fox\"".to_string()),
+            Attribute::Integer(5)
+        ]
+    );
+    Ok(())
+}
+
 #[test]
 fn test_delete_batch_unscoped() -> Result<(), Box<dyn StdError>> {
     let registry = registry(true);
@@ -553,12 +1217,31 @@ fn test_delete_batch_unscoped() -> Result<(), Box<dyn StdError>> {
 }
 
 #[test]
-fn test_filter_with_like_operator_on_non_text_attribute() {
+fn test_filter_with_text_operators_on_non_text_attribute() {
     let registry = registry(true);
-    let uri = mock_uri("filter[col3]=like:1");
-    let result = QueryBuilder::new(schema(&registry)).query(&parse(&registry, &uri));
 
-    assert!(result.is_err());
+    for (operator, value) in [
+        ("like", FilterValue::Like(Attribute::Integer(1))),
+        ("ilike", FilterValue::InsensitiveLike(Attribute::Integer(1))),
+        ("starts", FilterValue::StartsWith(Attribute::Integer(1))),
+        ("ends", FilterValue::EndsWith(Attribute::Integer(1))),
+        ("ieq", FilterValue::InsensitiveEqual(Attribute::Integer(1))),
+    ] {
+        let mut parameters = QueryParameters::new(schema(&registry));
+        parameters.filter = Some(IndexMap::from([("col3", vec![value])]));
+        let result = QueryBuilder::new(schema(&registry)).query(&parameters);
+
+        assert_eq!(
+            result,
+            Err(Error::QueryValidationFailure {
+                schema: "my_table".to_string(),
+                attribute: "col3".to_string(),
+                message: format!(
+                    "The '{operator}' operator can only be applied to text attributes"
+                ),
+            })
+        );
+    }
 }
 
 #[test]
@@ -579,7 +1262,7 @@ fn test_search_with_single_term() -> Result<(), Box<dyn StdError>> {
     );
     assert_eq!(
         bindings,
-        vec![Attribute::Text("a-value-to-search".to_string())]
+        vec![Attribute::Text("\"a-value-to-search\"".to_string())]
     );
     Ok(())
 }
@@ -603,8 +1286,34 @@ fn test_search_with_multiple_terms() -> Result<(), Box<dyn StdError>> {
     assert_eq!(
         bindings,
         vec![
-            Attribute::Text("a-value".to_string()),
-            Attribute::Text("another-value".to_string())
+            Attribute::Text("\"a-value\"".to_string()),
+            Attribute::Text("\"another-value\"".to_string())
+        ]
+    );
+    Ok(())
+}
+
+#[test]
+fn test_search_terms_are_quoted_scoped_and_prefixed() -> Result<(), Box<dyn StdError>> {
+    let registry = registry(true);
+    let uri = mock_uri("search=say%20%22hi%22%20OR*&search[col2]=fo*");
+    let (query, bindings) = QueryBuilder::new(schema(&registry))
+        .query(&parse(&registry, &uri))?
+        .ok_or("query should be satisfiable")?;
+
+    assert_eq!(
+        query,
+        "\
+        SELECT my_table.id, my_table.col1, my_table.col2, my_table.col3 FROM my_table \
+        JOIN my_table_fts fts ON my_table.id = fts.rowid \
+        WHERE my_table_fts MATCH ?1 AND my_table_fts MATCH ?2\
+        "
+    );
+    assert_eq!(
+        bindings,
+        vec![
+            Attribute::Text("\"say \"\"hi\"\" OR\"*".to_string()),
+            Attribute::Text("\"col2\" : \"fo\"*".to_string())
         ]
     );
     Ok(())
@@ -621,6 +1330,238 @@ fn test_search_on_table_without_text_index() {
     assert!(result.is_err());
 }
 
+#[test]
+fn test_search_joins_the_index_on_the_primary_key() -> Result<(), Box<dyn StdError>> {
+    for (primary_key, kind, join) in [
+        (
+            "code",
+            IdentifierType::Integer,
+            "JOIN my_table_fts fts ON my_table.code = fts.rowid",
+        ),
+        (
+            "slug",
+            IdentifierType::Text,
+            "JOIN my_table_fts_keys fts_keys ON fts_keys.key = my_table.slug \
+             JOIN my_table_fts fts ON fts_keys.rowid = fts.rowid",
+        ),
+    ] {
+        let registry = DatabaseRegistry::try_new([my_schema(true).primary_key(primary_key, kind)])?;
+        let uri = mock_uri("fields[my_table]=col1&search=fox&meta[snippets]=col1");
+        let parameters = parse(&registry, &uri);
+        let builder = QueryBuilder::new(schema(&registry));
+
+        let (query, _) = builder
+            .query(&parameters)?
+            .ok_or("query should be satisfiable")?;
+        assert_eq!(
+            query,
+            format!(
+                "SELECT my_table.{primary_key}, my_table.col1 FROM my_table {join} \
+                 WHERE my_table_fts MATCH ?1"
+            )
+        );
+
+        let ids = IndexSet::from([Attribute::Integer(1)]);
+        let search = parameters.search.as_ref().expect("search is requested");
+        let (query, _) = builder.excerpts(ids, search, &parameters.excerpts)?;
+        assert!(query.contains(join));
+        assert!(query.ends_with(&format!("my_table.{primary_key} IN (?2)")));
+    }
+    Ok(())
+}
+
+#[test]
+fn test_create_text_index() -> Result<(), Box<dyn StdError>> {
+    let indexed =
+        DatabaseRegistry::try_new([my_schema(true).primary_key("code", IdentifierType::Integer)])?;
+
+    assert_eq!(
+        QueryBuilder::new(schema(&indexed)).create_text_index()?,
+        vec![
+            "CREATE VIRTUAL TABLE my_table_fts USING fts5(col1, col2, content='my_table', \
+             content_rowid='code')",
+            "CREATE TRIGGER my_table_fts_insert AFTER INSERT ON my_table BEGIN \
+             INSERT INTO my_table_fts(rowid, col1, col2) VALUES (new.code, new.col1, new.col2); \
+             END",
+            "CREATE TRIGGER my_table_fts_delete AFTER DELETE ON my_table BEGIN \
+             INSERT INTO my_table_fts(my_table_fts, rowid, col1, col2) \
+             VALUES ('delete', old.code, old.col1, old.col2); END",
+            "CREATE TRIGGER my_table_fts_update AFTER UPDATE ON my_table BEGIN \
+             INSERT INTO my_table_fts(my_table_fts, rowid, col1, col2) \
+             VALUES ('delete', old.code, old.col1, old.col2); \
+             INSERT INTO my_table_fts(rowid, col1, col2) VALUES (new.code, new.col1, new.col2); \
+             END",
+            "INSERT INTO my_table_fts(my_table_fts) VALUES ('rebuild')",
+        ]
+    );
+    let unindexed = registry(false);
+    assert!(
+        QueryBuilder::new(schema(&unindexed))
+            .create_text_index()
+            .is_err()
+    );
+    Ok(())
+}
+
+#[test]
+fn test_create_text_index_numbers_a_text_key() -> Result<(), Box<dyn StdError>> {
+    let text_keyed =
+        DatabaseRegistry::try_new([my_schema(true).primary_key("slug", IdentifierType::Text)])?;
+    let rowid = |row: &str| format!("(SELECT rowid FROM my_table_fts_keys WHERE key = {row}.slug)");
+    let (old, new) = (rowid("old"), rowid("new"));
+
+    assert_eq!(
+        QueryBuilder::new(schema(&text_keyed)).create_text_index()?,
+        vec![
+            "CREATE TABLE my_table_fts_keys (rowid INTEGER PRIMARY KEY, key TEXT NOT NULL UNIQUE)"
+                .to_string(),
+            "INSERT INTO my_table_fts_keys(key) SELECT slug FROM my_table".to_string(),
+            "CREATE VIEW my_table_fts_content AS SELECT my_table_fts_keys.rowid AS rowid, \
+             my_table.col1, my_table.col2 FROM my_table \
+             JOIN my_table_fts_keys ON my_table_fts_keys.key = my_table.slug"
+                .to_string(),
+            "CREATE VIRTUAL TABLE my_table_fts USING fts5(col1, col2, \
+             content='my_table_fts_content', content_rowid='rowid')"
+                .to_string(),
+            format!(
+                "CREATE TRIGGER my_table_fts_insert AFTER INSERT ON my_table BEGIN \
+                 INSERT INTO my_table_fts_keys(key) VALUES (new.slug); \
+                 INSERT INTO my_table_fts(rowid, col1, col2) VALUES ({new}, new.col1, new.col2); \
+                 END"
+            ),
+            format!(
+                "CREATE TRIGGER my_table_fts_delete AFTER DELETE ON my_table BEGIN \
+                 INSERT INTO my_table_fts(my_table_fts, rowid, col1, col2) \
+                 VALUES ('delete', {old}, old.col1, old.col2); \
+                 DELETE FROM my_table_fts_keys WHERE key = old.slug; END"
+            ),
+            format!(
+                "CREATE TRIGGER my_table_fts_update AFTER UPDATE ON my_table BEGIN \
+                 INSERT INTO my_table_fts(my_table_fts, rowid, col1, col2) \
+                 VALUES ('delete', {old}, old.col1, old.col2); \
+                 UPDATE my_table_fts_keys SET key = new.slug WHERE key = old.slug; \
+                 INSERT INTO my_table_fts(rowid, col1, col2) VALUES ({new}, new.col1, new.col2); \
+                 END"
+            ),
+            "INSERT INTO my_table_fts(my_table_fts) VALUES ('rebuild')".to_string(),
+        ]
+    );
+    Ok(())
+}
+
+// --- History ---
+
+fn history_registry() -> Registry {
+    DatabaseRegistry::try_new([my_schema(false).keeps_history()]).expect("schema set is consistent")
+}
+
+fn as_of() -> Attribute {
+    Attribute::parse("2026-01-01T00:00:00Z", AttributeType::DateTime).expect("the instant is valid")
+}
+
+const VERSIONS_HELD_THEN: &str = "FROM (SELECT id, col1, col2, col3 FROM my_table_history \
+     WHERE valid_from <= strftime('%Y-%m-%dT%H:%M:%f+00:00', ?1) \
+     AND (valid_to IS NULL OR valid_to > strftime('%Y-%m-%dT%H:%M:%f+00:00', ?1))) AS my_table";
+
+#[test]
+fn test_query_as_of_reads_the_versions_held_then() -> Result<(), Box<dyn StdError>> {
+    let registry = history_registry();
+    let uri = mock_uri("filter[as_of]=2026-01-01T00:00:00Z&filter[col3]=gt:2&sort=col1");
+    let (query, bindings) = QueryBuilder::new(schema(&registry))
+        .query(&parse(&registry, &uri))?
+        .ok_or("query should be satisfiable")?;
+
+    assert_eq!(
+        query,
+        format!(
+            "SELECT my_table.id, my_table.col1, my_table.col2, my_table.col3 {VERSIONS_HELD_THEN} \
+             WHERE my_table.col3 > ?2 ORDER BY my_table.col1 ASC"
+        )
+    );
+    assert_eq!(bindings, vec![as_of(), Attribute::Integer(2)]);
+    Ok(())
+}
+
+#[test]
+fn test_find_as_of_reads_the_version_held_then() -> Result<(), Box<dyn StdError>> {
+    let registry = history_registry();
+    let uri = mock_uri("filter[as_of]=2026-01-01T00:00:00Z");
+    let (query, bindings) = QueryBuilder::new(schema(&registry))
+        .find(Identifier::Integer(1), &parse(&registry, &uri))?;
+
+    assert_eq!(
+        query,
+        format!(
+            "SELECT my_table.id, my_table.col1, my_table.col2, my_table.col3 {VERSIONS_HELD_THEN} \
+             WHERE id = ?2"
+        )
+    );
+    assert_eq!(bindings, vec![as_of(), Attribute::Integer(1)]);
+    Ok(())
+}
+
+#[test]
+fn test_writes_as_of_are_refused() -> Result<(), Box<dyn StdError>> {
+    let registry = history_registry();
+    let uri = mock_uri("filter[as_of]=2026-01-01T00:00:00Z");
+    let parameters = parse(&registry, &uri);
+    let builder = QueryBuilder::new(schema(&registry));
+    let refused = |result: Result<Option<(String, Bindings)>, Error>| {
+        matches!(
+            result,
+            Err(Error::QueryValidationFailure { attribute, message, .. })
+                if attribute == "as_of" && message == "Past states cannot be written"
+        )
+    };
+
+    let row = Attributes::from([("col1", Attribute::Text("value".to_string()))]);
+    assert!(refused(builder.update_batch(row, &parameters)));
+    assert!(refused(builder.delete_batch(&parameters)));
+    Ok(())
+}
+
+#[test]
+fn test_create_history() -> Result<(), Box<dyn StdError>> {
+    let history = history_registry();
+    let now = "strftime('%Y-%m-%dT%H:%M:%f+00:00', 'now')";
+    let open = format!(
+        "INSERT INTO my_table_history(id, col1, col2, col3, valid_from, valid_to) \
+         VALUES (new.id, new.col1, new.col2, new.col3, {now}, NULL);"
+    );
+    let close = format!(
+        "UPDATE my_table_history SET valid_to = {now} WHERE id = old.id AND valid_to IS NULL;"
+    );
+
+    assert_eq!(
+        QueryBuilder::new(schema(&history)).create_history()?,
+        vec![
+            format!(
+                "CREATE TABLE my_table_history AS SELECT id, col1, col2, col3, {now} AS valid_from, \
+                 NULL AS valid_to FROM my_table"
+            ),
+            "CREATE INDEX my_table_history_versions ON my_table_history(id, valid_to)".to_string(),
+            format!(
+                "CREATE TRIGGER my_table_history_insert AFTER INSERT ON my_table BEGIN {open} END"
+            ),
+            format!(
+                "CREATE TRIGGER my_table_history_update AFTER UPDATE ON my_table \
+                 WHEN old.id IS NOT new.id OR old.col1 IS NOT new.col1 \
+                 OR old.col2 IS NOT new.col2 OR old.col3 IS NOT new.col3 BEGIN {close} {open} END"
+            ),
+            format!(
+                "CREATE TRIGGER my_table_history_delete AFTER DELETE ON my_table BEGIN {close} END"
+            ),
+        ]
+    );
+
+    let unversioned = registry(false);
+    assert!(matches!(
+        QueryBuilder::new(schema(&unversioned)).create_history(),
+        Err(Error::QueryValidationFailure { attribute, .. }) if attribute == "as_of"
+    ));
+    Ok(())
+}
+
 // --- Unsatisfiable filters ---
 
 #[test]
@@ -33,8 +33,9 @@ mod tests {
         connection_manager::ConnectionManager,
         error::Error,
         query_parameters::{FilterParameters, FilterValue, QueryParameters},
+        record::SearchExcerpts,
         registry::Registry,
-        schema::{AttributeType, Schema, SchemaBuilder},
+        schema::{AttributeType, IdentifierType, Related, Schema, SchemaBuilder},
         table::Table,
     };
     use crate::http_wrappers::Uri;
@@ -47,7 +48,7 @@ mod tests {
             .attribute("col1", AttributeType::Text)
             .attribute("col2", AttributeType::Text)
             .attribute("col3", AttributeType::Integer)
-            .text_index()
+            .indexed(["col1", "col2"])
     }
 
     fn schema(manager: &Manager) -> &Schema<'_> {
@@ -209,6 +210,563 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_query_by_scoped_prefix_and_literal_search() -> Result<(), Box<dyn StdError>> {
+        let manager = seeded_manager()?;
+        let connection = manager.acquire()?;
+        let table = manager.table("my_table", &connection)?;
+
+        let found = |query: &str| -> Result<Vec<Attribute>, Box<dyn StdError>> {
+            let uri = mock_uri(query);
+            let rows = table.query(&QueryParameters::parse(
+                &uri,
+                schema(&manager),
+                manager.registry(),
+            )?)?;
+            Ok(rows
+                .into_iter()
+                .filter_map(|mut row| row.swap_remove("col3"))
+                .collect())
+        };
+
+        assert_eq!(found("search[col1]=five")?, vec![Attribute::Integer(1000)]);
+        assert_eq!(found("search[col2]=five")?, vec![Attribute::Integer(-1000)]);
+        assert_eq!(
+            found("search[col2]=jump*")?,
+            vec![Attribute::Integer(42), Attribute::Integer(1000)]
+        );
+        // FTS5 syntax in a term is matched literally rather than failing the query.
+        assert_eq!(found("search=AND,NEAR(")?, vec![]);
+        assert_eq!(found("search=%22box")?, vec![]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_search_a_table_through_a_created_index() -> Result<(), Box<dyn StdError>> {
+        let manager: Manager = ConnectionManager::new(
+            Registry::try_new([SchemaBuilder::table("notes")
+                .primary_key("note_id", IdentifierType::Integer)
+                .attribute("body", AttributeType::Text)
+                .indexed(["body"])])?,
+            Pool::memory()?,
+        );
+        let connection = manager.acquire()?;
+        connection.execute_batch(
+            "CREATE TABLE notes (note_id INTEGER PRIMARY KEY, body TEXT); \
+             INSERT INTO notes (note_id, body) VALUES (1, 'written before the index');",
+        )?;
+
+        let schema = manager
+            .registry()
+            .schema("notes")
+            .expect("notes are registered");
+        let table = manager.table("notes", &connection)?;
+        table.create_text_index()?;
+
+        for (id, body) in [(2, "an index entry"), (3, "another index entry")] {
+            table.insert(
+                Row::from_iter([
+                    ("note_id", Attribute::Integer(id)),
+                    ("body", Attribute::Text(body.to_string())),
+                ]),
+                &QueryParameters::new(schema),
+            )?;
+        }
+        table.update(
+            Identifier::Integer(2),
+            Row::from_iter([("body", Attribute::Text("a rewritten entry".to_string()))]),
+            &QueryParameters::new(schema),
+        )?;
+        table.delete(Identifier::Integer(3))?;
+
+        let found = |query: &str| -> Result<Vec<Attribute>, Box<dyn StdError>> {
+            let uri = mock_uri(query);
+            let rows = table.query(&QueryParameters::parse(&uri, schema, manager.registry())?)?;
+            Ok(rows
+                .into_iter()
+                .filter_map(|mut row| row.swap_remove("note_id"))
+                .collect())
+        };
+
+        assert_eq!(found("search=written")?, vec![Attribute::Integer(1)]);
+        assert_eq!(found("search=rewritten")?, vec![Attribute::Integer(2)]);
+        assert_eq!(found("search=index%20entry")?, vec![]);
+
+        let uri = mock_uri("search=rewritten&meta[highlights]=body");
+        let parameters = QueryParameters::parse(&uri, schema, manager.registry())?;
+        assert_eq!(
+            table.excerpts(
+                [Attribute::Integer(2)].into(),
+                parameters.search.as_ref().expect("search is requested"),
+                &parameters.excerpts,
+            )?,
+            vec![(
+                Attribute::Integer(2),
+                SearchExcerpts {
+                    snippets: [].into(),
+                    highlights: [("body", "a <mark>rewritten</mark> entry".to_string())].into(),
+                },
+            )]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_search_a_text_keyed_table_through_a_created_index() -> Result<(), Box<dyn StdError>> {
+        let manager: Manager = ConnectionManager::new(
+            Registry::try_new([SchemaBuilder::table("notes")
+                .primary_key("slug", IdentifierType::Text)
+                .attribute("body", AttributeType::Text)
+                .indexed(["body"])])?,
+            Pool::memory()?,
+        );
+        let connection = manager.acquire()?;
+        connection.execute_batch(
+            "CREATE TABLE notes (slug TEXT PRIMARY KEY, body TEXT); \
+             INSERT INTO notes (slug, body) VALUES ('before', 'written before the index');",
+        )?;
+
+        let schema = manager
+            .registry()
+            .schema("notes")
+            .expect("notes are registered");
+        let table = manager.table("notes", &connection)?;
+        table.create_text_index()?;
+
+        for (slug, body) in [
+            ("kept", "an index entry"),
+            ("dropped", "another index entry"),
+            ("draft", "a renamed entry"),
+        ] {
+            table.insert(
+                Row::from_iter([
+                    ("slug", Attribute::Text(slug.to_string())),
+                    ("body", Attribute::Text(body.to_string())),
+                ]),
+                &QueryParameters::new(schema),
+            )?;
+        }
+        table.update(
+            Identifier::Text("kept".to_string()),
+            Row::from_iter([("body", Attribute::Text("a rewritten entry".to_string()))]),
+            &QueryParameters::new(schema),
+        )?;
+        table.delete(Identifier::Text("dropped".to_string()))?;
+        // A renamed key keeps its number, and a `VACUUM` renumbers nothing the index holds.
+        connection
+            .execute_batch("UPDATE notes SET slug = 'published' WHERE slug = 'draft'; VACUUM;")?;
+
+        let found = |query: &str| -> Result<Vec<Attribute>, Box<dyn StdError>> {
+            let uri = mock_uri(query);
+            let rows = table.query(&QueryParameters::parse(&uri, schema, manager.registry())?)?;
+            Ok(rows
+                .into_iter()
+                .filter_map(|mut row| row.swap_remove("slug"))
+                .collect())
+        };
+        let slug = |slug: &str| Attribute::Text(slug.to_string());
+
+        assert_eq!(found("search=written")?, vec![slug("before")]);
+        assert_eq!(found("search=rewritten")?, vec![slug("kept")]);
+        assert_eq!(found("search=renamed")?, vec![slug("published")]);
+        assert_eq!(found("search=index%20entry")?, vec![]);
+
+        let uri = mock_uri("search=rewritten&meta[highlights]=body");
+        let parameters = QueryParameters::parse(&uri, schema, manager.registry())?;
+        assert_eq!(
+            table.excerpts(
+                [slug("kept")].into(),
+                parameters.search.as_ref().expect("search is requested"),
+                &parameters.excerpts,
+            )?,
+            vec![(
+                slug("kept"),
+                SearchExcerpts {
+                    snippets: [].into(),
+                    highlights: [("body", "a <mark>rewritten</mark> entry".to_string())].into(),
+                },
+            )]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_read_a_table_as_of_an_instant_through_a_created_history()
+    -> Result<(), Box<dyn StdError>> {
+        let manager: Manager = ConnectionManager::new(
+            Registry::try_new([SchemaBuilder::table("notes")
+                .attribute("body", AttributeType::Text)
+                .keeps_history()])?,
+            Pool::memory()?,
+        );
+        let connection = manager.acquire()?;
+        connection.execute_batch(
+            "CREATE TABLE notes (id INTEGER PRIMARY KEY, body TEXT); \
+             INSERT INTO notes (id, body) VALUES (1, 'written before the history');",
+        )?;
+
+        let schema = manager
+            .registry()
+            .schema("notes")
+            .expect("notes are registered");
+        let table = manager.table("notes", &connection)?;
+        table.create_history()?;
+        // Each phase of writes is stamped with a fixed instant, so the reads between them are
+        // pinned rather than timed.
+        let stamp = |previous: &str, instant: &str| {
+            connection.execute_batch(&format!(
+                "UPDATE notes_history SET valid_from = '{instant}' WHERE valid_from > '{previous}'; \
+                 UPDATE notes_history SET valid_to = '{instant}' WHERE valid_to > '{previous}';"
+            ))
+        };
+        stamp("", "2001-01-01T00:00:00.000+00:00")?;
+        let body = |body: &str| Row::from_iter([("body", Attribute::Text(body.to_string()))]);
+
+        table.insert(
+            Row::from_iter([
+                ("id", Attribute::Integer(2)),
+                ("body", Attribute::Text("written after".to_string())),
+            ]),
+            &QueryParameters::new(schema),
+        )?;
+        table.update(
+            Identifier::Integer(1),
+            body("rewritten"),
+            &QueryParameters::new(schema),
+        )?;
+        stamp(
+            "2001-01-01T00:00:00.000+00:00",
+            "2001-01-02T00:00:00.000+00:00",
+        )?;
+        table.delete(Identifier::Integer(2))?;
+        table.update(
+            Identifier::Integer(1),
+            body("rewritten"),
+            &QueryParameters::new(schema),
+        )?;
+        stamp(
+            "2001-01-02T00:00:00.000+00:00",
+            "2001-01-03T00:00:00.000+00:00",
+        )?;
+        let (before_history, before_writes, after_writes, after_delete) = (
+            "2000-12-31T12:00:00Z",
+            "2001-01-01T12:00:00Z",
+            "2001-01-02T12:00:00Z",
+            "2001-01-03T12:00:00Z",
+        );
+
+        let held = |query: &str| -> Result<Vec<(i64, Attribute)>, Box<dyn StdError>> {
+            let uri = mock_uri(query);
+            let rows = table.query(&QueryParameters::parse(&uri, schema, manager.registry())?)?;
+            let mut held: Vec<_> = rows
+                .into_iter()
+                .filter_map(|mut row| {
+                    let id = row.swap_remove("id")?.to_i64().ok()?;
+                    Some((id, row.swap_remove("body")?))
+                })
+                .collect();
+            held.sort_by_key(|&(id, _)| id);
+            Ok(held)
+        };
+        let note = |id: i64, body: &str| (id, Attribute::Text(body.to_string()));
+
+        assert_eq!(held(&format!("filter[as_of]={before_history}"))?, vec![]);
+        assert_eq!(
+            held(&format!("filter[as_of]={before_writes}"))?,
+            vec![note(1, "written before the history")]
+        );
+        assert_eq!(
+            held(&format!("filter[as_of]={after_writes}"))?,
+            vec![note(1, "rewritten"), note(2, "written after")]
+        );
+        assert_eq!(
+            held(&format!("filter[as_of]={after_delete}"))?,
+            vec![note(1, "rewritten")]
+        );
+        assert_eq!(held("")?, vec![note(1, "rewritten")]);
+
+        let find_as_of = |id: i64, instant: &str| {
+            let uri = mock_uri(&format!("filter[as_of]={instant}"));
+            let parameters = QueryParameters::parse(&uri, schema, manager.registry())?;
+            table.find(Identifier::Integer(id), &parameters)
+        };
+        assert_eq!(
+            find_as_of(2, after_writes)?.swap_remove("body"),
+            Some(Attribute::Text("written after".to_string()))
+        );
+        assert!(matches!(
+            find_as_of(2, after_delete),
+            Err(Error::RecordNotFound)
+        ));
+
+        // An update changing nothing keeps the open version.
+        let versions = connection.query_values(
+            "SELECT COUNT(*) FROM notes_history".to_string(),
+            Vec::new(),
+            schema,
+            &[AttributeType::Integer],
+        )?;
+        assert_eq!(versions, vec![vec![Attribute::Integer(3)]]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_sort_through_a_has_one_keeps_each_record_once() -> Result<(), Box<dyn StdError>> {
+        let manager: Manager = ConnectionManager::new(
+            Registry::try_new([
+                SchemaBuilder::table("parents")
+                    .attribute("name", AttributeType::Text)
+                    .has_one(
+                        "child",
+                        Related::to("children")
+                            .pointing_related("parent_id")
+                            .to_own("id"),
+                    ),
+                SchemaBuilder::table("children")
+                    .attribute("name", AttributeType::Text)
+                    .foreign_key("parent_id", AttributeType::Integer),
+            ])?,
+            Pool::memory()?,
+        );
+        let connection = manager.acquire()?;
+        // Nothing keeps `parent_id` unique, so two children point at the first parent.
+        connection.execute_batch(
+            "CREATE TABLE parents (id INTEGER PRIMARY KEY, name TEXT); \
+             CREATE TABLE children (id INTEGER PRIMARY KEY, parent_id INTEGER, name TEXT); \
+             INSERT INTO parents (id, name) VALUES (1, 'first'), (2, 'second'); \
+             INSERT INTO children (id, parent_id, name) \
+               VALUES (1, 1, 'a'), (2, 1, 'b'), (3, 2, 'c');",
+        )?;
+
+        let schema = manager
+            .registry()
+            .schema("parents")
+            .expect("parents are registered");
+        let table = manager.table("parents", &connection)?;
+        let ids = |query: &str| -> Result<Vec<Attribute>, Box<dyn StdError>> {
+            let uri = format!("http://host.com/parents?{query}").parse::<Uri>()?;
+            let parameters = QueryParameters::parse(&uri, schema, manager.registry())?;
+            Ok(table
+                .query(&parameters)?
+                .into_iter()
+                .filter_map(|mut row| row.swap_remove("id"))
+                .collect())
+        };
+
+        assert_eq!(
+            ids("sort=-child.name")?,
+            vec![Attribute::Integer(2), Attribute::Integer(1)]
+        );
+        assert_eq!(
+            ids("sort=child.name,name&page[number]=2&page[size]=1")?,
+            vec![Attribute::Integer(2)]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_query_by_relevance() -> Result<(), Box<dyn StdError>> {
+        let manager = seeded_manager()?;
+        let connection = manager.acquire()?;
+        let table = manager.table("my_table", &connection)?;
+
+        let ranked = |query: &str| -> Result<Vec<Attribute>, Box<dyn StdError>> {
+            let uri = mock_uri(query);
+            let rows = table.query(&QueryParameters::parse(
+                &uri,
+                schema(&manager),
+                manager.registry(),
+            )?)?;
+            Ok(rows
+                .into_iter()
+                .filter_map(|mut row| row.swap_remove("col3"))
+                .collect())
+        };
+
+        // "five" makes up more of the first record's indexed text than of the second's.
+        assert_eq!(
+            ranked("search=five&sort=-relevance")?,
+            vec![Attribute::Integer(1000), Attribute::Integer(-1000)]
+        );
+        assert_eq!(
+            ranked("search=five&sort=relevance")?,
+            vec![Attribute::Integer(-1000), Attribute::Integer(1000)]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_excerpts_of_matching_records() -> Result<(), Box<dyn StdError>> {
+        let manager = seeded_manager()?;
+        let connection = manager.acquire()?;
+        let uri = mock_uri("search=five&meta[snippets]=col2&meta[highlights]=col1");
+        let parameters = QueryParameters::parse(&uri, schema(&manager), manager.registry())?;
+        let excerpts = manager.table("my_table", &connection)?.excerpts(
+            [
+                Attribute::Integer(1),
+                Attribute::Integer(2),
+                Attribute::Integer(3),
+            ]
+            .into(),
+            parameters.search.as_ref().expect("search is requested"),
+            &parameters.excerpts,
+        )?;
+
+        // The first record does not match and is left out.
+        assert_eq!(
+            excerpts,
+            vec![
+                (
+                    Attribute::Integer(2),
+                    SearchExcerpts {
+                        snippets: [("col2", "jump quickly".to_string())].into(),
+                        highlights: [("col1", "The <mark>five</mark> boxing wizards".to_string())]
+                            .into(),
+                    },
+                ),
+                (
+                    Attribute::Integer(3),
+                    SearchExcerpts {
+                        snippets: [("col2", "with <mark>five</mark> dozen li…".to_string())].into(),
+                        highlights: [("col1", "Pack my box".to_string())].into(),
+                    },
+                ),
+            ]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_excerpts_escape_the_stored_text() -> Result<(), Box<dyn StdError>> {
+        let manager: Manager = ConnectionManager::new(
+            Registry::try_new([SchemaBuilder::table("notes")
+                .attribute("body", AttributeType::Text)
+                .indexed(["body"])])?,
+            Pool::memory()?,
+        );
+        let connection = manager.acquire()?;
+        connection.execute_batch(
+            "CREATE TABLE notes (id INTEGER PRIMARY KEY, body TEXT); \
+             INSERT INTO notes (id, body) VALUES (1, '<script>five</script> & \"five\"');",
+        )?;
+        let table = manager.table("notes", &connection)?;
+        table.create_text_index()?;
+
+        let schema = manager
+            .registry()
+            .schema("notes")
+            .expect("notes are registered");
+        let uri = mock_uri("search=five&meta[highlights]=body");
+        let parameters = QueryParameters::parse(&uri, schema, manager.registry())?;
+        assert_eq!(
+            table.excerpts(
+                [Attribute::Integer(1)].into(),
+                parameters.search.as_ref().expect("search is requested"),
+                &parameters.excerpts,
+            )?,
+            vec![(
+                Attribute::Integer(1),
+                SearchExcerpts {
+                    snippets: [].into(),
+                    highlights: [(
+                        "body",
+                        "&lt;script&gt;<mark>five</mark>&lt;/script&gt; &amp; &quot;<mark>five</mark>&quot;"
+                            .to_string()
+                    )]
+                    .into(),
+                },
+            )]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_query_text_matching() -> Result<(), Box<dyn StdError>> {
+        let manager = manager();
+        let connection = manager.acquire()?;
+        let table = manager.table("my_table", &connection)?;
+
+        for (col1, col3) in [
+            ("ÉCOLE", 1),
+            ("école", 2),
+            ("Ecole", 3),
+            ("50% off", 4),
+            ("500 off", 5),
+        ] {
+            table.insert(
+                Row::from_iter([
+                    ("col1", Attribute::Text(col1.to_string())),
+                    ("col3", Attribute::Integer(col3)),
+                ]),
+                &QueryParameters::new(schema(&manager)),
+            )?;
+        }
+
+        let matching = |query: &str| -> Result<Vec<Attribute>, Box<dyn StdError>> {
+            let uri = mock_uri(&format!("{query}&sort=col3"));
+            let rows = table.query(&QueryParameters::parse(
+                &uri,
+                schema(&manager),
+                manager.registry(),
+            )?)?;
+            Ok(rows
+                .into_iter()
+                .filter_map(|mut row| row.swap_remove("col3"))
+                .collect())
+        };
+
+        assert_eq!(
+            matching("filter[col1]=ieq:%C3%A9cole")?,
+            vec![Attribute::Integer(1), Attribute::Integer(2)]
+        );
+        assert_eq!(
+            matching("filter[col1]=ilike:COL")?,
+            vec![
+                Attribute::Integer(1),
+                Attribute::Integer(2),
+                Attribute::Integer(3)
+            ]
+        );
+        assert_eq!(
+            matching("filter[col1]=like:col")?,
+            vec![
+                Attribute::Integer(1),
+                Attribute::Integer(2),
+                Attribute::Integer(3)
+            ]
+        );
+        assert_eq!(
+            matching("filter[col1]=like:0%25")?,
+            vec![Attribute::Integer(4)]
+        );
+        assert_eq!(
+            matching("filter[col1]=starts:E")?,
+            vec![Attribute::Integer(3)]
+        );
+        assert_eq!(
+            matching("filter[col1]=ends:OLE")?,
+            vec![Attribute::Integer(1)]
+        );
+        assert_eq!(
+            matching("filter[col1]=ilike:0%25")?,
+            vec![Attribute::Integer(4)]
+        );
+        assert_eq!(
+            matching("filter[col1]=starts:50_")?,
+            Vec::<Attribute>::new()
+        );
+
+        Ok(())
+    }
+
     #[test]
     fn test_first() -> Result<(), Box<dyn StdError>> {
         let manager = seeded_manager()?;
@@ -108,6 +108,8 @@ pub enum Attribute {
     Float(f64),
     Boolean(bool),
     DateTime(DateTime),
+    /// A JSON document, held as written: an object, an array or a scalar.
+    Json(Value),
 }
 
 impl PartialEq for Attribute {
@@ -121,6 +123,7 @@ impl PartialEq for Attribute {
             }
             (Attribute::Boolean(a), Attribute::Boolean(b)) => a == b,
             (Attribute::DateTime(a), Attribute::DateTime(b)) => a == b,
+            (Attribute::Json(a), Attribute::Json(b)) => a == b,
             _ => false,
         }
     }
@@ -137,6 +140,7 @@ impl Hash for Attribute {
             Attribute::Float(value) => format!("{:.12}", value).hash(state),
             Attribute::Boolean(value) => value.hash(state),
             Attribute::DateTime(value) => value.hash(state),
+            Attribute::Json(value) => value.to_string().hash(state),
         }
     }
 }
@@ -163,6 +167,11 @@ impl Attribute {
                     })?
                     .to_utc(),
             ),
+            AttributeType::Json => Attribute::Json(serde_json::from_str(value).map_err(|_| {
+                Error::InvalidAttributeConversion {
+                    kind: "Json".to_string(),
+                }
+            })?),
         };
 
         Ok(attribute)
@@ -272,6 +281,7 @@ impl Display for Attribute {
             Attribute::Float(float) => f.write_str(float.to_string().as_str()),
             Attribute::Boolean(boolean) => f.write_str(boolean.to_string().as_str()),
             Attribute::DateTime(datetime) => f.write_str(datetime.to_string().as_str()),
+            Attribute::Json(value) => f.write_str(value.to_string().as_str()),
         }
     }
 }
@@ -296,6 +306,7 @@ impl From<Attribute> for Value {
                 .unwrap_or(Value::Null),
             Attribute::Boolean(value) => Value::Bool(value),
             Attribute::DateTime(value) => Value::String(value.to_rfc3339()),
+            Attribute::Json(value) => value,
         }
     }
 }
@@ -308,6 +319,7 @@ impl From<&Attribute> for Option<AttributeType> {
             Attribute::Float(_) => AttributeType::Float,
             Attribute::Boolean(_) => AttributeType::Boolean,
             Attribute::DateTime(_) => AttributeType::DateTime,
+            Attribute::Json(_) => AttributeType::Json,
             _ => return None,
         };
 
@@ -354,6 +366,7 @@ fn attribute_from_value(
 ) -> Result<Attribute, Error> {
     match value {
         Value::Null => Ok(Attribute::Null),
+        value if attribute_type == AttributeType::Json => Ok(Attribute::Json(value)),
         Value::String(value) => match attribute_type {
             AttributeType::Text => Ok(Attribute::Text(value)),
             AttributeType::DateTime => Ok(Attribute::DateTime(date_time_from_rfc3339(
@@ -494,6 +507,7 @@ mod tests {
                 .attribute("active", AttributeType::Boolean),
             SchemaBuilder::table("temporal").attribute("timestamp", AttributeType::DateTime),
             SchemaBuilder::table("flagged").attribute("flag", AttributeType::Boolean),
+            SchemaBuilder::table("documented").attribute("document", AttributeType::Json),
         ])
         .expect("schema set is consistent")
     }
@@ -531,6 +545,23 @@ mod tests {
         assert!(null.as_i64().is_err());
     }
 
+    #[test]
+    fn test_a_json_attribute_is_held_as_written() -> Result<(), Error> {
+        let registry = registry();
+        let schema = registry.schema("documented")?;
+        let document = json!({ "tags": ["a", "b"], "depth": 2 });
+
+        let attributes = from_value(schema, json!({ "document": document.clone() }))?;
+        assert_eq!(attributes["document"], Attribute::Json(document.clone()));
+        assert_eq!(Value::from(attributes["document"].clone()), document);
+        assert_eq!(
+            Attribute::parse(&document.to_string(), AttributeType::Json)?,
+            Attribute::Json(document)
+        );
+
+        Ok(())
+    }
+
     #[test]
     fn test_from_value_success_and_failures() {
         let registry = registry();
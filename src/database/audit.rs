@@ -0,0 +1,274 @@
+//! The audit log: an opt-in trail of the writes made to the resources a schema declares `audited()`.
+//! The store appends one entry per change it publishes (see `events`), in the transaction making the
+//! write, so an entry is kept exactly when its write is. The log is itself a registered resource, so
+//! it can be read — and mounted read-only — like any other.
+
+use super::attributes::{Attribute, Identifier, Row};
+use super::events::Change;
+use super::schema::{AttributeType, DateTime, Schema, SchemaBuilder};
+use serde_json::{Map, Value, json};
+
+/// The resource type, and table, of the audit log.
+pub const AUDIT_LOG: &str = "audit_log";
+
+/// The audit log's schema, registered alongside any audited resource. Each entry holds the
+/// `resource` type and `record_id` of the record written; the `operation` — `create`, `update`,
+/// `delete`, `link` or `unlink`; the `changes` as a JSON object, mapping each attribute written to
+/// its `old` and `new` values, or the relationship written to the members `added` or `removed`; the
+/// `actor` the request named, if any; and when it was `recorded_at`.
+pub fn audit_log() -> SchemaBuilder<'static> {
+    SchemaBuilder::table(AUDIT_LOG)
+        .attribute("resource", AttributeType::Text)
+        .attribute("record_id", AttributeType::Text)
+        .attribute("operation", AttributeType::Text)
+        .attribute("changes", AttributeType::Json)
+        .attribute("actor", AttributeType::Text)
+        .attribute("recorded_at", AttributeType::DateTime)
+}
+
+/// The audit entry of `change` to the record of `schema` at `id`, given its stored row `before`
+/// and `after` the write. Only the columns whose value it changed are listed.
+pub(crate) fn entry(
+    schema: &Schema,
+    id: &Identifier,
+    change: &Change,
+    before: Option<Row>,
+    after: Option<Row>,
+    actor: Option<&str>,
+    recorded_at: DateTime,
+) -> Row<'static> {
+    let (operation, changes) = match change {
+        Change::Created => ("create", attribute_changes(schema, before, after)),
+        Change::Updated => ("update", attribute_changes(schema, before, after)),
+        Change::Deleted => ("delete", attribute_changes(schema, before, after)),
+        Change::Linked {
+            relationship,
+            related,
+        } => ("link", linkage_changes(relationship, "added", related)),
+        Change::Unlinked {
+            relationship,
+            related,
+        } => ("unlink", linkage_changes(relationship, "removed", related)),
+    };
+
+    Row::from_iter([
+        ("resource", Attribute::Text(schema.name().to_string())),
+        ("record_id", Attribute::Text(id.to_string())),
+        ("operation", Attribute::Text(operation.to_string())),
+        ("changes", Attribute::Json(Value::Object(changes))),
+        (
+            "actor",
+            actor.map_or(Attribute::Null, |actor| Attribute::Text(actor.to_string())),
+        ),
+        ("recorded_at", Attribute::DateTime(recorded_at)),
+    ])
+}
+
+fn attribute_changes(
+    schema: &Schema,
+    before: Option<Row>,
+    after: Option<Row>,
+) -> Map<String, Value> {
+    let before = before.unwrap_or_default();
+    let mut after = after.unwrap_or_default();
+    let mut changes = Map::new();
+
+    for (column, old) in before {
+        let new = after.shift_remove(column).unwrap_or(Attribute::Null);
+        if old != new && !schema.is_primary_key(column) {
+            changes.insert(column.to_string(), json!({"old": old, "new": new}));
+        }
+    }
+    for (column, new) in after {
+        if new != Attribute::Null && !schema.is_primary_key(column) {
+            changes.insert(column.to_string(), json!({"old": null, "new": new}));
+        }
+    }
+
+    changes
+}
+
+fn linkage_changes(
+    relationship: &str,
+    direction: &str,
+    related: &[Identifier],
+) -> Map<String, Value> {
+    Map::from_iter([(relationship.to_string(), json!({ direction: related }))])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::error::Error;
+    use crate::database::registry::Registry;
+    use chrono::TimeZone;
+
+    fn registry() -> Registry<'static> {
+        Registry::try_new([
+            SchemaBuilder::table("notes")
+                .attribute("body", AttributeType::Text)
+                .attribute("pinned", AttributeType::Boolean)
+                .audited(),
+            audit_log(),
+        ])
+        .expect("the schemas are consistent")
+    }
+
+    fn recorded_at() -> DateTime {
+        chrono::Utc
+            .with_ymd_and_hms(2026, 3, 1, 9, 30, 0)
+            .single()
+            .expect("the instant is valid")
+    }
+
+    fn note(body: &str, pinned: bool) -> Row<'static> {
+        Row::from_iter([
+            ("id", Attribute::Integer(7)),
+            ("body", Attribute::Text(body.to_string())),
+            ("pinned", Attribute::Boolean(pinned)),
+        ])
+    }
+
+    fn expected(operation: &str, changes: Value, actor: Attribute) -> Row<'static> {
+        Row::from_iter([
+            ("resource", Attribute::Text("notes".to_string())),
+            ("record_id", Attribute::Text("7".to_string())),
+            ("operation", Attribute::Text(operation.to_string())),
+            ("changes", Attribute::Json(changes)),
+            ("actor", actor),
+            ("recorded_at", Attribute::DateTime(recorded_at())),
+        ])
+    }
+
+    #[test]
+    fn test_a_create_lists_every_column_set() -> Result<(), Box<dyn std::error::Error>> {
+        let registry = registry();
+        let entry = entry(
+            registry.schema("notes")?,
+            &Identifier::Integer(7),
+            &Change::Created,
+            None,
+            Some(note("Hello", false)),
+            Some("ada"),
+            recorded_at(),
+        );
+
+        let changes = json!({
+            "body": {"old": null, "new": "Hello"},
+            "pinned": {"old": null, "new": false},
+        });
+        assert_eq!(
+            entry,
+            expected("create", changes, Attribute::Text("ada".to_string()))
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_an_update_lists_only_the_columns_it_changed() -> Result<(), Box<dyn std::error::Error>>
+    {
+        let registry = registry();
+        let entry = entry(
+            registry.schema("notes")?,
+            &Identifier::Integer(7),
+            &Change::Updated,
+            Some(note("Hello", false)),
+            Some(note("Hello", true)),
+            None,
+            recorded_at(),
+        );
+
+        let changes = json!({"pinned": {"old": false, "new": true}});
+        assert_eq!(entry, expected("update", changes, Attribute::Null));
+        Ok(())
+    }
+
+    #[test]
+    fn test_a_delete_lists_every_column_cleared() -> Result<(), Box<dyn std::error::Error>> {
+        let registry = registry();
+        let entry = entry(
+            registry.schema("notes")?,
+            &Identifier::Integer(7),
+            &Change::Deleted,
+            Some(note("Hello", true)),
+            None,
+            None,
+            recorded_at(),
+        );
+
+        let changes = json!({
+            "body": {"old": "Hello", "new": null},
+            "pinned": {"old": true, "new": null},
+        });
+        assert_eq!(entry, expected("delete", changes, Attribute::Null));
+        Ok(())
+    }
+
+    #[test]
+    fn test_a_linkage_change_lists_the_members_added_or_removed()
+    -> Result<(), Box<dyn std::error::Error>> {
+        let registry = registry();
+        let schema = registry.schema("notes")?;
+        let linked = entry(
+            schema,
+            &Identifier::Integer(7),
+            &Change::Linked {
+                relationship: "tags".to_string(),
+                related: vec![Identifier::Integer(1), Identifier::Integer(2)],
+            },
+            None,
+            None,
+            None,
+            recorded_at(),
+        );
+        let unlinked = entry(
+            schema,
+            &Identifier::Integer(7),
+            &Change::Unlinked {
+                relationship: "tags".to_string(),
+                related: vec![Identifier::Text("x".to_string())],
+            },
+            None,
+            None,
+            None,
+            recorded_at(),
+        );
+
+        assert_eq!(
+            linked,
+            expected("link", json!({"tags": {"added": [1, 2]}}), Attribute::Null)
+        );
+        assert_eq!(
+            unlinked,
+            expected(
+                "unlink",
+                json!({"tags": {"removed": ["x"]}}),
+                Attribute::Null
+            )
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_an_audited_resource_requires_the_audit_log() {
+        let notes = || SchemaBuilder::table("notes").audited();
+        let altered = audit_log().attribute("reason", AttributeType::Text);
+
+        for schemas in [vec![notes()], vec![notes(), altered]] {
+            assert_eq!(
+                Registry::try_new(schemas).err(),
+                Some(Error::MissingAuditLog {
+                    schema: "notes".to_string()
+                })
+            );
+        }
+    }
+
+    #[test]
+    fn test_the_audit_log_cannot_be_audited() {
+        assert_eq!(
+            Registry::try_new([audit_log().audited()]).err(),
+            Some(Error::AuditedAuditLog)
+        );
+    }
+}
@@ -1,7 +1,8 @@
 use super::{
     attributes::{Attribute, Attributes},
     error::Error,
-    schema::Schema,
+    events::ChangeBuffer,
+    schema::{AttributeType, Schema},
 };
 
 pub trait Connection {
@@ -12,10 +13,33 @@ pub trait Connection {
         schema: &'sch Schema<'sch>,
     ) -> Result<Vec<Attributes<'sch>>, Error>;
 
+    /// Runs a query whose columns are not `schema`'s own — grouped keys, counts and other computed
+    /// values — reading each row positionally, its columns as the types listed in `kinds`.
+    fn query_values(
+        &self,
+        query: String,
+        bindings: Vec<Attribute>,
+        schema: &Schema,
+        kinds: &[AttributeType],
+    ) -> Result<Vec<Vec<Attribute>>, Error>;
+
     /// Runs a non-returning statement and reports how many rows it affected.
     fn execute(&self, query: String, bindings: Vec<Attribute>) -> Result<usize, Error>;
 
+    /// The buffer holding the change events published by writes on this connection until the
+    /// transaction making them commits.
+    fn changes(&self) -> &ChangeBuffer;
+
     /// Runs `operation` inside a database transaction, committing on `Ok` and rolling back on
     /// `Err` or panic.
-    fn transaction<R>(&self, operation: impl FnOnce() -> Result<R, Error>) -> Result<R, Error>;
+    fn transaction<R>(&self, operation: impl FnOnce() -> Result<R, Error>) -> Result<R, Error> {
+        self.transaction_with(operation)
+    }
+
+    /// `transaction` for an operation failing with an error of its own, into which the
+    /// transaction's own failures convert.
+    fn transaction_with<R, E: From<Error>>(
+        &self,
+        operation: impl FnOnce() -> Result<R, E>,
+    ) -> Result<R, E>;
 }
@@ -1,7 +1,9 @@
 use super::{
-    adapters::Adapter as AdapterInterface, error::Error, pool::Pool as PoolInterface,
-    registry::Registry, table::Table as TableInterface,
+    adapters::Adapter as AdapterInterface, connection::Connection as ConnectionInterface,
+    error::Error, events::ChangeSubscriber, pool::Pool as PoolInterface, registry::Registry,
+    table::Table as TableInterface,
 };
+use std::sync::Arc;
 
 /// Binds a validated `Registry` to a connection pool. Owns both — the registry is
 /// moved in pre-built — and is the request path's single handle: it lends schemas
@@ -9,12 +11,23 @@ use super::{
 pub struct ConnectionManager<'sch, Adapter: AdapterInterface> {
     registry: Registry<'sch>,
     pool: Adapter::Pool,
+    subscribers: Vec<Arc<dyn ChangeSubscriber>>,
 }
 
 impl<'sch, Adapter: AdapterInterface> ConnectionManager<'sch, Adapter> {
     /// Binds an already-validated registry to a pool.
     pub fn new(registry: Registry<'sch>, pool: Adapter::Pool) -> Self {
-        Self { registry, pool }
+        Self {
+            registry,
+            pool,
+            subscribers: Vec::new(),
+        }
+    }
+
+    /// Registers a subscriber to the changes committed through every connection acquired after.
+    pub fn with_subscriber(mut self, subscriber: impl ChangeSubscriber + 'static) -> Self {
+        self.subscribers.push(Arc::new(subscriber));
+        self
     }
 
     /// The underlying schema collection, for consumers that need only schemas.
@@ -24,7 +37,9 @@ impl<'sch, Adapter: AdapterInterface> ConnectionManager<'sch, Adapter> {
 
     /// Acquires a connection from the pool, held for the request.
     pub fn acquire(&self) -> Result<Adapter::Connection, Error> {
-        self.pool.acquire()
+        let connection = self.pool.acquire()?;
+        connection.changes().deliver_to(&self.subscribers);
+        Ok(connection)
     }
 
     /// Builds a request-scoped table bound to `connection`. The schema reference
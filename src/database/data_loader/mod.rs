@@ -6,7 +6,7 @@ use super::{
     attributes::Attribute,
     connection_manager::ConnectionManager,
     error::Error,
-    query_parameters::{FilterValue::In, QueryParameters},
+    query_parameters::{FilterValue::In, IncludeNode, QueryParameters, SearchParameters},
     record::Record,
     relationships::Relationship::*,
     schema::{RelatedResource, RelationshipDescriptor, RelationshipKind},
@@ -87,6 +87,105 @@ impl<'sch, 'req, Adapter: AdapterInterface> DataLoader<'sch, 'req, Adapter> {
             self.load_relationship(collection, query_parameters, relationship, descriptor)?;
         }
 
+        // Limited includes are counted too, so their linkage can be told apart from a complete one.
+        // Only relationships in the fieldset are counted, as only those are presented.
+        let limited = query_parameters
+            .include
+            .values()
+            .filter(|node| node.limit.is_some())
+            .map(|node| node.descriptor.name);
+        let counted: IndexSet<&'sch str> = query_parameters
+            .counts
+            .iter()
+            .copied()
+            .chain(limited)
+            .filter(|relationship| query_parameters.is_requested(relationship))
+            .collect();
+
+        for relationship in counted {
+            self.count_relationship(collection, query_parameters, relationship)?;
+        }
+
+        if let Some(search) = &query_parameters.search
+            && !query_parameters.excerpts.is_empty()
+        {
+            self.excerpt_search(collection, search, query_parameters)?;
+        }
+
+        Ok(())
+    }
+
+    /// Renders the requested search excerpts of the whole collection in a single query, storing
+    /// each record's.
+    fn excerpt_search(
+        &self,
+        collection: &mut [Record<'sch>],
+        search: &SearchParameters,
+        query_parameters: &QueryParameters<'sch, 'req>,
+    ) -> Result<(), Error> {
+        let table = self
+            .manager
+            .table(query_parameters.schema.name(), self.connection)?;
+        let ids = collection
+            .iter()
+            .filter_map(|record| record.id.clone().map(Attribute::from))
+            .collect();
+        let mut excerpts: HashMap<Attribute, _> = table
+            .excerpts(ids, search, &query_parameters.excerpts)?
+            .into_iter()
+            .collect();
+
+        for record in collection {
+            if let Some(excerpt) = record
+                .id
+                .clone()
+                .and_then(|id| excerpts.remove(&Attribute::from(id)))
+            {
+                record.excerpts = excerpt;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Counts the related records of a to-many relationship across the whole collection in a single
+    /// grouped query, storing each record's count, zero when it has no related records.
+    fn count_relationship(
+        &self,
+        collection: &mut [Record<'sch>],
+        query_parameters: &QueryParameters<'sch, 'req>,
+        relationship: &'sch str,
+    ) -> Result<(), Error> {
+        let schema = query_parameters.schema;
+        let descriptor = schema
+            .relationship(relationship)
+            .filter(|descriptor| descriptor.kind == RelationshipKind::HasMany)
+            .ok_or_else(|| Error::MismatchedRelationshipKind {
+                schema: schema.name().to_string(),
+                relationship: relationship.to_string(),
+            })?;
+        let keys = &descriptor.related.keys;
+
+        let table = self
+            .manager
+            .table(descriptor.related.resource, self.connection)?;
+        let own_attributes = Self::collection_attribute(collection, keys.own)
+            .into_iter()
+            .flatten()
+            .filter(|attribute| !matches!(attribute, Attribute::Null))
+            .collect();
+        let counts: HashMap<Attribute, u64> = table
+            .count_by(keys.related, own_attributes)?
+            .into_iter()
+            .collect();
+
+        for record in collection {
+            if let Some(attribute) = record.get_owned(keys.own) {
+                let count = counts.get(&attribute).copied().unwrap_or(0);
+                record.counts.insert(relationship, count);
+            }
+        }
+
         Ok(())
     }
 
@@ -127,10 +226,9 @@ impl<'sch, 'req, Adapter: AdapterInterface> DataLoader<'sch, 'req, Adapter> {
                 .entry((record.schema.name(), record.require_id()?.clone()))
             {
                 Entry::Occupied(mut existing) => {
-                    Self::merge_records(
-                        record.relationships,
-                        &mut existing.get_mut().relationships,
-                    )?;
+                    let existing = existing.get_mut();
+                    Self::merge_records(record.relationships, &mut existing.relationships)?;
+                    existing.counts.extend(record.counts);
                 }
                 Entry::Vacant(entry) => {
                     entry.insert(record);
@@ -262,12 +360,25 @@ impl<'sch, 'req, Adapter: AdapterInterface> DataLoader<'sch, 'req, Adapter> {
     ) -> Result<Vec<Record<'sch>>, Error> {
         let table = self.manager.table(descriptor.resource, self.connection)?;
         let own_attributes = Self::collection_attribute(collection, descriptor.keys.own);
-        let related_collection = Self::load_collection_by(
-            &table,
-            descriptor.keys.related,
-            own_attributes.as_slice(),
-            &query_parameters.fields,
-        )?;
+        let related_collection = match query_parameters
+            .include
+            .get(relationship)
+            .filter(|node| node.is_ranked())
+        {
+            Some(node) => Self::load_ranked_by(
+                &table,
+                descriptor.keys.related,
+                own_attributes.as_slice(),
+                node,
+                &query_parameters.fields,
+            )?,
+            None => Self::load_collection_by(
+                &table,
+                descriptor.keys.related,
+                own_attributes.as_slice(),
+                &query_parameters.fields,
+            )?,
+        };
         let mut index = Self::group_by_foreign_key(
             related_collection.as_slice(),
             descriptor.keys.related,
@@ -294,13 +405,7 @@ impl<'sch, 'req, Adapter: AdapterInterface> DataLoader<'sch, 'req, Adapter> {
         attributes: &[Option<Attribute>],
         fields: &FieldsParameters,
     ) -> Result<Vec<Record<'sch>>, Error> {
-        let attributes: IndexSet<Attribute> = attributes
-            .iter()
-            .filter_map(|entry| match entry {
-                None | Some(Attribute::Null) => None,
-                Some(attribute) => Some(attribute.clone()),
-            })
-            .collect();
+        let attributes = Self::present_attributes(attributes);
 
         // No keys to scope by means no related rows: skip the query the builder would render
         // unsatisfiable anyway.
@@ -324,6 +429,39 @@ impl<'sch, 'req, Adapter: AdapterInterface> DataLoader<'sch, 'req, Adapter> {
             .collect()
     }
 
+    /// Loads the rows holding each of `attributes` in `column`, ordered and limited per attribute
+    /// as the include `node` asks, in a single windowed query.
+    fn load_ranked_by(
+        table: &Adapter::Table<'sch, 'req>,
+        column: &'sch str,
+        attributes: &[Option<Attribute>],
+        node: &IncludeNode<'sch, 'req>,
+        fields: &FieldsParameters,
+    ) -> Result<Vec<Record<'sch>>, Error> {
+        table
+            .query_ranked(
+                column,
+                Self::present_attributes(attributes),
+                &node.sort,
+                node.limit,
+                fields,
+            )?
+            .into_iter()
+            .map(|row| Record::try_from_row(table.schema(), row))
+            .collect()
+    }
+
+    /// The distinct keys of a collection attribute, leaving out the missing and null ones.
+    fn present_attributes(attributes: &[Option<Attribute>]) -> IndexSet<Attribute> {
+        attributes
+            .iter()
+            .filter_map(|entry| match entry {
+                None | Some(Attribute::Null) => None,
+                Some(attribute) => Some(attribute.clone()),
+            })
+            .collect()
+    }
+
     /// Borrows a record's loaded foreign key, erroring if the column was not materialised.
     fn require_foreign_key<'a>(
         record: &'a Record<'sch>,
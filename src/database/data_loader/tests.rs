@@ -70,6 +70,7 @@ fn posts_schema() -> SchemaBuilder<'static> {
                 .pointing_related("post_id")
                 .to_own("id"),
         )
+        .always_count("comments")
 }
 
 fn comments_schema() -> SchemaBuilder<'static> {
@@ -973,3 +974,126 @@ fn test_include_null_belongs_to() -> Result<(), Box<dyn Error>> {
         Ok(())
     })
 }
+
+// The count of a relationship's related records, by the record's integer id.
+fn counts<'sch>(collection: &[&Record<'sch>], relationship: &str) -> Vec<(i64, u64)> {
+    let mut counts: Vec<(i64, u64)> = collection
+        .iter()
+        .filter_map(|record| Some((id(record)?, *record.counts.get(relationship)?)))
+        .collect();
+    counts.sort();
+    counts
+}
+
+#[test]
+fn test_counts_requested_relationship_across_collection() -> Result<(), Box<dyn Error>> {
+    with_database(|manager| {
+        seed_database(manager)?;
+
+        let (collection, included) = load_collection(
+            manager,
+            "users",
+            "/users?fields[users]=username,posts&meta[counts]=posts",
+        )?;
+
+        assert_eq!(
+            counts(&collection.iter().collect::<Vec<_>>(), "posts"),
+            vec![(1, 2), (2, 2), (3, 1), (4, 0)],
+            "a user without posts counts zero"
+        );
+        assert!(included.is_empty());
+
+        Ok(())
+    })
+}
+
+#[test]
+fn test_counts_nothing_outside_the_fieldset() -> Result<(), Box<dyn Error>> {
+    with_database(|manager| {
+        seed_database(manager)?;
+
+        let (collection, _) = load_collection(
+            manager,
+            "users",
+            "/users?fields[users]=username&meta[counts]=posts",
+        )?;
+
+        assert!(collection.iter().all(|user| user.counts.is_empty()));
+
+        Ok(())
+    })
+}
+
+#[test]
+fn test_counts_always_counted_relationship_on_included() -> Result<(), Box<dyn Error>> {
+    with_database(|manager| {
+        seed_database(manager)?;
+
+        let (record, included) = load_record(
+            manager,
+            "users",
+            Identifier::Integer(2),
+            "/users/2?include=posts",
+        )?;
+
+        assert!(record.counts.is_empty(), "users count nothing by default");
+        assert_eq!(
+            counts(&of_kind(&included, "posts"), "comments"),
+            vec![(3, 3), (4, 0)]
+        );
+
+        Ok(())
+    })
+}
+
+#[test]
+fn test_limited_include_keeps_the_first_ranked_per_record() -> Result<(), Box<dyn Error>> {
+    with_database(|manager| {
+        seed_database(manager)?;
+
+        let (collection, included) = load_collection(
+            manager,
+            "posts",
+            "/posts?include=comments&sort[comments]=-content&page[comments][limit]=2",
+        )?;
+
+        let linkage = |post_id: i64| {
+            collection
+                .iter()
+                .find(|post| id(post) == Some(post_id))
+                .and_then(|post| to_many(post, "comments"))
+                .map(|ids| ids.to_vec())
+        };
+        assert_eq!(
+            linkage(1),
+            Some(vec![Identifier::Integer(4), Identifier::Integer(2)])
+        );
+        assert_eq!(
+            linkage(2),
+            Some(vec![Identifier::Integer(8), Identifier::Integer(7)])
+        );
+        assert_eq!(
+            linkage(3),
+            Some(vec![Identifier::Integer(10), Identifier::Integer(11)])
+        );
+        assert_eq!(linkage(4), Some(Vec::new()));
+
+        let mut comment_ids: Vec<i64> = of_kind(&included, "comments")
+            .iter()
+            .filter_map(|&comment| id(comment))
+            .collect();
+        comment_ids.sort();
+        assert_eq!(
+            comment_ids,
+            vec![2, 4, 7, 8, 10, 11],
+            "only the kept comments"
+        );
+        assert_eq!(
+            counts(&collection.iter().collect::<Vec<_>>(), "comments"),
+            vec![(1, 6), (2, 2), (3, 3), (4, 0), (5, 0)],
+            "the full count sits beside the truncated linkage"
+        );
+
+        Ok(())
+    })
+}
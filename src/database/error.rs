@@ -1,3 +1,4 @@
+use crate::error::Source;
 use crate::http_wrappers::StatusCode;
 use crate::utils::indexing::Error as IndexingError;
 use std::string::FromUtf8Error;
@@ -36,12 +37,30 @@ pub enum Error {
         parameter: String,
         message: String,
     },
+    /// A query asks for more than one of its configured `QueryLimits` allows.
+    QueryLimitExceeded {
+        parameter: String,
+        measure: String,
+        limit: usize,
+        actual: usize,
+    },
     InvalidEncodingFailure,
     InconsistentSchema {
         schema: String,
         attribute: String,
         message: String,
     },
+    /// An audited schema is registered without the audit log, or beside one that differs from what
+    /// `audit_log()` declares.
+    MissingAuditLog {
+        schema: String,
+    },
+    /// The audit log is itself declared audited, which would log each of its own entries.
+    AuditedAuditLog,
+    InconsistentFilterOperator {
+        operator: String,
+        message: String,
+    },
     QueryValidationFailure {
         schema: String,
         attribute: String,
@@ -90,6 +109,27 @@ pub enum Error {
         operation: String,
         message: String,
     },
+    /// Linkage names a resource by a `lid` no record created before it was given.
+    UnresolvedLocalIdentifier {
+        schema: String,
+        lid: String,
+    },
+    /// An update to a versioned record does not say which version it was made against.
+    MissingLockVersion {
+        schema: String,
+        attribute: String,
+    },
+    /// An update was made against a version of the record other than its current one.
+    LockVersionMismatch {
+        schema: String,
+        expected: i64,
+        actual: i64,
+    },
+    /// A bulk update targets versioned records, which are updated one at a time, each against its
+    /// own version.
+    VersionedBulkUpdate {
+        schema: String,
+    },
     DatabaseFailure {
         message: String,
     },
@@ -128,15 +168,22 @@ impl Error {
 
         match self {
             ParseParameterFailure { .. }
+            | QueryLimitExceeded { .. }
             | InvalidEncodingFailure
             | QueryValidationFailure { .. } => StatusCode::BAD_REQUEST,
             ResourceValidationFailure { .. }
             | InvalidAttributeSet
             | InvalidAttribute { .. }
-            | InvalidOperation { .. } => StatusCode::UNPROCESSABLE_ENTITY,
-            ConstraintViolation { .. } => StatusCode::CONFLICT,
+            | InvalidOperation { .. }
+            | UnresolvedLocalIdentifier { .. }
+            | VersionedBulkUpdate { .. } => StatusCode::UNPROCESSABLE_ENTITY,
+            ConstraintViolation { .. } | LockVersionMismatch { .. } => StatusCode::CONFLICT,
+            MissingLockVersion { .. } => StatusCode::PRECONDITION_REQUIRED,
             RecordNotFound | RelatedRecordNotFound => StatusCode::NOT_FOUND,
             InconsistentSchema { .. }
+            | MissingAuditLog { .. }
+            | AuditedAuditLog
+            | InconsistentFilterOperator { .. }
             | UnknownSchema { .. }
             | InvalidAttributeConversion { .. }
             | InvalidAttributeAccess { .. }
@@ -161,8 +208,12 @@ impl Error {
 
         match self {
             ParseParameterFailure { .. } => "ParseParameterFailure",
+            QueryLimitExceeded { .. } => "QueryLimitExceeded",
             InvalidEncodingFailure => "InvalidEncodingFailure",
             InconsistentSchema { .. } => "InconsistentSchema",
+            MissingAuditLog { .. } => "MissingAuditLog",
+            AuditedAuditLog => "AuditedAuditLog",
+            InconsistentFilterOperator { .. } => "InconsistentFilterOperator",
             QueryValidationFailure { .. } => "QueryValidationFailure",
             ResourceValidationFailure { .. } => "ResourceValidationFailure",
             UnknownSchema { .. } => "UnknownSchema",
@@ -175,6 +226,10 @@ impl Error {
             MismatchedRelationshipKind { .. } => "MismatchedRelationshipKind",
             MismatchedQueryParameters { .. } => "MismatchedQueryParameters",
             InvalidOperation { .. } => "InvalidOperation",
+            UnresolvedLocalIdentifier { .. } => "UnresolvedLocalIdentifier",
+            MissingLockVersion { .. } => "MissingLockVersion",
+            LockVersionMismatch { .. } => "LockVersionMismatch",
+            VersionedBulkUpdate { .. } => "VersionedBulkUpdate",
             DatabaseFailure { .. } => "DatabaseFailure",
             ConstraintViolation { .. } => "ConstraintViolation",
             RecordNotFound => "RecordNotFound",
@@ -196,8 +251,12 @@ impl Error {
 
         match self {
             ParseParameterFailure { .. } => "Failed to parse a request parameter",
+            QueryLimitExceeded { .. } => "The query exceeds a configured limit",
             InvalidEncodingFailure => "A request parameter has an invalid encoding",
             InconsistentSchema { .. } => "The schema is inconsistent",
+            MissingAuditLog { .. } => "The audit log is not registered",
+            AuditedAuditLog => "The audit log is audited",
+            InconsistentFilterOperator { .. } => "A filter operator is inconsistent",
             QueryValidationFailure { .. } => "A query parameter is invalid",
             ResourceValidationFailure { .. } => "The submitted resource is invalid",
             UnknownSchema { .. } => "The requested schema is unknown",
@@ -212,6 +271,10 @@ impl Error {
             }
             MismatchedQueryParameters { .. } => "The query parameters target a different schema",
             InvalidOperation { .. } => "The operation is invalid",
+            UnresolvedLocalIdentifier { .. } => "A local identifier could not be resolved",
+            MissingLockVersion { .. } => "The update does not name the version it changes",
+            LockVersionMismatch { .. } => "The record was changed by another request",
+            VersionedBulkUpdate { .. } => "Versioned records cannot be updated in bulk",
             DatabaseFailure { .. } => "The database operation failed",
             ConstraintViolation { .. } => "A database constraint was violated",
             RecordNotFound => "The requested record was not found",
@@ -225,6 +288,19 @@ impl Error {
             IndexEntryFailure { .. } => "Failed to derive an index entry",
         }
     }
+
+    /// Names what in the request caused the failure. A query parameter that failed to parse or
+    /// exceeds a limit is the one database failure with a source the client can act on: the
+    /// parameter it wrote.
+    pub fn source(&self) -> Option<Source> {
+        match self {
+            Error::ParseParameterFailure { parameter, .. }
+            | Error::QueryLimitExceeded { parameter, .. } => {
+                Some(Source::Parameter(parameter.clone()))
+            }
+            _ => None,
+        }
+    }
 }
 
 #[cfg(feature = "sqlite")]
@@ -278,6 +354,15 @@ impl Display for Error {
             ParseParameterFailure { parameter, message } => {
                 write!(f, "Failed to parse parameter '{}': {}", parameter, message)
             }
+            QueryLimitExceeded {
+                parameter,
+                measure,
+                limit,
+                actual,
+            } => write!(
+                f,
+                "Parameter '{parameter}' exceeds the limit of {limit} {measure}, with {actual}"
+            ),
             InvalidEncodingFailure => write!(f, "A provided parameter has an invalid encoding"),
             InconsistentSchema {
                 schema,
@@ -288,6 +373,18 @@ impl Display for Error {
                 "Schema '{}' is inconsistent for attribute '{}': {}",
                 schema, attribute, message
             ),
+            MissingAuditLog { schema } => write!(
+                f,
+                "Schema '{schema}' is audited, which requires the audit log to be registered as declared"
+            ),
+            AuditedAuditLog => write!(f, "The audit log cannot itself be audited"),
+            InconsistentFilterOperator { operator, message } => {
+                write!(
+                    f,
+                    "Filter operator '{}' is inconsistent: {}",
+                    operator, message
+                )
+            }
             QueryValidationFailure {
                 schema,
                 attribute,
@@ -361,6 +458,26 @@ impl Display for Error {
                 "Operation '{}' is invalid for schema '{}': {}",
                 operation, schema, message
             ),
+            UnresolvedLocalIdentifier { schema, lid } => write!(
+                f,
+                "The local identifier '{lid}' names no '{schema}' resource created before the one linking to it"
+            ),
+            MissingLockVersion { schema, attribute } => write!(
+                f,
+                "An update to a '{schema}' resource must carry the '{attribute}' it was made against"
+            ),
+            LockVersionMismatch {
+                schema,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "The '{schema}' resource is at version {actual}, not the version {expected} this update was made against"
+            ),
+            VersionedBulkUpdate { schema } => write!(
+                f,
+                "The '{schema}' resources are versioned, so each is updated on its own against its version"
+            ),
             DatabaseFailure { message } => write!(f, "Failed to execute query: {}", message),
             ConstraintViolation { kind, message } => {
                 write!(f, "Constraint violation ({kind}): {message}")
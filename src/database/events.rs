@@ -0,0 +1,226 @@
+//! Defines the change events the store publishes as it writes, and their delivery once the
+//! transaction writing them commits. A `Store` write publishes to its connection's `ChangeBuffer`,
+//! which holds the events per transaction level; an embedder registers a `ChangeSubscriber` on the
+//! `ConnectionManager`, and is handed each transaction's events after its outermost commit. Events
+//! published under a level that rolls back are dropped with it.
+
+use super::attributes::Identifier;
+use log::error;
+use std::cell::RefCell;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::Arc;
+
+/// What a write did to a record.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Change {
+    Created,
+    Updated,
+    Deleted,
+    /// The record's `relationship` gained the `related` members.
+    Linked {
+        relationship: String,
+        related: Vec<Identifier>,
+    },
+    /// The record's `relationship` lost the `related` members.
+    Unlinked {
+        relationship: String,
+        related: Vec<Identifier>,
+    },
+}
+
+/// A committed change to the record of type `resource` at `id`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChangeEvent {
+    pub resource: String,
+    pub id: Identifier,
+    pub change: Change,
+}
+
+/// A receiver of committed changes, registered on the `ConnectionManager`.
+///
+/// It runs once the commit has been made, so it cannot fail the write: a side effect that may fail
+/// — a cache purge, a notification — handles its own failure, and a panic is logged and caught.
+pub trait ChangeSubscriber: Send + Sync {
+    /// Receives the events of one committed transaction, in the order they were published.
+    fn committed(&self, events: &[ChangeEvent]);
+}
+
+/// A shared subscriber, so its registrant keeps a handle on it.
+impl<T: ChangeSubscriber + ?Sized> ChangeSubscriber for Arc<T> {
+    fn committed(&self, events: &[ChangeEvent]) {
+        (**self).committed(events);
+    }
+}
+
+/// A connection's pending events, one list per open transaction level. Committing an inner level
+/// folds its events into the level around it; committing the outermost delivers them.
+#[derive(Default)]
+pub struct ChangeBuffer {
+    levels: RefCell<Vec<Vec<ChangeEvent>>>,
+    subscribers: RefCell<Vec<Arc<dyn ChangeSubscriber>>>,
+}
+
+impl ChangeBuffer {
+    /// Registers the subscribers an outermost commit delivers to.
+    pub fn deliver_to(&self, subscribers: &[Arc<dyn ChangeSubscriber>]) {
+        self.subscribers.borrow_mut().extend_from_slice(subscribers);
+    }
+
+    /// Records `event` against the innermost open level, or delivers it at once when no transaction
+    /// is open, the write having committed as it ran.
+    pub fn publish(&self, event: ChangeEvent) {
+        if let Some(level) = self.levels.borrow_mut().last_mut() {
+            return level.push(event);
+        }
+        self.deliver(&[event]);
+    }
+
+    /// Opens a level for a transaction or savepoint just begun.
+    pub fn begin(&self) {
+        self.levels.borrow_mut().push(Vec::new());
+    }
+
+    /// Closes the innermost level on its commit, delivering its events when it was the outermost.
+    pub fn commit(&self) {
+        let events = {
+            let mut levels = self.levels.borrow_mut();
+            let events = levels.pop().unwrap_or_default();
+            match levels.last_mut() {
+                Some(outer) => return outer.extend(events),
+                None => events,
+            }
+        };
+        if !events.is_empty() {
+            self.deliver(&events);
+        }
+    }
+
+    /// Drops the innermost level and its events on its rollback.
+    pub fn rollback(&self) {
+        self.levels.borrow_mut().pop();
+    }
+
+    /// Hands `events` to every subscriber. The write has committed by now, so a subscriber that
+    /// panics is logged and passed over rather than unwinding through the request that made it.
+    fn deliver(&self, events: &[ChangeEvent]) {
+        let subscribers = self.subscribers.borrow().clone();
+        for subscriber in subscribers {
+            if panic::catch_unwind(AssertUnwindSafe(|| subscriber.committed(events))).is_err() {
+                error!(
+                    "A change subscriber panicked on {} committed events",
+                    events.len()
+                );
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    #[derive(Default)]
+    struct Recorder(Mutex<Vec<Vec<ChangeEvent>>>);
+
+    impl ChangeSubscriber for Recorder {
+        fn committed(&self, events: &[ChangeEvent]) {
+            if let Ok(mut delivered) = self.0.lock() {
+                delivered.push(events.to_vec());
+            }
+        }
+    }
+
+    impl Recorder {
+        fn delivered(&self) -> Vec<Vec<ChangeEvent>> {
+            self.0
+                .lock()
+                .map(|delivered| delivered.clone())
+                .unwrap_or_default()
+        }
+    }
+
+    fn created(id: i64) -> ChangeEvent {
+        ChangeEvent {
+            resource: "posts".to_string(),
+            id: Identifier::Integer(id),
+            change: Change::Created,
+        }
+    }
+
+    fn buffer() -> (ChangeBuffer, Arc<Recorder>) {
+        let recorder = Arc::new(Recorder::default());
+        let buffer = ChangeBuffer::default();
+        let subscriber: Arc<dyn ChangeSubscriber> = recorder.clone();
+        buffer.deliver_to(&[subscriber]);
+        (buffer, recorder)
+    }
+
+    #[test]
+    fn test_events_are_delivered_on_the_outermost_commit() {
+        let (buffer, recorder) = buffer();
+        buffer.begin();
+        buffer.publish(created(1));
+        buffer.begin();
+        buffer.publish(created(2));
+        buffer.commit();
+        assert!(recorder.delivered().is_empty());
+
+        buffer.commit();
+        assert_eq!(recorder.delivered(), vec![vec![created(1), created(2)]]);
+    }
+
+    #[test]
+    fn test_a_rolled_back_savepoint_drops_only_its_events() {
+        let (buffer, recorder) = buffer();
+        buffer.begin();
+        buffer.publish(created(1));
+        buffer.begin();
+        buffer.publish(created(2));
+        buffer.rollback();
+        buffer.commit();
+
+        assert_eq!(recorder.delivered(), vec![vec![created(1)]]);
+    }
+
+    #[test]
+    fn test_a_rolled_back_transaction_delivers_nothing() {
+        let (buffer, recorder) = buffer();
+        buffer.begin();
+        buffer.publish(created(1));
+        buffer.rollback();
+        buffer.begin();
+        buffer.commit();
+
+        assert!(recorder.delivered().is_empty());
+    }
+
+    struct Panicking;
+
+    impl ChangeSubscriber for Panicking {
+        fn committed(&self, _: &[ChangeEvent]) {
+            panic!("Subscriber failed");
+        }
+    }
+
+    #[test]
+    fn test_a_panicking_subscriber_does_not_stop_delivery() {
+        let recorder = Arc::new(Recorder::default());
+        let buffer = ChangeBuffer::default();
+        let subscriber: Arc<dyn ChangeSubscriber> = recorder.clone();
+        buffer.deliver_to(&[Arc::new(Panicking), subscriber]);
+        buffer.begin();
+        buffer.publish(created(1));
+        buffer.commit();
+
+        assert_eq!(recorder.delivered(), vec![vec![created(1)]]);
+    }
+
+    #[test]
+    fn test_an_event_outside_a_transaction_is_delivered_at_once() {
+        let (buffer, recorder) = buffer();
+        buffer.publish(created(1));
+
+        assert_eq!(recorder.delivered(), vec![vec![created(1)]]);
+    }
+}
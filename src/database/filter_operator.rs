@@ -0,0 +1,162 @@
+//! Defines the extension point for filter operators beyond the built-in comparisons. An embedder
+//! implements `FilterOperator` and registers it on the `Registry`; `filter[field]=name:value` then
+//! parses through the operator and renders through it in the adapter's query builder.
+
+use super::attributes::Attribute;
+use super::query_builder::Binder;
+use super::schema::AttributeType;
+use std::fmt::{Debug, Formatter};
+use std::sync::Arc;
+
+/// A named filter operator, such as `within-days:7` or `near:lat,lng,km`.
+///
+/// The operator owns both halves of a condition: `parse` turns the raw value of a filter entry
+/// into the attributes to bind, and `render` turns them back into an SQL condition on a column.
+/// Values must only ever reach the SQL through the `Binder`; the column reference is trusted.
+pub trait FilterOperator: Send + Sync {
+    /// The name written before the colon of a filter entry. It must be a lowercase,
+    /// dash-separated word and cannot shadow a built-in operator.
+    fn name(&self) -> &str;
+
+    /// Whether the operator applies to attributes of type `kind`. Filtering any other attribute
+    /// with it is rejected as an invalid query.
+    fn applies_to(&self, kind: AttributeType) -> bool;
+
+    /// Parses the percent-decoded value of a filter entry on an attribute of type `kind`. The value
+    /// is taken whole, commas included. An `Err` carries the message reported to the client.
+    fn parse(&self, value: &str, kind: AttributeType) -> Result<Vec<Attribute>, String>;
+
+    /// Renders the condition on `column`, a qualified column reference, binding `values` (as
+    /// returned by `parse`) through `binder` and interpolating the placeholders it returns.
+    fn render(&self, column: &str, values: &[Attribute], binder: &mut dyn Binder) -> String;
+}
+
+/// A parsed filter entry of a registered operator: the operator and the values it parsed.
+#[derive(Clone)]
+pub struct CustomFilter {
+    pub(crate) operator: Arc<dyn FilterOperator>,
+    pub(crate) values: Vec<Attribute>,
+}
+
+impl CustomFilter {
+    pub fn name(&self) -> &str {
+        self.operator.name()
+    }
+
+    pub fn values(&self) -> &[Attribute] {
+        &self.values
+    }
+}
+
+/// Operator names are unique within a registry, so two filters are equal when they name the same
+/// operator and carry the same values.
+impl PartialEq for CustomFilter {
+    fn eq(&self, other: &Self) -> bool {
+        self.name() == other.name() && self.values == other.values
+    }
+}
+
+impl Eq for CustomFilter {}
+
+impl Debug for CustomFilter {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("CustomFilter")
+            .field("operator", &self.name())
+            .field("values", &self.values)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::error::Error;
+    use crate::database::registry::Registry;
+    use crate::database::schema::SchemaBuilder;
+
+    /// An operator that only carries a name, for registration checks.
+    struct Named(&'static str);
+
+    impl FilterOperator for Named {
+        fn name(&self) -> &str {
+            self.0
+        }
+
+        fn applies_to(&self, _: AttributeType) -> bool {
+            true
+        }
+
+        fn parse(&self, value: &str, _: AttributeType) -> Result<Vec<Attribute>, String> {
+            Ok(vec![Attribute::Text(value.to_string())])
+        }
+
+        fn render(&self, column: &str, values: &[Attribute], binder: &mut dyn Binder) -> String {
+            format!("{column} = {}", binder.bind(values[0].clone()))
+        }
+    }
+
+    fn register(names: &[&'static str]) -> Result<Registry<'static>, Error> {
+        names.iter().try_fold(
+            Registry::try_new([SchemaBuilder::table("articles")])?,
+            |registry, &name| registry.with_filter_operator(Named(name)),
+        )
+    }
+
+    fn rejection(names: &[&'static str]) -> String {
+        match register(names) {
+            Err(Error::InconsistentFilterOperator { message, .. }) => message,
+            Err(error) => panic!("unexpected error: {error}"),
+            Ok(_) => panic!("expected registration to fail"),
+        }
+    }
+
+    #[test]
+    fn test_register_operators() -> Result<(), Error> {
+        let registry = register(&["within-days", "near"])?;
+
+        assert_eq!(
+            registry.filter_operator("within-days").map(|op| op.name()),
+            Some("within-days")
+        );
+        assert!(registry.filter_operator("far").is_none());
+        Ok(())
+    }
+
+    #[test]
+    fn test_register_rejects_malformed_name() {
+        assert_eq!(
+            rejection(&["Within_Days"]),
+            "Operator names must be lowercase words separated by dashes"
+        );
+    }
+
+    #[test]
+    fn test_register_rejects_built_in_name() {
+        for name in ["eq", "nin", "exists", "count-gt"] {
+            assert_eq!(
+                rejection(&[name]),
+                "Operator name is taken by a built-in operator"
+            );
+        }
+    }
+
+    #[test]
+    fn test_register_rejects_duplicate() {
+        assert_eq!(
+            rejection(&["near", "near"]),
+            "Operator is registered more than once"
+        );
+    }
+
+    #[test]
+    fn test_custom_filters_compare_by_name_and_values() {
+        let filter = |name, value: i64| CustomFilter {
+            operator: Arc::new(Named(name)),
+            values: vec![Attribute::Integer(value)],
+        };
+
+        assert_eq!(filter("near", 1), filter("near", 1));
+        assert_ne!(filter("near", 1), filter("near", 2));
+        assert_ne!(filter("near", 1), filter("far", 1));
+    }
+}
@@ -1,10 +1,13 @@
 pub mod adapters;
 pub mod attributes;
+pub mod audit;
 pub mod composite;
 pub mod connection;
 pub mod connection_manager;
 pub mod data_loader;
 pub mod error;
+pub mod events;
+pub mod filter_operator;
 pub mod migrator;
 pub mod pool;
 pub mod query_builder;
@@ -2,12 +2,53 @@ use super::{
     QueryParameters,
     attributes::{Attribute, Attributes},
     error::Error,
+    query_parameters::{
+        AggregateParameters, ExcerptParameters, FieldsParameters, SearchParameters, SortParameters,
+    },
     schema::Schema,
 };
 use crate::database::attributes::Identifier;
+use indexmap::IndexSet;
 
 pub type Bindings = Vec<Attribute>;
 
+/// The characters an adapter wraps each search match in when rendering excerpts. Control
+/// characters, so the markers survive `mark_excerpt` escaping the text around them.
+pub const EXCERPT_OPEN: char = '\u{2}';
+pub const EXCERPT_CLOSE: char = '\u{3}';
+
+/// Renders an excerpt an adapter marked with `EXCERPT_OPEN` and `EXCERPT_CLOSE` as HTML: the stored
+/// text escaped, each match wrapped in `<mark>`.
+pub(crate) fn mark_excerpt(excerpt: &str) -> String {
+    let mut marked = String::with_capacity(excerpt.len());
+    for character in excerpt.chars() {
+        match character {
+            EXCERPT_OPEN => marked.push_str("<mark>"),
+            EXCERPT_CLOSE => marked.push_str("</mark>"),
+            '&' => marked.push_str("&amp;"),
+            '<' => marked.push_str("&lt;"),
+            '>' => marked.push_str("&gt;"),
+            '"' => marked.push_str("&quot;"),
+            '\'' => marked.push_str("&#39;"),
+            character => marked.push(character),
+        }
+    }
+    marked
+}
+
+/// Binds values to a query under construction, returning the placeholder that refers to each in
+/// the query text. The placeholder syntax is the adapter's.
+pub trait Binder {
+    fn bind(&mut self, value: Attribute) -> String;
+
+    fn bind_all(&mut self, values: impl IntoIterator<Item = Attribute>) -> Vec<String>
+    where
+        Self: Sized,
+    {
+        values.into_iter().map(|value| self.bind(value)).collect()
+    }
+}
+
 pub trait QueryBuilder<'sch> {
     fn new(schema: &'sch Schema<'sch>) -> Self;
     fn query(&self, parameters: &QueryParameters) -> Result<Option<(String, Bindings)>, Error>;
@@ -21,6 +62,13 @@ pub trait QueryBuilder<'sch> {
         attributes: Attributes<'sch>,
         parameters: &QueryParameters,
     ) -> Result<(String, Bindings), Error>;
+    /// Inserts `attributes`, which carry the primary key, or — when a row already holds that key —
+    /// updates the row with them instead. Either way the resulting row is returned.
+    fn upsert(
+        &self,
+        attributes: Attributes<'sch>,
+        parameters: &QueryParameters,
+    ) -> Result<(String, Bindings), Error>;
     fn update(
         &self,
         id: Identifier,
@@ -42,4 +90,41 @@ pub trait QueryBuilder<'sch> {
         &self,
         parameters: &QueryParameters,
     ) -> Result<Option<(String, Bindings)>, Error>;
+    /// Counts the rows holding each of `values` in `column`, one `(value, count)` row per value
+    /// found. `values` is never empty.
+    fn count_by(&self, column: &'sch str, values: IndexSet<Attribute>) -> (String, Bindings);
+    /// Selects the rows holding each of `values` in `column`, ranked within each value by `sort`
+    /// then the primary key, keeping at most `limit` rows per value. Rows come grouped by value, in
+    /// rank order. `values` is never empty.
+    fn query_ranked(
+        &self,
+        column: &'sch str,
+        values: IndexSet<Attribute>,
+        sort: &Option<SortParameters>,
+        limit: Option<u32>,
+        fields: &FieldsParameters,
+    ) -> Result<(String, Bindings), Error>;
+    /// Computes `aggregate` over the rows matched by `parameters`' filters and search, one row per
+    /// group: the group values in order, then the aggregated values in order. `None` when no row
+    /// can match.
+    fn aggregate(
+        &self,
+        aggregate: &AggregateParameters,
+        parameters: &QueryParameters,
+    ) -> Result<Option<(String, Bindings)>, Error>;
+    /// Renders the search excerpts of the records with the primary keys `ids` that match `search`:
+    /// one row per record, the primary key, then each snippet and each highlight in order, each
+    /// match between `EXCERPT_OPEN` and `EXCERPT_CLOSE`. `ids` is never empty.
+    fn excerpts(
+        &self,
+        ids: IndexSet<Attribute>,
+        search: &SearchParameters,
+        excerpts: &ExcerptParameters,
+    ) -> Result<(String, Bindings), Error>;
+    /// Renders the statements creating the schema's full-text index, laid out and keyed as its
+    /// searches expect, and the triggers keeping it in step with the table.
+    fn create_text_index(&self) -> Result<Vec<String>, Error>;
+    /// Renders the statements creating the schema's history table, holding the rows the table
+    /// already has as their first versions, and the triggers versioning every later write to it.
+    fn create_history(&self) -> Result<Vec<String>, Error>;
 }
@@ -0,0 +1,286 @@
+//! Parses the aggregation parameters: `aggregate[function]=columns`, computing statistics over the
+//! filtered collection, and `group=columns`, splitting them by the values of one or more columns.
+//! A date-time column can be grouped by calendar period, as `group=created_at:month`.
+
+use crate::database::attributes::Attribute;
+use crate::database::error::Error;
+use crate::database::schema::{AttributeType, Schema};
+use std::fmt::Display;
+
+/// Enumerates the statistics an aggregation can compute over a column.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum AggregateFunction {
+    Count,
+    Sum,
+    Average,
+    Minimum,
+    Maximum,
+}
+
+impl AggregateFunction {
+    fn parse(name: &str) -> Option<Self> {
+        match name {
+            "count" => Some(Self::Count),
+            "sum" => Some(Self::Sum),
+            "avg" => Some(Self::Average),
+            "min" => Some(Self::Minimum),
+            "max" => Some(Self::Maximum),
+            _ => None,
+        }
+    }
+
+    /// The name of the function in the query string and in the aggregate document.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Count => "count",
+            Self::Sum => "sum",
+            Self::Average => "avg",
+            Self::Minimum => "min",
+            Self::Maximum => "max",
+        }
+    }
+
+    /// Whether the function can be computed over a column of type `kind`: sums and averages need
+    /// numbers, extremes need an ordering, and anything can be counted.
+    pub fn applies_to(&self, kind: AttributeType) -> bool {
+        match self {
+            Self::Count => true,
+            Self::Sum | Self::Average => {
+                matches!(kind, AttributeType::Integer | AttributeType::Float)
+            }
+            Self::Minimum | Self::Maximum => kind != AttributeType::Boolean,
+        }
+    }
+
+    /// The type of the computed value for a column of type `kind`.
+    pub fn result_kind(&self, kind: AttributeType) -> AttributeType {
+        match self {
+            Self::Count => AttributeType::Integer,
+            Self::Average => AttributeType::Float,
+            Self::Sum | Self::Minimum | Self::Maximum => kind,
+        }
+    }
+}
+
+impl Display for AggregateFunction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// A single statistic requested by `aggregate[function]=column`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Aggregate<'sch> {
+    pub(crate) function: AggregateFunction,
+    pub(crate) column: &'sch str,
+    pub(crate) kind: AttributeType,
+}
+
+/// Enumerates the calendar periods a date-time column can be grouped by.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DateBucket {
+    Year,
+    Month,
+    Day,
+}
+
+impl DateBucket {
+    fn parse(name: &str) -> Option<Self> {
+        match name {
+            "year" => Some(Self::Year),
+            "month" => Some(Self::Month),
+            "day" => Some(Self::Day),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Year => "year",
+            Self::Month => "month",
+            Self::Day => "day",
+        }
+    }
+}
+
+/// A column the aggregation is grouped by, optionally truncated to a calendar period.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GroupKey<'sch> {
+    pub(crate) column: &'sch str,
+    pub(crate) kind: AttributeType,
+    pub(crate) bucket: Option<DateBucket>,
+}
+
+impl GroupKey<'_> {
+    /// The type of the group value: a bucketed date-time is grouped by its formatted period.
+    pub fn result_kind(&self) -> AttributeType {
+        match self.bucket {
+            Some(_) => AttributeType::Text,
+            None => self.kind,
+        }
+    }
+
+    /// The key of the group value in the aggregate document, as `created_at:month`.
+    pub fn label(&self) -> String {
+        match self.bucket {
+            Some(bucket) => format!("{}:{}", self.column, bucket.name()),
+            None => self.column.to_string(),
+        }
+    }
+}
+
+/// Stores the statistics to compute over the primary collection and the columns to group them by.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct AggregateParameters<'sch> {
+    pub(crate) aggregates: Vec<Aggregate<'sch>>,
+    pub(crate) group: Vec<GroupKey<'sch>>,
+}
+
+impl<'sch> AggregateParameters<'sch> {
+    /// Parses an `aggregate[function]` entry, a comma-separated list of columns. The primary key,
+    /// also as `id`, may be counted.
+    pub(super) fn parse_aggregate(
+        &mut self,
+        function: &str,
+        columns: &str,
+        schema: &'sch Schema<'sch>,
+    ) -> Result<(), Error> {
+        let function =
+            AggregateFunction::parse(function).ok_or_else(|| Error::ParseParameterFailure {
+                parameter: format!("aggregate[{function}]"),
+                message: format!("Invalid aggregate function: '{function}'"),
+            })?;
+
+        for name in columns.split(",").filter(|entry| !entry.is_empty()) {
+            let (column, kind) = resolve(name, schema, "Attempted to aggregate an unknown column")?;
+
+            if !function.applies_to(kind) {
+                return Err(Error::QueryValidationFailure {
+                    schema: schema.name().to_string(),
+                    attribute: name.to_string(),
+                    message: format!(
+                        "The '{function}' aggregate cannot be applied to {kind} attributes"
+                    ),
+                });
+            }
+
+            let aggregate = Aggregate {
+                function,
+                column,
+                kind,
+            };
+            if !self.aggregates.contains(&aggregate) {
+                self.aggregates.push(aggregate);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Parses the `group` parameter, a comma-separated list of columns, each optionally suffixed
+    /// with the calendar period to group a date-time column by.
+    pub(super) fn parse_group(
+        &mut self,
+        columns: &str,
+        schema: &'sch Schema<'sch>,
+    ) -> Result<(), Error> {
+        for entry in columns.split(",").filter(|entry| !entry.is_empty()) {
+            let (name, bucket) = match entry.split_once(":") {
+                Some((name, bucket)) => {
+                    let bucket =
+                        DateBucket::parse(bucket).ok_or_else(|| Error::ParseParameterFailure {
+                            parameter: "group".to_string(),
+                            message: format!("Invalid date grouping: '{bucket}'"),
+                        })?;
+                    (name, Some(bucket))
+                }
+                None => (entry, None),
+            };
+
+            let (column, kind) = resolve(name, schema, "Attempted to group by an unknown column")?;
+
+            if bucket.is_some() && kind != AttributeType::DateTime {
+                return Err(Error::QueryValidationFailure {
+                    schema: schema.name().to_string(),
+                    attribute: name.to_string(),
+                    message: "Only date-time attributes can be grouped by period".to_string(),
+                });
+            }
+
+            let key = GroupKey {
+                column,
+                kind,
+                bucket,
+            };
+            if !self.group.contains(&key) {
+                self.group.push(key);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// The types of a result row's columns: the group values, then the aggregated values.
+    pub fn result_kinds(&self) -> Vec<AttributeType> {
+        self.group
+            .iter()
+            .map(GroupKey::result_kind)
+            .chain(
+                self.aggregates
+                    .iter()
+                    .map(|aggregate| aggregate.function.result_kind(aggregate.kind)),
+            )
+            .collect()
+    }
+
+    /// Rejects a grouping with nothing to compute.
+    pub(super) fn validate(&self) -> Result<(), Error> {
+        if self.aggregates.is_empty() {
+            return Err(Error::ParseParameterFailure {
+                parameter: "group".to_string(),
+                message: "Grouping requires at least one aggregate".to_string(),
+            });
+        }
+
+        Ok(())
+    }
+}
+
+/// A computed group: its values for each `group` key and each aggregate, in the order requested.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AggregateRow {
+    pub group: Vec<Attribute>,
+    pub values: Vec<Attribute>,
+}
+
+impl AggregateRow {
+    /// Splits a result row, laid out as `AggregateParameters::result_kinds` describes.
+    pub(crate) fn from_values(
+        mut values: Vec<Attribute>,
+        parameters: &AggregateParameters,
+    ) -> Self {
+        let aggregated = values.split_off(parameters.group.len().min(values.len()));
+        Self {
+            group: values,
+            values: aggregated,
+        }
+    }
+}
+
+/// Resolves a column name, `id` standing for the primary key, to its schema name and type.
+fn resolve<'sch>(
+    name: &str,
+    schema: &'sch Schema<'sch>,
+    message: &str,
+) -> Result<(&'sch str, AttributeType), Error> {
+    match name {
+        "id" => schema.column(schema.primary_key().name),
+        name => schema.column(name),
+    }
+    .map(|column| (column.name, column.kind))
+    .ok_or_else(|| Error::QueryValidationFailure {
+        schema: schema.name().to_string(),
+        attribute: name.to_string(),
+        message: message.to_string(),
+    })
+}
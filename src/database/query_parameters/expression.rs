@@ -0,0 +1,471 @@
+//! Parses the filter expression profile's bare `filter` parameter: a boolean combination of
+//! conditions written as nested calls, e.g. `or(eq(status,'draft'),eq(author_id,3))`. A condition
+//! takes the operators of a `filter[field]` entry, registered ones included, and a relationship
+//! takes those of a relationship filter, e.g. `or(in(author,3,4),none(comments))`.
+//!
+//! Parsing runs in two passes: the source is first read into a syntax tree of calls and literals,
+//! which is then validated against the schema into a typed `FilterExpression`.
+
+use super::{
+    FILTER_OPERATORS, FilterValue, RelationshipFilter, RelationshipFilterValue,
+    is_reserved_operator, require_text, unsupported_relationship_filter,
+};
+use crate::database::attributes::Attribute;
+use crate::database::error::Error;
+use crate::database::filter_operator::CustomFilter;
+use crate::database::registry::Registry;
+use crate::database::schema::{AttributeType, RelationshipDescriptor, Schema};
+use std::borrow::Cow;
+use std::sync::Arc;
+
+/// A boolean combination of filter conditions over the columns of a single schema.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FilterExpression<'sch> {
+    And(Vec<FilterExpression<'sch>>),
+    Or(Vec<FilterExpression<'sch>>),
+    Not(Box<FilterExpression<'sch>>),
+    Condition {
+        attribute: &'sch str,
+        value: FilterValue,
+    },
+    /// A condition on a relationship's related records, holding a single value.
+    Relationship(RelationshipFilter<'sch>),
+}
+
+/// A node of the syntax tree: a call such as `eq(...)`, or a literal. Positions are 1-based byte
+/// offsets into the decoded source, reported back in error messages.
+#[derive(Debug)]
+enum Node<'a> {
+    Call {
+        name: &'a str,
+        arguments: Vec<Node<'a>>,
+        position: usize,
+    },
+    Literal {
+        value: Cow<'a, str>,
+        position: usize,
+    },
+}
+
+impl<'sch> FilterExpression<'sch> {
+    /// Parses an already percent-decoded expression and validates it against `schema`.
+    pub(super) fn parse(
+        source: &str,
+        schema: &'sch Schema<'sch>,
+        registry: &'sch Registry<'sch>,
+    ) -> Result<Self, Error> {
+        let node = Parser::parse(source)?;
+        Self::from_node(node, schema, registry)
+    }
+
+    /// Whether any condition of the expression is on a relationship's related records.
+    pub(super) fn names_relationship(&self) -> bool {
+        match self {
+            FilterExpression::And(operands) | FilterExpression::Or(operands) => {
+                operands.iter().any(FilterExpression::names_relationship)
+            }
+            FilterExpression::Not(operand) => operand.names_relationship(),
+            FilterExpression::Condition { .. } => false,
+            FilterExpression::Relationship(_) => true,
+        }
+    }
+
+    fn from_node(
+        node: Node,
+        schema: &'sch Schema<'sch>,
+        registry: &'sch Registry<'sch>,
+    ) -> Result<Self, Error> {
+        let (name, arguments, position) = match node {
+            Node::Call {
+                name,
+                arguments,
+                position,
+            } => (name, arguments, position),
+            Node::Literal { position, .. } => {
+                return Err(failure(format!(
+                    "Expected an operator at position {position}"
+                )));
+            }
+        };
+
+        match name {
+            "and" | "or" => {
+                if arguments.is_empty() {
+                    return Err(failure(format!(
+                        "'{name}' takes at least one expression, at position {position}"
+                    )));
+                }
+
+                let operands = arguments
+                    .into_iter()
+                    .map(|argument| Self::from_node(argument, schema, registry))
+                    .collect::<Result<Vec<_>, Error>>()?;
+
+                Ok(match name {
+                    "and" => FilterExpression::And(operands),
+                    _ => FilterExpression::Or(operands),
+                })
+            }
+            "not" => {
+                let Ok([operand]) = <[Node; 1]>::try_from(arguments) else {
+                    return Err(failure(format!(
+                        "'not' takes exactly one expression, at position {position}"
+                    )));
+                };
+
+                Ok(FilterExpression::Not(Box::new(Self::from_node(
+                    operand, schema, registry,
+                )?)))
+            }
+            operator => Self::condition(operator, arguments, position, schema, registry),
+        }
+    }
+
+    /// Validates a condition call: its first argument names a relationship or a column of
+    /// `schema` — an attribute, the primary key (also as `id`) or a foreign key — and the rest are
+    /// values parsed as that column's type, or as the related primary key's.
+    fn condition(
+        operator: &str,
+        arguments: Vec<Node>,
+        position: usize,
+        schema: &'sch Schema<'sch>,
+        registry: &'sch Registry<'sch>,
+    ) -> Result<Self, Error> {
+        if !is_reserved_operator(operator) && registry.filter_operator(operator).is_none() {
+            return Err(failure(format!("Invalid filter operator: '{operator}'")));
+        }
+
+        let mut arguments = arguments.into_iter().map(|argument| match argument {
+            Node::Literal { value, .. } => Ok(value),
+            Node::Call { position, .. } => Err(failure(format!(
+                "Expected an attribute or a value at position {position}"
+            ))),
+        });
+
+        let field = arguments.next().transpose()?.ok_or_else(|| {
+            failure(format!(
+                "'{operator}' takes an attribute, at position {position}"
+            ))
+        })?;
+        let values = arguments.collect::<Result<Vec<_>, Error>>()?;
+
+        if let Some(descriptor) = schema.relationship(&field) {
+            return Self::relationship_condition(
+                operator, descriptor, values, position, schema, registry,
+            );
+        }
+
+        let column = match field.as_ref() {
+            "id" => schema.column(schema.primary_key().name),
+            field => schema.column(field),
+        }
+        .ok_or_else(|| Error::QueryValidationFailure {
+            schema: schema.name().to_string(),
+            attribute: field.to_string(),
+            message: "Attempted to filter on an unknown attribute".to_string(),
+        })?;
+
+        let value = match registry.filter_operator(operator) {
+            Some(custom) => {
+                if !custom.applies_to(column.kind) {
+                    return Err(Error::QueryValidationFailure {
+                        schema: schema.name().to_string(),
+                        attribute: field.to_string(),
+                        message: format!(
+                            "The '{operator}' operator cannot be applied to {} attributes",
+                            column.kind
+                        ),
+                    });
+                }
+
+                let values = custom
+                    .parse(&values.join(","), column.kind)
+                    .map_err(failure)?;
+                FilterValue::Custom(CustomFilter {
+                    operator: Arc::clone(custom),
+                    values,
+                })
+            }
+            None if FILTER_OPERATORS.contains(&operator) => {
+                let value =
+                    Self::comparison(operator, parse_values(values, column.kind)?, position)?;
+                require_text(schema, &field, column.kind, &value)?;
+                value
+            }
+            None => {
+                return Err(failure(format!(
+                    "'{operator}' only applies to relationships, at position {position}"
+                )));
+            }
+        };
+
+        Ok(FilterExpression::Condition {
+            attribute: column.name,
+            value,
+        })
+    }
+
+    /// Validates a condition on a relationship: `eq`, `neq`, `in` and `nin` take primary keys of
+    /// the related resource, `exists` and `none` take no value, and a `count-` comparison takes an
+    /// integer.
+    fn relationship_condition(
+        operator: &str,
+        descriptor: &'sch RelationshipDescriptor<'sch>,
+        values: Vec<Cow<str>>,
+        position: usize,
+        schema: &'sch Schema<'sch>,
+        registry: &'sch Registry<'sch>,
+    ) -> Result<Self, Error> {
+        let primary_key = registry.schema(descriptor.related.resource)?.primary_key();
+
+        let value = match operator {
+            "eq" | "neq" | "in" | "nin" => RelationshipFilterValue::Identity(Self::comparison(
+                operator,
+                parse_values(values, primary_key.kind.into())?,
+                position,
+            )?),
+            "exists" | "none" => {
+                if !values.is_empty() {
+                    return Err(failure(format!(
+                        "'{operator}' takes no value, at position {position}"
+                    )));
+                }
+
+                match operator {
+                    "exists" => RelationshipFilterValue::Exists,
+                    _ => RelationshipFilterValue::NotExists,
+                }
+            }
+            operator => match operator.strip_prefix("count-") {
+                Some(comparison @ ("eq" | "neq" | "gt" | "gte" | "lt" | "lte")) => {
+                    RelationshipFilterValue::Count(Self::comparison(
+                        comparison,
+                        parse_values(values, AttributeType::Integer)?,
+                        position,
+                    )?)
+                }
+                Some(_) => return Err(failure(format!("Invalid filter operator: '{operator}'"))),
+                None => return Err(unsupported_relationship_filter(schema, descriptor.name)),
+            },
+        };
+
+        Ok(FilterExpression::Relationship(RelationshipFilter {
+            descriptor,
+            primary_key: primary_key.name,
+            values: vec![value],
+        }))
+    }
+
+    /// Builds the value of a built-in comparison. The set operators, `in` and `nin`, take one or
+    /// more values; every other operator takes exactly one.
+    fn comparison(
+        operator: &str,
+        mut values: Vec<Attribute>,
+        position: usize,
+    ) -> Result<FilterValue, Error> {
+        use FilterValue::*;
+        Ok(match operator {
+            "in" | "nin" => {
+                if values.is_empty() {
+                    return Err(failure(format!(
+                        "'{operator}' takes at least one value, at position {position}"
+                    )));
+                }
+
+                let values = values.into_iter().collect();
+                match operator {
+                    "in" => In(values),
+                    _ => NotIn(values),
+                }
+            }
+            _ => {
+                let (Some(value), true) = (values.pop(), values.is_empty()) else {
+                    return Err(failure(format!(
+                        "'{operator}' takes exactly one value, at position {position}"
+                    )));
+                };
+
+                match operator {
+                    "eq" => Equal(value),
+                    "neq" => NotEqual(value),
+                    "gt" => GreaterThan(value),
+                    "gte" => GreaterThanOrEqual(value),
+                    "lt" => LessThan(value),
+                    "lte" => LessThanOrEqual(value),
+                    "like" => Like(value),
+                    "ilike" => InsensitiveLike(value),
+                    "starts" => StartsWith(value),
+                    "ends" => EndsWith(value),
+                    _ => InsensitiveEqual(value),
+                }
+            }
+        })
+    }
+}
+
+/// Parses the value arguments of a condition as attributes of type `kind`.
+fn parse_values(values: Vec<Cow<str>>, kind: AttributeType) -> Result<Vec<Attribute>, Error> {
+    values
+        .iter()
+        .map(|value| {
+            Attribute::parse(value, kind).map_err(|error| match error {
+                Error::InvalidAttributeConversion { kind } => {
+                    failure(format!("Filter value is not a valid {kind}"))
+                }
+                error => error,
+            })
+        })
+        .collect()
+}
+
+/// The deepest nesting of calls an expression may hold, so that neither parsing nor any later walk
+/// of the tree can exhaust the stack.
+const MAX_DEPTH: usize = 64;
+
+fn failure(message: String) -> Error {
+    Error::ParseParameterFailure {
+        parameter: "filter".to_string(),
+        message,
+    }
+}
+
+/// A recursive-descent reader of the expression syntax:
+///
+/// ```text
+/// node      := call | literal
+/// call      := name '(' node (',' node)* ')'
+/// literal   := quoted | bare
+/// quoted    := "'" ( any character but "'" | "''" )* "'"
+/// bare      := ( any character but ',', '(', ')' or "'" )+
+/// ```
+///
+/// Whitespace around nodes is insignificant. A literal must be quoted to carry any of the
+/// delimiters, or leading or trailing whitespace; a doubled quote escapes a single one. Calls nest
+/// at most `MAX_DEPTH` deep.
+struct Parser<'a> {
+    source: &'a str,
+    position: usize,
+    depth: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn parse(source: &'a str) -> Result<Node<'a>, Error> {
+        let mut parser = Self {
+            source,
+            position: 0,
+            depth: 0,
+        };
+
+        let node = parser.node()?;
+        parser.skip_whitespace();
+
+        match parser.peek() {
+            None => Ok(node),
+            Some(_) => Err(parser.unexpected()),
+        }
+    }
+
+    fn node(&mut self) -> Result<Node<'a>, Error> {
+        self.skip_whitespace();
+        let position = self.position + 1;
+
+        if self.peek() == Some('\'') {
+            return self.quoted();
+        }
+
+        let rest = &self.source[self.position..];
+        let length = rest.find([',', '(', ')', '\'']).unwrap_or(rest.len());
+        let token = rest[..length].trim_end();
+
+        if token.is_empty() {
+            return Err(self.unexpected());
+        }
+
+        self.position += length;
+        if self.peek() != Some('(') {
+            return Ok(Node::Literal {
+                value: Cow::Borrowed(token),
+                position,
+            });
+        }
+
+        if self.depth == MAX_DEPTH {
+            return Err(failure(
+                "The filter expression is nested too deeply".to_string(),
+            ));
+        }
+
+        self.position += 1;
+        self.depth += 1;
+        let mut arguments = Vec::new();
+        loop {
+            arguments.push(self.node()?);
+            self.skip_whitespace();
+
+            match self.peek() {
+                Some(',') => self.position += 1,
+                Some(')') => {
+                    self.position += 1;
+                    break;
+                }
+                _ => return Err(self.unexpected()),
+            }
+        }
+        self.depth -= 1;
+
+        Ok(Node::Call {
+            name: token,
+            arguments,
+            position,
+        })
+    }
+
+    fn quoted(&mut self) -> Result<Node<'a>, Error> {
+        let position = self.position + 1;
+        self.position += 1;
+
+        let mut value = Cow::Borrowed("");
+        loop {
+            let rest = &self.source[self.position..];
+            let Some(end) = rest.find('\'') else {
+                return Err(failure(format!(
+                    "Unterminated string starting at position {position}"
+                )));
+            };
+
+            let chunk = &rest[..end];
+            self.position += end + 1;
+
+            if self.peek() == Some('\'') {
+                self.position += 1;
+                value.to_mut().push_str(chunk);
+                value.to_mut().push('\'');
+            } else if value.is_empty() {
+                value = Cow::Borrowed(chunk);
+                break;
+            } else {
+                value.to_mut().push_str(chunk);
+                break;
+            }
+        }
+
+        Ok(Node::Literal { value, position })
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.source[self.position..].chars().next()
+    }
+
+    fn skip_whitespace(&mut self) {
+        let rest = &self.source[self.position..];
+        self.position += rest.len() - rest.trim_start().len();
+    }
+
+    fn unexpected(&self) -> Error {
+        let position = self.position + 1;
+        failure(match self.peek() {
+            Some(character) => format!("Unexpected '{character}' at position {position}"),
+            None => format!("Unexpected end of expression at position {position}"),
+        })
+    }
+}
@@ -0,0 +1,223 @@
+//! Bounds the complexity of a parsed query, so a single request cannot ask for an unbounded amount
+//! of work: deep or wide `include` trees, long filter lists, huge `in:` sets, many sort fields or
+//! search terms, or large per-record caps on included relationships. Only what the client wrote is
+//! measured: the filters a scope expands into are the server's own.
+
+use super::{
+    FilterExpression, FilterValue, IncludeNode, IncludeParameters, QueryParameters,
+    RelationshipFilterValue,
+};
+use crate::database::error::Error;
+
+/// Configurable upper bounds on a query. Every bound is optional; an unset one is unlimited, and
+/// the default is entirely unlimited.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct QueryLimits {
+    include_depth: Option<usize>,
+    include_paths: Option<usize>,
+    filter_clauses: Option<usize>,
+    in_list_length: Option<usize>,
+    sort_fields: Option<usize>,
+    search_terms: Option<usize>,
+    include_limit: Option<usize>,
+}
+
+impl QueryLimits {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The longest relationship path in `include`, `a.b.c` being 3 deep.
+    pub fn include_depth(mut self, limit: usize) -> Self {
+        self.include_depth = Some(limit);
+        self
+    }
+
+    /// The number of distinct relationship paths `include` ends in, `a.b,a.c,d` being 3.
+    pub fn include_paths(mut self, limit: usize) -> Self {
+        self.include_paths = Some(limit);
+        self
+    }
+
+    /// The number of conditions across every `filter[...]` entry and the filter expression.
+    pub fn filter_clauses(mut self, limit: usize) -> Self {
+        self.filter_clauses = Some(limit);
+        self
+    }
+
+    /// The number of values in a single `in:` or `nin:` set.
+    pub fn in_list_length(mut self, limit: usize) -> Self {
+        self.in_list_length = Some(limit);
+        self
+    }
+
+    /// The number of fields in `sort`, and in each included relationship's `sort[path]`.
+    pub fn sort_fields(mut self, limit: usize) -> Self {
+        self.sort_fields = Some(limit);
+        self
+    }
+
+    /// The number of terms across `search` and every `search[attribute]`.
+    pub fn search_terms(mut self, limit: usize) -> Self {
+        self.search_terms = Some(limit);
+        self
+    }
+
+    /// The number of related records a `page[path][limit]` may cap an included relationship at.
+    pub fn include_limit(mut self, limit: usize) -> Self {
+        self.include_limit = Some(limit);
+        self
+    }
+
+    /// Takes every bound set on `self`, falling back to `fallback`'s for the rest — a resource's own
+    /// limits over the router-wide ones.
+    pub fn or(self, fallback: QueryLimits) -> Self {
+        Self {
+            include_depth: self.include_depth.or(fallback.include_depth),
+            include_paths: self.include_paths.or(fallback.include_paths),
+            filter_clauses: self.filter_clauses.or(fallback.filter_clauses),
+            in_list_length: self.in_list_length.or(fallback.in_list_length),
+            sort_fields: self.sort_fields.or(fallback.sort_fields),
+            search_terms: self.search_terms.or(fallback.search_terms),
+            include_limit: self.include_limit.or(fallback.include_limit),
+        }
+    }
+
+    /// Checks a parsed query against every bound, failing on the first one exceeded with the
+    /// offending parameter.
+    pub fn enforce(&self, parameters: &QueryParameters) -> Result<(), Error> {
+        let (depth, paths) = measure_include(&parameters.include);
+        check("include", "include depth", depth, self.include_depth)?;
+        check("include", "include paths", paths, self.include_paths)?;
+        self.enforce_include_options(&parameters.include, "")?;
+
+        let sort_fields = parameters.sort.as_ref().map_or(0, |sort| sort.len());
+        check("sort", "sort fields", sort_fields, self.sort_fields)?;
+
+        let search_terms = parameters.search.as_ref().map_or(0, |search| search.len());
+        check("search", "search terms", search_terms, self.search_terms)?;
+
+        let client_values = |field: &str, length: usize| {
+            length
+                - parameters
+                    .scope_values
+                    .get(field)
+                    .copied()
+                    .unwrap_or_default()
+        };
+        let mut clauses = 0;
+
+        for (field, values) in parameters.filter.iter().flatten() {
+            let values = &values[..client_values(field, values.len())];
+            clauses += values.len();
+            for value in values {
+                check_set(&format!("filter[{field}]"), value, self.in_list_length)?;
+            }
+        }
+
+        for (field, filter) in parameters.relationship_filter.iter().flatten() {
+            let values = &filter.values[..client_values(field, filter.values.len())];
+            clauses += values.len();
+            for value in values {
+                if let RelationshipFilterValue::Identity(value) = value {
+                    check_set(&format!("filter[{field}]"), value, self.in_list_length)?;
+                }
+            }
+        }
+
+        if let Some(expression) = &parameters.filter_expression {
+            clauses += measure_expression(expression, self.in_list_length)?;
+        }
+
+        check("filter", "filter clauses", clauses, self.filter_clauses)
+    }
+
+    /// Checks the `sort[path]` and `page[path][limit]` options of every included relationship,
+    /// `prefix` being the include path leading to `include`.
+    fn enforce_include_options(
+        &self,
+        include: &IncludeParameters,
+        prefix: &str,
+    ) -> Result<(), Error> {
+        for (name, node) in include {
+            let path = match prefix {
+                "" => name.to_string(),
+                prefix => format!("{prefix}.{name}"),
+            };
+
+            let IncludeNode { sort, limit, .. } = node;
+            if let Some(sort) = sort {
+                check(
+                    &format!("sort[{path}]"),
+                    "sort fields",
+                    sort.len(),
+                    self.sort_fields,
+                )?;
+            }
+            if let Some(limit) = limit {
+                check(
+                    &format!("page[{path}][limit]"),
+                    "included records",
+                    *limit as usize,
+                    self.include_limit,
+                )?;
+            }
+
+            self.enforce_include_options(&node.children, &path)?;
+        }
+
+        Ok(())
+    }
+}
+
+fn check(parameter: &str, measure: &str, actual: usize, limit: Option<usize>) -> Result<(), Error> {
+    match limit {
+        Some(limit) if actual > limit => Err(Error::QueryLimitExceeded {
+            parameter: parameter.to_string(),
+            measure: measure.to_string(),
+            limit,
+            actual,
+        }),
+        _ => Ok(()),
+    }
+}
+
+fn check_set(parameter: &str, value: &FilterValue, limit: Option<usize>) -> Result<(), Error> {
+    match value {
+        FilterValue::In(values) | FilterValue::NotIn(values) => {
+            check(parameter, "values in a set", values.len(), limit)
+        }
+        _ => Ok(()),
+    }
+}
+
+/// The depth of the include tree and the number of paths it ends in, i.e. its leaves.
+fn measure_include(include: &IncludeParameters) -> (usize, usize) {
+    include.values().fold((0, 0), |(depth, paths), node| {
+        let (child_depth, child_paths) = measure_include(&node.children);
+        (depth.max(child_depth + 1), paths + child_paths.max(1))
+    })
+}
+
+/// Counts the conditions of a filter expression, checking the sets among them on the way.
+fn measure_expression(expression: &FilterExpression, limit: Option<usize>) -> Result<usize, Error> {
+    match expression {
+        FilterExpression::Condition { value, .. } => {
+            check_set("filter", value, limit)?;
+            Ok(1)
+        }
+        FilterExpression::Relationship(filter) => {
+            for value in &filter.values {
+                if let RelationshipFilterValue::Identity(value) = value {
+                    check_set("filter", value, limit)?;
+                }
+            }
+            Ok(filter.values.len())
+        }
+        FilterExpression::Not(operand) => measure_expression(operand, limit),
+        FilterExpression::And(operands) | FilterExpression::Or(operands) => operands
+            .iter()
+            .map(|operand| measure_expression(operand, limit))
+            .sum(),
+    }
+}
@@ -0,0 +1,3655 @@
+//! Defines a data structure suitable for storing JSON:API-relevant query parameters.
+//! Also provides functions to extract those parameters from a query string, which is the only
+//! supported construction method.
+
+use super::error::Error;
+use crate::database::attributes::Attribute;
+use crate::database::error::Error::{
+    InvalidEncodingFailure, ParseParameterFailure, QueryValidationFailure,
+};
+use crate::database::filter_operator::{CustomFilter, FilterOperator};
+use crate::database::registry::Registry;
+use crate::database::schema::{
+    AttributeType, DateTime, RelationshipDescriptor, RelationshipKind, Schema, ScopeDescriptor,
+};
+use crate::http_wrappers::Uri;
+use indexmap::{IndexMap, IndexSet};
+use regex::Regex;
+use std::borrow::Cow;
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+use std::{num::NonZeroU32, sync::LazyLock};
+use urlencoding::decode;
+
+mod aggregate;
+mod expression;
+mod limits;
+
+pub use aggregate::{
+    Aggregate, AggregateFunction, AggregateParameters, AggregateRow, DateBucket, GroupKey,
+};
+pub use expression::FilterExpression;
+pub use limits::QueryLimits;
+
+mod regex_builder {
+    /// Generic pattern for identifiers -- model names, field names and relationship names
+    pub(crate) static ID: &str = r"[a-zA-Z](?:[-_]*[a-zA-Z0-9]+)*";
+}
+
+/// Matches exactly a sort directive: a dot-separated path of identifiers with an optional plus or
+/// minus sign, indicating sort direction. Every segment but the last names a relationship.
+static SORT_REGEX: LazyLock<Regex> = LazyLock::new(|| {
+    use regex_builder::ID;
+    Regex::new(format!(r"\A([-+]?)((?:{ID}\.)*{ID})\z").as_str()).unwrap()
+});
+
+/// Matches exactly a filter directive: a supported operand and a filter term.
+/// The term can be anything and will be percent-decoded before being considered by the filter.
+///
+/// The valid operands are: `eq`, `neq`, `gt`, `gte`, `lt`, `lte`, `like`, `ilike`, `starts`,
+/// `ends`, `ieq`, `in`, `nin`.
+static FILTER_REGEX: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"\A(eq|neq|gt|gte|lt|lte|like|ilike|starts|ends|ieq|in|nin):(.*)\z").unwrap()
+});
+
+/// The built-in comparison operators, as in `filter[field]` entries.
+pub(crate) const FILTER_OPERATORS: [&str; 13] = [
+    "eq", "neq", "gt", "gte", "lt", "lte", "like", "ilike", "starts", "ends", "ieq", "in", "nin",
+];
+
+/// The `filter[...]` family member applying named scopes, as `filter[scope]=published`.
+pub(crate) const SCOPE_FILTER: &str = "scope";
+
+/// The `filter[...]` family member reading a resource keeping history as it stood at an instant,
+/// as `filter[as_of]=2026-01-01T00:00:00Z`.
+pub(crate) const AS_OF_FILTER: &str = "as_of";
+
+/// The `sort` key ranking a searched collection by relevance, unless the schema has an attribute
+/// of that name.
+pub(crate) const RELEVANCE_SORT: &str = "relevance";
+
+/// Whether `name` is taken by a built-in filter operator or relationship filter directive, and so
+/// cannot name a registered operator.
+pub(crate) fn is_reserved_operator(name: &str) -> bool {
+    FILTER_OPERATORS.contains(&name)
+        || ["exists", "none"].contains(&name)
+        || name.starts_with("count-")
+}
+
+/// Rejects a text matching operation on `field` unless it is a text attribute.
+pub(crate) fn require_text(
+    schema: &Schema,
+    field: &str,
+    kind: AttributeType,
+    value: &FilterValue,
+) -> Result<(), Error> {
+    match value.text_operator() {
+        Some(operator) if kind != AttributeType::Text => Err(QueryValidationFailure {
+            schema: schema.name().to_string(),
+            attribute: field.to_string(),
+            message: format!("The '{operator}' operator can only be applied to text attributes"),
+        }),
+        _ => Ok(()),
+    }
+}
+
+/// Rejects a relationship filter with an operator other than an identity, existence or count one.
+pub(super) fn unsupported_relationship_filter(schema: &Schema, field: &str) -> Error {
+    QueryValidationFailure {
+        schema: schema.name().to_string(),
+        attribute: field.to_string(),
+        message: "Relationships can only be filtered with 'eq', 'neq', 'in', 'nin', 'exists', \
+                  'none' or a 'count-' comparison"
+            .to_string(),
+    }
+}
+
+/// Matches exactly a relationship filter directive beyond the identity operators: `exists`, `none`,
+/// or a count comparison such as `count-gt:5`.
+static RELATIONSHIP_FILTER_REGEX: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"\A(?:(exists|none)|count-(eq|neq|gt|gte|lt|lte):(.*))\z").unwrap()
+});
+
+/// Matches a family parameter in the form `$family[$param]`.
+///
+/// The following families are supported:
+///
+/// - `filter[$field_name]`
+/// - `fields[$model_name]`
+/// - `page[number]` and `page[size]`
+/// - `meta[counts]`
+/// - `aggregate[$function]`
+/// - `search[$attribute_name]`
+static FAMILY_REGEX: LazyLock<Regex> = LazyLock::new(|| {
+    use regex_builder::ID;
+    Regex::new(format!(r"\A(filter|page|fields|meta|aggregate|search)\[({ID})]\z").as_str())
+        .unwrap()
+});
+
+/// Matches an option of an included relationship, addressed by its dot-separated include path:
+/// `sort[$path]`, ordering its linkage, or `page[$path][limit]`, capping it per record.
+static INCLUDE_OPTION_REGEX: LazyLock<Regex> = LazyLock::new(|| {
+    use regex_builder::ID;
+    Regex::new(format!(r"\A(sort|page)\[((?:{ID}\.)*{ID})]((?:\[limit])?)\z").as_str()).unwrap()
+});
+
+/// Enumerates possible sort directions: ascending and descending
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SortDirection {
+    Ascending,
+    Descending,
+}
+
+/// Stores information for sorting a collection. `path` holds the to-one relationships leading from
+/// the primary schema to the schema owning `attribute`, empty when sorting by an own attribute.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SortingAttribute<'sch> {
+    pub(crate) path: Vec<&'sch RelationshipDescriptor<'sch>>,
+    pub(crate) attribute: &'sch str,
+    pub(crate) direction: SortDirection,
+}
+
+/// A single `sort` entry: by an attribute, or by how well a record matches the full-text `search`
+/// (`relevance`, ranked by the index).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SortDirective<'sch> {
+    Attribute(SortingAttribute<'sch>),
+    Relevance(SortDirection),
+}
+
+/// Enumerates possible comparison operations available for filtering.
+///
+/// The text matching operations apply to text attributes only. `Like` matches a literal substring
+/// as SQL `LIKE` does, folding ASCII case; `StartsWith` and `EndsWith` match a literal prefix or
+/// suffix case-sensitively, as `Equal` compares; the `Insensitive` operations fold case across all
+/// of Unicode first.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FilterValue {
+    Equal(Attribute),
+    NotEqual(Attribute),
+    GreaterThan(Attribute),
+    GreaterThanOrEqual(Attribute),
+    LessThan(Attribute),
+    LessThanOrEqual(Attribute),
+    Like(Attribute),
+    InsensitiveLike(Attribute),
+    StartsWith(Attribute),
+    EndsWith(Attribute),
+    InsensitiveEqual(Attribute),
+    In(IndexSet<Attribute>),
+    NotIn(IndexSet<Attribute>),
+    /// An operator registered on the `Registry`
+    Custom(CustomFilter),
+}
+
+impl FilterValue {
+    /// The operator of a text matching operation, as written in a filter entry.
+    pub(crate) fn text_operator(&self) -> Option<&'static str> {
+        use FilterValue::*;
+
+        match self {
+            Like(_) => Some("like"),
+            InsensitiveLike(_) => Some("ilike"),
+            StartsWith(_) => Some("starts"),
+            EndsWith(_) => Some("ends"),
+            InsensitiveEqual(_) => Some("ieq"),
+            _ => None,
+        }
+    }
+}
+
+/// Stores which fields should be returned for a given model type
+pub type FieldsParameters<'sch> = IndexMap<&'sch str, IndexSet<&'sch str>>;
+
+/// Stores which filters should be applied for each field from the primary data
+pub type FilterParameters<'sch> = IndexMap<&'sch str, Vec<FilterValue>>;
+
+/// Enumerates the conditions a relationship filter can place on a record's related records
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RelationshipFilterValue {
+    /// Linked to (`eq`, `in`) or not linked to (`neq`, `nin`) the related records with the given
+    /// primary keys
+    Identity(FilterValue),
+    Exists,
+    NotExists,
+    /// The number of related records compared against an integer
+    Count(FilterValue),
+}
+
+/// A filter on a relationship's related records, which live on another table.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RelationshipFilter<'sch> {
+    pub(crate) descriptor: &'sch RelationshipDescriptor<'sch>,
+    pub(crate) primary_key: &'sch str,
+    pub(crate) values: Vec<RelationshipFilterValue>,
+}
+
+/// Stores which relationship filters should be applied for each relationship of the primary data
+pub type RelationshipFilterParameters<'sch> = IndexMap<&'sch str, RelationshipFilter<'sch>>;
+
+/// The suffix marking a search term as a prefix, as in `search=rus*`
+pub const SEARCH_PREFIX: &str = "*";
+
+/// A single term of a full-text search: a phrase, matched as a whole, or as the prefix of a
+/// phrase, across every indexed attribute (`search=...`) or only one of them (`search[title]=...`).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SearchTerm<'sch, 'req> {
+    pub(crate) attribute: Option<&'sch str>,
+    pub(crate) phrase: Cow<'req, str>,
+    pub(crate) prefix: bool,
+}
+
+/// Stores a series of terms to be searched, all of which a record must match
+pub type SearchParameters<'sch, 'req> = Vec<SearchTerm<'sch, 'req>>;
+
+/// Represents a single node in the include tree. An included to-many relationship may be ordered
+/// by `sort` and capped at `limit` related records per record.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IncludeNode<'sch: 'req, 'req> {
+    pub(crate) relationship: &'req str,
+    pub(crate) descriptor: &'sch RelationshipDescriptor<'sch>,
+    pub(crate) children: HashMap<&'sch str, IncludeNode<'sch, 'req>>,
+    pub(crate) sort: Option<SortParameters<'sch>>,
+    pub(crate) limit: Option<u32>,
+}
+
+impl IncludeNode<'_, '_> {
+    /// Whether the relationship is loaded as a ranked subset rather than in full.
+    pub fn is_ranked(&self) -> bool {
+        self.sort.is_some() || self.limit.is_some()
+    }
+}
+
+/// Stores a series of relationship names which should be included in the final payload
+pub type IncludeParameters<'sch, 'req> = HashMap<&'sch str, IncludeNode<'sch, 'req>>;
+
+/// Stores which to-many relationships of the primary data should have their related records counted
+pub type CountParameters<'sch> = IndexSet<&'sch str>;
+
+/// Stores how the primary collection should be sorted
+pub type SortParameters<'sch> = Vec<SortDirective<'sch>>;
+
+/// Stores which full-text indexed attributes of the primary data should have search excerpts
+/// rendered into each resource's `meta`: `snippets` around the matches, and `highlights` marking
+/// them in the whole value.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ExcerptParameters<'sch> {
+    pub(crate) snippets: IndexSet<&'sch str>,
+    pub(crate) highlights: IndexSet<&'sch str>,
+}
+
+impl ExcerptParameters<'_> {
+    pub fn is_empty(&self) -> bool {
+        self.snippets.is_empty() && self.highlights.is_empty()
+    }
+}
+
+/// Stores how the primary collection should be paged
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PageParameters {
+    pub(crate) number: u32,
+    pub(crate) size: u32,
+}
+
+impl Default for PageParameters {
+    /// Constructs a new paging object, defaulting to first page with 20 records per page.
+    fn default() -> Self {
+        Self {
+            number: 1,
+            size: 20,
+        }
+    }
+}
+
+/// Stores how many values at the end of each filtered field's entries its scopes contributed, as
+/// opposed to the client
+pub type ScopeValues<'sch> = HashMap<&'sch str, usize>;
+
+/// Auxiliary struct to collect model schemas that should be loaded for all the requested
+/// information to be served
+pub type ModelsToSerialise<'sch> = HashMap<&'sch str, &'sch Schema<'sch>>;
+
+/// Stores all possible query parameters received.
+/// Those parameters will be used later to determine which and how data should be loaded.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct QueryParameters<'sch, 'req> {
+    pub schema: &'sch Schema<'sch>,
+    pub fields: FieldsParameters<'sch>,
+    pub include: IncludeParameters<'sch, 'req>,
+    pub filter: Option<FilterParameters<'sch>>,
+    pub relationship_filter: Option<RelationshipFilterParameters<'sch>>,
+    pub filter_expression: Option<FilterExpression<'sch>>,
+    pub search: Option<SearchParameters<'sch, 'req>>,
+    pub sort: Option<SortParameters<'sch>>,
+    pub page: Option<PageParameters>,
+    pub counts: CountParameters<'sch>,
+    pub excerpts: ExcerptParameters<'sch>,
+    pub aggregate: Option<AggregateParameters<'sch>>,
+    pub as_of: Option<DateTime>,
+    pub scope_values: ScopeValues<'sch>,
+}
+
+impl<'sch, 'req> QueryParameters<'sch, 'req> {
+    pub fn new(schema: &'sch Schema<'sch>) -> Self {
+        let mut parameters = Self {
+            schema,
+            fields: FieldsParameters::from_iter([(schema.name(), schema.fields().collect())]),
+            include: IncludeParameters::new(),
+            filter: None,
+            relationship_filter: None,
+            filter_expression: None,
+            search: None,
+            sort: None,
+            page: None,
+            counts: schema.counted_relationships().collect(),
+            excerpts: ExcerptParameters::default(),
+            aggregate: None,
+            as_of: None,
+            scope_values: ScopeValues::new(),
+        };
+        parameters.discover_fields_for_remaining_models(ModelsToSerialise::from([(
+            schema.name(),
+            schema,
+        )]));
+
+        parameters
+    }
+
+    /// The records `self` selects, without how they are shaped: its filters and search, with
+    /// every field and nothing included, counted, sorted or paged. A bulk write reads and writes
+    /// this scope.
+    pub fn scope_of(&self) -> Self {
+        Self {
+            filter: self.filter.clone(),
+            relationship_filter: self.relationship_filter.clone(),
+            filter_expression: self.filter_expression.clone(),
+            search: self.search.clone(),
+            counts: CountParameters::default(),
+            ..Self::new(self.schema)
+        }
+    }
+
+    /// Main entry point.
+    /// Attempts to extract supported parameters from the provided URI.
+    /// Any parsing errors (encoding errors, failed validations etc.) will cause the function to
+    /// return an `Err`.
+    pub fn parse(
+        uri: &'req Uri,
+        schema: &'sch Schema<'sch>,
+        registry: &'sch Registry<'sch>,
+    ) -> Result<QueryParameters<'sch, 'req>, Error> {
+        let mut query_parameters = Self {
+            schema,
+            fields: FieldsParameters::new(),
+            include: IncludeParameters::new(),
+            filter: None,
+            relationship_filter: None,
+            filter_expression: None,
+            search: None,
+            sort: None,
+            page: None,
+            counts: schema.counted_relationships().collect(),
+            excerpts: ExcerptParameters::default(),
+            aggregate: None,
+            as_of: None,
+            scope_values: ScopeValues::new(),
+        };
+        query_parameters.parse_query(uri.query().unwrap_or_default(), schema, registry)?;
+
+        Ok(query_parameters)
+    }
+
+    pub fn derive(
+        &self,
+        relationship: &str,
+        registry: &'sch Registry<'sch>,
+    ) -> Result<Self, Error> {
+        let include =
+            self.include
+                .get(relationship)
+                .ok_or_else(|| Error::QueryValidationFailure {
+                    schema: self.schema.name().to_string(),
+                    attribute: relationship.to_string(),
+                    message: "Invalid relationship requested".to_string(),
+                })?;
+
+        let schema = registry.schema(include.descriptor.related.resource)?;
+
+        Ok(Self {
+            fields: self.fields.clone(),
+            include: include.children.clone(),
+            ..QueryParameters::new(schema)
+        })
+    }
+
+    pub fn is_requested(&self, field: &str) -> bool {
+        match self.fields.get(self.schema.name()) {
+            Some(fields) => fields.contains(field),
+            None => false,
+        }
+    }
+
+    pub fn is_included(&self, relationship: &str) -> bool {
+        self.include.contains_key(relationship)
+    }
+
+    pub fn should_load(&self, relationship: &str) -> bool {
+        self.is_requested(relationship) || self.is_included(relationship)
+    }
+
+    pub fn relationships_to_load(
+        &self,
+    ) -> impl Iterator<Item = (&'sch str, &'sch RelationshipDescriptor<'sch>)> {
+        self.schema
+            .relationships()
+            .filter(|(relationship, _)| self.should_load(relationship))
+    }
+
+    /// Completes `self.fields` so that every model the request will touch can be queried.
+    ///
+    /// Runs in two phases over the *serialised* models -- the primary resource plus everything
+    /// reachable through `include`, which are exactly the contexts the data loader recurses into:
+    ///
+    /// 1. each serialised model that the request did not pin explicitly receives its full default
+    ///    fieldset, so included resources are presented in full;
+    /// 2. for every relationship out of a serialised model, the join key (`keys.related`) is forced
+    ///    into the *related* model's fieldset -- a minimal, join-key-only entry for link-only
+    ///    targets. This key lives on a table the query builder never sees on its own (it is scoped
+    ///    to a single schema), so it has to be planted here. When it is the related table's primary
+    ///    key -- the usual case -- `fields_for_model` folds the duplicate away against the primary
+    ///    key it always selects.
+    ///
+    /// Because the keys are forced for every relationship regardless of whether it ends up loaded,
+    /// no `should_load`/include-context bookkeeping is needed here.
+    fn discover_fields_for_remaining_models(
+        &mut self,
+        models_to_serialise: ModelsToSerialise<'sch>,
+    ) {
+        for (&model, &schema) in &models_to_serialise {
+            self.fields
+                .entry(model)
+                .or_insert_with(|| schema.fields().collect());
+        }
+
+        for (_, schema) in models_to_serialise {
+            for (_, relationship) in schema.relationships() {
+                let related = &relationship.related;
+                self.fields
+                    .entry(related.resource)
+                    .or_default()
+                    .insert(related.keys.related);
+            }
+        }
+    }
+
+    fn parse_fields(
+        &mut self,
+        model: &str,
+        fields: &'req str,
+        registry: &Registry<'sch>,
+    ) -> Result<(), Error> {
+        let schema = registry.schema(model)?;
+
+        if fields.is_empty() {
+            self.fields.entry(schema.name()).or_default();
+            return Ok(());
+        }
+
+        let schema_fields = schema.fields().collect::<HashSet<_>>();
+
+        let model_fields = fields
+            .split(',')
+            .map(|field| {
+                if let Some(&field) = schema_fields.get(field) {
+                    Ok(field)
+                } else {
+                    Err(Error::QueryValidationFailure {
+                        schema: schema.name().to_string(),
+                        attribute: field.to_string(),
+                        message: "Requested field is invalid".to_string(),
+                    })
+                }
+            })
+            .collect::<Result<Vec<_>, Error>>()?;
+
+        for field in model_fields {
+            self.fields.entry(schema.name()).or_default().insert(field);
+        }
+
+        Ok(())
+    }
+    fn decode_str(value: &'req str) -> Result<Cow<'req, str>, Error> {
+        decode(value).map_err(|_| InvalidEncodingFailure)
+    }
+
+    fn parse_attribute(value: &'req str, kind: AttributeType) -> Result<Attribute, Error> {
+        let value = Self::decode_str(value)?;
+        Attribute::parse(&value, kind)
+    }
+
+    fn parse_attribute_set(
+        value: &'req str,
+        kind: AttributeType,
+    ) -> Result<IndexSet<Attribute>, Error> {
+        value
+            .split(",")
+            .map(|value| Self::parse_attribute(value, kind))
+            .collect()
+    }
+
+    /// Parses a `filter[field]` entry. `field` names an attribute, the primary key (also as `id`),
+    /// a foreign key, or a relationship; a relationship is filtered by the identity of its related
+    /// records. A repeated field is ANDed with its earlier entries.
+    fn parse_filter(
+        &mut self,
+        field: &str,
+        entries: &'req str,
+        schema: &'sch Schema<'sch>,
+        registry: &'sch Registry<'sch>,
+    ) -> Result<(), Error> {
+        if let Some(descriptor) = schema.relationship(field) {
+            return self.parse_relationship_filter(descriptor, entries, schema, registry);
+        }
+
+        let column = match field {
+            "id" => schema.column(schema.primary_key().name),
+            field => schema.column(field),
+        }
+        .ok_or_else(|| QueryValidationFailure {
+            schema: schema.name().to_string(),
+            attribute: field.to_string(),
+            message: "Attempted to filter on an unknown attribute".to_string(),
+        })?;
+
+        let filter = Self::parse_filter_entries(field, entries, column.kind, registry)?;
+
+        for value in &filter {
+            require_text(schema, field, column.kind, value)?;
+            if let FilterValue::Custom(custom) = value
+                && !custom.operator.applies_to(column.kind)
+            {
+                return Err(QueryValidationFailure {
+                    schema: schema.name().to_string(),
+                    attribute: field.to_string(),
+                    message: format!(
+                        "The '{}' operator cannot be applied to {} attributes",
+                        custom.name(),
+                        column.kind
+                    ),
+                });
+            }
+        }
+
+        self.filter
+            .get_or_insert_default()
+            .entry(column.name)
+            .or_default()
+            .extend(filter);
+
+        Ok(())
+    }
+
+    /// Parses a `filter[relationship]` entry. Identity operators (`eq`, `neq`, `in`, `nin`) take
+    /// primary keys of the related resource; `exists`, `none` and the `count-` comparisons test
+    /// whether, or how many, related records there are.
+    fn parse_relationship_filter(
+        &mut self,
+        descriptor: &'sch RelationshipDescriptor<'sch>,
+        entries: &'req str,
+        schema: &'sch Schema<'sch>,
+        registry: &'sch Registry<'sch>,
+    ) -> Result<(), Error> {
+        let related = registry.schema(descriptor.related.resource)?;
+        let primary_key = related.primary_key();
+        let field = descriptor.name;
+
+        let values = Self::split_filter_entries(entries, registry, |chunk| {
+            FILTER_REGEX.is_match(chunk) || RELATIONSHIP_FILTER_REGEX.is_match(chunk)
+        })
+        .into_iter()
+        .map(|entry| {
+            use FilterValue::*;
+
+            let Some(captures) = RELATIONSHIP_FILTER_REGEX.captures(entry) else {
+                return match Self::parse_filter_entry(
+                    field,
+                    entry,
+                    primary_key.kind.into(),
+                    registry,
+                )
+                .map_err(Self::conversion_error(field))?
+                {
+                    value @ (Equal(_) | NotEqual(_) | In(_) | NotIn(_)) => {
+                        Ok(RelationshipFilterValue::Identity(value))
+                    }
+                    _ => Err(unsupported_relationship_filter(schema, field)),
+                };
+            };
+
+            let (Some(operator), Some(value)) = (captures.get(2), captures.get(3)) else {
+                return Ok(match &captures[1] {
+                    "exists" => RelationshipFilterValue::Exists,
+                    _ => RelationshipFilterValue::NotExists,
+                });
+            };
+
+            let value = Self::parse_attribute(value.as_str(), AttributeType::Integer)
+                .map_err(Self::conversion_error(field))?;
+            Ok(RelationshipFilterValue::Count(match operator.as_str() {
+                "eq" => Equal(value),
+                "neq" => NotEqual(value),
+                "gt" => GreaterThan(value),
+                "gte" => GreaterThanOrEqual(value),
+                "lt" => LessThan(value),
+                _ => LessThanOrEqual(value),
+            }))
+        })
+        .collect::<Result<Vec<_>, Error>>()?;
+
+        match self
+            .relationship_filter
+            .get_or_insert_default()
+            .entry(descriptor.name)
+        {
+            indexmap::map::Entry::Occupied(mut entry) => entry.get_mut().values.extend(values),
+            indexmap::map::Entry::Vacant(entry) => {
+                entry.insert(RelationshipFilter {
+                    descriptor,
+                    primary_key: primary_key.name,
+                    values,
+                });
+            }
+        }
+
+        Ok(())
+    }
+
+    fn parse_filter_entries(
+        field: &str,
+        entries: &'req str,
+        kind: AttributeType,
+        registry: &'sch Registry<'sch>,
+    ) -> Result<Vec<FilterValue>, Error> {
+        Self::split_filter_entries(entries, registry, |chunk| FILTER_REGEX.is_match(chunk))
+            .into_iter()
+            .map(|entry| {
+                Self::parse_filter_entry(field, entry, kind, registry)
+                    .map_err(Self::conversion_error(field))
+            })
+            .collect()
+    }
+
+    /// Splits a comma-separated list of filter entries. The values of a set operator (`in`, `nin`)
+    /// or of a registered operator may be comma-separated too, so a chunk that neither
+    /// `starts_entry` nor names a registered operator continues the values before it. Splitting
+    /// runs before percent-decoding: a value that would read as an entry, such as `eq:b`, stays a
+    /// value once its colon is encoded (`eq%3Ab`), as a comma within a value is (`%2C`).
+    fn split_filter_entries(
+        entries: &'req str,
+        registry: &'sch Registry<'sch>,
+        starts_entry: impl Fn(&str) -> bool,
+    ) -> Vec<&'req str> {
+        let mut ranges: Vec<(usize, usize)> = Vec::new();
+        let mut start = 0;
+        for chunk in entries.split(",") {
+            let end = start + chunk.len();
+            match ranges.last_mut() {
+                Some((from, to))
+                    if !starts_entry(chunk)
+                        && Self::custom_operator(chunk, registry).is_none()
+                        && (["in:", "nin:"]
+                            .iter()
+                            .any(|prefix| entries[*from..].starts_with(prefix))
+                            || Self::custom_operator(&entries[*from..], registry).is_some()) =>
+                {
+                    *to = end
+                }
+                _ => ranges.push((start, end)),
+            }
+            start = end + 1;
+        }
+
+        ranges
+            .into_iter()
+            .map(|(from, to)| &entries[from..to])
+            .collect()
+    }
+
+    fn parse_filter_entry(
+        field: &str,
+        entry: &'req str,
+        kind: AttributeType,
+        registry: &'sch Registry<'sch>,
+    ) -> Result<FilterValue, Error> {
+        if let Some((operator, value)) = Self::custom_operator(entry, registry) {
+            let values = operator
+                .parse(&Self::decode_str(value)?, kind)
+                .map_err(|message| Error::ParseParameterFailure {
+                    parameter: format!("filter[{field}]"),
+                    message,
+                })?;
+
+            return Ok(FilterValue::Custom(CustomFilter {
+                operator: Arc::clone(operator),
+                values,
+            }));
+        }
+
+        let Some((_, [operator, value])) = FILTER_REGEX.captures(entry).map(|c| c.extract()) else {
+            return Err(Error::ParseParameterFailure {
+                parameter: format!("filter[{field}]"),
+                message: format!("Invalid filter entry: '{entry}'"),
+            });
+        };
+
+        use FilterValue::*;
+        let filter_value = match operator {
+            "eq" => Equal(Self::parse_attribute(value, kind)?),
+            "neq" => NotEqual(Self::parse_attribute(value, kind)?),
+            "gt" => GreaterThan(Self::parse_attribute(value, kind)?),
+            "gte" => GreaterThanOrEqual(Self::parse_attribute(value, kind)?),
+            "lt" => LessThan(Self::parse_attribute(value, kind)?),
+            "lte" => LessThanOrEqual(Self::parse_attribute(value, kind)?),
+            "like" => Like(Self::parse_attribute(value, kind)?),
+            "ilike" => InsensitiveLike(Self::parse_attribute(value, kind)?),
+            "starts" => StartsWith(Self::parse_attribute(value, kind)?),
+            "ends" => EndsWith(Self::parse_attribute(value, kind)?),
+            "ieq" => InsensitiveEqual(Self::parse_attribute(value, kind)?),
+            "in" => In(Self::parse_attribute_set(value, kind)?),
+            "nin" => NotIn(Self::parse_attribute_set(value, kind)?),
+            _ => Err(Error::ParseParameterFailure {
+                parameter: format!("filter[{field}]"),
+                message: format!("Invalid filter operator: '{operator}'"),
+            })?,
+        };
+
+        Ok(filter_value)
+    }
+
+    /// Looks up the registered operator a filter entry names, if any, along with its raw value.
+    fn custom_operator<'a>(
+        entry: &'a str,
+        registry: &'sch Registry<'sch>,
+    ) -> Option<(&'sch Arc<dyn FilterOperator>, &'a str)> {
+        let (name, value) = entry.split_once(":")?;
+        Some((registry.filter_operator(name)?, value))
+    }
+
+    /// Maps a failed value conversion to a parse failure naming the filtered field.
+    fn conversion_error(field: &str) -> impl Fn(Error) -> Error {
+        move |error| match error {
+            Error::InvalidAttributeConversion { kind } => Error::ParseParameterFailure {
+                parameter: format!("filter[{field}]"),
+                message: format!("Filter value is not a valid {kind}"),
+            },
+            error => error,
+        }
+    }
+
+    /// Resolves the comma-separated scope names of a `filter[scope]` entry.
+    fn parse_scopes(
+        names: &'req str,
+        schema: &'sch Schema<'sch>,
+    ) -> Result<Vec<&'sch ScopeDescriptor<'sch>>, Error> {
+        names
+            .split(",")
+            .map(|name| {
+                let name = Self::decode_str(name)?;
+                schema.scope(&name).ok_or_else(|| ParseParameterFailure {
+                    parameter: format!("filter[{SCOPE_FILTER}]"),
+                    message: format!("Unknown filter scope: '{name}'"),
+                })
+            })
+            .collect()
+    }
+
+    /// Expands a scope into its filters, ANDing them with those already parsed, as a repeated
+    /// `filter[field]` entry is.
+    fn apply_scope(
+        &mut self,
+        scope: &'sch ScopeDescriptor<'sch>,
+        schema: &'sch Schema<'sch>,
+        registry: &'sch Registry<'sch>,
+    ) -> Result<(), Error> {
+        for &(field, entries) in &scope.filters {
+            self.apply_scope_entry(scope, field, entries, schema, registry)?;
+        }
+
+        Ok(())
+    }
+
+    /// Checks that a scope's `filter[field]` entry expands, as the registry does once for every
+    /// scope it holds.
+    pub(crate) fn validate_scope_entry(
+        scope: &'sch ScopeDescriptor<'sch>,
+        field: &'sch str,
+        entries: &'sch str,
+        schema: &'sch Schema<'sch>,
+        registry: &'sch Registry<'sch>,
+    ) -> Result<(), Error> {
+        QueryParameters::new(schema).apply_scope_entry(scope, field, entries, schema, registry)
+    }
+
+    /// A scope is part of the schema, so an entry that fails to parse is a server-side
+    /// inconsistency, not a bad request.
+    fn apply_scope_entry(
+        &mut self,
+        scope: &'sch ScopeDescriptor<'sch>,
+        field: &'sch str,
+        entries: &'sch str,
+        schema: &'sch Schema<'sch>,
+        registry: &'sch Registry<'sch>,
+    ) -> Result<(), Error> {
+        let before = self.filter_lengths();
+        self.parse_filter(field, entries, schema, registry)
+            .map_err(|error| Error::InconsistentSchema {
+                schema: schema.name().to_string(),
+                attribute: scope.name.to_string(),
+                message: format!("Scope failed to expand: {error}"),
+            })?;
+
+        for (field, length) in self.filter_lengths() {
+            let added = length - before.get(field).copied().unwrap_or_default();
+            if added > 0 {
+                *self.scope_values.entry(field).or_default() += added;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// The number of values each filtered field holds, across attribute and relationship filters.
+    fn filter_lengths(&self) -> HashMap<&'sch str, usize> {
+        let filter = self
+            .filter
+            .iter()
+            .flatten()
+            .map(|(&field, values)| (field, values.len()));
+        let relationship_filter = self
+            .relationship_filter
+            .iter()
+            .flatten()
+            .map(|(&field, filter)| (field, filter.values.len()));
+
+        filter.chain(relationship_filter).collect()
+    }
+
+    /// Parses the filter expression profile's bare `filter` parameter. Repeated expressions are
+    /// combined with `and`, as `filter[field]` entries are.
+    fn parse_filter_expression(
+        &mut self,
+        expression: &'req str,
+        schema: &'sch Schema<'sch>,
+        registry: &'sch Registry<'sch>,
+    ) -> Result<(), Error> {
+        let expression = FilterExpression::parse(&Self::decode_str(expression)?, schema, registry)?;
+
+        self.filter_expression = Some(match self.filter_expression.take() {
+            Some(previous) => FilterExpression::And(vec![previous, expression]),
+            None => expression,
+        });
+
+        Ok(())
+    }
+
+    /// Parses a `search` or `search[attribute]` entry, a comma-separated list of phrases, each
+    /// suffixed with `*` to match it as a prefix. A column-scoped search must name an attribute the
+    /// full-text index holds.
+    fn parse_search(
+        &mut self,
+        attribute: Option<&str>,
+        values: &'req str,
+        schema: &'sch Schema<'sch>,
+    ) -> Result<(), Error> {
+        let parameter = match attribute {
+            Some(attribute) => format!("search[{attribute}]"),
+            None => "search".to_string(),
+        };
+        let attribute = attribute
+            .map(|attribute| {
+                schema
+                    .attributes()
+                    .map(|(name, _)| name)
+                    .find(|&name| name == attribute && schema.index_column(name).is_some())
+                    .ok_or_else(|| QueryValidationFailure {
+                        schema: schema.name().to_string(),
+                        attribute: attribute.to_string(),
+                        message: "Only full-text indexed attributes can be searched".to_string(),
+                    })
+            })
+            .transpose()?;
+
+        for entry in values.split(",").filter(|entry| !entry.is_empty()) {
+            let phrase = Self::decode_str(entry)?;
+            let prefix = phrase.ends_with(SEARCH_PREFIX);
+            let phrase = match (prefix, phrase) {
+                (true, Cow::Borrowed(phrase)) => {
+                    Cow::Borrowed(&phrase[..phrase.len() - SEARCH_PREFIX.len()])
+                }
+                (true, Cow::Owned(mut phrase)) => {
+                    phrase.truncate(phrase.len() - SEARCH_PREFIX.len());
+                    Cow::Owned(phrase)
+                }
+                (false, phrase) => phrase,
+            };
+
+            if phrase.trim().is_empty() {
+                return Err(Error::ParseParameterFailure {
+                    parameter,
+                    message: format!("Invalid search term: '{entry}'"),
+                });
+            }
+
+            self.search.get_or_insert_default().push(SearchTerm {
+                attribute,
+                phrase,
+                prefix,
+            });
+        }
+
+        Ok(())
+    }
+
+    fn parse_include(
+        &mut self,
+        include: &str,
+        models: &mut HashMap<&'sch str, &'sch Schema<'sch>>,
+        schema: &'sch Schema<'sch>,
+        registry: &'sch Registry<'sch>,
+    ) -> Result<(), Error> {
+        if !include.is_empty() {
+            for include in include.split(",") {
+                let mut relationship;
+                let mut rest = Some(include);
+                let mut scope = &mut self.include;
+                let mut schema = schema;
+
+                while let Some(path) = rest {
+                    (relationship, rest) = match path.split_once(".") {
+                        Some((relationship, rest)) => (relationship, Some(rest)),
+                        None => (path, None),
+                    };
+
+                    let (relationship, descriptor) = schema
+                        .relationships()
+                        .find(|(r, _)| relationship == *r)
+                        .ok_or_else(|| Error::QueryValidationFailure {
+                            schema: schema.name().to_string(),
+                            attribute: relationship.to_string(),
+                            message: "Invalid relationship requested".to_string(),
+                        })?;
+
+                    schema = registry.schema(descriptor.related.resource)?;
+
+                    models.insert(schema.name(), schema);
+
+                    scope = &mut scope
+                        .entry(relationship)
+                        .or_insert(IncludeNode {
+                            relationship,
+                            descriptor,
+                            children: HashMap::new(),
+                            sort: None,
+                            limit: None,
+                        })
+                        .children;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn parse_sort(
+        &mut self,
+        entries: &'req str,
+        schema: &'sch Schema<'sch>,
+        registry: &'sch Registry<'sch>,
+    ) -> Result<(), Error> {
+        self.sort = Some(Self::parse_sort_directives(
+            entries, "sort", schema, registry,
+        )?);
+
+        Ok(())
+    }
+
+    /// Parses a comma-separated list of sort directives against `schema`, on behalf of the query
+    /// parameter `parameter`.
+    fn parse_sort_directives(
+        entries: &str,
+        parameter: &str,
+        schema: &'sch Schema<'sch>,
+        registry: &'sch Registry<'sch>,
+    ) -> Result<SortParameters<'sch>, Error> {
+        entries
+            .split(",")
+            .map(|entry| {
+                let result = SORT_REGEX.captures(entry).map(|c| c.extract());
+                let Some((_, [sign, path])) = result else {
+                    return Err(Error::ParseParameterFailure {
+                        parameter: parameter.to_string(),
+                        message: format!("Invalid sorting entry: '{entry}'"),
+                    });
+                };
+
+                let direction = match sign {
+                    "-" => SortDirection::Descending,
+                    "" | "+" => SortDirection::Ascending,
+                    _ => unreachable!(),
+                };
+
+                if path == RELEVANCE_SORT && schema.attribute(path).is_none() {
+                    return Ok(SortDirective::Relevance(direction));
+                }
+
+                let (relationships, attribute) = match path.rsplit_once(".") {
+                    Some((relationships, attribute)) => (Some(relationships), attribute),
+                    None => (None, path),
+                };
+
+                let mut schema = schema;
+                let mut descriptors = Vec::new();
+                for relationship in relationships.into_iter().flat_map(|r| r.split(".")) {
+                    let descriptor = schema.relationship(relationship).ok_or_else(|| {
+                        QueryValidationFailure {
+                            schema: schema.name().to_string(),
+                            attribute: relationship.to_string(),
+                            message: "Invalid relationship to sort".to_string(),
+                        }
+                    })?;
+
+                    if let RelationshipKind::HasMany = descriptor.kind {
+                        return Err(QueryValidationFailure {
+                            schema: schema.name().to_string(),
+                            attribute: relationship.to_string(),
+                            message: "Cannot sort by a to-many relationship".to_string(),
+                        });
+                    }
+
+                    schema = registry.schema(descriptor.related.resource)?;
+                    descriptors.push(descriptor);
+                }
+
+                let (attribute, _) = schema
+                    .attributes()
+                    .find(|(name, _)| *name == attribute)
+                    .ok_or_else(|| QueryValidationFailure {
+                        schema: schema.name().to_string(),
+                        attribute: attribute.to_string(),
+                        message: "Invalid attribute to sort".to_string(),
+                    })?;
+
+                Ok(SortDirective::Attribute(SortingAttribute {
+                    path: descriptors,
+                    attribute,
+                    direction,
+                }))
+            })
+            .collect()
+    }
+
+    /// Applies a `sort[path]` or `page[path][limit]` entry to the included to-many relationship at
+    /// `path`, which is only known once every `include` entry is parsed. An included relationship
+    /// is sorted by its own attributes only.
+    fn apply_include_option(
+        &mut self,
+        (family, path, value): (&str, String, Cow<'req, str>),
+        registry: &'sch Registry<'sch>,
+    ) -> Result<(), Error> {
+        let (parameter, failure) = match family {
+            "sort" => (
+                format!("sort[{path}]"),
+                "Only included to-many relationships can be sorted",
+            ),
+            _ => (
+                format!("page[{path}][limit]"),
+                "Only included to-many relationships can be limited",
+            ),
+        };
+
+        let schema = self.schema;
+        let invalid = || QueryValidationFailure {
+            schema: schema.name().to_string(),
+            attribute: path.clone(),
+            message: failure.to_string(),
+        };
+
+        let (parents, relationship) = match path.rsplit_once(".") {
+            Some((parents, relationship)) => (Some(parents), relationship),
+            None => (None, path.as_str()),
+        };
+        let mut scope = &mut self.include;
+        for parent in parents.into_iter().flat_map(|parents| parents.split(".")) {
+            scope = &mut scope.get_mut(parent).ok_or_else(invalid)?.children;
+        }
+        let node = scope
+            .get_mut(relationship)
+            .filter(|node| node.descriptor.kind == RelationshipKind::HasMany)
+            .ok_or_else(invalid)?;
+
+        match family {
+            "sort" => {
+                let schema = registry.schema(node.descriptor.related.resource)?;
+                let sort = Self::parse_sort_directives(&value, &parameter, schema, registry)?;
+                let foreign = sort.iter().find_map(|directive| match directive {
+                    SortDirective::Attribute(attribute) if attribute.path.is_empty() => None,
+                    SortDirective::Attribute(attribute) => Some(attribute.attribute),
+                    SortDirective::Relevance(_) => Some(RELEVANCE_SORT),
+                });
+                if let Some(attribute) = foreign {
+                    return Err(QueryValidationFailure {
+                        schema: schema.name().to_string(),
+                        attribute: attribute.to_string(),
+                        message: "Included relationships are sorted by their own attributes only"
+                            .to_string(),
+                    });
+                }
+                node.sort = Some(sort);
+            }
+            _ => {
+                let limit =
+                    value
+                        .parse::<NonZeroU32>()
+                        .map_err(|_| Error::ParseParameterFailure {
+                            parameter,
+                            message: format!("Invalid numeric value: '{value}'"),
+                        })?;
+                node.limit = Some(limit.get());
+            }
+        }
+
+        Ok(())
+    }
+
+    fn parse_page(&mut self, property: &str, value: &str) -> Result<(), Error> {
+        let value = value
+            .parse::<NonZeroU32>()
+            .map_err(|_| Error::ParseParameterFailure {
+                parameter: format!("page[{property}]"),
+                message: format!("Invalid numeric value: '{value}'"),
+            })?
+            .get();
+
+        let page = self.page.get_or_insert_default();
+        match property {
+            "number" => page.number = value,
+            "size" => page.size = value,
+            _ => Err(Error::ParseParameterFailure {
+                parameter: format!("page[{property}]"),
+                message: format!("Invalid page property: '{property}'"),
+            })?,
+        };
+
+        Ok(())
+    }
+
+    /// Parses `meta[counts]`, the to-many relationships to count on top of those the schema always
+    /// counts.
+    fn parse_meta(
+        &mut self,
+        property: &str,
+        value: &'req str,
+        schema: &'sch Schema<'sch>,
+    ) -> Result<(), Error> {
+        match property {
+            "counts" => {}
+            "snippets" | "highlights" => return self.parse_excerpts(property, value, schema),
+            _ => {
+                return Err(Error::ParseParameterFailure {
+                    parameter: format!("meta[{property}]"),
+                    message: format!("Invalid meta property: '{property}'"),
+                });
+            }
+        }
+
+        for relationship in value.split(",").filter(|entry| !entry.is_empty()) {
+            let relationship = Self::decode_str(relationship)?;
+            let (relationship, descriptor) = schema
+                .relationships()
+                .find(|(name, _)| *name == relationship)
+                .ok_or_else(|| QueryValidationFailure {
+                    schema: schema.name().to_string(),
+                    attribute: relationship.to_string(),
+                    message: "Invalid relationship to count".to_string(),
+                })?;
+
+            if descriptor.kind != RelationshipKind::HasMany {
+                return Err(QueryValidationFailure {
+                    schema: schema.name().to_string(),
+                    attribute: relationship.to_string(),
+                    message: "Only to-many relationships can be counted".to_string(),
+                });
+            }
+
+            self.counts.insert(relationship);
+        }
+
+        Ok(())
+    }
+
+    /// Parses a `meta[snippets]` or `meta[highlights]` entry, naming full-text indexed attributes.
+    fn parse_excerpts(
+        &mut self,
+        property: &str,
+        value: &'req str,
+        schema: &'sch Schema<'sch>,
+    ) -> Result<(), Error> {
+        for attribute in value.split(",").filter(|entry| !entry.is_empty()) {
+            let attribute = Self::decode_str(attribute)?;
+            let (attribute, _) = schema
+                .attributes()
+                .find(|(name, _)| *name == attribute)
+                .filter(|(name, _)| schema.index_column(name).is_some())
+                .ok_or_else(|| QueryValidationFailure {
+                    schema: schema.name().to_string(),
+                    attribute: attribute.to_string(),
+                    message: "Only full-text indexed attributes have search excerpts".to_string(),
+                })?;
+
+            match property {
+                "snippets" => self.excerpts.snippets.insert(attribute),
+                _ => self.excerpts.highlights.insert(attribute),
+            };
+        }
+
+        Ok(())
+    }
+
+    /// Parses the instant of a `filter[as_of]` entry.
+    fn parse_as_of(&mut self, value: &'req str) -> Result<(), Error> {
+        let instant = Self::parse_attribute(value, AttributeType::DateTime)
+            .and_then(Attribute::to_datetime)
+            .map_err(Self::conversion_error(AS_OF_FILTER))?;
+        self.as_of = Some(instant);
+
+        Ok(())
+    }
+
+    /// Rejects what a read of past states cannot answer: only the primary resource's own history is
+    /// kept, while the full-text index and related resources hold their current states alone. So a
+    /// past state cannot be searched, include, filter or sort by related resources, or count them;
+    /// the counts a schema always takes are dropped from it.
+    fn validate_as_of(&mut self) -> Result<(), Error> {
+        if self.as_of.is_none() {
+            return Ok(());
+        }
+
+        let failure = |attribute: &str, message: &str| QueryValidationFailure {
+            schema: self.schema.name().to_string(),
+            attribute: attribute.to_string(),
+            message: message.to_string(),
+        };
+        let sorts_related = self.sort.iter().flatten().any(|directive| {
+            matches!(directive, SortDirective::Attribute(sorting) if !sorting.path.is_empty())
+        });
+        let counted: CountParameters = self.schema.counted_relationships().collect();
+
+        if self.search.is_some() {
+            return Err(failure("search", "Past states cannot be searched"));
+        }
+        if !self.include.is_empty() {
+            return Err(failure(
+                "include",
+                "Past states cannot include related resources",
+            ));
+        }
+        if self.relationship_filter.is_some()
+            || self
+                .filter_expression
+                .as_ref()
+                .is_some_and(FilterExpression::names_relationship)
+        {
+            return Err(failure(
+                "filter",
+                "Past states cannot be filtered by related resources",
+            ));
+        }
+        if sorts_related {
+            return Err(failure(
+                "sort",
+                "Past states cannot be sorted by related resources",
+            ));
+        }
+        if !self.counts.is_subset(&counted) {
+            return Err(failure(
+                "meta[counts]",
+                "Past states cannot count related resources",
+            ));
+        }
+        self.counts.clear();
+
+        Ok(())
+    }
+
+    /// Rejects the parameters that only make sense for a full-text search when there is none.
+    fn validate_search(&self) -> Result<(), Error> {
+        if self.search.is_some() {
+            return Ok(());
+        }
+
+        let by_relevance = self
+            .sort
+            .iter()
+            .flatten()
+            .any(|directive| matches!(directive, SortDirective::Relevance(_)));
+        let failure = |attribute: &str, message: &str| QueryValidationFailure {
+            schema: self.schema.name().to_string(),
+            attribute: attribute.to_string(),
+            message: message.to_string(),
+        };
+
+        if by_relevance {
+            return Err(failure(
+                RELEVANCE_SORT,
+                "Sorting by relevance requires a search",
+            ));
+        }
+        if !self.excerpts.is_empty() {
+            return Err(failure("search", "Search excerpts require a search"));
+        }
+
+        Ok(())
+    }
+
+    fn parse_query(
+        &mut self,
+        query: &'req str,
+        schema: &'sch Schema<'sch>,
+        registry: &'sch Registry<'sch>,
+    ) -> Result<(), Error> {
+        let mut models_to_serialise = HashMap::from_iter([(schema.name(), schema)]);
+        let mut scopes = Vec::new();
+        let mut include_options = Vec::new();
+
+        for entry in query.split('&').filter(|entry| !entry.is_empty()) {
+            let (name, value) = entry
+                .split_once('=')
+                .map(|(name, value)| Self::decode_str(name).map(|name| (name, value)))
+                .unwrap_or_else(|| {
+                    Err(Error::ParseParameterFailure {
+                        parameter: entry.to_string(),
+                        message: format!("Invalid query entry: '{entry}'"),
+                    })
+                })?;
+
+            match name.as_ref() {
+                "search" => self.parse_search(None, value, schema)?,
+                "filter" => self.parse_filter_expression(value, schema, registry)?,
+                "include" => {
+                    self.parse_include(value, &mut models_to_serialise, schema, registry)?
+                }
+                "sort" => self.parse_sort(value, schema, registry)?,
+                "group" => self
+                    .aggregate
+                    .get_or_insert_default()
+                    .parse_group(&Self::decode_str(value)?, schema)?,
+                key => match FAMILY_REGEX.captures(key).map(|c| c.extract()) {
+                    Some((_, ["fields", model])) => self.parse_fields(model, value, registry)?,
+                    Some((_, ["filter", SCOPE_FILTER])) if schema.scope_applies() => {
+                        scopes.extend(Self::parse_scopes(value, schema)?)
+                    }
+                    Some((_, ["filter", AS_OF_FILTER])) if schema.history().is_some() => {
+                        self.parse_as_of(value)?
+                    }
+                    Some((_, ["filter", field])) => {
+                        self.parse_filter(field, value, schema, registry)?
+                    }
+                    Some((_, ["page", property])) => self.parse_page(property, value)?,
+                    Some((_, ["search", attribute])) => {
+                        self.parse_search(Some(attribute), value, schema)?
+                    }
+                    Some((_, ["meta", property])) => self.parse_meta(property, value, schema)?,
+                    Some((_, ["aggregate", function])) => self
+                        .aggregate
+                        .get_or_insert_default()
+                        .parse_aggregate(function, &Self::decode_str(value)?, schema)?,
+                    Some((parameter, [..])) => Err(Error::ParseParameterFailure {
+                        parameter: parameter.to_string(),
+                        message: "Unexpected parameter provided".to_string(),
+                    })?,
+                    None => match INCLUDE_OPTION_REGEX.captures(key).map(|c| c.extract()) {
+                        Some((_, ["sort", path, ""])) => include_options.push((
+                            "sort",
+                            path.to_string(),
+                            Self::decode_str(value)?,
+                        )),
+                        Some((_, ["page", path, "[limit]"])) => include_options.push((
+                            "page",
+                            path.to_string(),
+                            Self::decode_str(value)?,
+                        )),
+                        Some((parameter, [..])) => Err(Error::ParseParameterFailure {
+                            parameter: parameter.to_string(),
+                            message: "Unexpected parameter provided".to_string(),
+                        })?,
+                        None => Err(ParseParameterFailure {
+                            parameter: key.to_string(),
+                            message: "Unknown parameter provided".to_string(),
+                        })?,
+                    },
+                },
+            }
+        }
+
+        for scope in scopes {
+            self.apply_scope(scope, schema, registry)?;
+        }
+
+        for option in include_options {
+            self.apply_include_option(option, registry)?;
+        }
+
+        if let Some(aggregate) = &self.aggregate {
+            aggregate.validate()?;
+        }
+        self.validate_search()?;
+        self.validate_as_of()?;
+
+        self.discover_fields_for_remaining_models(models_to_serialise);
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::query_builder::Binder;
+    use crate::database::registry::Registry as DatabaseRegistry;
+    use crate::database::schema::{Related, SchemaBuilder};
+
+    type Registry = DatabaseRegistry<'static>;
+
+    /// A registered operator matching numbers within inclusive bounds, as `between:1,5`.
+    struct Between;
+
+    impl FilterOperator for Between {
+        fn name(&self) -> &str {
+            "between"
+        }
+
+        fn applies_to(&self, kind: AttributeType) -> bool {
+            matches!(kind, AttributeType::Integer | AttributeType::Float)
+        }
+
+        fn parse(&self, value: &str, kind: AttributeType) -> Result<Vec<Attribute>, String> {
+            let Some((low, high)) = value.split_once(",") else {
+                return Err("'between' takes a lower and an upper bound".to_string());
+            };
+
+            [low, high]
+                .into_iter()
+                .map(|bound| Attribute::parse(bound, kind).map_err(|error| error.to_string()))
+                .collect()
+        }
+
+        fn render(&self, column: &str, values: &[Attribute], binder: &mut dyn Binder) -> String {
+            let low = binder.bind(values[0].clone());
+            let high = binder.bind(values[1].clone());
+            format!("{column} BETWEEN {low} AND {high}")
+        }
+    }
+
+    fn articles() -> SchemaBuilder<'static> {
+        SchemaBuilder::table("articles")
+            .attribute("title", AttributeType::Text)
+            .attribute("views", AttributeType::Integer)
+            .attribute("published", AttributeType::Boolean)
+            .attribute("rating", AttributeType::Float)
+            .attribute("created_at", AttributeType::DateTime)
+            .foreign_key("author_id", AttributeType::Integer)
+            .belongs_to(
+                "author",
+                Related::to("users")
+                    .pointing_own("author_id")
+                    .to_related("id"),
+            )
+            .has_many(
+                "comments",
+                Related::to("comments")
+                    .pointing_related("article_id")
+                    .to_own("id"),
+            )
+            .scope("popular", [("views", "gte:100"), ("comments", "exists")])
+            .scope("published", [("published", "eq:true")])
+            .indexed(["title"])
+            .keeps_history()
+    }
+
+    fn users() -> SchemaBuilder<'static> {
+        SchemaBuilder::table("users")
+            .attribute("name", AttributeType::Text)
+            .has_many(
+                "articles",
+                Related::to("articles")
+                    .pointing_related("author_id")
+                    .to_own("id"),
+            )
+    }
+
+    fn comments() -> SchemaBuilder<'static> {
+        SchemaBuilder::table("comments")
+            .attribute("body", AttributeType::Text)
+            .foreign_key("article_id", AttributeType::Integer)
+            .belongs_to(
+                "article",
+                Related::to("articles")
+                    .pointing_own("article_id")
+                    .to_related("id"),
+            )
+    }
+
+    fn registry() -> Registry {
+        DatabaseRegistry::try_new([articles(), users(), comments()])
+            .and_then(|registry| registry.with_filter_operator(Between))
+            .expect("schema set is consistent")
+    }
+
+    fn mock_uri(query: &str) -> Uri {
+        format!("http://localhost:8000/articles?{}", query)
+            .parse::<Uri>()
+            .unwrap()
+    }
+
+    fn parse<'sch, 'req>(registry: &'sch Registry, uri: &'req Uri) -> QueryParameters<'sch, 'req> {
+        let articles = registry
+            .schema("articles")
+            .expect("articles schema is registered");
+        QueryParameters::parse(uri, articles, registry).expect("query parses")
+    }
+
+    /// Unwraps a sort made only of attribute directives.
+    fn by_attribute(sort: Option<SortParameters>) -> Vec<SortingAttribute> {
+        sort.expect("sort is requested")
+            .into_iter()
+            .map(|directive| match directive {
+                SortDirective::Attribute(attribute) => attribute,
+                SortDirective::Relevance(_) => panic!("expected an attribute sort"),
+            })
+            .collect()
+    }
+
+    fn parse_err(query: &str) -> Error {
+        let registry = registry();
+        let uri = mock_uri(query);
+        let articles = registry
+            .schema("articles")
+            .expect("articles schema is registered");
+        QueryParameters::parse(&uri, articles, &registry).expect_err("expected parsing to fail")
+    }
+
+    // --- Construction ---
+
+    #[test]
+    fn test_new() {
+        let registry = registry();
+        let articles = registry
+            .schema("articles")
+            .expect("articles schema is registered");
+        let params = QueryParameters::new(articles);
+
+        assert_eq!(params.schema, articles);
+        assert_eq!(
+            params.fields["articles"],
+            articles.fields().collect::<IndexSet<_>>()
+        );
+        assert!(params.include.is_empty());
+        assert!(params.filter.is_none());
+        assert!(params.sort.is_none());
+        assert!(params.page.is_none());
+        assert!(params.search.is_none());
+    }
+
+    #[test]
+    fn test_parse_empty_query() {
+        let registry = registry();
+        let articles = registry
+            .schema("articles")
+            .expect("articles schema is registered");
+        let uri = "http://localhost:8000/articles"
+            .parse::<Uri>()
+            .expect("valid uri");
+        let params = QueryParameters::parse(&uri, articles, &registry).expect("query parses");
+
+        assert_eq!(
+            params.fields["articles"],
+            articles.fields().collect::<IndexSet<_>>()
+        );
+        assert!(params.include.is_empty());
+        assert!(params.filter.is_none());
+        assert!(params.sort.is_none());
+        assert!(params.page.is_none());
+        assert!(params.search.is_none());
+    }
+
+    // --- Fields ---
+
+    #[test]
+    fn test_parse_fields_single_model() {
+        let registry = registry();
+        let uri = mock_uri("fields[articles]=title,views");
+        let params = parse(&registry, &uri);
+
+        assert_eq!(
+            params.fields["articles"],
+            IndexSet::from(["title", "views"])
+        );
+    }
+
+    #[test]
+    fn test_parse_fields_preserves_request_order() {
+        let registry = registry();
+        let uri = mock_uri("fields[articles]=views,title");
+        let params = parse(&registry, &uri);
+
+        assert_eq!(
+            params.fields["articles"]
+                .iter()
+                .copied()
+                .collect::<Vec<_>>(),
+            vec!["views", "title"]
+        );
+    }
+
+    #[test]
+    fn test_parse_fields_multiple_models() {
+        let registry = registry();
+        let uri = mock_uri("fields[articles]=title&fields[users]=name");
+        let params = parse(&registry, &uri);
+
+        assert_eq!(params.fields["articles"], IndexSet::from(["title"]));
+        assert_eq!(params.fields["users"], IndexSet::from(["name", "id"]));
+    }
+
+    #[test]
+    fn test_parse_fields_accepts_relationship_names() {
+        let registry = registry();
+        let uri = mock_uri("fields[articles]=title,author");
+        let params = parse(&registry, &uri);
+
+        assert_eq!(
+            params.fields["articles"],
+            IndexSet::from(["title", "author"])
+        );
+    }
+
+    #[test]
+    fn test_parse_fields_empty_value_selects_nothing() {
+        let registry = registry();
+        let uri = mock_uri("fields[articles]=");
+        let params = parse(&registry, &uri);
+
+        assert_eq!(params.fields["articles"], IndexSet::new());
+    }
+
+    #[test]
+    fn test_parse_fields_invalid_field() {
+        assert!(matches!(
+            parse_err("fields[articles]=nonexistent"),
+            Error::QueryValidationFailure { .. }
+        ));
+    }
+
+    #[test]
+    fn test_parse_fields_unknown_model() {
+        assert!(matches!(
+            parse_err("fields[ghosts]=title"),
+            Error::UnknownSchema { .. }
+        ));
+    }
+
+    // --- Filters ---
+
+    #[test]
+    fn test_parse_filter_eq() {
+        let registry = registry();
+        let uri = mock_uri("filter[title]=eq:hello");
+        let params = parse(&registry, &uri);
+
+        assert_eq!(
+            params.filter.unwrap()["title"],
+            vec![FilterValue::Equal(Attribute::Text("hello".to_string()))]
+        );
+    }
+
+    #[test]
+    fn test_parse_filter_all_operators() {
+        let registry = registry();
+        let uri = mock_uri("filter[views]=eq:1,neq:2,gt:3,gte:4,lt:5,lte:6");
+        let params = parse(&registry, &uri);
+
+        use FilterValue::*;
+        assert_eq!(
+            params.filter.unwrap()["views"],
+            vec![
+                Equal(Attribute::Integer(1)),
+                NotEqual(Attribute::Integer(2)),
+                GreaterThan(Attribute::Integer(3)),
+                GreaterThanOrEqual(Attribute::Integer(4)),
+                LessThan(Attribute::Integer(5)),
+                LessThanOrEqual(Attribute::Integer(6)),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_filter_multiple_fields_preserve_order() {
+        let registry = registry();
+        let uri = mock_uri("filter[title]=eq:x&filter[views]=gt:5");
+        let params = parse(&registry, &uri);
+
+        let filter = params.filter.unwrap();
+        assert_eq!(
+            filter.keys().copied().collect::<Vec<_>>(),
+            vec!["title", "views"]
+        );
+    }
+
+    #[test]
+    fn test_parse_filter_typed_values() {
+        let registry = registry();
+        let uri = mock_uri("filter[published]=eq:true&filter[rating]=gt:4.5");
+        let params = parse(&registry, &uri);
+
+        let filter = params.filter.unwrap();
+        assert_eq!(
+            filter["published"],
+            vec![FilterValue::Equal(Attribute::Boolean(true))]
+        );
+        assert_eq!(
+            filter["rating"],
+            vec![FilterValue::GreaterThan(Attribute::Float(4.5))]
+        );
+    }
+
+    #[test]
+    fn test_parse_filter_datetime_value() {
+        let registry = registry();
+        let uri = mock_uri("filter[created_at]=gte:2020-01-01T00:00:00Z");
+        let params = parse(&registry, &uri);
+
+        assert!(matches!(
+            params.filter.unwrap()["created_at"][0],
+            FilterValue::GreaterThanOrEqual(Attribute::DateTime(_))
+        ));
+    }
+
+    #[test]
+    fn test_parse_filter_in() {
+        let registry = registry();
+        let uri = mock_uri("filter[views]=in:42");
+        let params = parse(&registry, &uri);
+
+        assert_eq!(
+            params.filter.unwrap()["views"],
+            vec![FilterValue::In(IndexSet::from([Attribute::Integer(42)]))]
+        );
+    }
+
+    #[test]
+    fn test_parse_filter_like_decodes_value() {
+        let registry = registry();
+        let uri = mock_uri("filter[title]=like:%25john%25");
+        let params = parse(&registry, &uri);
+
+        assert_eq!(
+            params.filter.unwrap()["title"],
+            vec![FilterValue::Like(Attribute::Text("%john%".to_string()))]
+        );
+    }
+
+    #[test]
+    fn test_parse_filter_text_matching_operators() {
+        let registry = registry();
+        let uri = mock_uri("filter[title]=ilike:Rust,starts:The,ends:%3F,ieq:%C3%89cole");
+        let params = parse(&registry, &uri);
+        let filter = params.filter.unwrap();
+
+        assert_eq!(
+            filter["title"],
+            vec![
+                FilterValue::InsensitiveLike(Attribute::Text("Rust".to_string())),
+                FilterValue::StartsWith(Attribute::Text("The".to_string())),
+                FilterValue::EndsWith(Attribute::Text("?".to_string())),
+                FilterValue::InsensitiveEqual(Attribute::Text("École".to_string())),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_filter_text_matching_operators_on_non_text_attribute() {
+        for (query, attribute, operator) in [
+            ("filter[views]=ieq:1", "views", "ieq"),
+            ("filter[published]=eq:true,like:true", "published", "like"),
+            ("filter=or(eq(title,a),ieq(views,1))", "views", "ieq"),
+        ] {
+            assert_eq!(
+                parse_err(query),
+                Error::QueryValidationFailure {
+                    schema: "articles".to_string(),
+                    attribute: attribute.to_string(),
+                    message: format!(
+                        "The '{operator}' operator can only be applied to text attributes"
+                    ),
+                },
+                "{query}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_parse_filter_decodes_special_characters() {
+        let registry = registry();
+        let uri = mock_uri("filter[title]=eq:value%26");
+        let params = parse(&registry, &uri);
+
+        assert_eq!(
+            params.filter.unwrap()["title"],
+            vec![FilterValue::Equal(Attribute::Text("value&".to_string()))]
+        );
+    }
+
+    #[test]
+    fn test_parse_filter_unknown_attribute() {
+        assert!(matches!(
+            parse_err("filter[ghost]=eq:1"),
+            Error::QueryValidationFailure { .. }
+        ));
+    }
+
+    #[test]
+    fn test_parse_filter_on_primary_key() {
+        let registry = registry();
+        let uri = mock_uri("filter[id]=in:1,2,3");
+        let params = parse(&registry, &uri);
+
+        assert_eq!(
+            params.filter.unwrap()["id"],
+            vec![FilterValue::In(IndexSet::from([
+                Attribute::Integer(1),
+                Attribute::Integer(2),
+                Attribute::Integer(3),
+            ]))]
+        );
+    }
+
+    #[test]
+    fn test_parse_filter_set_values_continue_until_next_operator() {
+        let registry = registry();
+        let uri = mock_uri("filter[views]=in:1,2,neq:3");
+        let params = parse(&registry, &uri);
+
+        assert_eq!(
+            params.filter.unwrap()["views"],
+            vec![
+                FilterValue::In(IndexSet::from([
+                    Attribute::Integer(1),
+                    Attribute::Integer(2)
+                ])),
+                FilterValue::NotEqual(Attribute::Integer(3)),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_filter_encoded_entry_stays_a_set_value() {
+        let registry = registry();
+        let uri = mock_uri("filter[title]=in:a,eq%3Ab%2Cc,eq:d");
+        let params = parse(&registry, &uri);
+
+        assert_eq!(
+            params.filter.unwrap()["title"],
+            vec![
+                FilterValue::In(IndexSet::from([
+                    Attribute::Text("a".to_string()),
+                    Attribute::Text("eq:b,c".to_string())
+                ])),
+                FilterValue::Equal(Attribute::Text("d".to_string())),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_filter_repeated_field_is_conjoined() {
+        let registry = registry();
+        let uri = mock_uri(
+            "filter[views]=gt:1&filter[author]=exists&filter[views]=lt:9&filter[author]=neq:3",
+        );
+        let params = parse(&registry, &uri);
+
+        assert_eq!(
+            params.filter.unwrap()["views"],
+            vec![
+                FilterValue::GreaterThan(Attribute::Integer(1)),
+                FilterValue::LessThan(Attribute::Integer(9)),
+            ]
+        );
+        assert_eq!(
+            params.relationship_filter.unwrap()["author"].values,
+            vec![
+                RelationshipFilterValue::Exists,
+                RelationshipFilterValue::Identity(FilterValue::NotEqual(Attribute::Integer(3))),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_filter_on_foreign_key() {
+        let registry = registry();
+        let uri = mock_uri("filter[author_id]=eq:1");
+        let params = parse(&registry, &uri);
+
+        assert_eq!(
+            params.filter.unwrap()["author_id"],
+            vec![FilterValue::Equal(Attribute::Integer(1))]
+        );
+    }
+
+    #[test]
+    fn test_parse_filter_on_relationship() {
+        let registry = registry();
+        let uri = mock_uri("filter[author]=in:3,4&filter[comments]=neq:9");
+        let params = parse(&registry, &uri);
+        let filter = params.relationship_filter.unwrap();
+
+        assert_eq!(filter["author"].descriptor.name, "author");
+        assert_eq!(filter["author"].primary_key, "id");
+        assert_eq!(
+            filter["author"].values,
+            vec![RelationshipFilterValue::Identity(FilterValue::In(
+                IndexSet::from([Attribute::Integer(3), Attribute::Integer(4)])
+            ))]
+        );
+        assert_eq!(
+            filter["comments"].values,
+            vec![RelationshipFilterValue::Identity(FilterValue::NotEqual(
+                Attribute::Integer(9)
+            ))]
+        );
+        assert!(params.filter.is_none());
+    }
+
+    #[test]
+    fn test_parse_filter_on_relationship_rejects_comparisons() {
+        assert_eq!(
+            parse_err("filter[author]=gt:1"),
+            Error::QueryValidationFailure {
+                schema: "articles".to_string(),
+                attribute: "author".to_string(),
+                message: "Relationships can only be filtered with 'eq', 'neq', 'in', 'nin', \
+                          'exists', 'none' or a 'count-' comparison"
+                    .to_string(),
+            }
+        );
+    }
+
+    #[test]
+    fn test_parse_filter_on_relationship_existence_and_count() {
+        let registry = registry();
+        let uri = mock_uri("filter[comments]=exists,count-gte:2,count-lt:10&filter[author]=none");
+        let params = parse(&registry, &uri);
+        let filter = params.relationship_filter.unwrap();
+
+        assert_eq!(
+            filter["comments"].values,
+            vec![
+                RelationshipFilterValue::Exists,
+                RelationshipFilterValue::Count(FilterValue::GreaterThanOrEqual(
+                    Attribute::Integer(2)
+                )),
+                RelationshipFilterValue::Count(FilterValue::LessThan(Attribute::Integer(10))),
+            ]
+        );
+        assert_eq!(
+            filter["author"].values,
+            vec![RelationshipFilterValue::NotExists]
+        );
+    }
+
+    #[test]
+    fn test_parse_filter_on_relationship_set_followed_by_existence() {
+        let registry = registry();
+        let uri = mock_uri("filter[comments]=in:1,2,exists");
+        let params = parse(&registry, &uri);
+
+        assert_eq!(
+            params.relationship_filter.unwrap()["comments"].values,
+            vec![
+                RelationshipFilterValue::Identity(FilterValue::In(IndexSet::from([
+                    Attribute::Integer(1),
+                    Attribute::Integer(2),
+                ]))),
+                RelationshipFilterValue::Exists,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_filter_on_relationship_invalid_count() {
+        assert_eq!(
+            parse_err("filter[comments]=count-gt:many"),
+            Error::ParseParameterFailure {
+                parameter: "filter[comments]".to_string(),
+                message: "Filter value is not a valid i64".to_string(),
+            }
+        );
+    }
+
+    #[test]
+    fn test_parse_filter_with_custom_operator() {
+        let registry = registry();
+        let uri = mock_uri("filter[views]=between:1,5,neq:3");
+        let params = parse(&registry, &uri);
+        let filter = params.filter.unwrap();
+
+        let [FilterValue::Custom(custom), not_equal] = filter["views"].as_slice() else {
+            panic!("expected a custom filter and a comparison");
+        };
+        assert_eq!(custom.name(), "between");
+        assert_eq!(
+            custom.values(),
+            [Attribute::Integer(1), Attribute::Integer(5)]
+        );
+        assert_eq!(not_equal, &FilterValue::NotEqual(Attribute::Integer(3)));
+    }
+
+    #[test]
+    fn test_parse_filter_custom_operator_continues_set() {
+        let registry = registry();
+        let uri = mock_uri("filter[views]=in:1,2,between:3,4");
+        let params = parse(&registry, &uri);
+        let filter = params.filter.unwrap();
+
+        assert_eq!(filter["views"].len(), 2);
+        assert!(matches!(filter["views"][1], FilterValue::Custom(_)));
+    }
+
+    #[test]
+    fn test_parse_filter_custom_operator_inapplicable_type() {
+        assert_eq!(
+            parse_err("filter[title]=between:a,b"),
+            Error::QueryValidationFailure {
+                schema: "articles".to_string(),
+                attribute: "title".to_string(),
+                message: "The 'between' operator cannot be applied to Text attributes".to_string(),
+            }
+        );
+    }
+
+    #[test]
+    fn test_parse_filter_custom_operator_invalid_value() {
+        assert_eq!(
+            parse_err("filter[views]=between:1"),
+            Error::ParseParameterFailure {
+                parameter: "filter[views]".to_string(),
+                message: "'between' takes a lower and an upper bound".to_string(),
+            }
+        );
+    }
+
+    #[test]
+    fn test_parse_filter_scopes_compose_with_filters() {
+        let registry = registry();
+        let uri = mock_uri(
+            "filter[scope]=popular,published&filter[views]=lt:1000&filter[comments]=neq:4",
+        );
+        let params = parse(&registry, &uri);
+        let filter = params.filter.unwrap();
+        let relationship_filter = params.relationship_filter.unwrap();
+
+        assert_eq!(
+            filter["views"],
+            vec![
+                FilterValue::LessThan(Attribute::Integer(1000)),
+                FilterValue::GreaterThanOrEqual(Attribute::Integer(100)),
+            ]
+        );
+        assert_eq!(
+            filter["published"],
+            vec![FilterValue::Equal(Attribute::Boolean(true))]
+        );
+        assert_eq!(
+            relationship_filter["comments"].values,
+            vec![
+                RelationshipFilterValue::Identity(FilterValue::NotEqual(Attribute::Integer(4))),
+                RelationshipFilterValue::Exists,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_filter_unknown_scope() {
+        assert_eq!(
+            parse_err("filter[scope]=popular,archived"),
+            Error::ParseParameterFailure {
+                parameter: "filter[scope]".to_string(),
+                message: "Unknown filter scope: 'archived'".to_string(),
+            }
+        );
+    }
+
+    #[test]
+    fn test_parse_filter_as_of() {
+        let registry = registry();
+        let uri = mock_uri("filter[as_of]=2026-01-01T01:00:00%2B01:00&filter[views]=gt:5");
+        let params = parse(&registry, &uri);
+
+        assert_eq!(
+            params.as_of,
+            Some(
+                Attribute::parse("2026-01-01T00:00:00Z", AttributeType::DateTime)
+                    .and_then(Attribute::to_datetime)
+                    .expect("the instant is valid")
+            )
+        );
+        assert_eq!(
+            params.filter.unwrap()["views"],
+            vec![FilterValue::GreaterThan(Attribute::Integer(5))]
+        );
+        assert_eq!(
+            parse_err("filter[as_of]=yesterday"),
+            Error::ParseParameterFailure {
+                parameter: "filter[as_of]".to_string(),
+                message: "Filter value is not a valid DateTime".to_string(),
+            }
+        );
+        assert!(matches!(
+            parse_err("filter[as_of]=2026-01-01T00:00:00Z&search=rust"),
+            Error::QueryValidationFailure { attribute, message, .. }
+                if attribute == "search" && message == "Past states cannot be searched"
+        ));
+    }
+
+    #[test]
+    fn test_parse_filter_as_of_refuses_related_resources() {
+        for (query, parameter, refusal) in [
+            ("include=author", "include", "include related resources"),
+            (
+                "filter[author]=in:3",
+                "filter",
+                "be filtered by related resources",
+            ),
+            (
+                "filter=or(eq(views,1),none(comments))",
+                "filter",
+                "be filtered by related resources",
+            ),
+            (
+                "sort=-author.name",
+                "sort",
+                "be sorted by related resources",
+            ),
+            (
+                "meta[counts]=comments",
+                "meta[counts]",
+                "count related resources",
+            ),
+        ] {
+            assert_eq!(
+                parse_err(&format!("filter[as_of]=2026-01-01T00:00:00Z&{query}")),
+                Error::QueryValidationFailure {
+                    schema: "articles".to_string(),
+                    attribute: parameter.to_string(),
+                    message: format!("Past states cannot {refusal}"),
+                }
+            );
+        }
+    }
+
+    #[test]
+    fn test_parse_filter_as_of_without_a_history() {
+        let registry = registry();
+        let uri = mock_uri("filter[as_of]=2026-01-01T00:00:00Z");
+
+        assert!(matches!(
+            QueryParameters::parse(&uri, registry.schema("users").unwrap(), &registry),
+            Err(Error::QueryValidationFailure { attribute, .. }) if attribute == "as_of"
+        ));
+    }
+
+    #[test]
+    fn test_history_reserved_field_is_inconsistent() {
+        for field in ["valid_from", "valid_to", "as_of"] {
+            let broken = comments()
+                .attribute(field, AttributeType::DateTime)
+                .keeps_history();
+
+            assert!(matches!(
+                DatabaseRegistry::try_new([articles(), users(), broken]),
+                Err(Error::InconsistentSchema { attribute, message, .. })
+                    if attribute == field
+                        && message
+                            == format!("A schema keeping history cannot have a field named '{field}'")
+            ));
+        }
+    }
+
+    #[test]
+    fn test_scope_on_unknown_field_is_inconsistent() {
+        let broken = SchemaBuilder::table("drafts").scope("stale", [("edited_at", "lt:1")]);
+
+        assert_eq!(
+            DatabaseRegistry::try_new([broken]).err(),
+            Some(Error::InconsistentSchema {
+                schema: "drafts".to_string(),
+                attribute: "stale".to_string(),
+                message: "Scope filters on unknown field 'edited_at'".to_string(),
+            })
+        );
+    }
+
+    #[test]
+    fn test_scope_failing_to_expand_is_inconsistent() {
+        let drafts = SchemaBuilder::table("drafts")
+            .attribute("views", AttributeType::Integer)
+            .scope("broken", [("views", "eq:many")]);
+
+        assert_eq!(
+            DatabaseRegistry::try_new([drafts]).err(),
+            Some(Error::InconsistentSchema {
+                schema: "drafts".to_string(),
+                attribute: "broken".to_string(),
+                message: "Scope failed to expand: Failed to parse parameter 'filter[views]': Filter value \
+                          is not a valid i64"
+                    .to_string(),
+            })
+        );
+    }
+
+    #[test]
+    fn test_scope_naming_an_operator_is_validated_once_registered() -> Result<(), Error> {
+        let drafts = || {
+            SchemaBuilder::table("drafts")
+                .attribute("title", AttributeType::Text)
+                .attribute("views", AttributeType::Integer)
+        };
+
+        let registry =
+            DatabaseRegistry::try_new([drafts().scope("few", [("views", "between:1,5")])])?
+                .with_filter_operator(Between)?;
+        let uri = mock_uri("filter[scope]=few");
+        let params = QueryParameters::parse(&uri, registry.schema("drafts")?, &registry)?;
+        assert_eq!(params.filter.map(|filter| filter["views"].len()), Some(1));
+
+        assert_eq!(
+            DatabaseRegistry::try_new([drafts().scope("few", [("title", "between:a,b")])])?
+                .with_filter_operator(Between)
+                .err(),
+            Some(Error::InconsistentSchema {
+                schema: "drafts".to_string(),
+                attribute: "few".to_string(),
+                message:
+                    "Scope failed to expand: Invalid query parameter 'title' for schema 'drafts': \
+                          The 'between' operator cannot be applied to Text attributes"
+                        .to_string(),
+            })
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_filter_unregistered_operator() {
+        assert!(matches!(
+            parse_err("filter[views]=around:1"),
+            Error::ParseParameterFailure { .. }
+        ));
+    }
+
+    #[test]
+    fn test_parse_filter_on_relationship_invalid_identifier() {
+        assert_eq!(
+            parse_err("filter[author]=eq:alice"),
+            Error::ParseParameterFailure {
+                parameter: "filter[author]".to_string(),
+                message: "Filter value is not a valid i64".to_string(),
+            }
+        );
+    }
+
+    #[test]
+    fn test_parse_filter_invalid_value_for_type() {
+        assert!(matches!(
+            parse_err("filter[views]=eq:not_a_number"),
+            Error::ParseParameterFailure { .. }
+        ));
+    }
+
+    #[test]
+    fn test_parse_filter_invalid_datetime_value() {
+        assert!(matches!(
+            parse_err("filter[created_at]=eq:not_a_date"),
+            Error::ParseParameterFailure { .. }
+        ));
+    }
+
+    #[test]
+    fn test_parse_filter_missing_colon() {
+        assert!(matches!(
+            parse_err("filter[title]=eqhello"),
+            Error::ParseParameterFailure { .. }
+        ));
+    }
+
+    #[test]
+    fn test_parse_filter_invalid_operator() {
+        assert!(matches!(
+            parse_err("filter[title]=foo:bar"),
+            Error::ParseParameterFailure { .. }
+        ));
+    }
+
+    #[test]
+    fn test_parse_filter_empty_value() {
+        assert!(matches!(
+            parse_err("filter[title]="),
+            Error::ParseParameterFailure { .. }
+        ));
+    }
+
+    // --- Filter expressions ---
+
+    #[test]
+    fn test_parse_filter_expression_disjunction() {
+        let registry = registry();
+        let uri = mock_uri("filter=or(eq(title,'Draft%2C%20final'),gte(views,%2010))");
+        let params = parse(&registry, &uri);
+
+        assert_eq!(
+            params.filter_expression,
+            Some(FilterExpression::Or(vec![
+                FilterExpression::Condition {
+                    attribute: "title",
+                    value: FilterValue::Equal(Attribute::Text("Draft, final".to_string())),
+                },
+                FilterExpression::Condition {
+                    attribute: "views",
+                    value: FilterValue::GreaterThanOrEqual(Attribute::Integer(10)),
+                },
+            ]))
+        );
+        assert!(params.filter.is_none());
+    }
+
+    #[test]
+    fn test_parse_filter_expression_nested() {
+        let registry = registry();
+        let uri = mock_uri("filter=and(not(in(views,1,2)),like(title,'it''s'))");
+        let params = parse(&registry, &uri);
+
+        assert_eq!(
+            params.filter_expression,
+            Some(FilterExpression::And(vec![
+                FilterExpression::Not(Box::new(FilterExpression::Condition {
+                    attribute: "views",
+                    value: FilterValue::In([Attribute::Integer(1), Attribute::Integer(2)].into()),
+                })),
+                FilterExpression::Condition {
+                    attribute: "title",
+                    value: FilterValue::Like(Attribute::Text("it's".to_string())),
+                },
+            ]))
+        );
+    }
+
+    #[test]
+    fn test_parse_filter_expression_repeated_is_conjoined() {
+        let registry = registry();
+        let uri = mock_uri("filter=eq(views,1)&filter=eq(published,true)");
+        let params = parse(&registry, &uri);
+
+        assert_eq!(
+            params.filter_expression,
+            Some(FilterExpression::And(vec![
+                FilterExpression::Condition {
+                    attribute: "views",
+                    value: FilterValue::Equal(Attribute::Integer(1)),
+                },
+                FilterExpression::Condition {
+                    attribute: "published",
+                    value: FilterValue::Equal(Attribute::Boolean(true)),
+                },
+            ]))
+        );
+    }
+
+    #[test]
+    fn test_parse_filter_expression_on_a_foreign_key() {
+        let registry = registry();
+        let uri = mock_uri("filter=or(eq(title,'draft'),eq(author_id,3))");
+        let params = parse(&registry, &uri);
+
+        assert_eq!(
+            params.filter_expression,
+            Some(FilterExpression::Or(vec![
+                FilterExpression::Condition {
+                    attribute: "title",
+                    value: FilterValue::Equal(Attribute::Text("draft".to_string())),
+                },
+                FilterExpression::Condition {
+                    attribute: "author_id",
+                    value: FilterValue::Equal(Attribute::Integer(3)),
+                },
+            ]))
+        );
+    }
+
+    #[test]
+    fn test_parse_filter_expression_with_custom_operator() {
+        let registry = registry();
+        let uri = mock_uri("filter=or(between(views,1,5),eq(published,true))");
+        let params = parse(&registry, &uri);
+
+        let Some(FilterExpression::Or(operands)) = params.filter_expression else {
+            panic!("expected a disjunction");
+        };
+        let FilterExpression::Condition {
+            attribute: "views",
+            value: FilterValue::Custom(custom),
+        } = &operands[0]
+        else {
+            panic!("expected a custom condition on views");
+        };
+        assert_eq!(custom.name(), "between");
+        assert_eq!(
+            custom.values(),
+            [Attribute::Integer(1), Attribute::Integer(5)]
+        );
+    }
+
+    #[test]
+    fn test_parse_filter_expression_custom_operator_inapplicable_type() {
+        assert_eq!(
+            parse_err("filter=between(title,a,b)"),
+            Error::QueryValidationFailure {
+                schema: "articles".to_string(),
+                attribute: "title".to_string(),
+                message: "The 'between' operator cannot be applied to Text attributes".to_string(),
+            }
+        );
+        assert_eq!(
+            parse_err("filter=between(views,1)"),
+            Error::ParseParameterFailure {
+                parameter: "filter".to_string(),
+                message: "'between' takes a lower and an upper bound".to_string(),
+            }
+        );
+    }
+
+    #[test]
+    fn test_parse_filter_expression_on_relationships() {
+        let registry = registry();
+        let uri = mock_uri("filter=or(in(author,3,4),none(comments),count-gt(comments,2))");
+        let params = parse(&registry, &uri);
+
+        let articles = registry.schema("articles").expect("articles is registered");
+        let relationship = |name, value| {
+            FilterExpression::Relationship(RelationshipFilter {
+                descriptor: articles.relationship(name).expect("relationship exists"),
+                primary_key: "id",
+                values: vec![value],
+            })
+        };
+        assert_eq!(
+            params.filter_expression,
+            Some(FilterExpression::Or(vec![
+                relationship(
+                    "author",
+                    RelationshipFilterValue::Identity(FilterValue::In(
+                        [Attribute::Integer(3), Attribute::Integer(4)].into()
+                    ))
+                ),
+                relationship("comments", RelationshipFilterValue::NotExists),
+                relationship(
+                    "comments",
+                    RelationshipFilterValue::Count(FilterValue::GreaterThan(Attribute::Integer(2)))
+                ),
+            ]))
+        );
+        assert!(params.relationship_filter.is_none());
+    }
+
+    #[test]
+    fn test_parse_filter_expression_invalid_relationship_conditions() {
+        assert_eq!(
+            parse_err("filter=gt(author,3)"),
+            Error::QueryValidationFailure {
+                schema: "articles".to_string(),
+                attribute: "author".to_string(),
+                message: "Relationships can only be filtered with 'eq', 'neq', 'in', 'nin', \
+                          'exists', 'none' or a 'count-' comparison"
+                    .to_string(),
+            }
+        );
+        assert_eq!(
+            parse_err("filter=exists(comments,1)"),
+            Error::ParseParameterFailure {
+                parameter: "filter".to_string(),
+                message: "'exists' takes no value, at position 1".to_string(),
+            }
+        );
+        assert_eq!(
+            parse_err("filter=exists(views)"),
+            Error::ParseParameterFailure {
+                parameter: "filter".to_string(),
+                message: "'exists' only applies to relationships, at position 1".to_string(),
+            }
+        );
+        assert_eq!(
+            parse_err("filter=count-ilike(comments,1)"),
+            Error::ParseParameterFailure {
+                parameter: "filter".to_string(),
+                message: "Invalid filter operator: 'count-ilike'".to_string(),
+            }
+        );
+    }
+
+    #[test]
+    fn test_parse_filter_expression_unknown_attribute() {
+        assert_eq!(
+            parse_err("filter=or(eq(title,a),eq(ghost,b))"),
+            Error::QueryValidationFailure {
+                schema: "articles".to_string(),
+                attribute: "ghost".to_string(),
+                message: "Attempted to filter on an unknown attribute".to_string(),
+            }
+        );
+    }
+
+    #[test]
+    fn test_parse_filter_expression_invalid_value() {
+        assert_eq!(
+            parse_err("filter=eq(views,many)"),
+            Error::ParseParameterFailure {
+                parameter: "filter".to_string(),
+                message: "Filter value is not a valid i64".to_string(),
+            }
+        );
+    }
+
+    #[test]
+    fn test_parse_filter_expression_unknown_operator() {
+        assert_eq!(
+            parse_err("filter=xor(eq(views,1),eq(views,2))"),
+            Error::ParseParameterFailure {
+                parameter: "filter".to_string(),
+                message: "Invalid filter operator: 'xor'".to_string(),
+            }
+        );
+    }
+
+    #[test]
+    fn test_parse_filter_expression_wrong_arity() {
+        assert_eq!(
+            parse_err("filter=eq(views,1,2)"),
+            Error::ParseParameterFailure {
+                parameter: "filter".to_string(),
+                message: "'eq' takes exactly one value, at position 1".to_string(),
+            }
+        );
+        assert_eq!(
+            parse_err("filter=not(eq(views,1),eq(views,2))"),
+            Error::ParseParameterFailure {
+                parameter: "filter".to_string(),
+                message: "'not' takes exactly one expression, at position 1".to_string(),
+            }
+        );
+    }
+
+    #[test]
+    fn test_parse_filter_expression_nested_too_deeply() {
+        let nested = format!(
+            "filter={}eq(views,1){}",
+            "not(".repeat(10_000),
+            ")".repeat(10_000)
+        );
+        assert_eq!(
+            parse_err(&nested),
+            Error::ParseParameterFailure {
+                parameter: "filter".to_string(),
+                message: "The filter expression is nested too deeply".to_string(),
+            }
+        );
+
+        let registry = registry();
+        let deepest = mock_uri(&format!(
+            "filter={}eq(views,1){}",
+            "not(".repeat(63),
+            ")".repeat(63)
+        ));
+        assert!(parse(&registry, &deepest).filter_expression.is_some());
+    }
+
+    #[test]
+    fn test_parse_filter_expression_malformed() {
+        assert_eq!(
+            parse_err("filter=or(eq(views,1)"),
+            Error::ParseParameterFailure {
+                parameter: "filter".to_string(),
+                message: "Unexpected end of expression at position 15".to_string(),
+            }
+        );
+        assert_eq!(
+            parse_err("filter=eq(title,'open"),
+            Error::ParseParameterFailure {
+                parameter: "filter".to_string(),
+                message: "Unterminated string starting at position 10".to_string(),
+            }
+        );
+        assert_eq!(
+            parse_err("filter=eq(views,1))"),
+            Error::ParseParameterFailure {
+                parameter: "filter".to_string(),
+                message: "Unexpected ')' at position 12".to_string(),
+            }
+        );
+        assert_eq!(
+            parse_err("filter=views"),
+            Error::ParseParameterFailure {
+                parameter: "filter".to_string(),
+                message: "Expected an operator at position 1".to_string(),
+            }
+        );
+    }
+
+    // --- Sort ---
+
+    #[test]
+    fn test_parse_sort_single_descending() {
+        let registry = registry();
+        let uri = mock_uri("sort=-title");
+        let params = parse(&registry, &uri);
+
+        let sort = by_attribute(params.sort);
+        assert_eq!(sort.len(), 1);
+        assert_eq!(sort[0].attribute, "title");
+        assert_eq!(sort[0].direction, SortDirection::Descending);
+    }
+
+    #[test]
+    fn test_parse_sort_multiple_mixed() {
+        let registry = registry();
+        let uri = mock_uri("sort=-title,views");
+        let params = parse(&registry, &uri);
+
+        let sort = by_attribute(params.sort);
+        assert_eq!(sort[0].direction, SortDirection::Descending);
+        assert_eq!(sort[0].attribute, "title");
+        assert_eq!(sort[1].direction, SortDirection::Ascending);
+        assert_eq!(sort[1].attribute, "views");
+    }
+
+    #[test]
+    fn test_parse_sort_explicit_plus() {
+        let registry = registry();
+        let uri = mock_uri("sort=+title");
+        let params = parse(&registry, &uri);
+
+        assert_eq!(
+            by_attribute(params.sort)[0].direction,
+            SortDirection::Ascending
+        );
+    }
+
+    #[test]
+    fn test_parse_sort_invalid_attribute() {
+        assert!(matches!(
+            parse_err("sort=ghost"),
+            Error::QueryValidationFailure { .. }
+        ));
+    }
+
+    #[test]
+    fn test_parse_sort_related_attribute() {
+        let registry = registry();
+        let uri = mock_uri("sort=-author.name");
+        let params = parse(&registry, &uri);
+
+        let sort = by_attribute(params.sort);
+        let author = params
+            .schema
+            .relationship("author")
+            .expect("articles declare an author");
+        assert_eq!(sort[0].path, vec![author]);
+        assert_eq!(sort[0].attribute, "name");
+        assert_eq!(sort[0].direction, SortDirection::Descending);
+    }
+
+    #[test]
+    fn test_parse_sort_mixes_related_and_own_attributes() {
+        let registry = registry();
+        let uri = mock_uri("sort=author.name,title");
+        let params = parse(&registry, &uri);
+
+        let sort = by_attribute(params.sort);
+        assert_eq!(
+            sort[0]
+                .path
+                .iter()
+                .map(|descriptor| descriptor.name)
+                .collect::<Vec<_>>(),
+            vec!["author"]
+        );
+        assert_eq!(sort[0].attribute, "name");
+        assert!(sort[1].path.is_empty());
+        assert_eq!(sort[1].attribute, "title");
+    }
+
+    #[test]
+    fn test_parse_sort_through_to_many_relationship() {
+        assert_eq!(
+            parse_err("sort=comments.body"),
+            Error::QueryValidationFailure {
+                schema: "articles".to_string(),
+                attribute: "comments".to_string(),
+                message: "Cannot sort by a to-many relationship".to_string(),
+            }
+        );
+    }
+
+    #[test]
+    fn test_parse_sort_unknown_relationship() {
+        assert_eq!(
+            parse_err("sort=editor.name"),
+            Error::QueryValidationFailure {
+                schema: "articles".to_string(),
+                attribute: "editor".to_string(),
+                message: "Invalid relationship to sort".to_string(),
+            }
+        );
+    }
+
+    #[test]
+    fn test_parse_sort_unknown_related_attribute() {
+        assert_eq!(
+            parse_err("sort=author.ghost"),
+            Error::QueryValidationFailure {
+                schema: "users".to_string(),
+                attribute: "ghost".to_string(),
+                message: "Invalid attribute to sort".to_string(),
+            }
+        );
+    }
+
+    #[test]
+    fn test_parse_sort_invalid_format() {
+        assert!(matches!(
+            parse_err("sort=--title"),
+            Error::ParseParameterFailure { .. }
+        ));
+    }
+
+    #[test]
+    fn test_parse_sort_invalid_characters() {
+        assert!(matches!(
+            parse_err("sort=col@1"),
+            Error::ParseParameterFailure { .. }
+        ));
+    }
+
+    #[test]
+    fn test_parse_sort_empty() {
+        assert!(matches!(
+            parse_err("sort="),
+            Error::ParseParameterFailure { .. }
+        ));
+    }
+
+    // --- Pagination ---
+
+    #[test]
+    fn test_parse_page_number_and_size() {
+        let registry = registry();
+        let uri = mock_uri("page[number]=2&page[size]=25");
+        let params = parse(&registry, &uri);
+
+        assert_eq!(
+            params.page,
+            Some(PageParameters {
+                number: 2,
+                size: 25
+            })
+        );
+    }
+
+    #[test]
+    fn test_parse_page_only_number() {
+        let registry = registry();
+        let uri = mock_uri("page[number]=2");
+        let params = parse(&registry, &uri);
+
+        assert_eq!(
+            params.page,
+            Some(PageParameters {
+                number: 2,
+                size: 20
+            })
+        );
+    }
+
+    #[test]
+    fn test_parse_page_only_size() {
+        let registry = registry();
+        let uri = mock_uri("page[size]=50");
+        let params = parse(&registry, &uri);
+
+        assert_eq!(
+            params.page,
+            Some(PageParameters {
+                number: 1,
+                size: 50
+            })
+        );
+    }
+
+    #[test]
+    fn test_parse_page_invalid_property() {
+        assert!(matches!(
+            parse_err("page[limit]=10"),
+            Error::ParseParameterFailure { .. }
+        ));
+    }
+
+    #[test]
+    fn test_parse_page_zero_value() {
+        assert!(matches!(
+            parse_err("page[number]=0"),
+            Error::ParseParameterFailure { .. }
+        ));
+    }
+
+    #[test]
+    fn test_parse_page_negative_value() {
+        assert!(matches!(
+            parse_err("page[size]=-5"),
+            Error::ParseParameterFailure { .. }
+        ));
+    }
+
+    #[test]
+    fn test_parse_page_non_numeric() {
+        assert!(matches!(
+            parse_err("page[size]=abc"),
+            Error::ParseParameterFailure { .. }
+        ));
+    }
+
+    // --- Search ---
+
+    fn term<'req>(
+        attribute: Option<&'static str>,
+        phrase: &'req str,
+        prefix: bool,
+    ) -> SearchTerm<'static, 'req> {
+        SearchTerm {
+            attribute,
+            phrase: Cow::Borrowed(phrase),
+            prefix,
+        }
+    }
+
+    #[test]
+    fn test_parse_search_single_value() {
+        let registry = registry();
+        let uri = mock_uri("search=some-value");
+        let params = parse(&registry, &uri);
+
+        assert_eq!(params.search, Some(vec![term(None, "some-value", false)]));
+    }
+
+    #[test]
+    fn test_parse_search_multiple_values() {
+        let registry = registry();
+        let uri = mock_uri("search=some-value,another-value");
+        let params = parse(&registry, &uri);
+
+        assert_eq!(
+            params.search,
+            Some(vec![
+                term(None, "some-value", false),
+                term(None, "another-value", false)
+            ])
+        );
+    }
+
+    #[test]
+    fn test_parse_search_decodes_spaces() {
+        let registry = registry();
+        let uri = mock_uri("search=hello%20world,foo%20bar");
+        let params = parse(&registry, &uri);
+
+        assert_eq!(
+            params.search,
+            Some(vec![
+                SearchTerm {
+                    attribute: None,
+                    phrase: Cow::Owned("hello world".to_string()),
+                    prefix: false,
+                },
+                SearchTerm {
+                    attribute: None,
+                    phrase: Cow::Owned("foo bar".to_string()),
+                    prefix: false,
+                },
+            ])
+        );
+    }
+
+    #[test]
+    fn test_parse_search_prefix() {
+        let registry = registry();
+        let uri = mock_uri("search=rus*,go");
+        let params = parse(&registry, &uri);
+
+        assert_eq!(
+            params.search,
+            Some(vec![term(None, "rus", true), term(None, "go", false)])
+        );
+    }
+
+    #[test]
+    fn test_parse_search_scoped_to_an_attribute() {
+        let registry = registry();
+        let uri = mock_uri("search=rust&search[title]=intro*");
+        let params = parse(&registry, &uri);
+
+        assert_eq!(
+            params.search,
+            Some(vec![
+                term(None, "rust", false),
+                term(Some("title"), "intro", true)
+            ])
+        );
+    }
+
+    #[test]
+    fn test_parse_search_rejects_invalid_terms() {
+        assert_eq!(
+            parse_err("search=*"),
+            Error::ParseParameterFailure {
+                parameter: "search".to_string(),
+                message: "Invalid search term: '*'".to_string(),
+            }
+        );
+        assert_eq!(
+            parse_err("search[title]=%20*"),
+            Error::ParseParameterFailure {
+                parameter: "search[title]".to_string(),
+                message: "Invalid search term: '%20*'".to_string(),
+            }
+        );
+    }
+
+    #[test]
+    fn test_parse_search_scoped_to_an_unindexed_attribute() {
+        for (query, attribute) in [
+            ("search[views]=rust", "views"),
+            ("search[nonexistent]=rust", "nonexistent"),
+        ] {
+            assert_eq!(
+                parse_err(query),
+                Error::QueryValidationFailure {
+                    schema: "articles".to_string(),
+                    attribute: attribute.to_string(),
+                    message: "Only full-text indexed attributes can be searched".to_string(),
+                }
+            );
+        }
+    }
+
+    #[test]
+    fn test_parse_search_empty_is_none() {
+        let registry = registry();
+        let uri = mock_uri("search=");
+        let params = parse(&registry, &uri);
+
+        assert!(params.search.is_none());
+    }
+
+    // --- Include ---
+
+    #[test]
+    fn test_parse_include_single() {
+        let registry = registry();
+        let uri = mock_uri("include=author");
+        let params = parse(&registry, &uri);
+
+        assert!(params.is_included("author"));
+        assert!(!params.is_included("comments"));
+    }
+
+    #[test]
+    fn test_parse_include_multiple() {
+        let registry = registry();
+        let uri = mock_uri("include=author,comments");
+        let params = parse(&registry, &uri);
+
+        assert!(params.is_included("author"));
+        assert!(params.is_included("comments"));
+    }
+
+    #[test]
+    fn test_parse_include_nested() {
+        let registry = registry();
+        let uri = mock_uri("include=comments.article");
+        let params = parse(&registry, &uri);
+
+        assert!(params.is_included("comments"));
+        assert!(params.include["comments"].children.contains_key("article"));
+    }
+
+    #[test]
+    fn test_parse_include_empty_is_noop() {
+        let registry = registry();
+        let uri = mock_uri("include=");
+        let params = parse(&registry, &uri);
+
+        assert!(params.include.is_empty());
+    }
+
+    #[test]
+    fn test_parse_include_invalid_relationship() {
+        assert!(matches!(
+            parse_err("include=ghost"),
+            Error::QueryValidationFailure { .. }
+        ));
+    }
+
+    #[test]
+    fn test_parse_include_invalid_nested_relationship() {
+        assert!(matches!(
+            parse_err("include=comments.ghost"),
+            Error::QueryValidationFailure { .. }
+        ));
+    }
+
+    #[test]
+    fn test_parse_include_encoded_value_is_rejected() {
+        assert!(matches!(
+            parse_err("include=author%2Dprofile"),
+            Error::QueryValidationFailure { .. }
+        ));
+    }
+
+    #[test]
+    fn test_include_discovers_join_keys() {
+        let registry = registry();
+        let uri = mock_uri("include=comments");
+        let params = parse(&registry, &uri);
+
+        assert_eq!(
+            params.fields["comments"],
+            IndexSet::from(["body", "article", "article_id"])
+        );
+    }
+
+    #[test]
+    fn test_parse_include_sort_and_limit() {
+        let registry = registry();
+        let uri = mock_uri("include=comments&sort[comments]=-body&page[comments][limit]=3");
+        let params = parse(&registry, &uri);
+        let comments = &params.include["comments"];
+
+        assert!(comments.is_ranked());
+        assert_eq!(comments.limit, Some(3));
+        assert_eq!(
+            by_attribute(comments.sort.clone()),
+            vec![SortingAttribute {
+                path: Vec::new(),
+                attribute: "body",
+                direction: SortDirection::Descending,
+            }]
+        );
+        assert_eq!(params.sort, None);
+        assert_eq!(params.page, None);
+    }
+
+    #[test]
+    fn test_parse_include_options_follow_nested_paths() {
+        let registry = registry();
+        let uri = mock_uri("include=author.articles&page[author.articles][limit]=2");
+        let params = parse(&registry, &uri);
+
+        assert!(!params.include["author"].is_ranked());
+        assert_eq!(params.include["author"].children["articles"].limit, Some(2));
+    }
+
+    #[test]
+    fn test_parse_include_options_require_an_included_to_many_relationship() {
+        for (query, attribute, message) in [
+            (
+                "sort[comments]=body",
+                "comments",
+                "Only included to-many relationships can be sorted",
+            ),
+            (
+                "include=author&page[author][limit]=2",
+                "author",
+                "Only included to-many relationships can be limited",
+            ),
+            (
+                "include=comments.article&sort[comments.article]=title",
+                "comments.article",
+                "Only included to-many relationships can be sorted",
+            ),
+        ] {
+            assert_eq!(
+                parse_err(query),
+                Error::QueryValidationFailure {
+                    schema: "articles".to_string(),
+                    attribute: attribute.to_string(),
+                    message: message.to_string(),
+                },
+                "{query}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_parse_include_sort_rejects_unknown_and_foreign_attributes() {
+        for (query, message) in [
+            (
+                "include=comments&sort[comments]=title",
+                "Invalid attribute to sort",
+            ),
+            (
+                "include=comments&sort[comments]=article.title",
+                "Included relationships are sorted by their own attributes only",
+            ),
+        ] {
+            assert_eq!(
+                parse_err(query),
+                Error::QueryValidationFailure {
+                    schema: "comments".to_string(),
+                    attribute: "title".to_string(),
+                    message: message.to_string(),
+                },
+                "{query}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_parse_include_limit_invalid_value() {
+        for (query, parameter, message) in [
+            (
+                "include=comments&page[comments][limit]=0",
+                "page[comments][limit]",
+                "Invalid numeric value: '0'",
+            ),
+            (
+                "include=comments&page[comments][limit]=many",
+                "page[comments][limit]",
+                "Invalid numeric value: 'many'",
+            ),
+            (
+                "include=comments&page[comments]=3",
+                "page[comments]",
+                "Invalid page property: 'comments'",
+            ),
+        ] {
+            assert_eq!(
+                parse_err(query),
+                Error::ParseParameterFailure {
+                    parameter: parameter.to_string(),
+                    message: message.to_string(),
+                },
+                "{query}"
+            );
+        }
+    }
+
+    // --- Predicates and derivation ---
+
+    #[test]
+    fn test_is_requested() {
+        let registry = registry();
+        let uri = mock_uri("fields[articles]=title");
+        let params = parse(&registry, &uri);
+
+        assert!(params.is_requested("title"));
+        assert!(!params.is_requested("views"));
+    }
+
+    #[test]
+    fn test_relationships_to_load_respects_sparse_fieldset() {
+        let registry = registry();
+        let uri = mock_uri("fields[articles]=title&include=comments");
+        let params = parse(&registry, &uri);
+
+        let loaded = params
+            .relationships_to_load()
+            .map(|(name, _)| name)
+            .collect::<Vec<_>>();
+
+        assert_eq!(loaded, vec!["comments"]);
+    }
+
+    #[test]
+    fn test_derive_descends_into_relationship() {
+        let registry = registry();
+        let uri = mock_uri("include=comments.article");
+        let params = parse(&registry, &uri);
+
+        let derived = params.derive("comments", &registry).unwrap();
+
+        assert_eq!(derived.schema.name(), "comments");
+        assert!(derived.is_included("article"));
+    }
+
+    #[test]
+    fn test_derive_unknown_relationship_fails() {
+        let registry = registry();
+        let uri = mock_uri("include=author");
+        let params = parse(&registry, &uri);
+
+        assert!(params.derive("comments", &registry).is_err());
+    }
+
+    // --- Malformed queries ---
+
+    #[test]
+    fn test_parse_missing_equals_sign() {
+        assert!(matches!(
+            parse_err("noequals"),
+            Error::ParseParameterFailure { .. }
+        ));
+    }
+
+    #[test]
+    fn test_parse_unknown_parameter() {
+        assert!(matches!(
+            parse_err("bogus=value"),
+            Error::ParseParameterFailure { .. }
+        ));
+    }
+
+    #[test]
+    fn test_parse_all_parameters_combined() {
+        let registry = registry();
+        let uri = mock_uri(
+            "fields[articles]=title,views&\
+             filter[published]=eq:true&\
+             sort=-created_at&\
+             page[number]=2&page[size]=25&\
+             include=author&\
+             search=query",
+        );
+        let params = parse(&registry, &uri);
+
+        assert_eq!(
+            params.fields["articles"],
+            IndexSet::from(["title", "views", "author_id"])
+        );
+        assert!(params.filter.is_some());
+        assert!(params.sort.is_some());
+        assert_eq!(
+            params.page,
+            Some(PageParameters {
+                number: 2,
+                size: 25
+            })
+        );
+        assert!(params.is_included("author"));
+        assert_eq!(params.search, Some(vec![term(None, "query", false)]));
+    }
+
+    // --- Counts ---
+
+    #[test]
+    fn test_parse_meta_counts() {
+        let registry = registry();
+        let uri = mock_uri("meta[counts]=comments");
+        let params = parse(&registry, &uri);
+
+        assert_eq!(params.counts, IndexSet::from(["comments"]));
+        assert!(parse(&registry, &mock_uri("")).counts.is_empty());
+    }
+
+    #[test]
+    fn test_parse_meta_counts_only_to_many() {
+        assert!(matches!(
+            parse_err("meta[counts]=author"),
+            Error::QueryValidationFailure { attribute, message, .. }
+                if attribute == "author" && message == "Only to-many relationships can be counted"
+        ));
+        assert!(matches!(
+            parse_err("meta[counts]=reviews"),
+            Error::QueryValidationFailure { attribute, .. } if attribute == "reviews"
+        ));
+    }
+
+    #[test]
+    fn test_parse_meta_unknown_property() {
+        assert_eq!(
+            parse_err("meta[totals]=comments"),
+            Error::ParseParameterFailure {
+                parameter: "meta[totals]".to_string(),
+                message: "Invalid meta property: 'totals'".to_string(),
+            }
+        );
+    }
+
+    #[test]
+    fn test_always_counted_relationships_are_counted_by_default() {
+        let users = users().always_count("articles");
+        let registry = DatabaseRegistry::try_new([articles(), users, comments()])
+            .expect("schema set is consistent");
+        let users = registry
+            .schema("users")
+            .expect("users schema is registered");
+
+        assert_eq!(
+            QueryParameters::new(users).counts,
+            IndexSet::from(["articles"])
+        );
+    }
+
+    #[test]
+    fn test_always_counting_a_to_one_relationship_is_inconsistent() {
+        let broken = comments().always_count("article");
+
+        assert!(matches!(
+            DatabaseRegistry::try_new([articles(), users(), broken]),
+            Err(Error::InconsistentSchema { attribute, .. }) if attribute == "article"
+        ));
+    }
+
+    // --- Limits ---
+
+    fn exceeded(limits: QueryLimits, query: &str) -> Error {
+        let registry = registry();
+        let uri = mock_uri(query);
+        limits
+            .enforce(&parse(&registry, &uri))
+            .expect_err("expected the query to exceed its limits")
+    }
+
+    fn within(limits: QueryLimits, query: &str) -> bool {
+        let registry = registry();
+        let uri = mock_uri(query);
+        limits.enforce(&parse(&registry, &uri)).is_ok()
+    }
+
+    #[test]
+    fn test_limits_default_to_unlimited() {
+        assert!(within(
+            QueryLimits::new(),
+            "include=comments.article.author&sort=title,views&search=a,b&filter[views]=in:1,2,3"
+        ));
+    }
+
+    fn limit_exceeded(parameter: &str, measure: &str, limit: usize, actual: usize) -> Error {
+        Error::QueryLimitExceeded {
+            parameter: parameter.to_string(),
+            measure: measure.to_string(),
+            limit,
+            actual,
+        }
+    }
+
+    #[test]
+    fn test_limits_include_depth_and_paths() {
+        let query = "include=author,comments.article";
+        assert!(within(
+            QueryLimits::new().include_depth(2).include_paths(2),
+            query
+        ));
+        assert_eq!(
+            exceeded(QueryLimits::new().include_depth(1), query),
+            limit_exceeded("include", "include depth", 1, 2)
+        );
+        assert_eq!(
+            exceeded(QueryLimits::new().include_paths(1), query),
+            limit_exceeded("include", "include paths", 1, 2)
+        );
+    }
+
+    #[test]
+    fn test_limits_filter_clauses_span_entries_and_expression() {
+        let query = "filter[views]=gte:1,lte:9&filter[comments]=exists&filter=eq(title,'a')";
+        assert!(within(QueryLimits::new().filter_clauses(4), query));
+        assert_eq!(
+            exceeded(QueryLimits::new().filter_clauses(3), query),
+            limit_exceeded("filter", "filter clauses", 3, 4)
+        );
+    }
+
+    #[test]
+    fn test_limits_leave_out_scope_filters() {
+        let query = "filter[views]=lte:9&filter[scope]=popular";
+        assert!(within(QueryLimits::new().filter_clauses(1), query));
+        assert_eq!(
+            exceeded(
+                QueryLimits::new().filter_clauses(1),
+                &format!("{query}&filter[comments]=count-gt:1")
+            ),
+            limit_exceeded("filter", "filter clauses", 1, 2)
+        );
+    }
+
+    #[test]
+    fn test_limits_in_list_length_names_the_filter() {
+        assert!(within(
+            QueryLimits::new().in_list_length(3),
+            "filter[views]=in:1,2,3"
+        ));
+        assert_eq!(
+            exceeded(
+                QueryLimits::new().in_list_length(2),
+                "filter[views]=nin:1,2,3"
+            ),
+            limit_exceeded("filter[views]", "values in a set", 2, 3)
+        );
+    }
+
+    #[test]
+    fn test_limits_sort_fields_and_search_terms() {
+        assert_eq!(
+            exceeded(QueryLimits::new().sort_fields(1), "sort=title,-views"),
+            limit_exceeded("sort", "sort fields", 1, 2)
+        );
+        assert_eq!(
+            exceeded(QueryLimits::new().search_terms(1), "search=a,b"),
+            limit_exceeded("search", "search terms", 1, 2)
+        );
+    }
+
+    #[test]
+    fn test_limits_included_relationship_options() {
+        let query = "include=author.articles&sort[author.articles]=title,-views\
+                     &page[author.articles][limit]=5";
+        assert!(within(
+            QueryLimits::new().sort_fields(2).include_limit(5),
+            query
+        ));
+        assert_eq!(
+            exceeded(QueryLimits::new().sort_fields(1), query),
+            limit_exceeded("sort[author.articles]", "sort fields", 1, 2)
+        );
+        assert_eq!(
+            exceeded(QueryLimits::new().include_limit(4), query),
+            limit_exceeded("page[author.articles][limit]", "included records", 4, 5)
+        );
+    }
+
+    #[test]
+    fn test_limits_fall_back_bound_by_bound() {
+        let global = QueryLimits::new().sort_fields(1).search_terms(1);
+        let limits = QueryLimits::new().sort_fields(3).or(global);
+
+        assert_eq!(limits, QueryLimits::new().sort_fields(3).search_terms(1));
+    }
+
+    // --- Aggregation ---
+
+    #[test]
+    fn test_parse_aggregate_with_group() {
+        let registry = registry();
+        let uri = mock_uri("aggregate[sum]=views,rating&aggregate[count]=id&group=author_id");
+        let aggregate = parse(&registry, &uri)
+            .aggregate
+            .expect("aggregation is requested");
+
+        assert_eq!(
+            aggregate.aggregates,
+            vec![
+                Aggregate {
+                    function: AggregateFunction::Sum,
+                    column: "views",
+                    kind: AttributeType::Integer,
+                },
+                Aggregate {
+                    function: AggregateFunction::Sum,
+                    column: "rating",
+                    kind: AttributeType::Float,
+                },
+                Aggregate {
+                    function: AggregateFunction::Count,
+                    column: "id",
+                    kind: AttributeType::Integer,
+                },
+            ]
+        );
+        assert_eq!(
+            aggregate.group,
+            vec![GroupKey {
+                column: "author_id",
+                kind: AttributeType::Integer,
+                bucket: None,
+            }]
+        );
+        assert!(parse(&registry, &mock_uri("")).aggregate.is_none());
+    }
+
+    #[test]
+    fn test_parse_group_by_date_bucket() {
+        let registry = registry();
+        let uri = mock_uri("aggregate[max]=views&group=created_at%3Amonth");
+        let aggregate = parse(&registry, &uri)
+            .aggregate
+            .expect("aggregation is requested");
+
+        assert_eq!(aggregate.group[0].bucket, Some(DateBucket::Month));
+        assert_eq!(aggregate.group[0].label(), "created_at:month");
+        assert_eq!(
+            aggregate.result_kinds(),
+            vec![AttributeType::Text, AttributeType::Integer]
+        );
+    }
+
+    #[test]
+    fn test_parse_aggregate_validates_types() {
+        assert!(matches!(
+            parse_err("aggregate[sum]=title"),
+            Error::QueryValidationFailure { attribute, message, .. }
+                if attribute == "title"
+                    && message == "The 'sum' aggregate cannot be applied to Text attributes"
+        ));
+        assert!(matches!(
+            parse_err("aggregate[max]=published"),
+            Error::QueryValidationFailure { attribute, .. } if attribute == "published"
+        ));
+        assert!(matches!(
+            parse_err("aggregate[avg]=reviews"),
+            Error::QueryValidationFailure { attribute, .. } if attribute == "reviews"
+        ));
+        assert!(matches!(
+            parse_err("aggregate[count]=id&group=title:month"),
+            Error::QueryValidationFailure { attribute, message, .. }
+                if attribute == "title"
+                    && message == "Only date-time attributes can be grouped by period"
+        ));
+    }
+
+    #[test]
+    fn test_parse_aggregate_rejects_malformed_requests() {
+        assert_eq!(
+            parse_err("aggregate[median]=views"),
+            Error::ParseParameterFailure {
+                parameter: "aggregate[median]".to_string(),
+                message: "Invalid aggregate function: 'median'".to_string(),
+            }
+        );
+        assert_eq!(
+            parse_err("group=author_id"),
+            Error::ParseParameterFailure {
+                parameter: "group".to_string(),
+                message: "Grouping requires at least one aggregate".to_string(),
+            }
+        );
+        assert!(matches!(
+            parse_err("aggregate[count]=id&group=created_at:week"),
+            Error::ParseParameterFailure { parameter, .. } if parameter == "group"
+        ));
+    }
+
+    // --- Search ranking and excerpts ---
+
+    #[test]
+    fn test_parse_sort_by_relevance() {
+        let registry = registry();
+        let uri = mock_uri("search=rust&sort=-relevance,title");
+        let sort = parse(&registry, &uri).sort.expect("sort is requested");
+
+        assert_eq!(sort[0], SortDirective::Relevance(SortDirection::Descending));
+        assert!(matches!(
+            &sort[1],
+            SortDirective::Attribute(SortingAttribute {
+                attribute: "title",
+                ..
+            })
+        ));
+        assert!(matches!(
+            parse_err("sort=relevance"),
+            Error::QueryValidationFailure { attribute, message, .. }
+                if attribute == "relevance" && message == "Sorting by relevance requires a search"
+        ));
+    }
+
+    #[test]
+    fn test_parse_excerpts() {
+        let registry = registry();
+        let uri = mock_uri("search=rust&meta[snippets]=title&meta[highlights]=title");
+        let params = parse(&registry, &uri);
+
+        assert_eq!(
+            params.excerpts,
+            ExcerptParameters {
+                snippets: IndexSet::from(["title"]),
+                highlights: IndexSet::from(["title"]),
+            }
+        );
+        assert!(matches!(
+            parse_err("search=rust&meta[snippets]=views"),
+            Error::QueryValidationFailure { attribute, .. } if attribute == "views"
+        ));
+        assert!(matches!(
+            parse_err("meta[highlights]=title"),
+            Error::QueryValidationFailure { attribute, .. } if attribute == "search"
+        ));
+    }
+
+    #[test]
+    fn test_indexing_a_non_text_attribute_is_inconsistent() {
+        let broken = comments().indexed(["article_id"]);
+
+        assert!(matches!(
+            DatabaseRegistry::try_new([articles(), users(), broken]),
+            Err(Error::InconsistentSchema { attribute, message, .. })
+                if attribute == "article_id"
+                    && message == "Only text attributes can be full-text indexed"
+        ));
+    }
+
+    #[test]
+    fn test_versioning_a_non_integer_attribute_is_inconsistent() {
+        let broken = comments().versioned("body");
+
+        assert!(matches!(
+            DatabaseRegistry::try_new([articles(), users(), broken]),
+            Err(Error::InconsistentSchema { attribute, message, .. })
+                if attribute == "body"
+                    && message == "Only integer attributes can hold a lock version"
+        ));
+    }
+}
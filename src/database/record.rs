@@ -1,11 +1,12 @@
 use super::{
     attributes::{Attributes, Identifier},
     error::Error,
-    relationships::{Relationship, Relationships},
+    relationships::{LocalLinkage, Relationship, RelationshipCounts, Relationships},
     schema::{IdentifierType, Schema},
 };
 use crate::database::attributes::{Attribute, ForeignKeys, Row};
 use crate::json_api::identifier::Identifier as JsonApiIdentifier;
+use indexmap::IndexMap;
 
 pub trait Builder<'sch>: From<(&'sch Schema<'sch>, Attributes<'sch>, Relationships<'sch>)> {
     fn new(schema: &'sch Schema<'sch>) -> Self;
@@ -13,12 +14,32 @@ pub trait Builder<'sch>: From<(&'sch Schema<'sch>, Attributes<'sch>, Relationshi
     fn from_relationships(schema: &'sch Schema<'sch>, relationships: Relationships<'sch>) -> Self;
 }
 
+/// The full-text search context of a record, by attribute: `snippets` of the text around the
+/// matches, and `highlights` of the whole value with the matches marked.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SearchExcerpts<'sch> {
+    pub snippets: IndexMap<&'sch str, String>,
+    pub highlights: IndexMap<&'sch str, String>,
+}
+
+impl SearchExcerpts<'_> {
+    pub fn is_empty(&self) -> bool {
+        self.snippets.is_empty() && self.highlights.is_empty()
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct Record<'sch> {
     pub schema: &'sch Schema<'sch>,
     pub id: Option<Identifier>,
     pub attributes: Attributes<'sch>,
     pub relationships: Relationships<'sch>,
+    /// Linkage to resources created alongside this one, resolved by the store once they exist.
+    pub local_linkage: LocalLinkage<'sch>,
+    /// The `lid` the client created the record under, if any.
+    pub lid: Option<String>,
+    pub counts: RelationshipCounts<'sch>,
+    pub excerpts: SearchExcerpts<'sch>,
     pub(crate) foreign_keys: ForeignKeys<'sch>,
 }
 
@@ -57,11 +78,12 @@ impl<'sch> Record<'sch> {
                 JsonApiIdentifier::Existing {
                     kind: self.kind().to_string(),
                     id,
+                    lid: self.lid.clone(),
                 }
             })
             .unwrap_or_else(|| JsonApiIdentifier::New {
                 kind: self.kind().to_string(),
-                lid: None,
+                lid: self.lid.clone(),
             })
     }
 
@@ -182,6 +204,10 @@ impl<'sch> Record<'sch> {
             id,
             attributes,
             relationships: Relationships::new(),
+            local_linkage: LocalLinkage::new(),
+            lid: None,
+            counts: RelationshipCounts::new(),
+            excerpts: SearchExcerpts::default(),
             foreign_keys,
         })
     }
@@ -202,6 +228,10 @@ impl<'sch> Builder<'sch> for Record<'sch> {
             id: None,
             attributes: Attributes::new(),
             relationships: Relationships::new(),
+            local_linkage: LocalLinkage::new(),
+            lid: None,
+            counts: RelationshipCounts::new(),
+            excerpts: SearchExcerpts::default(),
             foreign_keys: ForeignKeys::new(),
         }
     }
@@ -239,6 +269,10 @@ impl<'sch> From<RecordPatch<'sch>> for Record<'sch> {
             id: None,
             attributes: patch.attributes,
             relationships: patch.relationships,
+            local_linkage: LocalLinkage::new(),
+            lid: None,
+            counts: RelationshipCounts::new(),
+            excerpts: SearchExcerpts::default(),
             foreign_keys: ForeignKeys::new(),
         }
     }
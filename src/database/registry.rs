@@ -1,15 +1,27 @@
 use super::{
+    audit::{AUDIT_LOG, audit_log},
     error::Error,
+    filter_operator::FilterOperator,
+    query_parameters::{AS_OF_FILTER, QueryParameters, SCOPE_FILTER, is_reserved_operator},
     schema::{
         AttributeType, RelatedResource, RelationshipKind, Schema, SchemaBuilder, SchemaParts,
+        VALID_FROM, VALID_TO,
     },
 };
-use std::collections::HashMap;
+use regex::Regex;
+use std::collections::{HashMap, hash_map::Entry};
+use std::sync::{Arc, LazyLock};
 
-/// An immutable, validated collection of schemas keyed by resource type. Holds no
-/// storage: binding a registry to a connection pool is `ConnectionManager`'s job.
+/// Matches exactly a valid filter operator name: lowercase words separated by single dashes.
+static OPERATOR_NAME_REGEX: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"\A[a-z][a-z0-9]*(?:-[a-z0-9]+)*\z").unwrap());
+
+/// An immutable, validated collection of schemas keyed by resource type, along with the
+/// filter operators registered beyond the built-in ones. Holds no storage: binding a
+/// registry to a connection pool is `ConnectionManager`'s job.
 pub struct Registry<'sch> {
     schemas: HashMap<&'sch str, Schema<'sch>>,
+    filter_operators: HashMap<String, Arc<dyn FilterOperator>>,
 }
 
 impl<'sch> Registry<'sch> {
@@ -22,11 +34,81 @@ impl<'sch> Registry<'sch> {
             .map(SchemaBuilder::into_parts)
             .collect();
 
-        Ok(Self {
+        let registry = Self {
             schemas: try_build(parts)?,
+            filter_operators: HashMap::new(),
+        };
+        registry.validate_scopes()?;
+
+        Ok(registry)
+    }
+
+    /// Registers a custom filter operator, usable in `filter[field]` entries on the attributes
+    /// it applies to. Its name must be well-formed, and not be taken by a built-in operator or
+    /// one registered before. The scope entries naming it are validated once it is registered.
+    pub fn with_filter_operator(
+        mut self,
+        operator: impl FilterOperator + 'static,
+    ) -> Result<Self, Error> {
+        let name = operator.name().to_string();
+        let inconsistent = |message: &str| Error::InconsistentFilterOperator {
+            operator: name.clone(),
+            message: message.to_string(),
+        };
+
+        if !OPERATOR_NAME_REGEX.is_match(&name) {
+            return Err(inconsistent(
+                "Operator names must be lowercase words separated by dashes",
+            ));
+        }
+        if is_reserved_operator(&name) {
+            return Err(inconsistent(
+                "Operator name is taken by a built-in operator",
+            ));
+        }
+
+        match self.filter_operators.entry(name.clone()) {
+            Entry::Occupied(_) => Err(inconsistent("Operator is registered more than once")),
+            Entry::Vacant(entry) => {
+                entry.insert(Arc::new(operator));
+                self.validate_scopes()?;
+                Ok(self)
+            }
+        }
+    }
+
+    /// Expands every scope entry once, so a scope that cannot expand fails the registry rather
+    /// than every request applying it. An entry naming an operator that is not registered yet is
+    /// left for `with_filter_operator` to validate once it is.
+    fn validate_scopes(&self) -> Result<(), Error> {
+        for schema in self.schemas.values() {
+            for scope in schema.scopes() {
+                for &(field, entries) in &scope.filters {
+                    if !self.awaits_operator(entries) {
+                        QueryParameters::validate_scope_entry(scope, field, entries, schema, self)?;
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Whether an entry of a scope names an operator that could still be registered.
+    fn awaits_operator(&self, entries: &str) -> bool {
+        entries.split(',').any(|entry| {
+            entry.split_once(':').is_some_and(|(name, _)| {
+                OPERATOR_NAME_REGEX.is_match(name)
+                    && !is_reserved_operator(name)
+                    && self.filter_operator(name).is_none()
+            })
         })
     }
 
+    pub fn filter_operator(&self, name: &str) -> Option<&Arc<dyn FilterOperator>> {
+        self.filter_operators.get(name)
+    }
+
     pub fn schema(&self, name: &str) -> Result<&Schema<'sch>, Error> {
         self.schemas.get(name).ok_or_else(|| Error::UnknownSchema {
             schema: name.to_string(),
@@ -57,6 +139,7 @@ fn try_build<'sch>(
     for schema in registry.values() {
         validate_schema(schema)?;
         validate_relationships(schema, &registry)?;
+        validate_audit_log(schema, &registry)?;
     }
 
     Ok(registry
@@ -67,7 +150,11 @@ fn try_build<'sch>(
 
 /// Intra-schema invariants: a column name denotes at most one of the primary
 /// key, an attribute, or a foreign key; attributes and relationships share the
-/// JSON:API "fields" namespace; and `type`/`id` are reserved field names.
+/// JSON:API "fields" namespace; `type`/`id` are reserved field names; a scope
+/// filters only on known fields, with `scope` itself left free to name it;
+/// only to-many relationships are counted; only text attributes are indexed; the lock version
+/// is an integer attribute; and a schema keeping history leaves its validity columns and
+/// `as_of` free.
 fn validate_schema(schema: &SchemaParts) -> Result<(), Error> {
     let primary_key = schema.primary_key.name;
     if schema.attributes.contains_key(primary_key) || schema.foreign_keys.contains_key(primary_key)
@@ -109,6 +196,114 @@ fn validate_schema(schema: &SchemaParts) -> Result<(), Error> {
         }
     }
 
+    let is_filterable = |field: &str| {
+        field == "id"
+            || field == primary_key
+            || schema.attributes.contains_key(field)
+            || schema.foreign_keys.contains_key(field)
+            || schema.relationships.contains_key(field)
+    };
+
+    if !schema.scopes.is_empty() && is_filterable(SCOPE_FILTER) {
+        return Err(Error::InconsistentSchema {
+            schema: schema.name.to_string(),
+            attribute: SCOPE_FILTER.to_string(),
+            message: "A schema declaring scopes cannot have a field named 'scope'".to_string(),
+        });
+    }
+
+    if schema.history {
+        for field in [VALID_FROM, VALID_TO, AS_OF_FILTER] {
+            if is_filterable(field) {
+                return Err(Error::InconsistentSchema {
+                    schema: schema.name.to_string(),
+                    attribute: field.to_string(),
+                    message: format!(
+                        "A schema keeping history cannot have a field named '{field}'"
+                    ),
+                });
+            }
+        }
+    }
+
+    for scope in schema.scopes.values() {
+        for &(field, _) in &scope.filters {
+            if !is_filterable(field) {
+                return Err(Error::InconsistentSchema {
+                    schema: schema.name.to_string(),
+                    attribute: scope.name.to_string(),
+                    message: format!("Scope filters on unknown field '{field}'"),
+                });
+            }
+        }
+    }
+
+    for &relationship in &schema.counted {
+        let is_to_many = schema
+            .relationships
+            .get(relationship)
+            .is_some_and(|descriptor| descriptor.kind == RelationshipKind::HasMany);
+
+        if !is_to_many {
+            return Err(Error::InconsistentSchema {
+                schema: schema.name.to_string(),
+                attribute: relationship.to_string(),
+                message: "Only to-many relationships can be counted".to_string(),
+            });
+        }
+    }
+
+    for &attribute in &schema.indexed {
+        let is_text = schema
+            .attributes
+            .get(attribute)
+            .is_some_and(|column| column.kind == AttributeType::Text);
+
+        if !is_text {
+            return Err(Error::InconsistentSchema {
+                schema: schema.name.to_string(),
+                attribute: attribute.to_string(),
+                message: "Only text attributes can be full-text indexed".to_string(),
+            });
+        }
+    }
+
+    if let Some(version) = schema.version {
+        let is_integer = schema
+            .attributes
+            .get(version)
+            .is_some_and(|column| column.kind == AttributeType::Integer);
+
+        if !is_integer {
+            return Err(Error::InconsistentSchema {
+                schema: schema.name.to_string(),
+                attribute: version.to_string(),
+                message: "Only integer attributes can hold a lock version".to_string(),
+            });
+        }
+    }
+
+    Ok(())
+}
+
+/// An audited schema's entries are written to the audit log, which must be registered as
+/// `audit_log()` declares it, and is not itself audited.
+fn validate_audit_log<'sch>(
+    schema: &SchemaParts<'sch>,
+    registry: &HashMap<&'sch str, SchemaParts<'sch>>,
+) -> Result<(), Error> {
+    if !schema.audited {
+        return Ok(());
+    }
+    if schema.name == AUDIT_LOG {
+        return Err(Error::AuditedAuditLog);
+    }
+    if registry.get(AUDIT_LOG) != Some(&audit_log().into_parts()) {
+        return Err(Error::MissingAuditLog {
+            schema: schema.name.to_string(),
+        });
+    }
+
     Ok(())
 }
 
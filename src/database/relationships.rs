@@ -10,3 +10,10 @@ pub enum Relationship {
 }
 
 pub type Relationships<'sch> = HashMap<&'sch str, Relationship>;
+
+/// The `lid`s each relationship links to: resources created in the same request as the record,
+/// linked by the ids they are assigned once inserted.
+pub type LocalLinkage<'sch> = HashMap<&'sch str, Vec<String>>;
+
+/// The number of related records each counted to-many relationship holds
+pub type RelationshipCounts<'sch> = HashMap<&'sch str, u64>;
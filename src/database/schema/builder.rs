@@ -1,8 +1,8 @@
 use super::{
     AttributeType, ColumnDescriptor, IdentifierType, PrimaryKey, RelatedResource,
-    RelationshipDescriptor, RelationshipKeys, RelationshipKind, SchemaParts,
+    RelationshipDescriptor, RelationshipKeys, RelationshipKind, SchemaParts, ScopeDescriptor,
 };
-use indexmap::IndexMap;
+use indexmap::{IndexMap, IndexSet};
 
 /// Fluent construction of a relationship's target. `to` names the related
 /// resource; the join columns follow, labelled by which side carries the
@@ -85,7 +85,12 @@ impl<'sch> SchemaBuilder<'sch> {
                 attributes: IndexMap::new(),
                 foreign_keys: IndexMap::new(),
                 relationships: IndexMap::new(),
-                text_index: false,
+                scopes: IndexMap::new(),
+                counted: IndexSet::new(),
+                indexed: IndexSet::new(),
+                version: None,
+                audited: false,
+                history: false,
             },
         }
     }
@@ -145,8 +150,57 @@ impl<'sch> SchemaBuilder<'sch> {
         self
     }
 
-    pub fn text_index(mut self) -> Self {
-        self.parts.text_index = true;
+    /// Declares a named filter scope expanding into `filters`, pairs of a filterable field and
+    /// its entries, e.g. `.scope("published", [("status", "eq:published")])`.
+    pub fn scope(
+        mut self,
+        name: &'sch str,
+        filters: impl IntoIterator<Item = (&'sch str, &'sch str)>,
+    ) -> Self {
+        self.parts.scopes.insert(
+            name,
+            ScopeDescriptor {
+                name,
+                filters: filters.into_iter().collect(),
+            },
+        );
+        self
+    }
+
+    /// Counts the related records of the to-many `relationship` into the `meta` of its relationship
+    /// object on every serialised resource, as a client would with `meta[counts]`.
+    pub fn always_count(mut self, relationship: &'sch str) -> Self {
+        self.parts.counted.insert(relationship);
+        self
+    }
+
+    /// Declares the text attributes the full-text index holds, in the index's column order, making
+    /// the resource searchable, each of them by `search[attribute]`, and their excerpts renderable.
+    pub fn indexed(mut self, attributes: impl IntoIterator<Item = &'sch str>) -> Self {
+        self.parts.indexed.extend(attributes);
+        self
+    }
+
+    /// Declares the integer attribute holding the record's lock version. An update must present
+    /// the version it was made against, and bumps it; a create starts it at 1.
+    pub fn versioned(mut self, attribute: &'sch str) -> Self {
+        self.parts.version = Some(attribute);
+        self
+    }
+
+    /// Records each write to the resource's records in the audit log — their type, id and
+    /// operation, the attributes and linkage they changed, when and by whom — in the transaction
+    /// making it. The log's own schema, `audit::audit_log()`, must be registered alongside.
+    pub fn audited(mut self) -> Self {
+        self.parts.audited = true;
+        self
+    }
+
+    /// Keeps every version of the resource's records in a `<table>_history` table, so a collection
+    /// or record can be read as it stood at an instant, by `filter[as_of]`. The table and the
+    /// triggers maintaining it are made by `Table::create_history`.
+    pub fn keeps_history(mut self) -> Self {
+        self.parts.history = true;
         self
     }
 
@@ -249,6 +303,21 @@ mod tests {
                 ),
             ])
         );
-        assert!(parts.text_index);
+        assert_eq!(
+            parts.scopes,
+            IndexMap::from([(
+                "bargains",
+                ScopeDescriptor {
+                    name: "bargains",
+                    filters: vec![("price", "lt:10"), ("variants", "exists")],
+                },
+            )])
+        );
+        assert_eq!(parts.counted, IndexSet::from(["variants"]));
+        assert_eq!(parts.indexed, IndexSet::from(["name"]));
+        assert!(!parts.audited);
+        assert!(products().audited().into_parts().audited);
+        assert!(!parts.history);
+        assert!(products().keeps_history().into_parts().history);
     }
 }
@@ -1,4 +1,4 @@
-use indexmap::IndexMap;
+use indexmap::{IndexMap, IndexSet};
 use std::fmt::Display;
 
 pub mod builder;
@@ -26,6 +26,7 @@ pub enum AttributeType {
     Float,
     Boolean,
     DateTime,
+    Json,
 }
 
 impl Display for AttributeType {
@@ -51,6 +52,104 @@ pub struct ColumnDescriptor<'sch> {
     pub kind: AttributeType,
 }
 
+/// The integer column numbering each text key in a `TextIndex`'s side table, and the text column
+/// holding the key.
+pub const INDEX_ROWID: &str = "rowid";
+pub const INDEX_KEY: &str = "key";
+
+/// A schema's full-text index: an external-content index named `<table>_fts`, holding the indexed
+/// attributes in its column order and keyed by an integer `rowid`. An integer primary key is that
+/// `rowid` itself; a table keyed by text numbers its keys in the `<table>_fts_keys` side table, and
+/// the index reads its content through the `<table>_fts_content` view joining the two, so no
+/// `VACUUM` can renumber what it holds.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TextIndex<'sch> {
+    pub table: &'sch str,
+    pub attributes: IndexSet<&'sch str>,
+    pub primary_key: PrimaryKey<'sch>,
+}
+
+impl<'sch> TextIndex<'sch> {
+    fn new(
+        table: &'sch str,
+        primary_key: PrimaryKey<'sch>,
+        attributes: IndexSet<&'sch str>,
+    ) -> Self {
+        Self {
+            table,
+            attributes,
+            primary_key,
+        }
+    }
+
+    pub fn name(&self) -> String {
+        format!("{}_fts", self.table)
+    }
+
+    /// The side table numbering a text primary key, when the table is keyed by text.
+    pub fn keys(&self) -> Option<String> {
+        match self.primary_key.kind {
+            IdentifierType::Integer => None,
+            IdentifierType::Text => Some(format!("{}_fts_keys", self.table)),
+        }
+    }
+
+    /// The table or view the index reads its content from.
+    pub fn content(&self) -> String {
+        match self.primary_key.kind {
+            IdentifierType::Integer => self.table.to_string(),
+            IdentifierType::Text => format!("{}_fts_content", self.table),
+        }
+    }
+
+    /// The column of `content` the index's `rowid` mirrors.
+    pub fn content_rowid(&self) -> &'sch str {
+        match self.primary_key.kind {
+            IdentifierType::Integer => self.primary_key.name,
+            IdentifierType::Text => INDEX_ROWID,
+        }
+    }
+
+    /// The position of `attribute` among the index's columns, when it is indexed.
+    pub fn column(&self, attribute: &str) -> Option<usize> {
+        self.attributes.get_index_of(attribute)
+    }
+}
+
+/// The columns of a history table bounding the instants each version held, as UTC RFC 3339 text.
+pub const VALID_FROM: &str = "valid_from";
+pub const VALID_TO: &str = "valid_to";
+
+/// A schema's row history: a table named `<table>_history` holding every version of each record —
+/// its stored `columns` — with the instants it was `VALID_FROM` and `VALID_TO`. A record's current
+/// version is open, its `VALID_TO` unset; a version the record already held when the history was
+/// created is valid from that creation.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct History<'sch> {
+    pub table: &'sch str,
+    pub primary_key: &'sch str,
+    pub columns: IndexSet<&'sch str>,
+}
+
+impl<'sch> History<'sch> {
+    fn new(parts: &SchemaParts<'sch>) -> Self {
+        let columns = std::iter::once(parts.primary_key.name)
+            .chain(parts.attributes.keys().copied())
+            .chain(parts.foreign_keys.keys().copied())
+            .collect();
+
+        Self {
+            table: parts.name,
+            primary_key: parts.primary_key.name,
+            columns,
+        }
+    }
+
+    pub fn name(&self) -> String {
+        format!("{}_history", self.table)
+    }
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct PrimaryKey<'sch> {
     pub name: &'sch str,
@@ -109,6 +208,15 @@ impl Display for RelationshipDescriptor<'_> {
     }
 }
 
+/// A named filter scope: a server-defined combination of `filter[field]` entries, each a field
+/// name and its entries as they would appear in a query string (`("status", "eq:published")`).
+/// A client applies it with `filter[scope]=name`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ScopeDescriptor<'sch> {
+    pub name: &'sch str,
+    pub filters: Vec<(&'sch str, &'sch str)>,
+}
+
 /// The inert, unvalidated extract of a `SchemaBuilder`. The registry reads it to
 /// validate cross-schema, then mints a `Schema` from it; it is the only
 /// path to a schema.
@@ -119,7 +227,12 @@ pub(crate) struct SchemaParts<'sch> {
     pub attributes: IndexMap<&'sch str, ColumnDescriptor<'sch>>,
     pub foreign_keys: IndexMap<&'sch str, ColumnDescriptor<'sch>>,
     pub relationships: IndexMap<&'sch str, RelationshipDescriptor<'sch>>,
-    pub text_index: bool,
+    pub scopes: IndexMap<&'sch str, ScopeDescriptor<'sch>>,
+    pub counted: IndexSet<&'sch str>,
+    pub indexed: IndexSet<&'sch str>,
+    pub version: Option<&'sch str>,
+    pub audited: bool,
+    pub history: bool,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -129,20 +242,33 @@ pub struct Schema<'sch> {
     attributes: IndexMap<&'sch str, ColumnDescriptor<'sch>>,
     foreign_keys: IndexMap<&'sch str, ColumnDescriptor<'sch>>,
     relationships: IndexMap<&'sch str, RelationshipDescriptor<'sch>>,
-    text_index: bool,
+    scopes: IndexMap<&'sch str, ScopeDescriptor<'sch>>,
+    counted: IndexSet<&'sch str>,
+    text_index: Option<TextIndex<'sch>>,
+    version: Option<&'sch str>,
+    audited: bool,
+    history: Option<History<'sch>>,
 }
 
 impl<'sch> Schema<'sch> {
     /// Mints a validated schema from a builder's extract. Restricted to the
     /// crate so construction always flows through the registry's validation.
     pub(crate) fn new(parts: SchemaParts<'sch>) -> Self {
+        let history = parts.history.then(|| History::new(&parts));
+
         Self {
             name: parts.name,
             primary_key: parts.primary_key,
             attributes: parts.attributes,
             foreign_keys: parts.foreign_keys,
             relationships: parts.relationships,
-            text_index: parts.text_index,
+            scopes: parts.scopes,
+            counted: parts.counted,
+            text_index: (!parts.indexed.is_empty())
+                .then(|| TextIndex::new(parts.name, parts.primary_key, parts.indexed)),
+            version: parts.version,
+            audited: parts.audited,
+            history,
         }
     }
 
@@ -154,8 +280,29 @@ impl<'sch> Schema<'sch> {
         self.primary_key
     }
 
-    pub fn text_index(&self) -> bool {
-        self.text_index
+    /// The full-text index to search, when the schema declares any indexed attribute.
+    pub fn text_index(&self) -> Option<&TextIndex<'sch>> {
+        self.text_index.as_ref()
+    }
+
+    /// The integer attribute holding the record's lock version, when the schema is versioned.
+    pub fn version(&self) -> Option<&'sch str> {
+        self.version
+    }
+
+    /// Whether the store records each write to the schema's records in the audit log.
+    pub fn audited(&self) -> bool {
+        self.audited
+    }
+
+    /// The history table keeping every version of the schema's records, when it keeps one.
+    pub fn history(&self) -> Option<&History<'sch>> {
+        self.history.as_ref()
+    }
+
+    /// The position of `attribute` among the full-text index's columns, when it is indexed.
+    pub fn index_column(&self, attribute: &str) -> Option<usize> {
+        self.text_index.as_ref()?.column(attribute)
     }
 
     // The `&'sch self` receiver lends the borrowed values out of the owned maps
@@ -192,6 +339,26 @@ impl<'sch> Schema<'sch> {
         self.relationships.get(relationship_name)
     }
 
+    pub fn scope(&self, scope_name: &str) -> Option<&ScopeDescriptor<'sch>> {
+        self.scopes.get(scope_name)
+    }
+
+    pub fn scopes(&self) -> impl Iterator<Item = &ScopeDescriptor<'sch>> {
+        self.scopes.values()
+    }
+
+    /// The to-many relationships whose related records are counted into every serialised resource.
+    pub fn counted_relationships(&self) -> impl Iterator<Item = &'sch str> {
+        self.counted.iter().copied()
+    }
+
+    /// Whether `filter[scope]` applies scopes rather than filtering a field named `scope`: always,
+    /// unless the schema has such a field, which the registry forbids alongside declared scopes.
+    pub fn scope_applies(&self) -> bool {
+        !self.scopes.is_empty()
+            || (self.column("scope").is_none() && !self.has_relationship("scope"))
+    }
+
     /// Resolves any stored column -- primary key, attribute, or foreign key -- to its
     /// descriptor, synthesising the primary key's (widening its identifier type to the
     /// matching `AttributeType`).
@@ -257,7 +424,9 @@ pub(crate) fn products() -> SchemaBuilder<'static> {
                 .pointing_related("product_id")
                 .to_own("id"),
         )
-        .text_index()
+        .scope("bargains", [("price", "lt:10"), ("variants", "exists")])
+        .always_count("variants")
+        .indexed(["name"])
 }
 
 #[cfg(test)]
@@ -361,4 +530,47 @@ pub(crate) mod tests {
         assert_eq!(schema.column("variants"), None);
         assert_eq!(schema.column("nonexistent"), None);
     }
+
+    #[test]
+    fn test_text_index_maps_the_primary_key_to_its_rowid() {
+        let schema = Schema::new(products().into_parts());
+        let index = schema.text_index().expect("products are indexed");
+        assert_eq!(index.name(), "products_fts");
+        assert_eq!(index.content_rowid(), "id");
+        assert_eq!(index.content(), "products");
+        assert_eq!(index.keys(), None);
+        assert_eq!(schema.index_column("name"), Some(0));
+        assert_eq!(schema.index_column("price"), None);
+
+        let renamed = products().primary_key("product_id", IdentifierType::Integer);
+        let schema = Schema::new(renamed.into_parts());
+        let index = schema.text_index().expect("renamed products are indexed");
+        assert_eq!(index.content_rowid(), "product_id");
+
+        let text_keyed = products().primary_key("sku", IdentifierType::Text);
+        let schema = Schema::new(text_keyed.into_parts());
+        let index = schema
+            .text_index()
+            .expect("text-keyed products are indexed");
+        assert_eq!(index.content_rowid(), INDEX_ROWID);
+        assert_eq!(index.content(), "products_fts_content");
+        assert_eq!(index.keys(), Some("products_fts_keys".to_string()));
+
+        let unindexed = SchemaBuilder::table("plain").attribute("name", Text);
+        assert_eq!(Schema::new(unindexed.into_parts()).text_index(), None);
+    }
+
+    #[test]
+    fn test_history_keeps_every_stored_column() {
+        let schema = Schema::new(products().keeps_history().into_parts());
+        let history = schema.history().expect("products keep a history");
+        assert_eq!(history.name(), "products_history");
+        assert_eq!(history.primary_key, "id");
+        assert_eq!(
+            history.columns,
+            IndexSet::from(["id", "name", "price", "category_id"])
+        );
+
+        assert_eq!(Schema::new(products().into_parts()).history(), None);
+    }
 }
@@ -3,12 +3,17 @@ use std::collections::HashMap;
 
 use crate::database::adapters::Adapter as AdapterInterface;
 use crate::database::attributes::{Attribute, Identifier, Row};
+use crate::database::audit::{self, AUDIT_LOG};
 use crate::database::composite::{Composite, CompositeCollection, CompositeRecord};
 use crate::database::connection::Connection as ConnectionInterface;
 use crate::database::connection_manager::ConnectionManager;
 use crate::database::data_loader::DataLoader;
 use crate::database::error::{ConstraintKind, Error};
-use crate::database::query_parameters::{FilterParameters, FilterValue, QueryParameters};
+use crate::database::events::{Change, ChangeEvent};
+use crate::database::query_parameters::{
+    Aggregate, AggregateFunction, AggregateParameters, AggregateRow, FilterParameters, FilterValue,
+    QueryParameters,
+};
 use crate::database::record::{Record, RecordPatch, Refreshable};
 use crate::database::relationships::Relationship as DatabaseRelationship;
 use crate::database::schema::{RelationshipKind, Schema};
@@ -50,6 +55,7 @@ mod error_mapper {
 pub struct Store<'sch: 'req, 'req, Adapter: AdapterInterface> {
     manager: &'sch ConnectionManager<'sch, Adapter>,
     connection: &'req Adapter::Connection,
+    actor: Option<&'req str>,
 }
 
 impl<'sch: 'req, 'req, Adapter: AdapterInterface> Store<'sch, 'req, Adapter> {
@@ -60,9 +66,16 @@ impl<'sch: 'req, 'req, Adapter: AdapterInterface> Store<'sch, 'req, Adapter> {
         Store {
             manager,
             connection,
+            actor: None,
         }
     }
 
+    /// Names who makes this store's writes, as the audit log records them.
+    pub fn acting_as(mut self, actor: Option<&'req str>) -> Self {
+        self.actor = actor;
+        self
+    }
+
     pub fn fetch_record(
         &self,
         schema: &'sch Schema<'sch>,
@@ -78,6 +91,47 @@ impl<'sch: 'req, 'req, Adapter: AdapterInterface> Store<'sch, 'req, Adapter> {
         })
     }
 
+    /// The lock version the record at `id` is at, or none when its schema is unversioned.
+    pub fn fetch_version(
+        &self,
+        schema: &'sch Schema<'sch>,
+        id: Identifier,
+    ) -> Result<Option<i64>, Error> {
+        let Some(version) = schema.version() else {
+            return Ok(None);
+        };
+
+        let row = self.table(schema)?.find_by(
+            schema.primary_key().name,
+            id.into(),
+            Some(IndexSet::from([version])),
+        )?;
+        match row.get(version) {
+            Some(&Attribute::Integer(current)) => Ok(Some(current)),
+            _ => Err(Error::InvalidAttributeAccess {
+                schema: schema.name().to_string(),
+                attribute: version.to_string(),
+            }),
+        }
+    }
+
+    /// Whether a record of `schema` is held under `id`.
+    pub fn contains_record(
+        &self,
+        schema: &'sch Schema<'sch>,
+        id: &Identifier,
+    ) -> Result<bool, Error> {
+        match self.table(schema)?.find_by(
+            schema.primary_key().name,
+            id.clone().into(),
+            Some(IndexSet::from([schema.primary_key().name])),
+        ) {
+            Ok(_) => Ok(true),
+            Err(Error::RecordNotFound) => Ok(false),
+            Err(error) => Err(error),
+        }
+    }
+
     pub fn fetch_collection(
         &self,
         schema: &'sch Schema<'sch>,
@@ -98,24 +152,55 @@ impl<'sch: 'req, 'req, Adapter: AdapterInterface> Store<'sch, 'req, Adapter> {
         })
     }
 
+    /// Computes the statistics `aggregate` asks for over the collection `parameters` filters and
+    /// searches, one row per group.
+    pub fn aggregate_collection(
+        &self,
+        schema: &'sch Schema<'sch>,
+        aggregate: &AggregateParameters<'sch>,
+        parameters: &QueryParameters<'sch, 'req>,
+    ) -> Result<Vec<AggregateRow>, Error> {
+        self.connection
+            .transaction(|| self.table(schema)?.aggregate(aggregate, parameters))
+    }
+
+    /// Creates `record` together with the new resources `included` alongside it, all or none of
+    /// them. The included records are inserted first, in order, each resolving its local linkage
+    /// against those inserted before it; `record` then resolves its own against them all. The
+    /// created records join the composite's included records, each keeping the `lid` it was
+    /// created under.
     pub fn create_record(
         &self,
         mut record: Record<'sch>,
+        included: Vec<Record<'sch>>,
         parameters: &QueryParameters<'sch, 'req>,
     ) -> Result<CompositeRecord<'sch>, Error> {
         self.connection
             .transaction(|| {
-                let schema = record.schema;
-                self.attach_belongs_to(slice::from_mut(&mut record))?;
-                let id = record.id.take();
-                record.refresh_with(|mut row| {
-                    if let Some(id) = id {
-                        row.insert(schema.primary_key().name, id.into());
+                let mut assigned = HashMap::new();
+                let mut created = Vec::with_capacity(included.len());
+                for mut local in included {
+                    self.resolve_local_linkage(&mut local, &assigned)?;
+                    let schema = local.schema;
+                    self.insert_record(&mut local, &QueryParameters::new(schema))?;
+                    if let Some(lid) = &local.lid {
+                        assigned.insert((local.kind(), lid.clone()), local.require_id()?.clone());
                     }
-                    self.table(schema)?.insert(row, parameters)
-                })?;
-                self.attach_has_one_many(slice::from_ref(&record), false)?;
-                let included = self.loader().load_for_record(&mut record, parameters)?;
+                    created.push(local);
+                }
+
+                self.resolve_local_linkage(&mut record, &assigned)?;
+                self.insert_record(&mut record, parameters)?;
+                let mut included = self.loader().load_for_record(&mut record, parameters)?;
+                for local in created {
+                    match included
+                        .iter_mut()
+                        .find(|loaded| loaded.kind() == local.kind() && loaded.id == local.id)
+                    {
+                        Some(loaded) => loaded.lid = local.lid,
+                        None => included.push(local),
+                    }
+                }
 
                 Ok(Composite {
                     content: record,
@@ -125,6 +210,89 @@ impl<'sch: 'req, 'req, Adapter: AdapterInterface> Store<'sch, 'req, Adapter> {
             .map_err(error_mapper::fk_violation_to_missing_reference)
     }
 
+    /// Inserts `record` with its linkage, refreshing it from the persisted row.
+    fn insert_record(
+        &self,
+        record: &mut Record<'sch>,
+        parameters: &QueryParameters<'sch, '_>,
+    ) -> Result<(), Error> {
+        let schema = record.schema;
+        self.attach_belongs_to(slice::from_mut(record))?;
+        let id = record.id.take();
+        record.refresh_with(|mut row| {
+            if let Some(id) = id {
+                row.insert(schema.primary_key().name, id.into());
+            }
+            if let Some(version) = schema.version() {
+                row.insert(version, Attribute::Integer(1));
+            }
+            self.table(schema)?.insert(row, parameters)
+        })?;
+        self.attach_has_one_many(slice::from_ref(record), false)?;
+        self.publish(schema, record.require_id()?.clone(), Change::Created, None)
+    }
+
+    /// Moves `record`'s local linkage into its relationships, each `lid` resolved to the id
+    /// `assigned` to the resource created under it.
+    fn resolve_local_linkage(
+        &self,
+        record: &mut Record<'sch>,
+        assigned: &HashMap<(&'sch str, String), Identifier>,
+    ) -> Result<(), Error> {
+        let schema = record.schema;
+        for (name, lids) in std::mem::take(&mut record.local_linkage) {
+            let descriptor =
+                schema
+                    .relationship(name)
+                    .ok_or_else(|| Error::ResourceValidationFailure {
+                        schema: schema.name().to_string(),
+                        attribute: name.to_string(),
+                        message: "Attempted to attach unknown relationship".to_string(),
+                    })?;
+            let related = descriptor.related.resource;
+            let mut ids: Vec<Identifier> = lids
+                .into_iter()
+                .map(|lid| {
+                    assigned
+                        .get(&(related, lid.clone()))
+                        .cloned()
+                        .ok_or_else(|| Error::UnresolvedLocalIdentifier {
+                            schema: related.to_string(),
+                            lid,
+                        })
+                })
+                .try_collect()?;
+
+            let relationship = match descriptor.kind {
+                RelationshipKind::HasMany => {
+                    let mut linked = match record.relationships.remove(descriptor.name) {
+                        Some(DatabaseRelationship::HasMany(linked)) => linked,
+                        _ => Vec::new(),
+                    };
+                    linked.append(&mut ids);
+                    DatabaseRelationship::HasMany(linked)
+                }
+                kind => {
+                    let id = ids.into_iter().exactly_one().map_err(|_| {
+                        Error::ResourceValidationFailure {
+                            schema: schema.name().to_string(),
+                            attribute: name.to_string(),
+                            message: "Attempted to attach relationship with wrong linkage"
+                                .to_string(),
+                        }
+                    })?;
+                    match kind {
+                        RelationshipKind::BelongsTo => DatabaseRelationship::BelongsTo(id),
+                        _ => DatabaseRelationship::HasOne(id),
+                    }
+                }
+            };
+            record.relationships.insert(descriptor.name, relationship);
+        }
+
+        Ok(())
+    }
+
     pub fn update_record(
         &self,
         mut record: Record<'sch>,
@@ -135,14 +303,19 @@ impl<'sch: 'req, 'req, Adapter: AdapterInterface> Store<'sch, 'req, Adapter> {
                 let schema = record.schema;
                 self.attach_belongs_to(slice::from_mut(&mut record))?;
                 let id = record.require_id()?.clone();
-                record.refresh_with(|row| {
-                    if row.is_empty() {
-                        self.table(schema)?.find(id, parameters)
-                    } else {
-                        self.table(schema)?.update(id, row, parameters)
-                    }
+                let before = self.snapshot(schema, &id)?;
+                record.refresh_with(|row| match schema.version() {
+                    Some(version) => self.update_versioned(schema, id, version, row, parameters),
+                    None if row.is_empty() => self.table(schema)?.find(id, parameters),
+                    None => self.table(schema)?.update(id, row, parameters),
                 })?;
                 self.attach_has_one_many(slice::from_ref(&record), true)?;
+                self.publish(
+                    schema,
+                    record.require_id()?.clone(),
+                    Change::Updated,
+                    before,
+                )?;
                 let included = self.loader().load_for_record(&mut record, parameters)?;
 
                 Ok(Composite {
@@ -153,9 +326,166 @@ impl<'sch: 'req, 'req, Adapter: AdapterInterface> Store<'sch, 'req, Adapter> {
             .map_err(error_mapper::fk_violation_to_missing_reference)
     }
 
+    /// Creates `record` under the id it carries, or replaces the record already holding that id,
+    /// returning it with whether it was created. A replaced record keeps nothing `record` omits:
+    /// its other columns, foreign keys among them, fall back to their defaults, so to-one linkage
+    /// it omits is cleared. Its linkage is attached as `create_record` or `update_record` would,
+    /// and a versioned record that exists is replaced against its version.
+    pub fn upsert_record(
+        &self,
+        record: Record<'sch>,
+        parameters: &QueryParameters<'sch, 'req>,
+    ) -> Result<(CompositeRecord<'sch>, bool), Error> {
+        self.upsert_record_with(record, parameters, |_, _| Ok(()))
+    }
+
+    /// `upsert_record`, first handing `prepare` the record and whether one is already held under
+    /// its id, so a caller can run its create or update hooks on it inside the same transaction.
+    pub fn upsert_record_with<E: From<Error>>(
+        &self,
+        mut record: Record<'sch>,
+        parameters: &QueryParameters<'sch, 'req>,
+        prepare: impl FnOnce(&mut Record<'sch>, bool) -> Result<(), E>,
+    ) -> Result<(CompositeRecord<'sch>, bool), E> {
+        self.connection.transaction_with(|| {
+            let exists = self.contains_record(record.schema, record.require_id()?)?;
+            prepare(&mut record, exists)?;
+            let upserted = self
+                .replace_record(record, exists, parameters)
+                .map_err(error_mapper::fk_violation_to_missing_reference)?;
+
+            Ok((upserted, !exists))
+        })
+    }
+
+    /// Writes `record` over the row under its id, which is held as `exists` says.
+    fn replace_record(
+        &self,
+        mut record: Record<'sch>,
+        exists: bool,
+        parameters: &QueryParameters<'sch, 'req>,
+    ) -> Result<CompositeRecord<'sch>, Error> {
+        let schema = record.schema;
+        let primary_key = schema.primary_key().name;
+        let id = record.require_id()?.clone();
+        let before = if exists {
+            self.snapshot(schema, &id)?
+        } else {
+            None
+        };
+
+        self.attach_belongs_to(slice::from_mut(&mut record))?;
+        record.refresh_with(|mut row| {
+            row.insert(primary_key, id.clone().into());
+            if let Some(version) = schema.version() {
+                let bumped = if exists {
+                    let expected = match row.get(version) {
+                        Some(&Attribute::Integer(expected)) => Some(expected),
+                        _ => None,
+                    };
+                    self.bump_version(schema, id.clone(), expected)?
+                } else {
+                    Some(1)
+                };
+                row.extend(bumped.map(|bumped| (version, Attribute::Integer(bumped))));
+            }
+            self.table(schema)?.upsert(row, parameters)
+        })?;
+        self.attach_has_one_many(slice::from_ref(&record), exists)?;
+        let change = if exists {
+            Change::Updated
+        } else {
+            Change::Created
+        };
+        self.publish(schema, id, change, before)?;
+        let included = self.loader().load_for_record(&mut record, parameters)?;
+
+        Ok(Composite {
+            content: record,
+            included,
+        })
+    }
+
+    /// Updates a versioned record's row only while it is still at the version `row` was made
+    /// against, bumping that version in the same statement.
+    fn update_versioned(
+        &self,
+        schema: &'sch Schema<'sch>,
+        id: Identifier,
+        version: &'sch str,
+        mut row: Row<'sch>,
+        parameters: &QueryParameters<'sch, '_>,
+    ) -> Result<Row<'sch>, Error> {
+        let Some(&Attribute::Integer(expected)) = row.get(version) else {
+            return Err(Error::MissingLockVersion {
+                schema: schema.name().to_string(),
+                attribute: version.to_string(),
+            });
+        };
+        row.insert(version, Attribute::Integer(expected + 1));
+
+        let table = self.table(schema)?;
+        let current = QueryParameters {
+            fields: parameters.fields.clone(),
+            filter: Some(FilterParameters::from([
+                (
+                    schema.primary_key().name,
+                    vec![FilterValue::Equal(id.clone().into())],
+                ),
+                (
+                    version,
+                    vec![FilterValue::Equal(Attribute::Integer(expected))],
+                ),
+            ])),
+            ..QueryParameters::new(schema)
+        };
+        if let Some(updated) = table.update_batch(row, &current)?.into_iter().next() {
+            return Ok(updated);
+        }
+
+        match table.find(id, &QueryParameters::new(schema))?.get(version) {
+            Some(&Attribute::Integer(actual)) => Err(Error::LockVersionMismatch {
+                schema: schema.name().to_string(),
+                expected,
+                actual,
+            }),
+            _ => Err(Error::InvalidAttributeAccess {
+                schema: schema.name().to_string(),
+                attribute: version.to_string(),
+            }),
+        }
+    }
+
+    /// Moves a versioned record on from the version `expected` a change to its linkage was made
+    /// against, failing as `update_record` would when it names none or the record has moved on, and
+    /// yields the version it moved to. An unversioned record is left as it is.
+    pub fn bump_version(
+        &self,
+        schema: &'sch Schema<'sch>,
+        id: Identifier,
+        expected: Option<i64>,
+    ) -> Result<Option<i64>, Error> {
+        let Some(version) = schema.version() else {
+            return Ok(None);
+        };
+        let row = Row::from_iter(expected.map(|expected| (version, Attribute::Integer(expected))));
+        let updated =
+            self.update_versioned(schema, id, version, row, &QueryParameters::new(schema))?;
+        match updated.get(version) {
+            Some(&Attribute::Integer(bumped)) => Ok(Some(bumped)),
+            _ => Err(Error::InvalidAttributeAccess {
+                schema: schema.name().to_string(),
+                attribute: version.to_string(),
+            }),
+        }
+    }
+
     pub fn delete_record(&self, schema: &'sch Schema<'sch>, id: Identifier) -> Result<(), Error> {
-        self.connection
-            .transaction(|| self.table(schema)?.delete(id))
+        self.connection.transaction(|| {
+            let before = self.snapshot(schema, &id)?;
+            self.table(schema)?.delete(id.clone())?;
+            self.publish(schema, id, Change::Deleted, before)
+        })
     }
 
     pub fn create_collection(
@@ -185,10 +515,16 @@ impl<'sch: 'req, 'req, Adapter: AdapterInterface> Store<'sch, 'req, Adapter> {
                         if let Some(id) = id {
                             row.insert(schema.primary_key().name, id.into());
                         }
+                        if let Some(version) = schema.version() {
+                            row.insert(version, Attribute::Integer(1));
+                        }
                     }
                     self.table(schema)?.insert_batch(rows, parameters)
                 })?;
                 self.attach_has_one_many(&records, false)?;
+                for record in &records {
+                    self.publish(schema, record.require_id()?.clone(), Change::Created, None)?;
+                }
                 let included = self
                     .loader()
                     .load_for_collection(&mut records, parameters)?;
@@ -201,16 +537,39 @@ impl<'sch: 'req, 'req, Adapter: AdapterInterface> Store<'sch, 'req, Adapter> {
             .map_err(error_mapper::fk_violation_to_missing_reference)
     }
 
+    /// Loads the resources `parameters` include for `records` already read or written, as a
+    /// collection's own fetch would.
+    pub fn load_included(
+        &self,
+        records: &mut [Record<'sch>],
+        parameters: &QueryParameters<'sch, 'req>,
+    ) -> Result<Vec<Record<'sch>>, Error> {
+        self.loader().load_for_collection(records, parameters)
+    }
+
     pub fn update_collection(
         &self,
         patch: RecordPatch<'sch>,
         parameters: &QueryParameters<'sch, 'req>,
     ) -> Result<CompositeCollection<'sch>, Error> {
         let schema = patch.schema;
+        if schema.version().is_some() {
+            return Err(Error::VersionedBulkUpdate {
+                schema: schema.name().to_string(),
+            });
+        }
+
         let mut patch = Record::from(patch);
         self.connection
             .transaction(|| {
                 self.attach_belongs_to(slice::from_mut(&mut patch))?;
+                let mut before: HashMap<_, _> = if schema.audited() {
+                    self.snapshot_collection(schema, parameters)?
+                        .into_iter()
+                        .collect()
+                } else {
+                    HashMap::new()
+                };
                 let row = patch.take_row();
                 let mut records = self
                     .table(schema)?
@@ -222,6 +581,11 @@ impl<'sch: 'req, 'req, Adapter: AdapterInterface> Store<'sch, 'req, Adapter> {
                     })
                     .collect::<Result<Vec<_>, _>>()?;
                 self.attach_has_one_many(&records, true)?;
+                for record in &records {
+                    let id = record.require_id()?.clone();
+                    let before = before.remove(&id).flatten();
+                    self.publish(schema, id, Change::Updated, before)?;
+                }
                 let included = self
                     .loader()
                     .load_for_collection(&mut records, parameters)?;
@@ -234,14 +598,21 @@ impl<'sch: 'req, 'req, Adapter: AdapterInterface> Store<'sch, 'req, Adapter> {
             .map_err(error_mapper::fk_violation_to_missing_reference)
     }
 
+    /// Deletes the records matched by `parameters`, reporting how many were removed.
     pub fn delete_collection(
         &self,
         schema: &'sch Schema<'sch>,
         parameters: &QueryParameters<'sch, 'req>,
-    ) -> Result<(), Error> {
-        self.connection
-            .transaction(|| self.table(schema)?.delete_batch(parameters))
-            .and(Ok(()))
+    ) -> Result<usize, Error> {
+        let scope = parameters.scope_of();
+        self.connection.transaction(|| {
+            let before = self.snapshot_collection(schema, &scope)?;
+            let deleted = self.table(schema)?.delete_batch(&scope)?;
+            for (id, before) in before {
+                self.publish(schema, id, Change::Deleted, before)?;
+            }
+            Ok(deleted)
+        })
     }
 
     /// Fetches the full records targeted by the already-loaded `record`'s `relationship`, scoped to
@@ -253,6 +624,56 @@ impl<'sch: 'req, 'req, Adapter: AdapterInterface> Store<'sch, 'req, Adapter> {
         relationship: &'req str,
         mut parameters: QueryParameters<'sch, 'req>,
     ) -> Result<CompositeCollection<'sch>, Error> {
+        match self.scope_to_related(record, relationship, &mut parameters)? {
+            Some(related_schema) => self.fetch_collection(related_schema, &parameters),
+            None => Ok(Composite {
+                content: Default::default(),
+                included: Default::default(),
+            }),
+        }
+    }
+
+    /// Counts the records `relationship` of the already-loaded `record` targets among those
+    /// `parameters` filters and searches, regardless of its paging, as a paged related collection's
+    /// total. Errors like `fetch_related_collection`.
+    pub fn count_related_collection(
+        &self,
+        record: &Record<'sch>,
+        relationship: &'req str,
+        parameters: &QueryParameters<'sch, 'req>,
+    ) -> Result<u64, Error> {
+        let mut parameters = parameters.clone();
+        let Some(related_schema) = self.scope_to_related(record, relationship, &mut parameters)?
+        else {
+            return Ok(0);
+        };
+
+        let primary_key = related_schema.primary_key();
+        let count = AggregateParameters {
+            aggregates: vec![Aggregate {
+                function: AggregateFunction::Count,
+                column: primary_key.name,
+                kind: primary_key.kind.into(),
+            }],
+            group: Vec::new(),
+        };
+
+        let rows = self.aggregate_collection(related_schema, &count, &parameters)?;
+        match rows.first().and_then(|row| row.values.first()) {
+            Some(Attribute::Integer(count)) => Ok(count.unsigned_abs()),
+            _ => Ok(0),
+        }
+    }
+
+    /// Narrows `parameters`, parsed against the schema `relationship` targets, to the records
+    /// related to `record`, returning that schema; `None` when the relationship is unset, so no
+    /// record can be related.
+    fn scope_to_related(
+        &self,
+        record: &Record<'sch>,
+        relationship: &'req str,
+        parameters: &mut QueryParameters<'sch, 'req>,
+    ) -> Result<Option<&'sch Schema<'sch>>, Error> {
         let schema = record.schema();
         let descriptor =
             schema
@@ -275,11 +696,7 @@ impl<'sch: 'req, 'req, Adapter: AdapterInterface> Store<'sch, 'req, Adapter> {
         }
 
         match record.require_owned(keys.own)? {
-            Attribute::Null => Ok(Composite {
-                content: Default::default(),
-                included: Default::default(),
-            }),
-
+            Attribute::Null => Ok(None),
             value => {
                 parameters
                     .filter
@@ -288,7 +705,7 @@ impl<'sch: 'req, 'req, Adapter: AdapterInterface> Store<'sch, 'req, Adapter> {
                     .or_default()
                     .push(FilterValue::Equal(value));
 
-                self.fetch_collection(related_schema, &parameters)
+                Ok(Some(related_schema))
             }
         }
     }
@@ -339,7 +756,7 @@ impl<'sch: 'req, 'req, Adapter: AdapterInterface> Store<'sch, 'req, Adapter> {
     /// for those.
     pub fn peek_related_record(
         &self,
-        record: &'req Record<'sch>,
+        record: &Record<'sch>,
         relationship: &'req str,
     ) -> Result<Option<Identifier>, Error> {
         let descriptor = record.schema.relationship(relationship).ok_or_else(|| {
@@ -382,9 +799,30 @@ impl<'sch: 'req, 'req, Adapter: AdapterInterface> Store<'sch, 'req, Adapter> {
             .manager
             .registry()
             .schema(descriptor.related.resource)?;
+
+        self.peek_related_collection_with(
+            record,
+            relationship,
+            QueryParameters::new(related_schema),
+        )
+    }
+
+    /// Resolves `relationship` of the already-loaded `record` to the identifiers of the records it
+    /// targets among those `parameters`, parsed against the related schema, filters and searches,
+    /// in its order and on its page. Only the ids are requested: nothing is included, counted or
+    /// excerpted.
+    pub fn peek_related_collection_with(
+        &self,
+        record: &Record<'sch>,
+        relationship: &'req str,
+        parameters: QueryParameters<'sch, 'req>,
+    ) -> Result<Vec<Identifier>, Error> {
         let parameters = QueryParameters {
-            fields: [(related_schema.name(), Default::default())].into(),
-            ..QueryParameters::new(related_schema)
+            fields: [(parameters.schema.name(), Default::default())].into(),
+            include: Default::default(),
+            counts: Default::default(),
+            excerpts: Default::default(),
+            ..parameters
         };
 
         self.fetch_related_collection(record, relationship, parameters)?
@@ -403,6 +841,19 @@ impl<'sch: 'req, 'req, Adapter: AdapterInterface> Store<'sch, 'req, Adapter> {
         record: Record<'sch>,
         relationship_name: &'req str,
         related_id: Identifier,
+    ) -> Result<Option<Identifier>, Error> {
+        self.connection.transaction(|| {
+            self.publishing_linkage(record, relationship_name, |record| {
+                self.write_link_record(record, relationship_name, related_id)
+            })
+        })
+    }
+
+    fn write_link_record(
+        &self,
+        record: Record<'sch>,
+        relationship_name: &'req str,
+        related_id: Identifier,
     ) -> Result<Option<Identifier>, Error> {
         self.connection.transaction(|| {
             let schema = record.schema();
@@ -467,6 +918,19 @@ impl<'sch: 'req, 'req, Adapter: AdapterInterface> Store<'sch, 'req, Adapter> {
         record: Record<'sch>,
         relationship_name: &'req str,
         related_id: Identifier,
+    ) -> Result<Option<Identifier>, Error> {
+        self.connection.transaction(|| {
+            self.publishing_linkage(record, relationship_name, |record| {
+                self.write_relink_record(record, relationship_name, related_id)
+            })
+        })
+    }
+
+    fn write_relink_record(
+        &self,
+        record: Record<'sch>,
+        relationship_name: &'req str,
+        related_id: Identifier,
     ) -> Result<Option<Identifier>, Error> {
         self.connection.transaction(|| {
             let schema = record.schema();
@@ -529,6 +993,18 @@ impl<'sch: 'req, 'req, Adapter: AdapterInterface> Store<'sch, 'req, Adapter> {
         &self,
         record: Record<'sch>,
         relationship_name: &'req str,
+    ) -> Result<Option<Identifier>, Error> {
+        self.connection.transaction(|| {
+            self.publishing_linkage(record, relationship_name, |record| {
+                self.write_unlink_record(record, relationship_name)
+            })
+        })
+    }
+
+    fn write_unlink_record(
+        &self,
+        record: Record<'sch>,
+        relationship_name: &'req str,
     ) -> Result<Option<Identifier>, Error> {
         self.connection.transaction(|| {
             let schema = record.schema();
@@ -578,6 +1054,19 @@ impl<'sch: 'req, 'req, Adapter: AdapterInterface> Store<'sch, 'req, Adapter> {
         record: Record<'sch>,
         relationship_name: &'req str,
         related_ids: Vec<Identifier>,
+    ) -> Result<Vec<Identifier>, Error> {
+        self.connection.transaction(|| {
+            self.publishing_linkage(record, relationship_name, |record| {
+                self.write_link_collection(record, relationship_name, related_ids)
+            })
+        })
+    }
+
+    fn write_link_collection(
+        &self,
+        record: Record<'sch>,
+        relationship_name: &'req str,
+        related_ids: Vec<Identifier>,
     ) -> Result<Vec<Identifier>, Error> {
         self.connection.transaction(|| {
             let schema = record.schema();
@@ -612,6 +1101,19 @@ impl<'sch: 'req, 'req, Adapter: AdapterInterface> Store<'sch, 'req, Adapter> {
         record: Record<'sch>,
         relationship_name: &'req str,
         related_ids: Vec<Identifier>,
+    ) -> Result<Vec<Identifier>, Error> {
+        self.connection.transaction(|| {
+            self.publishing_linkage(record, relationship_name, |record| {
+                self.write_relink_collection(record, relationship_name, related_ids)
+            })
+        })
+    }
+
+    fn write_relink_collection(
+        &self,
+        record: Record<'sch>,
+        relationship_name: &'req str,
+        related_ids: Vec<Identifier>,
     ) -> Result<Vec<Identifier>, Error> {
         self.connection.transaction(|| {
             let schema = record.schema();
@@ -659,6 +1161,19 @@ impl<'sch: 'req, 'req, Adapter: AdapterInterface> Store<'sch, 'req, Adapter> {
         record: Record<'sch>,
         relationship_name: &'req str,
         related_ids: Vec<Identifier>,
+    ) -> Result<Vec<Identifier>, Error> {
+        self.connection.transaction(|| {
+            self.publishing_linkage(record, relationship_name, |record| {
+                self.write_unlink_collection(record, relationship_name, related_ids)
+            })
+        })
+    }
+
+    fn write_unlink_collection(
+        &self,
+        record: Record<'sch>,
+        relationship_name: &'req str,
+        related_ids: Vec<Identifier>,
     ) -> Result<Vec<Identifier>, Error> {
         self.connection.transaction(|| {
             let schema = record.schema();
@@ -704,23 +1219,215 @@ impl<'sch: 'req, 'req, Adapter: AdapterInterface> Store<'sch, 'req, Adapter> {
         })
     }
 
-    fn table(&self, schema: &'sch Schema<'sch>) -> Result<Adapter::Table<'sch, 'req>, Error> {
-        self.manager.table(schema.name(), self.connection)
-    }
+    /// Runs `write`, a change to `relationship` of `record` answering the linkage it leaves, and
+    /// publishes the members the relationship gained and lost. The linkage before is read off the
+    /// stored record; a record without an id publishes nothing.
+    fn publishing_linkage<T: Clone + IntoIterator<Item = Identifier>>(
+        &self,
+        record: Record<'sch>,
+        relationship: &'req str,
+        write: impl FnOnce(Record<'sch>) -> Result<T, Error>,
+    ) -> Result<T, Error> {
+        let Some(id) = record.get_id().cloned() else {
+            return write(record);
+        };
+        let schema = record.schema;
+        let stored = self
+            .table(schema)?
+            .find(id.clone(), &QueryParameters::new(schema))?;
+        let stored = Record::try_from_row(schema, stored)?;
+        let before = match schema
+            .relationship(relationship)
+            .map(|descriptor| descriptor.kind)
+        {
+            Some(RelationshipKind::HasMany) => {
+                self.peek_related_collection(&stored, relationship)?
+            }
+            _ => Vec::from_iter(self.peek_related_record(&stored, relationship)?),
+        };
 
-    fn loader(&self) -> DataLoader<'sch, 'req, Adapter> {
-        DataLoader::new(self.manager, self.connection)
-    }
+        let linkage = write(record)?;
+        let after = Vec::from_iter(linkage.clone());
+        let unlinked: Vec<_> = before
+            .iter()
+            .filter(|member| !after.contains(member))
+            .cloned()
+            .collect();
+        let linked: Vec<_> = after
+            .into_iter()
+            .filter(|member| !before.contains(member))
+            .collect();
+        if !unlinked.is_empty() {
+            let relationship = relationship.to_string();
+            self.publish(
+                schema,
+                id.clone(),
+                Change::Unlinked {
+                    relationship,
+                    related: unlinked,
+                },
+                None,
+            )?;
+        }
+        if !linked.is_empty() {
+            let relationship = relationship.to_string();
+            self.publish(
+                schema,
+                id,
+                Change::Linked {
+                    relationship,
+                    related: linked,
+                },
+                None,
+            )?;
+        }
 
-    /// Populates each record's `foreign_keys` with whatever `belongs_to` relationships
-    /// are specified.
-    /// This prepares the records for inserting or updating and must be called prior to any
-    /// of these operations.
-    fn attach_belongs_to(&self, records: &mut [Record<'sch>]) -> Result<(), Error> {
-        let mut required_queries = HashMap::new();
+        Ok(linkage)
+    }
 
-        for record in records.iter_mut() {
-            let schema = record.schema();
+    /// Publishes `change` to the record of `schema` at `id`, for delivery once it commits. An
+    /// audited schema's change is first entered in the audit log, `before` being the record's row
+    /// ahead of the write.
+    fn publish(
+        &self,
+        schema: &'sch Schema<'sch>,
+        id: Identifier,
+        change: Change,
+        before: Option<Row<'sch>>,
+    ) -> Result<(), Error> {
+        if schema.audited() {
+            let after = match change {
+                Change::Created | Change::Updated => Some(
+                    self.table(schema)?
+                        .find(id.clone(), &QueryParameters::new(schema))?,
+                ),
+                _ => None,
+            };
+            let entry = audit::entry(
+                schema,
+                &id,
+                &change,
+                before,
+                after,
+                self.actor,
+                chrono::Utc::now(),
+            );
+            let audit_log = self.manager.registry().schema(AUDIT_LOG)?;
+            self.table(audit_log)?
+                .insert(entry, &QueryParameters::new(audit_log))?;
+        }
+
+        self.connection.changes().publish(ChangeEvent {
+            resource: schema.name().to_string(),
+            id,
+            change,
+        });
+        Ok(())
+    }
+
+    /// The stored row of the record of `schema` at `id` ahead of a write to it, read only when the
+    /// schema is audited.
+    fn snapshot(
+        &self,
+        schema: &'sch Schema<'sch>,
+        id: &Identifier,
+    ) -> Result<Option<Row<'sch>>, Error> {
+        if !schema.audited() {
+            return Ok(None);
+        }
+        self.table(schema)?
+            .find(id.clone(), &QueryParameters::new(schema))
+            .map(Some)
+    }
+
+    /// The ids of the records `parameters` filters ahead of a write to them, each with its stored
+    /// row when the schema is audited.
+    fn snapshot_collection(
+        &self,
+        schema: &'sch Schema<'sch>,
+        parameters: &QueryParameters<'sch, 'req>,
+    ) -> Result<Vec<(Identifier, Option<Row<'sch>>)>, Error> {
+        let mut scope = parameters.scope_of();
+        if !schema.audited() {
+            scope.fields = [(schema.name(), [schema.primary_key().name].into())].into();
+        }
+
+        self.table(schema)?
+            .query(&scope)?
+            .into_iter()
+            .map(|row| {
+                let id = Record::try_from_row(schema, row.clone())?.pluck_id()?;
+                Ok((id, schema.audited().then_some(row)))
+            })
+            .collect()
+    }
+
+    /// The ids of the records `parameters` filters that writing `patch` would change, ahead of the
+    /// write, each with its stored row when the schema is audited.
+    fn snapshot_rekeyed(
+        &self,
+        schema: &'sch Schema<'sch>,
+        patch: &Row<'sch>,
+        parameters: &QueryParameters<'sch, 'req>,
+    ) -> Result<Vec<(Identifier, Option<Row<'sch>>)>, Error> {
+        let mut scope = QueryParameters {
+            counts: Default::default(),
+            ..parameters.clone()
+        };
+        if !schema.audited() {
+            let columns = std::iter::once(schema.primary_key().name).chain(patch.keys().copied());
+            scope.fields = [(schema.name(), columns.collect())].into();
+        }
+
+        self.table(schema)?
+            .query(&scope)?
+            .into_iter()
+            .filter(|row| {
+                patch
+                    .iter()
+                    .any(|(column, value)| row.get(column) != Some(value))
+            })
+            .map(|row| {
+                let id = Record::try_from_row(schema, row.clone())?.pluck_id()?;
+                Ok((id, schema.audited().then_some(row)))
+            })
+            .collect()
+    }
+
+    /// Writes `patch` to the records of `schema` that `parameters` filters, re-keying them to or
+    /// from a parent, and publishes each one it changes as updated. Returns the rows written.
+    fn rekey(
+        &self,
+        schema: &'sch Schema<'sch>,
+        patch: Row<'sch>,
+        parameters: &QueryParameters<'sch, 'req>,
+    ) -> Result<Vec<Row<'sch>>, Error> {
+        let rekeyed = self.snapshot_rekeyed(schema, &patch, parameters)?;
+        let written = self.table(schema)?.update_batch(patch, parameters)?;
+        for (id, before) in rekeyed {
+            self.publish(schema, id, Change::Updated, before)?;
+        }
+
+        Ok(written)
+    }
+
+    fn table(&self, schema: &'sch Schema<'sch>) -> Result<Adapter::Table<'sch, 'req>, Error> {
+        self.manager.table(schema.name(), self.connection)
+    }
+
+    fn loader(&self) -> DataLoader<'sch, 'req, Adapter> {
+        DataLoader::new(self.manager, self.connection)
+    }
+
+    /// Populates each record's `foreign_keys` with whatever `belongs_to` relationships
+    /// are specified.
+    /// This prepares the records for inserting or updating and must be called prior to any
+    /// of these operations.
+    fn attach_belongs_to(&self, records: &mut [Record<'sch>]) -> Result<(), Error> {
+        let mut required_queries = HashMap::new();
+
+        for record in records.iter_mut() {
+            let schema = record.schema();
             for (&name, linkage) in &record.relationships {
                 let descriptor =
                     schema
@@ -887,10 +1594,10 @@ impl<'sch: 'req, 'req, Adapter: AdapterInterface> Store<'sch, 'req, Adapter> {
         if replace {
             for (schema, columns) in full_detachments {
                 let schema = self.manager.registry().schema(schema)?;
-                let table = self.table(schema)?;
 
                 for (column, values) in columns {
-                    table.update_batch(
+                    self.rekey(
+                        schema,
                         Row::from([(column, Attribute::Null)]),
                         &QueryParameters {
                             filter: Some(
@@ -907,7 +1614,6 @@ impl<'sch: 'req, 'req, Adapter: AdapterInterface> Store<'sch, 'req, Adapter> {
 
         for (name, patches) in queries {
             let schema = self.manager.registry().schema(name)?;
-            let table = self.table(schema)?;
 
             if replace {
                 let complement = patches.iter().fold(
@@ -924,7 +1630,8 @@ impl<'sch: 'req, 'req, Adapter: AdapterInterface> Store<'sch, 'req, Adapter> {
                 );
 
                 for ((column, value), ids) in complement {
-                    table.update_batch(
+                    self.rekey(
+                        schema,
                         Row::from([(column, Attribute::Null)]),
                         &QueryParameters {
                             filter: Some(FilterParameters::from([
@@ -938,8 +1645,9 @@ impl<'sch: 'req, 'req, Adapter: AdapterInterface> Store<'sch, 'req, Adapter> {
             }
 
             for (patch, ids) in &patches {
-                let attached = table
-                    .update_batch(
+                let attached = self
+                    .rekey(
+                        schema,
                         Row::from_iter(patch.clone()),
                         &QueryParameters {
                             filter: Some(FilterParameters::from([(
@@ -978,22 +1686,31 @@ mod tests {
     use crate::database::adapters::SqliteAdapter;
     use crate::database::adapters::sqlite::{Connection, Pool};
     use crate::database::attributes::{Attribute, Attributes, Identifier, Row};
+    use crate::database::audit::audit_log;
+    use crate::database::connection::Connection as ConnectionInterface;
     use crate::database::connection_manager::ConnectionManager;
     use crate::database::error::{ConstraintKind, Error};
-    use crate::database::query_parameters::{FilterParameters, FilterValue, QueryParameters};
+    use crate::database::events::{Change, ChangeEvent, ChangeSubscriber};
+    use crate::database::query_parameters::{
+        AggregateRow, FilterParameters, FilterValue, QueryParameters,
+    };
     use crate::database::record::{Builder, Record, RecordPatch};
     use crate::database::registry::Registry;
-    use crate::database::relationships::{Relationship, Relationships};
+    use crate::database::relationships::{LocalLinkage, Relationship, Relationships};
     use crate::database::schema::{AttributeType, Related, Schema, SchemaBuilder};
     use crate::database::table::Table;
     use crate::http_wrappers::Uri;
+    use indexmap::IndexSet;
+    use serde_json::{Value, json};
     use std::collections::HashMap;
     use std::error::Error as StdError;
+    use std::sync::{Arc, Mutex};
     use test_log::test;
 
     fn users_schema() -> SchemaBuilder<'static> {
         SchemaBuilder::table("users")
             .attribute("name", AttributeType::Text)
+            .audited()
             .has_many(
                 "posts",
                 Related::to("posts")
@@ -1045,6 +1762,14 @@ mod tests {
             )
     }
 
+    fn notes_schema() -> SchemaBuilder<'static> {
+        SchemaBuilder::table("notes")
+            .attribute("body", AttributeType::Text)
+            .attribute("revision", AttributeType::Integer)
+            .versioned("revision")
+            .audited()
+    }
+
     fn members_schema() -> SchemaBuilder<'static> {
         SchemaBuilder::table("members")
             .attribute("handle", AttributeType::Text)
@@ -1071,16 +1796,52 @@ mod tests {
     where
         F: FnOnce(&ConnectionManager<SqliteAdapter>) -> Result<(), Box<dyn StdError>>,
     {
-        let manager: ConnectionManager<SqliteAdapter> = ConnectionManager::new(
+        with_subscribed_manager(None, func)
+    }
+
+    /// A subscriber keeping the events of each committed transaction, in delivery order.
+    #[derive(Default)]
+    struct Recorder(Mutex<Vec<Vec<ChangeEvent>>>);
+
+    impl ChangeSubscriber for Recorder {
+        fn committed(&self, events: &[ChangeEvent]) {
+            if let Ok(mut delivered) = self.0.lock() {
+                delivered.push(events.to_vec());
+            }
+        }
+    }
+
+    impl Recorder {
+        fn delivered(&self) -> Vec<Vec<ChangeEvent>> {
+            self.0
+                .lock()
+                .map(|delivered| delivered.clone())
+                .unwrap_or_default()
+        }
+    }
+
+    fn with_subscribed_manager<F>(
+        subscriber: Option<Arc<Recorder>>,
+        func: F,
+    ) -> Result<(), Box<dyn StdError>>
+    where
+        F: FnOnce(&ConnectionManager<SqliteAdapter>) -> Result<(), Box<dyn StdError>>,
+    {
+        let mut manager: ConnectionManager<SqliteAdapter> = ConnectionManager::new(
             Registry::try_new([
                 users_schema(),
                 posts_schema(),
                 profiles_schema(),
                 orgs_schema(),
                 members_schema(),
+                notes_schema(),
+                audit_log(),
             ])?,
             Pool::memory()?,
         );
+        if let Some(subscriber) = subscriber {
+            manager = manager.with_subscriber(subscriber);
+        }
 
         manager.acquire()?.execute_batch(
             "
@@ -1114,6 +1875,22 @@ mod tests {
                 org_code TEXT UNIQUE,
                 FOREIGN KEY(org_code) REFERENCES orgs(code)
             );
+
+            CREATE TABLE notes (
+                id INTEGER PRIMARY KEY,
+                body TEXT NOT NULL,
+                revision INTEGER NOT NULL
+            );
+
+            CREATE TABLE audit_log (
+                id INTEGER PRIMARY KEY,
+                resource TEXT NOT NULL,
+                record_id TEXT NOT NULL,
+                operation TEXT NOT NULL,
+                changes TEXT NOT NULL,
+                actor TEXT,
+                recorded_at TEXT NOT NULL
+            );
             ",
         )?;
 
@@ -1353,6 +2130,49 @@ mod tests {
         })
     }
 
+    #[test]
+    fn test_fetch_collection_scoped_by_relationship_filter() -> Result<(), Box<dyn StdError>> {
+        with_manager(|manager| {
+            let connection = manager.acquire()?;
+            seed_user(manager, &connection, 1, "alice")?;
+            seed_user(manager, &connection, 2, "bob")?;
+            seed_user(manager, &connection, 3, "carol")?;
+            seed_post(manager, &connection, 1, 1, "alice-one")?;
+            seed_post(manager, &connection, 2, 1, "alice-two")?;
+            seed_post(manager, &connection, 3, 2, "bob-one")?;
+            seed_org(manager, &connection, 1, "acme")?;
+            seed_org(manager, &connection, 2, "initech")?;
+            seed_member(manager, &connection, 7, "wile", "acme")?;
+
+            let store = Store::new(manager, &connection);
+            let fetch = |resource: &str, query: &str| -> Result<Vec<i64>, Box<dyn StdError>> {
+                let uri: Uri = format!("/{resource}?{query}").parse()?;
+                let parameters =
+                    QueryParameters::parse(&uri, schema(manager, resource), manager.registry())?;
+                Ok(store
+                    .fetch_collection(schema(manager, resource), &parameters)?
+                    .content
+                    .iter()
+                    .map(|record| record.require_id()?.to_i64())
+                    .collect::<Result<Vec<_>, Error>>()?)
+            };
+
+            assert_eq!(fetch("posts", "filter[author]=in:2,3")?, vec![3]);
+            assert_eq!(fetch("users", "filter[posts]=eq:2")?, vec![1]);
+            assert_eq!(fetch("users", "filter[posts]=nin:1,3")?, vec![3]);
+            assert_eq!(fetch("users", "filter[id]=in:1,3")?, vec![1, 3]);
+            assert_eq!(fetch("orgs", "filter[member]=eq:7")?, vec![1]);
+            assert_eq!(fetch("orgs", "filter[member]=neq:7")?, vec![2]);
+            assert_eq!(fetch("users", "filter[posts]=exists")?, vec![1, 2]);
+            assert_eq!(fetch("users", "filter[posts]=none")?, vec![3]);
+            assert_eq!(fetch("users", "filter[posts]=count-gt:1")?, vec![1]);
+            assert_eq!(fetch("users", "filter[posts]=count-lt:2")?, vec![2, 3]);
+            assert_eq!(fetch("orgs", "filter[member]=none")?, vec![2]);
+
+            Ok(())
+        })
+    }
+
     #[test]
     fn test_fetch_collection_loads_includes() -> Result<(), Box<dyn StdError>> {
         with_manager(|manager| {
@@ -1391,7 +2211,8 @@ mod tests {
 
             let store = Store::new(manager, &connection);
             let parameters = QueryParameters::new(schema(manager, "posts"));
-            let created = store.create_record(new_post(manager, "Hello", 1), &parameters)?;
+            let created =
+                store.create_record(new_post(manager, "Hello", 1), Vec::new(), &parameters)?;
 
             assert_eq!(created.content.require("title")?.as_string()?, "Hello");
             assert_eq!(
@@ -1426,7 +2247,7 @@ mod tests {
             let user = Record::from((schema(manager, "users"), attributes, relationships));
 
             let parameters = QueryParameters::new(schema(manager, "users"));
-            let created = store.create_record(user, &parameters)?;
+            let created = store.create_record(user, Vec::new(), &parameters)?;
             let new_id = *created.content.require_id()?.as_i64()?;
 
             let posts = manager
@@ -1441,6 +2262,72 @@ mod tests {
         })
     }
 
+    #[test]
+    fn test_create_record_links_included_records_by_lid() -> Result<(), Box<dyn StdError>> {
+        with_manager(|manager| {
+            let connection = manager.acquire()?;
+
+            let store = Store::new(manager, &connection);
+            let mut user = Record::from_attributes(
+                schema(manager, "users"),
+                Attributes::from_iter([("name", Attribute::Text("erin".to_string()))]),
+            );
+            user.lid = Some("erin".to_string());
+            let mut post = Record::from_attributes(
+                schema(manager, "posts"),
+                Attributes::from_iter([("title", Attribute::Text("Linked".to_string()))]),
+            );
+            post.local_linkage = LocalLinkage::from_iter([("author", vec!["erin".to_string()])]);
+
+            let parameters = QueryParameters::new(schema(manager, "posts"));
+            let created = store.create_record(post, vec![user], &parameters)?;
+
+            let [user] = created.included.as_slice() else {
+                panic!("expected the included user alone");
+            };
+            assert_eq!(user.lid.as_deref(), Some("erin"));
+            assert_eq!(
+                created.content.require_related("author")?,
+                &Relationship::BelongsTo(user.require_id()?.clone())
+            );
+
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn test_create_record_unresolved_lid_creates_nothing() -> Result<(), Box<dyn StdError>> {
+        with_manager(|manager| {
+            let connection = manager.acquire()?;
+
+            let store = Store::new(manager, &connection);
+            let mut post = Record::from_attributes(
+                schema(manager, "posts"),
+                Attributes::from_iter([("title", Attribute::Text("Linked".to_string()))]),
+            );
+            post.local_linkage = LocalLinkage::from_iter([("author", vec!["nobody".to_string()])]);
+            let user = Record::from_attributes(
+                schema(manager, "users"),
+                Attributes::from_iter([("name", Attribute::Text("erin".to_string()))]),
+            );
+
+            let parameters = QueryParameters::new(schema(manager, "posts"));
+            let result = store.create_record(post, vec![user], &parameters);
+
+            assert!(matches!(
+                result,
+                Err(Error::UnresolvedLocalIdentifier { ref schema, ref lid })
+                    if schema == "users" && lid == "nobody"
+            ));
+            let users = manager
+                .table("users", &connection)?
+                .query(&QueryParameters::new(schema(manager, "users")))?;
+            assert!(users.is_empty());
+
+            Ok(())
+        })
+    }
+
     #[test]
     fn test_create_record_invalid_belongs_to_is_related_not_found() -> Result<(), Box<dyn StdError>>
     {
@@ -1450,7 +2337,8 @@ mod tests {
             let store = Store::new(manager, &connection);
             // `author` 999 is provided but references no user: a missing reference (404).
             let parameters = QueryParameters::new(schema(manager, "posts"));
-            let result = store.create_record(new_post(manager, "Orphan", 999), &parameters);
+            let result =
+                store.create_record(new_post(manager, "Orphan", 999), Vec::new(), &parameters);
 
             assert!(matches!(result, Err(Error::RelatedRecordNotFound)));
 
@@ -1472,7 +2360,7 @@ mod tests {
                 Attributes::from_iter([("bio", Attribute::Text("no user".to_string()))]),
             );
             let parameters = QueryParameters::new(schema(manager, "profiles"));
-            let result = store.create_record(profile, &parameters);
+            let result = store.create_record(profile, Vec::new(), &parameters);
 
             assert!(matches!(
                 result,
@@ -1499,7 +2387,7 @@ mod tests {
                 Attributes::from_iter([("title", Attribute::Text("no author".to_string()))]),
             );
             let parameters = QueryParameters::new(schema(manager, "posts"));
-            store.create_record(post, &parameters)?;
+            store.create_record(post, Vec::new(), &parameters)?;
 
             let persisted = manager
                 .table("posts", &connection)?
@@ -1526,7 +2414,7 @@ mod tests {
             .with_id(Identifier::Integer(42).into());
 
             let parameters = QueryParameters::new(schema(manager, "users"));
-            let created = store.create_record(user, &parameters)?;
+            let created = store.create_record(user, Vec::new(), &parameters)?;
             assert_eq!(created.content.require_id()?, &Identifier::Integer(42));
 
             let persisted = manager
@@ -1568,6 +2456,157 @@ mod tests {
         })
     }
 
+    fn create_note<'sch>(
+        store: &Store<'sch, '_, SqliteAdapter>,
+        manager: &'sch ConnectionManager<SqliteAdapter>,
+    ) -> Result<Record<'sch>, Error> {
+        let note = Record::from_attributes(
+            schema(manager, "notes"),
+            Attributes::from_iter([("body", Attribute::Text("draft".to_string()))]),
+        );
+        let parameters = QueryParameters::new(schema(manager, "notes"));
+        Ok(store.create_record(note, Vec::new(), &parameters)?.content)
+    }
+
+    fn note_edit<'sch>(
+        manager: &'sch ConnectionManager<SqliteAdapter>,
+        id: &Identifier,
+        revision: Option<i64>,
+    ) -> Record<'sch> {
+        let mut attributes =
+            Attributes::from_iter([("body", Attribute::Text("edited".to_string()))]);
+        if let Some(revision) = revision {
+            attributes.insert("revision", Attribute::Integer(revision));
+        }
+        Record::from_attributes(schema(manager, "notes"), attributes).with_id(id.clone().into())
+    }
+
+    #[test]
+    fn test_update_record_bumps_the_lock_version() -> Result<(), Box<dyn StdError>> {
+        with_manager(|manager| {
+            let connection = manager.acquire()?;
+            let store = Store::new(manager, &connection);
+            let note = create_note(&store, manager)?;
+            assert_eq!(note.require("revision")?, &Attribute::Integer(1));
+
+            let parameters = QueryParameters::new(schema(manager, "notes"));
+            let updated = store
+                .update_record(note_edit(manager, note.require_id()?, Some(1)), &parameters)?;
+
+            assert_eq!(updated.content.require("revision")?, &Attribute::Integer(2));
+            assert_eq!(
+                updated.content.require("body")?,
+                &Attribute::Text("edited".to_string())
+            );
+
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn test_update_record_against_a_stale_version_conflicts() -> Result<(), Box<dyn StdError>> {
+        with_manager(|manager| {
+            let connection = manager.acquire()?;
+            let store = Store::new(manager, &connection);
+            let note = create_note(&store, manager)?;
+            let id = note.require_id()?;
+            let parameters = QueryParameters::new(schema(manager, "notes"));
+            store.update_record(note_edit(manager, id, Some(1)), &parameters)?;
+
+            let result = store.update_record(note_edit(manager, id, Some(1)), &parameters);
+
+            assert_eq!(
+                result.err(),
+                Some(Error::LockVersionMismatch {
+                    schema: "notes".to_string(),
+                    expected: 1,
+                    actual: 2,
+                })
+            );
+
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn test_update_record_without_a_version_is_refused() -> Result<(), Box<dyn StdError>> {
+        with_manager(|manager| {
+            let connection = manager.acquire()?;
+            let store = Store::new(manager, &connection);
+            let note = create_note(&store, manager)?;
+            let parameters = QueryParameters::new(schema(manager, "notes"));
+
+            let result =
+                store.update_record(note_edit(manager, note.require_id()?, None), &parameters);
+
+            assert_eq!(
+                result.err(),
+                Some(Error::MissingLockVersion {
+                    schema: "notes".to_string(),
+                    attribute: "revision".to_string(),
+                })
+            );
+
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn test_bump_version_moves_a_record_on_from_the_matched_version()
+    -> Result<(), Box<dyn StdError>> {
+        with_manager(|manager| {
+            let connection = manager.acquire()?;
+            let store = Store::new(manager, &connection);
+            let note = create_note(&store, manager)?;
+            let id = note.require_id()?;
+            let notes = schema(manager, "notes");
+
+            store.bump_version(notes, id.clone(), Some(1))?;
+            assert_eq!(store.fetch_version(notes, id.clone())?, Some(2));
+            assert_eq!(
+                store.bump_version(notes, id.clone(), Some(1)).err(),
+                Some(Error::LockVersionMismatch {
+                    schema: "notes".to_string(),
+                    expected: 1,
+                    actual: 2,
+                })
+            );
+            assert_eq!(
+                store.bump_version(notes, id.clone(), None).err(),
+                Some(Error::MissingLockVersion {
+                    schema: "notes".to_string(),
+                    attribute: "revision".to_string(),
+                })
+            );
+
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn test_update_collection_of_versioned_records_is_refused() -> Result<(), Box<dyn StdError>> {
+        with_manager(|manager| {
+            let connection = manager.acquire()?;
+            let store = Store::new(manager, &connection);
+            let notes = schema(manager, "notes");
+            let patch = RecordPatch::from_attributes(
+                notes,
+                Attributes::from_iter([("body", Attribute::Text("patched".to_string()))]),
+            );
+
+            let result = store.update_collection(patch, &QueryParameters::new(notes));
+
+            assert_eq!(
+                result.err(),
+                Some(Error::VersionedBulkUpdate {
+                    schema: "notes".to_string(),
+                })
+            );
+
+            Ok(())
+        })
+    }
+
     #[test]
     fn test_update_record_replaces_has_many() -> Result<(), Box<dyn StdError>> {
         with_manager(|manager| {
@@ -1613,27 +2652,194 @@ mod tests {
     }
 
     #[test]
-    fn test_update_record_invalid_belongs_to_is_related_not_found() -> Result<(), Box<dyn StdError>>
-    {
+    fn test_update_record_invalid_belongs_to_is_related_not_found() -> Result<(), Box<dyn StdError>>
+    {
+        with_manager(|manager| {
+            let connection = manager.acquire()?;
+            seed_user(manager, &connection, 1, "alice")?;
+            seed_post(manager, &connection, 1, 1, "before")?;
+
+            let store = Store::new(manager, &connection);
+            let record = Record::from_relationships(
+                schema(manager, "posts"),
+                Relationships::from([(
+                    "author",
+                    Relationship::BelongsTo(Identifier::Integer(999)),
+                )]),
+            )
+            .with_id(Some(Identifier::Integer(1)));
+
+            let parameters = QueryParameters::new(schema(manager, "posts"));
+            let result = store.update_record(record, &parameters);
+
+            assert!(matches!(result, Err(Error::RelatedRecordNotFound)));
+
+            Ok(())
+        })
+    }
+
+    // --- upsert_record -----------------------------------------------------
+
+    #[test]
+    fn test_upsert_record_creates_a_missing_record() -> Result<(), Box<dyn StdError>> {
+        with_manager(|manager| {
+            let connection = manager.acquire()?;
+            seed_user(manager, &connection, 1, "alice")?;
+            let store = Store::new(manager, &connection);
+            let record = Record::from_attributes(
+                schema(manager, "posts"),
+                Attributes::from_iter([("title", Attribute::Text("fresh".to_string()))]),
+            )
+            .with_relationships(Relationships::from([(
+                "author",
+                Relationship::BelongsTo(Identifier::Integer(1)),
+            )]))
+            .with_id(Identifier::Integer(9).into());
+
+            let parameters = QueryParameters::new(schema(manager, "posts"));
+            let (upserted, created) = store.upsert_record(record, &parameters)?;
+
+            assert!(created);
+            assert_eq!(upserted.content.id, Some(Identifier::Integer(9)));
+            let post = manager
+                .table("posts", &connection)?
+                .find(Identifier::Integer(9), &parameters)?;
+            assert_eq!(post["title"], Attribute::Text("fresh".to_string()));
+            assert_eq!(post["author_id"], Attribute::Integer(1));
+
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn test_upsert_record_updates_an_existing_record() -> Result<(), Box<dyn StdError>> {
+        with_manager(|manager| {
+            let connection = manager.acquire()?;
+            seed_user(manager, &connection, 1, "alice")?;
+            seed_user(manager, &connection, 2, "bob")?;
+            seed_post(manager, &connection, 1, 1, "p1")?;
+            seed_post(manager, &connection, 2, 2, "p2")?;
+            let store = Store::new(manager, &connection);
+            let record = Record::from_attributes(
+                schema(manager, "users"),
+                Attributes::from_iter([("name", Attribute::Text("alicia".to_string()))]),
+            )
+            .with_relationships(Relationships::from([(
+                "posts",
+                Relationship::HasMany(vec![Identifier::Integer(2)]),
+            )]))
+            .with_id(Identifier::Integer(1).into());
+
+            let parameters = QueryParameters::new(schema(manager, "users"));
+            let (upserted, created) = store.upsert_record(record, &parameters)?;
+
+            assert!(!created);
+            assert_eq!(
+                upserted.content.require("name")?,
+                &Attribute::Text("alicia".to_string())
+            );
+            let posts: HashMap<Attribute, Row> = manager
+                .table("posts", &connection)?
+                .query(&QueryParameters::new(schema(manager, "posts")))?
+                .into_iter()
+                .map(|row| (row["id"].clone(), row))
+                .collect();
+            assert_eq!(posts[&Attribute::Integer(1)]["author_id"], Attribute::Null);
+            assert_eq!(
+                posts[&Attribute::Integer(2)]["author_id"],
+                Attribute::Integer(1)
+            );
+
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn test_upsert_record_clears_what_the_record_omits() -> Result<(), Box<dyn StdError>> {
+        with_manager(|manager| {
+            let connection = manager.acquire()?;
+            seed_user(manager, &connection, 1, "alice")?;
+            seed_post(manager, &connection, 1, 1, "p1")?;
+            let store = Store::new(manager, &connection);
+            let record = Record::from_attributes(
+                schema(manager, "posts"),
+                Attributes::from_iter([("title", Attribute::Text("replaced".to_string()))]),
+            )
+            .with_id(Identifier::Integer(1).into());
+
+            let parameters = QueryParameters::new(schema(manager, "posts"));
+            store.upsert_record(record, &parameters)?;
+
+            let post = manager
+                .table("posts", &connection)?
+                .find(Identifier::Integer(1), &parameters)?;
+            assert_eq!(post["title"], Attribute::Text("replaced".to_string()));
+            assert_eq!(post["author_id"], Attribute::Null);
+
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn test_upsert_record_with_prepares_the_record_as_held() -> Result<(), Box<dyn StdError>> {
         with_manager(|manager| {
             let connection = manager.acquire()?;
             seed_user(manager, &connection, 1, "alice")?;
-            seed_post(manager, &connection, 1, 1, "before")?;
+            let store = Store::new(manager, &connection);
+            let parameters = QueryParameters::new(schema(manager, "users"));
+            let user = |id: i64| {
+                Record::from_attributes(
+                    schema(manager, "users"),
+                    Attributes::from_iter([("name", Attribute::Text("unnamed".to_string()))]),
+                )
+                .with_id(Identifier::Integer(id).into())
+            };
+            let prepare = |record: &mut Record, exists: bool| {
+                let name = if exists { "replaced" } else { "created" };
+                record
+                    .attributes
+                    .insert("name", Attribute::Text(name.to_string()));
+                Ok::<_, Error>(())
+            };
+
+            for (id, name) in [(1, "replaced"), (2, "created")] {
+                let (upserted, created) =
+                    store.upsert_record_with(user(id), &parameters, prepare)?;
+                assert_eq!(created, id == 2);
+                assert_eq!(
+                    upserted.content.require("name")?,
+                    &Attribute::Text(name.to_string())
+                );
+            }
+
+            Ok(())
+        })
+    }
 
+    #[test]
+    fn test_upsert_record_versions_like_create_and_update() -> Result<(), Box<dyn StdError>> {
+        with_manager(|manager| {
+            let connection = manager.acquire()?;
             let store = Store::new(manager, &connection);
-            let record = Record::from_relationships(
-                schema(manager, "posts"),
-                Relationships::from([(
-                    "author",
-                    Relationship::BelongsTo(Identifier::Integer(999)),
-                )]),
-            )
-            .with_id(Some(Identifier::Integer(1)));
+            let parameters = QueryParameters::new(schema(manager, "notes"));
+            let id = Identifier::Integer(5);
 
-            let parameters = QueryParameters::new(schema(manager, "posts"));
-            let result = store.update_record(record, &parameters);
+            let (created, _) = store.upsert_record(note_edit(manager, &id, None), &parameters)?;
+            assert_eq!(created.content.require("revision")?, &Attribute::Integer(1));
 
-            assert!(matches!(result, Err(Error::RelatedRecordNotFound)));
+            let (updated, _) =
+                store.upsert_record(note_edit(manager, &id, Some(1)), &parameters)?;
+            assert_eq!(updated.content.require("revision")?, &Attribute::Integer(2));
+
+            let result = store.upsert_record(note_edit(manager, &id, Some(1)), &parameters);
+            assert_eq!(
+                result.err(),
+                Some(Error::LockVersionMismatch {
+                    schema: "notes".to_string(),
+                    expected: 1,
+                    actual: 2,
+                })
+            );
 
             Ok(())
         })
@@ -1964,8 +3170,9 @@ mod tests {
                 ..QueryParameters::new(schema(manager, "posts"))
             };
 
-            store.delete_collection(schema(manager, "posts"), &parameters)?;
+            let deleted = store.delete_collection(schema(manager, "posts"), &parameters)?;
 
+            assert_eq!(deleted, 2);
             let posts = manager
                 .table("posts", &connection)?
                 .query(&QueryParameters::new(schema(manager, "posts")))?;
@@ -1999,6 +3206,57 @@ mod tests {
         })
     }
 
+    // --- aggregate_collection ----------------------------------------------
+
+    #[test]
+    fn test_aggregate_collection_groups_by_date_bucket() -> Result<(), Box<dyn StdError>> {
+        let manager: ConnectionManager<SqliteAdapter> = ConnectionManager::new(
+            Registry::try_new([SchemaBuilder::table("orders")
+                .attribute("total", AttributeType::Float)
+                .attribute("placed_at", AttributeType::DateTime)])?,
+            Pool::memory()?,
+        );
+        let connection = manager.acquire()?;
+        // Date-times are stored both as RFC 3339 text and as Unix milliseconds.
+        connection.execute_batch(
+            "CREATE TABLE orders (id INTEGER PRIMARY KEY, total REAL, placed_at);
+             INSERT INTO orders (id, total, placed_at) VALUES
+               (1, 10.0, '2024-01-05T10:00:00+00:00'),
+               (2, 20.0, 1704931200000),
+               (3, 5.5, '2024-02-01T00:00:00.250+00:00');",
+        )?;
+
+        let orders = schema(&manager, "orders");
+        let uri: Uri =
+            "/orders?aggregate[sum]=total&aggregate[count]=id&group=placed_at:month".parse()?;
+        let parameters = QueryParameters::parse(&uri, orders, manager.registry())?;
+        let aggregate = parameters
+            .aggregate
+            .as_ref()
+            .expect("aggregation is requested");
+        let rows = Store::new(&manager, &connection).aggregate_collection(
+            orders,
+            aggregate,
+            &parameters,
+        )?;
+
+        assert_eq!(
+            rows,
+            vec![
+                AggregateRow {
+                    group: vec![Attribute::Text("2024-01".to_string())],
+                    values: vec![Attribute::Float(30.0), Attribute::Integer(2)],
+                },
+                AggregateRow {
+                    group: vec![Attribute::Text("2024-02".to_string())],
+                    values: vec![Attribute::Float(5.5), Attribute::Integer(1)],
+                },
+            ]
+        );
+
+        Ok(())
+    }
+
     // --- peek_related_collection -------------------------------------------
 
     #[test]
@@ -2029,6 +3287,42 @@ mod tests {
         })
     }
 
+    #[test]
+    fn test_peek_related_collection_with_filters_sorts_and_pages() -> Result<(), Box<dyn StdError>>
+    {
+        with_manager(|manager| {
+            let connection = manager.acquire()?;
+            seed_user(manager, &connection, 1, "alice")?;
+            seed_user(manager, &connection, 2, "bob")?;
+            for (id, author_id, title) in [(1, 1, "a"), (2, 1, "b"), (3, 1, "c"), (4, 2, "d")] {
+                seed_post(manager, &connection, id, author_id, title)?;
+            }
+
+            let store = Store::new(manager, &connection);
+            let users = schema(manager, "users");
+            let user = store
+                .fetch_record(users, Identifier::Integer(1), &QueryParameters::new(users))?
+                .content;
+
+            let uri: Uri = "/users/1/relationships/posts?filter[title]=nin:b&sort=-title\
+                            &page[size]=1&page[number]=2&include=author"
+                .parse()?;
+            let parameters =
+                QueryParameters::parse(&uri, schema(manager, "posts"), manager.registry())?;
+
+            assert_eq!(
+                store.count_related_collection(&user, "posts", &parameters)?,
+                2
+            );
+            assert_eq!(
+                store.peek_related_collection_with(&user, "posts", parameters)?,
+                vec![Identifier::Integer(1)]
+            );
+
+            Ok(())
+        })
+    }
+
     #[test]
     fn test_peek_related_collection_has_many_empty_when_none() -> Result<(), Box<dyn StdError>> {
         with_manager(|manager| {
@@ -3030,4 +4324,383 @@ mod tests {
             Ok(())
         })
     }
+
+    // --- change events -----------------------------------------------------
+
+    fn event(resource: &str, id: i64, change: Change) -> ChangeEvent {
+        ChangeEvent {
+            resource: resource.to_string(),
+            id: Identifier::Integer(id),
+            change,
+        }
+    }
+
+    #[test]
+    fn test_events_are_delivered_once_the_outermost_transaction_commits()
+    -> Result<(), Box<dyn StdError>> {
+        let recorder = Arc::new(Recorder::default());
+        with_subscribed_manager(Some(recorder.clone()), |manager| {
+            let connection = manager.acquire()?;
+            let store = Store::new(manager, &connection);
+            let users = schema(manager, "users");
+
+            connection.transaction_with(|| {
+                let user = Record::from_attributes(
+                    users,
+                    Attributes::from_iter([("name", Attribute::Text("alice".to_string()))]),
+                );
+                store.create_record(user, Vec::new(), &QueryParameters::new(users))?;
+                store.delete_record(users, Identifier::Integer(1))?;
+                assert!(recorder.delivered().is_empty());
+                Ok::<_, Error>(())
+            })?;
+
+            assert_eq!(
+                recorder.delivered(),
+                vec![vec![
+                    event("users", 1, Change::Created),
+                    event("users", 1, Change::Deleted)
+                ]]
+            );
+
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn test_events_of_a_rolled_back_transaction_are_dropped() -> Result<(), Box<dyn StdError>> {
+        let recorder = Arc::new(Recorder::default());
+        with_subscribed_manager(Some(recorder.clone()), |manager| {
+            let connection = manager.acquire()?;
+            let store = Store::new(manager, &connection);
+            let users = schema(manager, "users");
+
+            let rolled_back = connection.transaction_with(|| {
+                let user = Record::from_attributes(
+                    users,
+                    Attributes::from_iter([("name", Attribute::Text("alice".to_string()))]),
+                );
+                store.create_record(user, Vec::new(), &QueryParameters::new(users))?;
+                Err::<(), _>(Error::RecordNotFound)
+            });
+
+            assert!(matches!(rolled_back, Err(Error::RecordNotFound)));
+            assert!(recorder.delivered().is_empty());
+
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn test_relinking_publishes_the_members_gained_and_lost() -> Result<(), Box<dyn StdError>> {
+        let recorder = Arc::new(Recorder::default());
+        with_subscribed_manager(Some(recorder.clone()), |manager| {
+            let connection = manager.acquire()?;
+            seed_user(manager, &connection, 1, "alice")?;
+            seed_user(manager, &connection, 2, "bob")?;
+            seed_post(manager, &connection, 10, 1, "one")?;
+            seed_post(manager, &connection, 11, 1, "two")?;
+            seed_post(manager, &connection, 12, 2, "three")?;
+
+            let store = Store::new(manager, &connection);
+            let users = schema(manager, "users");
+            let user = store
+                .fetch_record(users, Identifier::Integer(1), &QueryParameters::new(users))?
+                .content;
+            store.relink_collection(
+                user,
+                "posts",
+                vec![Identifier::Integer(11), Identifier::Integer(12)],
+            )?;
+
+            assert_eq!(
+                recorder.delivered(),
+                vec![vec![
+                    event("posts", 10, Change::Updated),
+                    event("posts", 12, Change::Updated),
+                    event(
+                        "users",
+                        1,
+                        Change::Unlinked {
+                            relationship: "posts".to_string(),
+                            related: vec![Identifier::Integer(10)],
+                        }
+                    ),
+                    event(
+                        "users",
+                        1,
+                        Change::Linked {
+                            relationship: "posts".to_string(),
+                            related: vec![Identifier::Integer(12)],
+                        }
+                    ),
+                ]]
+            );
+
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn test_a_create_publishes_each_row_it_rekeys() -> Result<(), Box<dyn StdError>> {
+        let recorder = Arc::new(Recorder::default());
+        with_subscribed_manager(Some(recorder.clone()), |manager| {
+            let connection = manager.acquire()?;
+            seed_user(manager, &connection, 1, "alice")?;
+            seed_post(manager, &connection, 10, 1, "one")?;
+
+            let store = Store::new(manager, &connection);
+            let users = schema(manager, "users");
+            let user = Record::from_attributes(
+                users,
+                Attributes::from_iter([("name", Attribute::Text("bob".to_string()))]),
+            )
+            .with_relationships(Relationships::from([(
+                "posts",
+                Relationship::HasMany(vec![Identifier::Integer(10)]),
+            )]));
+            store.create_record(user, Vec::new(), &QueryParameters::new(users))?;
+
+            assert_eq!(
+                recorder.delivered(),
+                vec![vec![
+                    event("posts", 10, Change::Updated),
+                    event("users", 2, Change::Created)
+                ]]
+            );
+
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn test_delete_collection_publishes_each_deleted_record() -> Result<(), Box<dyn StdError>> {
+        let recorder = Arc::new(Recorder::default());
+        with_subscribed_manager(Some(recorder.clone()), |manager| {
+            let connection = manager.acquire()?;
+            seed_user(manager, &connection, 1, "alice")?;
+            seed_post(manager, &connection, 10, 1, "one")?;
+            seed_post(manager, &connection, 11, 1, "two")?;
+            seed_post(manager, &connection, 12, 1, "three")?;
+
+            let store = Store::new(manager, &connection);
+            let posts = schema(manager, "posts");
+            let parameters = QueryParameters {
+                filter: Some(FilterParameters::from([(
+                    "title",
+                    vec![FilterValue::In(IndexSet::from([
+                        Attribute::Text("one".to_string()),
+                        Attribute::Text("three".to_string()),
+                    ]))],
+                )])),
+                ..QueryParameters::new(posts)
+            };
+            store.delete_collection(posts, &parameters)?;
+
+            assert_eq!(
+                recorder.delivered(),
+                vec![vec![
+                    event("posts", 10, Change::Deleted),
+                    event("posts", 12, Change::Deleted)
+                ]]
+            );
+
+            Ok(())
+        })
+    }
+
+    // --- audit log ---------------------------------------------------------
+
+    /// An audit entry's operation, resource, record id, changes and actor.
+    type Entry = (String, String, String, Value, Attribute);
+
+    /// The audit entries, in the order written.
+    fn audit_entries(
+        manager: &ConnectionManager<SqliteAdapter>,
+        connection: &Connection,
+    ) -> Result<Vec<Entry>, Box<dyn StdError>> {
+        let rows = manager
+            .table("audit_log", connection)?
+            .query(&QueryParameters::new(schema(manager, "audit_log")))?;
+        rows.into_iter()
+            .map(|mut row| {
+                let mut take = |column| row.shift_remove(column).unwrap_or(Attribute::Null);
+                let operation = take("operation").to_string()?;
+                let resource = take("resource").to_string()?;
+                let id = take("record_id").to_string()?;
+                let changes = Value::from(take("changes"));
+                Ok((operation, resource, id, changes, take("actor")))
+            })
+            .collect()
+    }
+
+    fn entry(
+        operation: &str,
+        resource: &str,
+        id: &str,
+        changes: Value,
+        actor: Option<&str>,
+    ) -> Entry {
+        (
+            operation.to_string(),
+            resource.to_string(),
+            id.to_string(),
+            changes,
+            actor.map_or(Attribute::Null, |actor| Attribute::Text(actor.to_string())),
+        )
+    }
+
+    #[test]
+    fn test_audited_writes_record_the_attributes_they_change() -> Result<(), Box<dyn StdError>> {
+        with_manager(|manager| {
+            let connection = manager.acquire()?;
+            let store = Store::new(manager, &connection).acting_as(Some("ada"));
+            let notes = schema(manager, "notes");
+            let note = create_note(&store, manager)?;
+            let id = note.require_id()?.clone();
+            store.update_record(
+                note_edit(manager, &id, Some(1)),
+                &QueryParameters::new(notes),
+            )?;
+            store.delete_record(notes, id)?;
+
+            assert_eq!(
+                audit_entries(manager, &connection)?,
+                vec![
+                    entry(
+                        "create",
+                        "notes",
+                        "1",
+                        json!({
+                            "body": {"old": null, "new": "draft"},
+                            "revision": {"old": null, "new": 1},
+                        }),
+                        Some("ada"),
+                    ),
+                    entry(
+                        "update",
+                        "notes",
+                        "1",
+                        json!({
+                            "body": {"old": "draft", "new": "edited"},
+                            "revision": {"old": 1, "new": 2},
+                        }),
+                        Some("ada"),
+                    ),
+                    entry(
+                        "delete",
+                        "notes",
+                        "1",
+                        json!({
+                            "body": {"old": "edited", "new": null},
+                            "revision": {"old": 2, "new": null},
+                        }),
+                        Some("ada"),
+                    ),
+                ]
+            );
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn test_audited_linkage_writes_record_the_members_added_and_removed()
+    -> Result<(), Box<dyn StdError>> {
+        with_manager(|manager| {
+            let connection = manager.acquire()?;
+            seed_user(manager, &connection, 1, "alice")?;
+            seed_post(manager, &connection, 10, 1, "one")?;
+            seed_post(manager, &connection, 11, 1, "two")?;
+
+            let store = Store::new(manager, &connection);
+            let users = schema(manager, "users");
+            let user = store
+                .fetch_record(users, Identifier::Integer(1), &QueryParameters::new(users))?
+                .content;
+            store.relink_collection(user, "posts", vec![Identifier::Integer(11)])?;
+
+            assert_eq!(
+                audit_entries(manager, &connection)?,
+                vec![entry(
+                    "unlink",
+                    "users",
+                    "1",
+                    json!({"posts": {"removed": [10]}}),
+                    None,
+                )]
+            );
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn test_an_audited_delete_collection_records_each_deleted_record()
+    -> Result<(), Box<dyn StdError>> {
+        with_manager(|manager| {
+            let connection = manager.acquire()?;
+            seed_user(manager, &connection, 1, "alice")?;
+            seed_user(manager, &connection, 2, "bob")?;
+
+            let store = Store::new(manager, &connection);
+            let users = schema(manager, "users");
+            store.delete_collection(users, &QueryParameters::new(users))?;
+
+            assert_eq!(
+                audit_entries(manager, &connection)?,
+                vec![
+                    entry(
+                        "delete",
+                        "users",
+                        "1",
+                        json!({"name": {"old": "alice", "new": null}}),
+                        None,
+                    ),
+                    entry(
+                        "delete",
+                        "users",
+                        "2",
+                        json!({"name": {"old": "bob", "new": null}}),
+                        None,
+                    ),
+                ]
+            );
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn test_audit_entries_roll_back_with_their_write() -> Result<(), Box<dyn StdError>> {
+        with_manager(|manager| {
+            let connection = manager.acquire()?;
+            let store = Store::new(manager, &connection);
+
+            let rolled_back = connection.transaction_with(|| {
+                create_note(&store, manager)?;
+                Err::<(), _>(Error::RecordNotFound)
+            });
+
+            assert!(matches!(rolled_back, Err(Error::RecordNotFound)));
+            assert!(audit_entries(manager, &connection)?.is_empty());
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn test_unaudited_writes_record_nothing() -> Result<(), Box<dyn StdError>> {
+        with_manager(|manager| {
+            let connection = manager.acquire()?;
+            seed_user(manager, &connection, 1, "alice")?;
+
+            let store = Store::new(manager, &connection);
+            let posts = schema(manager, "posts");
+            store.create_record(
+                new_post(manager, "hello", 1),
+                Vec::new(),
+                &QueryParameters::new(posts),
+            )?;
+
+            assert!(audit_entries(manager, &connection)?.is_empty());
+            Ok(())
+        })
+    }
 }
@@ -4,13 +4,17 @@ use super::{
     attributes::{Attribute, Row},
     connection::Connection as ConnectionInterface,
     error::Error,
-    query_builder::QueryBuilder as QueryBuilderInterface,
+    query_builder::{QueryBuilder as QueryBuilderInterface, mark_excerpt},
     query_parameters::QueryParameters,
-    schema::Schema,
+    schema::{AttributeType, Schema},
 };
 use crate::database::{
     attributes::Identifier,
-    query_parameters::{FieldsParameters, FilterParameters, FilterValue},
+    query_parameters::{
+        AggregateParameters, AggregateRow, ExcerptParameters, FieldsParameters, FilterParameters,
+        FilterValue, SearchParameters, SortParameters,
+    },
+    record::SearchExcerpts,
 };
 
 pub trait Table<
@@ -88,6 +92,12 @@ pub trait Table<
         self.run_fetch_single(query, bindings)
     }
 
+    fn upsert(&self, row: Row<'sch>, parameters: &QueryParameters) -> Result<Row<'sch>, Error> {
+        let (query, bindings) = QueryBuilder::new(self.schema()).upsert(row, parameters)?;
+
+        self.run_fetch_single(query, bindings)
+    }
+
     fn update(
         &self,
         id: Identifier,
@@ -153,6 +163,163 @@ pub trait Table<
             .unwrap_or_else(|| Ok(0))
     }
 
+    /// Creates the schema's full-text index and the triggers maintaining it, indexing the rows the
+    /// table already holds.
+    fn create_text_index(&self) -> Result<(), Error> {
+        let statements = QueryBuilder::new(self.schema()).create_text_index()?;
+        self.connection().transaction(|| {
+            for statement in statements {
+                self.connection().execute(statement, Vec::new())?;
+            }
+            Ok(())
+        })
+    }
+
+    /// Creates the schema's history table and the triggers versioning every write to the table,
+    /// taking the rows the table already holds as their first versions.
+    fn create_history(&self) -> Result<(), Error> {
+        let statements = QueryBuilder::new(self.schema()).create_history()?;
+        self.connection().transaction(|| {
+            for statement in statements {
+                self.connection().execute(statement, Vec::new())?;
+            }
+            Ok(())
+        })
+    }
+
+    /// Counts the rows holding each of `values` in `column`, leaving out the values no row holds.
+    fn count_by(
+        &self,
+        column: &'sch str,
+        values: IndexSet<Attribute>,
+    ) -> Result<Vec<(Attribute, u64)>, Error> {
+        if values.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let kind = self
+            .schema()
+            .column(column)
+            .map(|descriptor| descriptor.kind)
+            .ok_or_else(|| Error::InconsistentSchema {
+                schema: self.schema().name().to_string(),
+                attribute: column.to_string(),
+                message: "Cannot count by an unknown column".to_string(),
+            })?;
+
+        let (query, bindings) = QueryBuilder::new(self.schema()).count_by(column, values);
+        self.connection()
+            .query_values(
+                query,
+                bindings,
+                self.schema(),
+                &[kind, AttributeType::Integer],
+            )?
+            .into_iter()
+            .map(|row| match <[Attribute; 2]>::try_from(row) {
+                Ok([value, Attribute::Integer(count)]) => Ok((value, count.unsigned_abs())),
+                _ => Err(Error::InconsistentSchema {
+                    schema: self.schema().name().to_string(),
+                    attribute: column.to_string(),
+                    message: "A count returned a malformed row".to_string(),
+                }),
+            })
+            .collect()
+    }
+
+    /// Selects the rows holding each of `values` in `column`, at most `limit` per value, grouped
+    /// by value and ordered within each by `sort`.
+    fn query_ranked(
+        &self,
+        column: &'sch str,
+        values: IndexSet<Attribute>,
+        sort: &Option<SortParameters>,
+        limit: Option<u32>,
+        fields: &FieldsParameters,
+    ) -> Result<Vec<Row<'sch>>, Error> {
+        if values.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let (query, bindings) =
+            QueryBuilder::new(self.schema()).query_ranked(column, values, sort, limit, fields)?;
+        self.run_fetch(query, bindings)
+    }
+
+    /// Computes `aggregate` over the rows matched by `parameters`, one row per group.
+    fn aggregate(
+        &self,
+        aggregate: &AggregateParameters,
+        parameters: &QueryParameters,
+    ) -> Result<Vec<AggregateRow>, Error> {
+        let Some((query, bindings)) =
+            QueryBuilder::new(self.schema()).aggregate(aggregate, parameters)?
+        else {
+            return Ok(Vec::new());
+        };
+
+        Ok(self
+            .connection()
+            .query_values(query, bindings, self.schema(), &aggregate.result_kinds())?
+            .into_iter()
+            .map(|values| AggregateRow::from_values(values, aggregate))
+            .collect())
+    }
+
+    /// Renders the search excerpts of the records with the primary keys `ids`, by primary key. A
+    /// record that no longer matches `search` is left out.
+    fn excerpts(
+        &self,
+        ids: IndexSet<Attribute>,
+        search: &SearchParameters,
+        excerpts: &ExcerptParameters<'sch>,
+    ) -> Result<Vec<(Attribute, SearchExcerpts<'sch>)>, Error> {
+        if ids.is_empty() || excerpts.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let kinds = [AttributeType::from(self.schema().primary_key().kind)]
+            .into_iter()
+            .chain(
+                (0..excerpts.snippets.len() + excerpts.highlights.len())
+                    .map(|_| AttributeType::Text),
+            )
+            .collect::<Vec<_>>();
+        let (query, bindings) = QueryBuilder::new(self.schema()).excerpts(ids, search, excerpts)?;
+
+        self.connection()
+            .query_values(query, bindings, self.schema(), &kinds)?
+            .into_iter()
+            .map(|row| {
+                let mut values = row.into_iter();
+                let id = values.next().unwrap_or(Attribute::Null);
+                let mut text = values.map(|value| match value {
+                    Attribute::Text(text) => mark_excerpt(&text),
+                    _ => String::new(),
+                });
+
+                let snippets = excerpts
+                    .snippets
+                    .iter()
+                    .map(|&attribute| (attribute, text.next().unwrap_or_default()))
+                    .collect();
+                let highlights = excerpts
+                    .highlights
+                    .iter()
+                    .map(|&attribute| (attribute, text.next().unwrap_or_default()))
+                    .collect();
+
+                Ok((
+                    id,
+                    SearchExcerpts {
+                        snippets,
+                        highlights,
+                    },
+                ))
+            })
+            .collect()
+    }
+
     fn run_fetch(&self, query: String, bindings: Vec<Attribute>) -> Result<Vec<Row<'sch>>, Error> {
         self.connection().query(query, bindings, self.schema())
     }
@@ -67,7 +67,7 @@ impl From<DatabaseError> for Error {
             code: Cow::Borrowed(error.code()),
             title: Cow::Borrowed(error.title()),
             detail: error.to_string(),
-            source: None,
+            source: error.source().map(Box::new),
             meta: None,
         }
     }
@@ -37,6 +37,67 @@ pub fn for_relationship(relationship: impl Display) -> Source {
     ))
 }
 
+/// Points at an operation of an Atomic Operations document as a whole.
+pub fn for_operation(index: usize) -> Source {
+    Source::Pointer(format!("/atomic:operations/{index}"))
+}
+
+/// Points at a member of an operation, such as `ref`.
+pub fn for_operation_member(index: usize, member: impl Display) -> Source {
+    Source::Pointer(format!(
+        "/atomic:operations/{index}/{}",
+        escape_token(member)
+    ))
+}
+
+/// Points at a resource object of the document's `included` array.
+pub fn for_included(index: usize) -> Source {
+    Source::Pointer(format!("/included/{index}"))
+}
+
+/// Points at a resource object of an array of primary data.
+pub fn for_data(index: usize) -> Source {
+    Source::Pointer(format!("/data/{index}"))
+}
+
+/// Re-roots a source raised while reading a member of an array of primary data as if it were the
+/// document's single resource, so that it addresses that member instead. A source naming no
+/// pointer names the member.
+pub fn within_data(index: usize, source: Option<Source>) -> Source {
+    reroot(&format!("/data/{index}"), for_data(index), source)
+}
+
+/// Re-roots a source raised while reading an operation's `data` as if it were a document's primary
+/// data, so that it addresses the operation instead. A source naming no pointer names the operation.
+pub fn within_operation(index: usize, source: Option<Source>) -> Source {
+    reroot(
+        &format!("/atomic:operations/{index}/data"),
+        for_operation(index),
+        source,
+    )
+}
+
+/// Re-roots a source raised while reading an included resource as if it were the document's
+/// primary data, so that it addresses the included resource instead. A source naming no pointer
+/// names the included resource.
+pub fn within_included(index: usize, source: Option<Source>) -> Source {
+    reroot(&format!("/included/{index}"), for_included(index), source)
+}
+
+/// Moves a pointer into `/data` to the same place beneath `data`; a pointer elsewhere is kept, and
+/// any other source falls back to `fallback`.
+fn reroot(data: &str, fallback: Source, source: Option<Source>) -> Source {
+    match source {
+        Some(Source::Pointer(pointer)) => match pointer.strip_prefix("/data") {
+            Some(rest) if rest.is_empty() || rest.starts_with('/') => {
+                Source::Pointer(format!("{data}{rest}"))
+            }
+            _ => Source::Pointer(pointer),
+        },
+        Some(Source::Parameter(_) | Source::Header(_)) | None => fallback,
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -86,6 +147,63 @@ mod tests {
         );
     }
 
+    #[test]
+    fn an_operation_points_into_the_operations_array() {
+        assert_eq!(
+            for_operation(2),
+            Source::Pointer("/atomic:operations/2".to_string())
+        );
+        assert_eq!(
+            for_operation_member(2, "ref"),
+            Source::Pointer("/atomic:operations/2/ref".to_string())
+        );
+    }
+
+    #[test]
+    fn a_primary_data_pointer_is_rerooted_under_its_operation() {
+        assert_eq!(
+            within_operation(1, Some(for_attribute("title"))),
+            Source::Pointer("/atomic:operations/1/data/attributes/title".to_string())
+        );
+        assert_eq!(
+            within_operation(1, Some(for_operation_member(1, "ref"))),
+            Source::Pointer("/atomic:operations/1/ref".to_string())
+        );
+        assert_eq!(
+            within_operation(1, None),
+            Source::Pointer("/atomic:operations/1".to_string())
+        );
+    }
+
+    #[test]
+    fn a_primary_data_pointer_is_rerooted_onto_its_included_resource() {
+        assert_eq!(
+            within_included(0, Some(for_member("lid"))),
+            Source::Pointer("/included/0/lid".to_string())
+        );
+        assert_eq!(
+            within_included(2, Some(for_primary_data())),
+            Source::Pointer("/included/2".to_string())
+        );
+        assert_eq!(
+            within_included(2, Some(Source::Header("Content-Type".to_string()))),
+            Source::Pointer("/included/2".to_string())
+        );
+    }
+
+    #[test]
+    fn a_primary_data_pointer_is_rerooted_onto_its_array_member() {
+        assert_eq!(
+            within_data(1, Some(for_attribute("title"))),
+            Source::Pointer("/data/1/attributes/title".to_string())
+        );
+        assert_eq!(
+            within_data(3, Some(for_primary_data())),
+            Source::Pointer("/data/3".to_string())
+        );
+        assert_eq!(within_data(0, None), Source::Pointer("/data/0".to_string()));
+    }
+
     /// Tilde must be escaped before solidus, or the tilde of an escaped solidus is escaped in turn
     /// and the pointer addresses a different location.
     #[test]
@@ -28,6 +28,19 @@ fn a_database_fault_drains_without_naming_a_source() {
     assert_eq!(error.source, None);
 }
 
+#[test]
+fn a_parameter_parse_failure_drains_naming_the_parameter() {
+    let error = Error::from(DatabaseError::ParseParameterFailure {
+        parameter: "sort".to_string(),
+        message: "The query exceeds the limit of 1 sort fields, with 2".to_string(),
+    });
+
+    assert_eq!(
+        error.source.as_deref(),
+        Some(&Source::Parameter("sort".to_string()))
+    );
+}
+
 /// The detail is what distinguishes one occurrence from another; the title never moves.
 #[test]
 fn draining_keeps_the_title_generic_and_the_detail_specific() {
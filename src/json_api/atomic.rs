@@ -0,0 +1,149 @@
+use crate::json_api::{
+    document::ImplementationInfo, identifier::Identifier, primary_content::PrimaryContent,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+/// The URI of the Atomic Operations extension, as applied in a media type's `ext` parameter.
+pub const ATOMIC_EXTENSION: &str = "https://jsonapi.org/ext/atomic";
+
+/// A request document of the Atomic Operations extension: the operations to perform, in order, all
+/// or none of them.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct OperationsDocument {
+    #[serde(rename = "atomic:operations")]
+    pub operations: Vec<Operation>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub meta: Option<Value>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub jsonapi: Option<ImplementationInfo>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OperationCode {
+    Add,
+    Update,
+    Remove,
+}
+
+/// What an operation targets: a resource, by `id` or by the `lid` an earlier operation created it
+/// under, or one of its relationships.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Reference {
+    #[serde(flatten)]
+    pub identifier: Identifier,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub relationship: Option<String>,
+}
+
+/// A single operation. Its `data` is a resource object for an operation on a resource, and
+/// relationship linkage for an operation on a relationship.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Operation {
+    pub op: OperationCode,
+
+    #[serde(rename = "ref", skip_serializing_if = "Option::is_none")]
+    pub reference: Option<Reference>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub href: Option<String>,
+
+    #[serde(flatten)]
+    pub content: PrimaryContent,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub meta: Option<Value>,
+}
+
+/// The response document of a successful atomic request: one result per operation, in order.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ResultsDocument {
+    #[serde(rename = "atomic:results")]
+    pub results: Vec<OperationResult>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub jsonapi: Option<ImplementationInfo>,
+}
+
+/// The outcome of one operation: the resource it created or updated, or nothing.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct OperationResult {
+    #[serde(flatten)]
+    pub content: PrimaryContent,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub meta: Option<Value>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_operations_deserialise_by_target() -> Result<(), serde_json::Error> {
+        let document: OperationsDocument = serde_json::from_value(json!({
+            "atomic:operations": [
+                { "op": "add", "data": { "type": "orders", "lid": "o1" } },
+                {
+                    "op": "update",
+                    "ref": { "type": "orders", "lid": "o1", "relationship": "items" },
+                    "data": [{ "type": "items", "id": "3" }]
+                },
+                { "op": "remove", "ref": { "type": "items", "id": "4" } }
+            ]
+        }))?;
+
+        let [add, update, remove] = document.operations.as_slice() else {
+            panic!("expected three operations");
+        };
+        assert_eq!(add.op, OperationCode::Add);
+        assert!(add.reference.is_none());
+        assert!(matches!(add.content, PrimaryContent::Record { .. }));
+
+        let reference = update.reference.as_ref().expect("update carries a ref");
+        assert_eq!(
+            reference.identifier,
+            Identifier::New {
+                kind: "orders".to_string(),
+                lid: Some("o1".to_string()),
+            }
+        );
+        assert_eq!(reference.relationship.as_deref(), Some("items"));
+        assert!(matches!(update.content, PrimaryContent::Collection { .. }));
+
+        assert_eq!(remove.op, OperationCode::Remove);
+        assert!(matches!(remove.content, PrimaryContent::Absent {}));
+        Ok(())
+    }
+
+    #[test]
+    fn test_unknown_operation_code_is_rejected() {
+        let result = serde_json::from_value::<OperationsDocument>(json!({
+            "atomic:operations": [{ "op": "upsert", "data": { "type": "orders" } }]
+        }));
+
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_empty_result_serialises_as_an_empty_object() -> Result<(), serde_json::Error> {
+        let document = ResultsDocument {
+            results: vec![OperationResult {
+                content: PrimaryContent::Absent {},
+                meta: None,
+            }],
+            jsonapi: None,
+        };
+
+        assert_eq!(
+            serde_json::to_value(&document)?,
+            json!({ "atomic:results": [{}] })
+        );
+        Ok(())
+    }
+}
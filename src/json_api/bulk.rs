@@ -0,0 +1,5 @@
+/// The URI of the bulk extension, as applied in a media type's `ext` parameter. A request of the
+/// extension creates every resource of an array of primary data, or applies a single resource
+/// object as a patch to every record a `filter` matches.
+/// TODO: Spec and publish the extension.
+pub const BULK_EXTENSION: &str = "https://example.com/ext/bulk";
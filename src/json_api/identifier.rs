@@ -13,9 +13,21 @@ pub enum Identifier {
         #[serde(rename = "type")]
         kind: String,
         id: String,
+        /// The `lid` the resource was created under, echoed back to the client that chose it.
+        #[serde(skip_serializing_if = "Option::is_none")]
+        lid: Option<String>,
     },
 }
 
+impl Identifier {
+    /// The resource type the identifier names, whether by `id` or by `lid`.
+    pub fn kind(&self) -> &str {
+        match self {
+            Identifier::New { kind, .. } | Identifier::Existing { kind, .. } => kind,
+        }
+    }
+}
+
 #[derive(Deserialize)]
 struct IdentifierFields {
     #[serde(rename = "type")]
@@ -30,6 +42,7 @@ impl From<IdentifierFields> for Identifier {
             Some(id) => Identifier::Existing {
                 kind: fields.kind,
                 id,
+                lid: fields.lid,
             },
             None => Identifier::New {
                 kind: fields.kind,
@@ -1,3 +1,5 @@
+pub mod atomic;
+pub mod bulk;
 pub mod document;
 pub mod error;
 pub mod identifier;
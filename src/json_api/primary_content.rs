@@ -19,6 +19,9 @@ pub enum PrimaryContent {
     Errors {
         errors: Vec<Error>,
     },
+    /// No primary data at all: a document carrying only top-level `meta`, such as an aggregation.
+    /// Listed last so that deserialising prefers every variant with a member of its own.
+    Absent {},
 }
 
 impl From<Resource> for PrimaryContent {
@@ -68,6 +71,7 @@ mod tests {
             identifier: Identifier::Existing {
                 kind: "articles".to_string(),
                 id: id.to_string(),
+                lid: None,
             },
             attributes: None,
             relationships: None,
@@ -41,6 +41,7 @@ mod tests {
             identifier: Identifier::Existing {
                 kind: "articles".to_string(),
                 id: "1".to_string(),
+                lid: None,
             },
             attributes: Some(HashMap::from([("title".to_string(), json!("Hello"))])),
             relationships: None,
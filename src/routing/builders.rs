@@ -1,9 +1,10 @@
 use super::{
     Error, ResourceResult,
     controller::{ResourceContext, ResourceController},
-    middleware::json_api::JsonApi,
+    middleware::json_api::{JsonApi, RequiresExtension},
     middleware::{Middleware, PrimaryMiddleware, ResourceMiddleware},
     mount_table::{RelationshipMounts, ResourceMount},
+    operations::serve_operations,
     router::{
         EndpointHandler, MaterialisedRoutes, MountSlot, PrimaryEndpointHandler,
         ResourceEndpointHandler, Route, RouterError, split_segments,
@@ -11,8 +12,10 @@ use super::{
 };
 use crate::database::{
     adapters::Adapter as AdapterInterface,
+    query_parameters::QueryLimits,
     schema::{RelationshipKind, Schema},
 };
+use crate::json_api::bulk::BULK_EXTENSION;
 use http::Method;
 use indexmap::IndexMap;
 use itertools::Itertools;
@@ -372,6 +375,18 @@ impl<'sch, Adapter: AdapterInterface + 'sch> PrimaryRouteBuilder<'sch, Adapter>
         self
     }
 
+    /// Mounts an Atomic Operations endpoint at `segment`, taking `POST`s of `atomic:operations`.
+    /// Its operations reach the resources mounted on this router, and write only what their own
+    /// mounts let a client write.
+    pub fn operations(mut self, segment: impl Into<Cow<'sch, str>>) -> Self {
+        self.mount(
+            Method::POST,
+            split_segments(segment),
+            EndpointHandler::primary(|context| serve_operations(context)),
+        );
+        self
+    }
+
     pub fn resource<T>(self, segment: impl Into<Cow<'sch, str>>, schema: &'sch Schema<'sch>) -> Self
     where
         T: ResourceController<'sch, Adapter> + Default + 'sch,
@@ -555,6 +570,7 @@ pub struct ResourceRouteBuilder<'sch, T, Adapter: AdapterInterface> {
     routes: MaterialisedRoutes<'sch, Adapter>,
     mounted: HashSet<(&'sch str, MountSlot)>,
     relationships: IndexMap<&'sch str, RelationshipMounts<'sch>>,
+    query_limits: QueryLimits,
     controller: PhantomData<fn() -> T>,
 }
 
@@ -590,6 +606,7 @@ impl<'sch, T, Adapter: AdapterInterface + 'sch> RouteBuilder<'sch, Adapter>
             routes: MaterialisedRoutes::new(),
             mounted: HashSet::new(),
             relationships: IndexMap::new(),
+            query_limits: self.query_limits,
             controller: PhantomData,
         }
     }
@@ -625,10 +642,18 @@ where
             routes: MaterialisedRoutes::new(),
             mounted: HashSet::new(),
             relationships: IndexMap::new(),
+            query_limits: QueryLimits::default(),
             controller: PhantomData,
         }
     }
 
+    /// Bounds the complexity of queries against this resource, overriding the router-wide limits
+    /// bound by bound.
+    pub fn query_limits(mut self, limits: QueryLimits) -> Self {
+        self.query_limits = limits;
+        self
+    }
+
     /// Wraps the routes `build` mounts with `middleware`, on the JSON:API tier: spawns a sibling
     /// carrying the extended middleware, runs `build`, merges it back. The merge folds the child's
     /// relationship templates and claimed slots home, so a relationship mounted inside a block still
@@ -651,6 +676,7 @@ where
             let entry = self.relationships.entry(name).or_default();
             entry.linkage = entry.linkage.take().or(mounts.linkage);
             entry.related = entry.related.take().or(mounts.related);
+            entry.writable |= mounts.writable;
         }
         self.routes.absorb(child.routes);
     }
@@ -662,6 +688,8 @@ where
             factory: || Box::new(T::default()) as Box<dyn ResourceController<'sch, Adapter>>,
             base: self.path.clone(),
             relationships: self.relationships,
+            query_limits: self.query_limits,
+            read_only: self.read_only,
         });
         self.routes
     }
@@ -711,7 +739,7 @@ where
             self.mount(
                 Method::PUT,
                 [Cow::Borrowed(":id")],
-                EndpointHandler::resource(schema, |context| T::default().update(context)),
+                EndpointHandler::resource(schema, |context| T::default().upsert(context)),
             );
             self.mount(
                 Method::PATCH,
@@ -728,6 +756,53 @@ where
         self
     }
 
+    /// Mounts the resource's bulk endpoints: a `POST` of an array of resources, and a `PATCH` and a
+    /// `DELETE` of the records a `filter` matches, each writing at most `limit` records. Opt-in, and
+    /// reached only by a request applying the bulk extension, so they are matched ahead of the
+    /// resource's other routes; a read-only mount refuses them.
+    pub fn bulk_endpoints(mut self, limit: usize) -> Self {
+        const EXTENSIONS: &[&str] = &[BULK_EXTENSION];
+        let schema = self.schema;
+        let mut bulk = self.spawn_with_middleware(Middleware::Resource(Arc::new(
+            RequiresExtension(BULK_EXTENSION),
+        )));
+
+        if self.read_only {
+            for method in [Method::POST, Method::PATCH, Method::DELETE] {
+                bulk.mount(
+                    method,
+                    std::iter::empty(),
+                    EndpointHandler::extended(schema, EXTENSIONS, forbidden),
+                );
+            }
+        } else {
+            bulk.mount(
+                Method::POST,
+                std::iter::empty(),
+                EndpointHandler::extended(schema, EXTENSIONS, move |context| {
+                    T::default().create_many(context, limit)
+                }),
+            );
+            bulk.mount(
+                Method::PATCH,
+                std::iter::empty(),
+                EndpointHandler::extended(schema, EXTENSIONS, move |context| {
+                    T::default().update_many(context, limit)
+                }),
+            );
+            bulk.mount(
+                Method::DELETE,
+                std::iter::empty(),
+                EndpointHandler::extended(schema, EXTENSIONS, move |context| {
+                    T::default().delete_many(context, limit)
+                }),
+            );
+        }
+
+        self.routes.absorb_ahead(bulk.routes);
+        self
+    }
+
     fn default_config(&self) -> SlotConfig<'sch> {
         SlotConfig {
             read_only: self.read_only,
@@ -793,8 +868,9 @@ where
             .clone()
             .unwrap_or(Cow::Borrowed("relationships"));
         let path = || [Cow::Borrowed(":id"), keyword.clone(), segment.clone()];
-        self.relationships.entry(relationship).or_default().linkage =
-            Some(self.path.iter().cloned().chain(path()).collect());
+        let mounts = self.relationships.entry(relationship).or_default();
+        mounts.linkage = Some(self.path.iter().cloned().chain(path()).collect());
+        mounts.writable = !config.read_only;
         let to_many = kind == RelationshipKind::HasMany;
 
         self.mount(
@@ -3,23 +3,36 @@ use crate::database::error::Error;
 use crate::database::schema::Schema;
 use crate::{
     database::{
-        adapters::Adapter as AdapterInterface, connection::Connection as ConnectionInterface,
-        connection_manager::ConnectionManager, query_parameters::QueryParameters, store::Store,
+        adapters::Adapter as AdapterInterface,
+        connection::Connection as ConnectionInterface,
+        connection_manager::ConnectionManager,
+        query_parameters::{QueryLimits, QueryParameters},
+        store::Store,
     },
     http_wrappers::Uri,
-    routing::{BaseUri, Error as RoutingError, MountTable, RouteParameters},
+    routing::{
+        BaseUri, Error as RoutingError, MountTable, RouteParameters, middleware::Middleware,
+        mount_table::ResourceMount, router::Route,
+    },
     serialisation::ByteStream,
     serialisation::uri_generator::CanonicalUriGenerator,
 };
-use http::HeaderMap;
+use http::{
+    HeaderMap, Method,
+    header::{IF_MATCH, IF_NONE_MATCH},
+};
 use std::cell::{LazyCell, OnceCell};
 use std::io::{Cursor, Read};
+use std::rc::Rc;
 
 /// A lazily-acquired request connection: unforced until first use, then the pooled handle or the
-/// failure that acquiring it produced. Boxed because the init closure captures the manager.
-type LazyConnection<'sch, Adapter> = LazyCell<
-    Result<<Adapter as AdapterInterface>::Connection, Error>,
-    Box<dyn FnOnce() -> Result<<Adapter as AdapterInterface>::Connection, Error> + 'sch>,
+/// failure that acquiring it produced. Boxed because the init closure captures the manager; shared,
+/// so the contexts an Atomic Operations request runs its operations in write on its connection.
+type LazyConnection<'sch, Adapter> = Rc<
+    LazyCell<
+        Result<<Adapter as AdapterInterface>::Connection, Error>,
+        Box<dyn FnOnce() -> Result<<Adapter as AdapterInterface>::Connection, Error> + 'sch>,
+    >,
 >;
 
 /// The raw byte tier's request context: the request head and a streamed body, schema-oblivious. The
@@ -31,14 +44,19 @@ where
 {
     pub manager: &'sch ConnectionManager<'sch, Adapter>,
     pub uri: &'req Uri,
+    method: Method,
     base_uri: &'req BaseUri<'sch>,
     mount_table: &'req MountTable<'sch, Adapter>,
+    /// The router's routes, and the middleware of the one serving this request.
+    routes: &'req [Route<'sch, Adapter>],
+    middleware: &'req [Middleware<'sch, Adapter>],
     body: Option<ByteStream>,
     /// Whether the body carries content, filled once by the first `contains_body` probe.
     body_present: OnceCell<bool>,
     headers: HeaderMap,
     route: RouteParameters<'sch, 'req>,
     connection: LazyConnection<'sch, Adapter>,
+    actor: Option<String>,
 }
 
 impl<'sch: 'req, 'req, Adapter: AdapterInterface> PrimaryContext<'sch, 'req, Adapter> {
@@ -59,16 +77,70 @@ impl<'sch: 'req, 'req, Adapter: AdapterInterface> PrimaryContext<'sch, 'req, Ada
         Self {
             manager,
             uri,
+            method: parts.method,
             base_uri,
             mount_table,
+            routes: &[],
+            middleware: &[],
             body: Some(body),
             body_present: OnceCell::new(),
             headers: parts.headers,
             route,
-            connection: LazyCell::new(acquire),
+            connection: Rc::new(LazyCell::new(acquire)),
+            actor: None,
+        }
+    }
+
+    /// Binds the router's routes, and the middleware of the route serving this request.
+    pub(crate) fn serving(
+        mut self,
+        routes: &'req [Route<'sch, Adapter>],
+        middleware: &'req [Middleware<'sch, Adapter>],
+    ) -> Self {
+        self.routes = routes;
+        self.middleware = middleware;
+        self
+    }
+
+    /// A context for one operation of an Atomic Operations request: a `method` request carrying
+    /// `body` to the route `route` resolves, on this request's connection and on behalf of its
+    /// actor. The request's preconditions govern no single operation, so they are left out.
+    pub(crate) fn for_operation(
+        &self,
+        method: Method,
+        route: RouteParameters<'sch, 'req>,
+        body: ByteStream,
+    ) -> Self {
+        let mut headers = self.headers.clone();
+        headers.remove(IF_MATCH);
+        headers.remove(IF_NONE_MATCH);
+
+        Self {
+            manager: self.manager,
+            uri: self.uri,
+            method,
+            base_uri: self.base_uri,
+            mount_table: self.mount_table,
+            routes: self.routes,
+            middleware: self.middleware,
+            body: Some(body),
+            body_present: OnceCell::new(),
+            headers,
+            route,
+            connection: Rc::clone(&self.connection),
+            actor: self.actor.clone(),
         }
     }
 
+    pub(crate) fn routes(&self) -> &'req [Route<'sch, Adapter>] {
+        self.routes
+    }
+
+    /// The middleware of the route serving this request, schema-less first.
+    pub(crate) fn middleware(&self) -> &'req [Middleware<'sch, Adapter>] {
+        self.middleware
+    }
+
     /// The link generator for this request, resolving each record's links against where its type is
     /// mounted. Cheap to build — a view over the base, the mount table, and the request.
     pub(crate) fn uri_generator(&self) -> CanonicalUriGenerator<'sch, '_, Adapter> {
@@ -112,7 +184,7 @@ impl<'sch: 'req, 'req, Adapter: AdapterInterface> PrimaryContext<'sch, 'req, Ada
 
     /// Lazily acquires the request connection from the pool and lends it as a shared reference.
     pub fn connection(&self) -> Result<&Adapter::Connection, Error> {
-        LazyCell::force(&self.connection)
+        LazyCell::force(&*self.connection)
             .as_ref()
             .map_err(|error| error.clone())
     }
@@ -122,7 +194,17 @@ impl<'sch: 'req, 'req, Adapter: AdapterInterface> PrimaryContext<'sch, 'req, Ada
     }
 
     pub fn store(&self) -> Result<Store<'sch, '_, Adapter>, Error> {
-        Ok(Store::new(self.manager, self.connection()?))
+        Ok(Store::new(self.manager, self.connection()?).acting_as(self.actor()))
+    }
+
+    /// Names who makes this request — typically set by an authenticating middleware — so the audit
+    /// log records it against the writes the request makes.
+    pub fn act_as(&mut self, actor: impl Into<String>) {
+        self.actor = Some(actor.into());
+    }
+
+    pub fn actor(&self) -> Option<&str> {
+        self.actor.as_deref()
     }
 
     /// Runs `operation` inside a transaction on the request connection.
@@ -133,6 +215,38 @@ impl<'sch: 'req, 'req, Adapter: AdapterInterface> PrimaryContext<'sch, 'req, Ada
         self.connection()?.transaction(|| operation(self))
     }
 
+    /// Resolves a resource type a request names in its body to its mount and schema. A request
+    /// reaches only the types the router serves.
+    pub(crate) fn mount(
+        &self,
+        kind: &str,
+    ) -> Result<(&'req ResourceMount<'sch, Adapter>, &'sch Schema<'sch>), RoutingError> {
+        let mount =
+            self.mount_table
+                .get(kind)
+                .ok_or_else(|| RoutingError::UnservedResourceType {
+                    kind: kind.to_string(),
+                })?;
+        let schema = self.manager.registry().schema(mount.kind)?;
+        Ok((mount, schema))
+    }
+
+    /// Resolves a resource type whose resources a request writes, refusing one mounted read-only.
+    pub(crate) fn writable_mount(
+        &self,
+        kind: &str,
+    ) -> Result<(&'req ResourceMount<'sch, Adapter>, &'sch Schema<'sch>), RoutingError> {
+        let (mount, schema) = self.mount(kind)?;
+        if mount.read_only {
+            return Err(RoutingError::UnsupportedOperation);
+        }
+        Ok((mount, schema))
+    }
+
+    pub fn method(&self) -> &Method {
+        &self.method
+    }
+
     pub fn headers(&self) -> &HeaderMap {
         &self.headers
     }
@@ -141,13 +255,21 @@ impl<'sch: 'req, 'req, Adapter: AdapterInterface> PrimaryContext<'sch, 'req, Ada
         &self.route
     }
 
-    /// Parses this request's query string against `schema` — the hatch for a `QueryParameters` bound
-    /// to any schema (e.g. `related`'s related type). Uncached; the cached, own-schema query is the
-    /// `LazyCell` on `ResourceContext`.
+    /// The complexity limits a query against `schema` is held to: its resource's own, falling back
+    /// to the router-wide ones.
+    pub fn query_limits(&self, schema: &Schema) -> QueryLimits {
+        self.mount_table.query_limits(schema.name())
+    }
+
+    /// Parses this request's query string against `schema` and holds it to that schema's limits —
+    /// the hatch for a `QueryParameters` bound to any schema (e.g. `related`'s related type).
+    /// Uncached; the cached, own-schema query is the `LazyCell` on `ResourceContext`.
     pub fn parse_query(
         &self,
         schema: &'sch Schema<'sch>,
     ) -> Result<QueryParameters<'sch, 'req>, Error> {
-        QueryParameters::parse(self.uri, schema, self.manager.registry())
+        let parameters = QueryParameters::parse(self.uri, schema, self.manager.registry())?;
+        self.query_limits(schema).enforce(&parameters)?;
+        Ok(parameters)
     }
 }
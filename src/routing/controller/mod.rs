@@ -6,23 +6,32 @@ use std::ops::{Deref, DerefMut};
 use crate::{
     database::{
         adapters::Adapter as AdapterInterface,
-        attributes::{ForeignKeys, Identifier},
+        attributes::{Attribute, ForeignKeys, Identifier},
         composite::Composite,
+        connection::Connection as ConnectionInterface,
         error::Error as DatabaseError,
-        query_parameters::QueryParameters,
-        record::Record,
-        relationships::Relationship,
+        query_parameters::{FieldsParameters, IncludeParameters, QueryParameters},
+        record::{Record, RecordPatch, SearchExcerpts},
+        registry::Registry,
+        relationships::{LocalLinkage, Relationship, RelationshipCounts, Relationships},
         schema::{IdentifierType, RelationshipDescriptor, RelationshipKind, Schema},
     },
+    error::{Error as CrateError, pointer},
     http_wrappers::{StatusCode, Uri},
     json_api::{
         document::Document, identifier::Identifier as JsonApiIdentifier,
         primary_content::PrimaryContent, relationship::Linkage, resource::Resource,
     },
-    routing::{Error, PrimaryContext, ResourceResult, RouteParameters, responder::*},
-    serialisation::factories::{Content, to_document},
+    routing::{
+        Error, PrimaryContext, ResourceResult, RouteParameters,
+        preconditions::{EntityTags, tag_collection, tag_record},
+        responder::*,
+        router::route_at,
+    },
+    serialisation::factories::{Content, pagination_links, to_aggregate_document, to_document},
 };
-use http::HeaderMap;
+use http::{HeaderMap, Method, header::IF_MATCH};
+use indexmap::IndexSet;
 use itertools::Itertools;
 use std::borrow::Cow;
 use std::cell::LazyCell;
@@ -43,21 +52,46 @@ pub struct ResourceContext<'sch: 'req, 'req, Adapter: AdapterInterface + 'sch> {
     schema: &'sch Schema<'sch>,
     context: PrimaryContext<'sch, 'req, Adapter>,
     query_parameters: LazyQueryParameters<'sch, 'req>,
+    extensions: &'static [&'static str],
+    locals: LocalIdentifiers,
 }
 
 impl<'sch: 'req, 'req, Adapter: AdapterInterface + 'sch> ResourceContext<'sch, 'req, Adapter> {
     pub fn new(schema: &'sch Schema<'sch>, context: PrimaryContext<'sch, 'req, Adapter>) -> Self {
         let uri = context.uri;
         let registry = context.manager.registry();
+        let limits = context.query_limits(schema);
         Self {
             schema,
             context,
             query_parameters: LazyCell::new(Box::new(move || {
-                QueryParameters::parse(uri, schema, registry)
+                let parameters = QueryParameters::parse(uri, schema, registry)?;
+                limits.enforce(&parameters)?;
+                Ok(parameters)
             })),
+            extensions: &[],
+            locals: LocalIdentifiers::new(),
         }
     }
 
+    /// Binds the JSON:API extensions the endpoint serves, for the boundary to negotiate.
+    pub(crate) fn supporting(mut self, extensions: &'static [&'static str]) -> Self {
+        self.extensions = extensions;
+        self
+    }
+
+    /// Binds the `lid`s created earlier in an Atomic Operations request, for the operation's
+    /// document to name resources by.
+    pub(crate) fn resolving(mut self, locals: LocalIdentifiers) -> Self {
+        self.locals = locals;
+        self
+    }
+
+    /// The JSON:API extensions the endpoint serves; none for the default endpoints.
+    pub fn extensions(&self) -> &'static [&'static str] {
+        self.extensions
+    }
+
     pub fn schema(&self) -> &'sch Schema<'sch> {
         self.schema
     }
@@ -87,60 +121,155 @@ impl<'sch: 'req, 'req, Adapter: AdapterInterface + 'sch> ResourceContext<'sch, '
         serde_json::from_reader(body).map_err(Into::into)
     }
 
+    /// Reads request documents against the registry, resolving `locals` — the `lid`s of the
+    /// resources created before the request reads, which only an atomic operation has.
+    fn reader<'a>(&self, locals: &'a LocalIdentifiers) -> DocumentReader<'sch, 'a> {
+        DocumentReader::new(self.context.manager.registry(), locals)
+    }
+
     /// Parses the request body into a record validated against the resource schema.
     pub fn require_record(&mut self) -> std::result::Result<Record<'sch>, Error> {
         let schema = self.schema;
         let resource = self.require_resource()?;
-        let record = Record {
-            schema,
-            id: match resource.identifier {
-                JsonApiIdentifier::New { .. } => None,
-                identifier => Some(self.materialise_id(identifier, schema.name())?),
-            },
-            attributes: resource
-                .attributes
-                .unwrap_or_default()
-                .into_iter()
-                .map(|(name, value)| {
-                    let column =
-                        schema
-                            .attribute(&name)
-                            .ok_or_else(|| Error::UnknownAttribute {
-                                kind: schema.name().to_string(),
-                                attribute: name.clone(),
-                            })?;
+        self.reader(&self.locals).record(schema, resource)
+    }
 
-                    Ok((column.name, serde_json::from_value(value)?))
-                })
-                .try_collect::<_, _, Error>()?,
-            relationships: resource
-                .relationships
-                .unwrap_or_default()
-                .into_iter()
-                .map(|(name, relationship)| {
-                    let descriptor = schema.relationship(&name).ok_or_else(|| {
-                        DatabaseError::ResourceValidationFailure {
-                            schema: schema.name().to_string(),
-                            attribute: name,
-                            message: "Attempted to attach unknown relationship".to_string(),
-                        }
-                    })?;
-
-                    Ok((
-                        descriptor.name,
-                        self.require_relationship(relationship.data, descriptor)?,
-                    ))
-                })
-                .try_collect::<_, _, Error>()?,
-            foreign_keys: ForeignKeys::new(),
+    /// Parses a bulk create request's body, an array of resource objects, into the records it
+    /// creates. An error reading any one of them points at that member of the array.
+    pub fn require_collection(&mut self) -> std::result::Result<Vec<Record<'sch>>, CrateError> {
+        let schema = self.schema;
+        let document = self.parse_body()?.ok_or(Error::MissingResourceBody)?;
+        let resources = match document.content {
+            PrimaryContent::Collection { data } => data,
+            PrimaryContent::Errors { .. } => return Err(Error::ErrorDocumentSubmitted.into()),
+            PrimaryContent::Record { .. } | PrimaryContent::Empty { .. } => {
+                return Err(Error::PrimaryDataIsNotACollection.into());
+            }
+            PrimaryContent::Absent {} => return Err(Error::MissingResourceBody.into()),
         };
 
-        Ok(record)
+        let reader = self.reader(&self.locals);
+        resources
+            .into_iter()
+            .enumerate()
+            .map(|(index, resource)| {
+                let kind = match &resource.identifier {
+                    JsonApiIdentifier::Existing { kind, .. }
+                    | JsonApiIdentifier::New { kind, .. } => kind,
+                };
+                if kind != schema.name() {
+                    return Err(within_data(index)(Error::ResourceTypeMismatch {
+                        expected: schema.name().to_string(),
+                        actual: kind.to_string(),
+                    }));
+                }
+                reader.record(schema, resource).map_err(within_data(index))
+            })
+            .try_collect()
+    }
+
+    /// Parses a bulk update request's body into the patch it applies to every record its filter
+    /// matches. The resource object names no id; the filter alone names the records.
+    pub fn require_patch(&mut self) -> std::result::Result<RecordPatch<'sch>, Error> {
+        let record = self.require_record()?;
+        if let Some(id) = record.id {
+            return Err(Error::UnexpectedResourceId {
+                id: format!("{id}"),
+            });
+        }
+
+        Ok(RecordPatch {
+            schema: record.schema,
+            attributes: record.attributes,
+            relationships: record.relationships,
+        })
+    }
+
+    /// Parses a create request's body into the record it creates and the new resources its
+    /// `included` member carries to be created with it. Each included resource is named by a `lid`
+    /// that the record's linkage, or an included resource's after it, references.
+    pub fn require_records(
+        &mut self,
+    ) -> std::result::Result<(Record<'sch>, Vec<Record<'sch>>), CrateError> {
+        let schema = self.schema;
+        let (resource, included) = self.require_compound_resource()?;
+
+        let mut locals = self.locals.clone();
+        let schemas: Vec<_> = included
+            .iter()
+            .enumerate()
+            .map(|(index, resource)| {
+                self.included_schema(resource, &mut locals)
+                    .map_err(within_included(index))
+            })
+            .try_collect()?;
+        if let JsonApiIdentifier::New {
+            kind,
+            lid: Some(lid),
+        } = &resource.identifier
+            && locals.contains_key(&(kind.clone(), lid.clone()))
+        {
+            return Err(Error::DuplicateLocalIdentifier { lid: lid.clone() }.into());
+        }
+
+        let reader = self.reader(&locals);
+        let included = schemas
+            .into_iter()
+            .zip(included)
+            .enumerate()
+            .map(|(index, (schema, resource))| {
+                reader
+                    .record(schema, resource)
+                    .map_err(within_included(index))
+            })
+            .try_collect()?;
+
+        Ok((reader.record(schema, resource)?, included))
+    }
+
+    /// Resolves the schema of a resource included to be created, registering its `lid` as one to
+    /// be created. Its type must accept being created so, and its create route must run no
+    /// middleware this request has not.
+    fn included_schema(
+        &self,
+        resource: &Resource,
+        locals: &mut LocalIdentifiers,
+    ) -> std::result::Result<&'sch Schema<'sch>, Error> {
+        let JsonApiIdentifier::New {
+            kind,
+            lid: Some(lid),
+        } = &resource.identifier
+        else {
+            return Err(Error::IncludedResourceNotNew {
+                kind: resource.identifier.kind().to_string(),
+            });
+        };
+
+        let (mount, schema) = self.writable_mount(kind)?;
+        if !(mount.factory)().configuration().accepts_inclusion {
+            return Err(Error::InclusionNotAccepted { kind: kind.clone() });
+        }
+        if !route_at(self, &Method::POST, &mount.base).is_some_and(|route| route.wrapped_by(self)) {
+            return Err(Error::IncludedResourceBehindMiddleware { kind: kind.clone() });
+        }
+        if locals.insert((kind.clone(), lid.clone()), None).is_some() {
+            return Err(Error::DuplicateLocalIdentifier { lid: lid.clone() });
+        }
+        Ok(schema)
     }
 
     /// Extracts the request body as a single resource object, validating its type and — at a
     /// targeted endpoint — its id against the `:id` route parameter.
     pub fn require_resource(&mut self) -> std::result::Result<Resource, Error> {
+        self.require_compound_resource()
+            .map(|(resource, _)| resource)
+    }
+
+    /// Extracts the request body as `require_resource` does, together with the resources of its
+    /// `included` member.
+    fn require_compound_resource(
+        &mut self,
+    ) -> std::result::Result<(Resource, Vec<Resource>), Error> {
         let schema = self.schema;
         let document = self.parse_body()?.ok_or(Error::MissingResourceBody)?;
 
@@ -150,10 +279,11 @@ impl<'sch: 'req, 'req, Adapter: AdapterInterface + 'sch> ResourceContext<'sch, '
             PrimaryContent::Collection { .. } | PrimaryContent::Empty { .. } => {
                 return Err(Error::PrimaryDataIsNotAResource);
             }
+            PrimaryContent::Absent {} => return Err(Error::MissingResourceBody),
         };
 
         let (kind, id) = match &resource.identifier {
-            JsonApiIdentifier::Existing { kind, id } => (kind.as_str(), Some(id)),
+            JsonApiIdentifier::Existing { kind, id, .. } => (kind.as_str(), Some(id)),
             JsonApiIdentifier::New { kind, .. } => (kind.as_str(), None),
         };
 
@@ -181,68 +311,328 @@ impl<'sch: 'req, 'req, Adapter: AdapterInterface + 'sch> ResourceContext<'sch, '
             }
         }
 
-        Ok(resource)
+        Ok((resource, document.included.unwrap_or_default()))
+    }
+
+    /// Resolves request-supplied linkage against the relationship it targets; see
+    /// `DocumentReader::relationship`.
+    pub fn require_relationship(
+        &self,
+        linkage: Option<Linkage>,
+        descriptor: &RelationshipDescriptor<'sch>,
+    ) -> std::result::Result<Relationship, Error> {
+        self.reader(&self.locals)
+            .relationship(self.schema, linkage, descriptor)
+    }
+
+    /// Extracts the request body as relationship linkage, the counterpart of `require_resource` for
+    /// the relationship-endpoint family. Type and id validation against the target resource is
+    /// deferred to materialisation.
+    pub fn require_linkage(&mut self) -> std::result::Result<Linkage, Error> {
+        let document = self.parse_body()?.ok_or(Error::MissingLinkageBody)?;
+        DocumentReader::linkage(document.content)?.ok_or(Error::MissingLinkageBody)
+    }
+
+    /// Checks the request's `If-Match` precondition against the version the record at `id` is at
+    /// now, yielding that version once it matches. A request setting no precondition, or one on an
+    /// unversioned resource, yields none.
+    pub fn require_precondition(&self, id: &Identifier) -> std::result::Result<Option<i64>, Error> {
+        let Some(condition) = EntityTags::parse(self.headers(), IF_MATCH)? else {
+            return Ok(None);
+        };
+
+        let version = self.store()?.fetch_version(self.schema, id.clone())?;
+        if !condition.matches(version) {
+            return Err(Error::PreconditionFailed);
+        }
+        Ok(version)
+    }
+
+    /// Resolves the endpoint's `:id` route parameter into a typed primary key.
+    pub fn require_id(&self) -> std::result::Result<Identifier, Error> {
+        let parameters = self.route_parameters();
+        let identifier = match self.schema.primary_key().kind {
+            IdentifierType::Text => Identifier::Text(parameters.require_as("id")?),
+            IdentifierType::Integer => Identifier::Integer(parameters.require_as("id")?),
+        };
+
+        Ok(identifier)
+    }
+}
+
+/// Re-points an error raised reading the included resource at `index` at that resource.
+fn within_included(index: usize) -> impl Fn(Error) -> CrateError {
+    move |error| {
+        let mut error = CrateError::from(error);
+        let source = error.source.take().map(|source| *source);
+        error.source = Some(Box::new(pointer::within_included(index, source)));
+        error
+    }
+}
+
+/// Re-points an error raised over the member at `index` of the primary data at that member.
+fn within_data<E: Into<CrateError>>(index: usize) -> impl Fn(E) -> CrateError {
+    move |error| {
+        let mut error = error.into();
+        let source = error.source.take().map(|source| *source);
+        error.source = Some(Box::new(pointer::within_data(index, source)));
+        error
+    }
+}
+
+/// Refuses a bulk update or deletion its query does not scope by a filter or search, which would
+/// otherwise write every record of the resource.
+fn require_scope(parameters: &QueryParameters) -> std::result::Result<(), Error> {
+    if parameters.filter.is_none()
+        && parameters.relationship_filter.is_none()
+        && parameters.filter_expression.is_none()
+        && parameters.search.is_none()
+    {
+        return Err(Error::UnscopedBulkWrite);
+    }
+
+    Ok(())
+}
+
+/// Reads the ids of the records `scope` selects, unpaged and without any other column, refusing
+/// more than `limit` of them before a bulk write touches any.
+fn scope_ids<'sch, 'req, Adapter: AdapterInterface>(
+    context: &ResourceContext<'sch, 'req, Adapter>,
+    scope: &QueryParameters<'sch, 'req>,
+    limit: usize,
+) -> std::result::Result<Vec<Identifier>, CrateError> {
+    let schema = scope.schema;
+    let keys = QueryParameters {
+        fields: FieldsParameters::from_iter([(schema.name(), IndexSet::new())]),
+        ..scope.clone()
+    };
+    let ids = context
+        .store()?
+        .fetch_collection(schema, &keys)?
+        .content
+        .iter()
+        .map(|record| record.require_id().cloned())
+        .collect::<std::result::Result<Vec<_>, _>>()?;
+    if ids.len() > limit {
+        return Err(Error::BulkScopeTooLarge {
+            limit,
+            actual: ids.len(),
+        }
+        .into());
+    }
+
+    Ok(ids)
+}
+
+/// The resources a request creates, keyed by their type and `lid`: the id each was assigned once
+/// created, or none while it is still to be created by the store alongside the resource linking it.
+pub(crate) type LocalIdentifiers = HashMap<(String, String), Option<Identifier>>;
+
+/// A linkage identifier read against the request's local identifiers: resolved to a typed key, or
+/// deferred as the `lid` of a resource the store is yet to create.
+enum LinkageTarget {
+    Resolved(Identifier),
+    Pending(String),
+}
+
+/// Reads request-supplied resource objects and linkage into the records and typed keys the store
+/// takes. Every failure validates client input, so every failure is a routing error. A `lid`
+/// resolves against the resources the request created before it, or is deferred to the store when
+/// it names one the request has yet to create; one naming neither cannot.
+pub(crate) struct DocumentReader<'sch, 'a> {
+    registry: &'sch Registry<'sch>,
+    locals: &'a LocalIdentifiers,
+}
+
+impl<'sch, 'a> DocumentReader<'sch, 'a> {
+    pub(crate) fn new(registry: &'sch Registry<'sch>, locals: &'a LocalIdentifiers) -> Self {
+        Self { registry, locals }
+    }
+
+    /// Reads a resource object into a record of `schema`. A resource carrying no id, or a `lid`
+    /// no earlier resource was created under, yields a record without one. Linkage to resources
+    /// yet to be created is kept apart, as the record's local linkage.
+    pub(crate) fn record(
+        &self,
+        schema: &'sch Schema<'sch>,
+        resource: Resource,
+    ) -> std::result::Result<Record<'sch>, Error> {
+        let lid = match &resource.identifier {
+            JsonApiIdentifier::New { lid, .. } => lid.clone(),
+            JsonApiIdentifier::Existing { .. } => None,
+        };
+        let id = match resource.identifier {
+            JsonApiIdentifier::New {
+                ref kind,
+                lid: Some(ref lid),
+            } if self
+                .locals
+                .get(&(kind.clone(), lid.clone()))
+                .is_some_and(Option::is_some) =>
+            {
+                Some(self.id(resource.identifier, schema.name())?)
+            }
+            JsonApiIdentifier::New { .. } => None,
+            identifier => Some(self.id(identifier, schema.name())?),
+        };
+
+        let mut relationships = Relationships::new();
+        let mut local_linkage = LocalLinkage::new();
+        for (name, relationship) in resource.relationships.unwrap_or_default() {
+            let descriptor = schema.relationship(&name).ok_or_else(|| {
+                DatabaseError::ResourceValidationFailure {
+                    schema: schema.name().to_string(),
+                    attribute: name,
+                    message: "Attempted to attach unknown relationship".to_string(),
+                }
+            })?;
+
+            let (relationship, pending) =
+                self.linkage_targets(schema, relationship.data, descriptor)?;
+            // Linkage wholly deferred to a resource yet to be created leaves a to-one relationship
+            // for the store to fill in, rather than clearing it.
+            if pending.is_empty() || descriptor.kind == RelationshipKind::HasMany {
+                relationships.insert(descriptor.name, relationship);
+            }
+            if !pending.is_empty() {
+                local_linkage.insert(descriptor.name, pending);
+            }
+        }
+
+        let record = Record {
+            schema,
+            id,
+            attributes: resource
+                .attributes
+                .unwrap_or_default()
+                .into_iter()
+                .map(|(name, value)| {
+                    let column =
+                        schema
+                            .attribute(&name)
+                            .ok_or_else(|| Error::UnknownAttribute {
+                                kind: schema.name().to_string(),
+                                attribute: name.clone(),
+                            })?;
+
+                    Ok((column.name, serde_json::from_value(value)?))
+                })
+                .try_collect::<_, _, Error>()?,
+            relationships,
+            local_linkage,
+            lid,
+            counts: RelationshipCounts::new(),
+            excerpts: SearchExcerpts::default(),
+            foreign_keys: ForeignKeys::new(),
+        };
+
+        Ok(record)
     }
 
-    /// Resolves request-supplied linkage against the relationship it targets, materialising its
+    /// Resolves linkage against the relationship of `owner` it targets, materialising its
     /// identifiers into the typed keys the record layer stores. Absent and explicitly null linkage
     /// alike clear the relationship; linkage whose cardinality contradicts the relationship's
-    /// direction is rejected.
-    pub fn require_relationship(
+    /// direction is rejected, as is linkage to a resource not created yet.
+    pub(crate) fn relationship(
         &self,
+        owner: &Schema<'sch>,
         linkage: Option<Linkage>,
         descriptor: &RelationshipDescriptor<'sch>,
     ) -> std::result::Result<Relationship, Error> {
-        let related = &descriptor.related;
-        let relationship = match (linkage, descriptor.kind) {
+        let (relationship, pending) = self.linkage_targets(owner, linkage, descriptor)?;
+        match pending.into_iter().next() {
+            Some(lid) => Err(Error::UnknownLocalIdentifier { lid }),
+            None => Ok(relationship),
+        }
+    }
+
+    /// `relationship`, deferring rather than rejecting linkage to resources not created yet: it
+    /// yields their `lid`s beside the relationship their resolved peers make up.
+    fn linkage_targets(
+        &self,
+        owner: &Schema<'sch>,
+        linkage: Option<Linkage>,
+        descriptor: &RelationshipDescriptor<'sch>,
+    ) -> std::result::Result<(Relationship, Vec<String>), Error> {
+        let related = descriptor.related.resource;
+        let to_one = |identifier, relationship: fn(Identifier) -> Relationship| {
+            Ok(match self.target(identifier, related)? {
+                LinkageTarget::Resolved(id) => (relationship(id), Vec::new()),
+                LinkageTarget::Pending(lid) => (Relationship::Empty, vec![lid]),
+            })
+        };
+
+        match (linkage, descriptor.kind) {
             (Some(Linkage::ToOne(identifier)), RelationshipKind::HasOne) => {
-                Relationship::HasOne(self.materialise_id(identifier, related.resource)?)
+                to_one(identifier, Relationship::HasOne)
             }
             (Some(Linkage::ToOne(identifier)), RelationshipKind::BelongsTo) => {
-                Relationship::BelongsTo(self.materialise_id(identifier, related.resource)?)
+                to_one(identifier, Relationship::BelongsTo)
             }
-            (Some(Linkage::ToMany(ids)), RelationshipKind::HasMany) => Relationship::HasMany(
-                ids.into_iter()
-                    .map(|identifier| self.materialise_id(identifier, related.resource))
-                    .try_collect()?,
-            ),
-            (None | Some(Linkage::Empty), _) => Relationship::Empty,
+            (Some(Linkage::ToMany(identifiers)), RelationshipKind::HasMany) => {
+                let (mut ids, mut pending) = (Vec::new(), Vec::new());
+                for identifier in identifiers {
+                    match self.target(identifier, related)? {
+                        LinkageTarget::Resolved(id) => ids.push(id),
+                        LinkageTarget::Pending(lid) => pending.push(lid),
+                    }
+                }
+                Ok((Relationship::HasMany(ids), pending))
+            }
+            (None | Some(Linkage::Empty), _) => Ok((Relationship::Empty, Vec::new())),
 
             (Some(Linkage::ToOne(_)), RelationshipKind::HasMany)
             | (Some(Linkage::ToMany(_)), RelationshipKind::HasOne | RelationshipKind::BelongsTo) => {
                 Err(DatabaseError::ResourceValidationFailure {
-                    schema: self.schema.name().to_string(),
+                    schema: owner.name().to_string(),
                     attribute: descriptor.name.to_string(),
                     message: "Attempted to attach relationship with wrong linkage".to_string(),
                 })?
             }
-        };
-
-        Ok(relationship)
+        }
     }
 
-    /// Extracts the request body as relationship linkage, the counterpart of `require_resource` for
-    /// the relationship-endpoint family. Each resource object must be a bare identifier: carrying
-    /// attributes, relationships, or links makes it a resource rather than linkage and is rejected.
-    /// Type and id validation against the target resource is deferred to materialisation.
-    pub fn require_linkage(&mut self) -> std::result::Result<Linkage, Error> {
-        let document = self.parse_body()?.ok_or(Error::MissingLinkageBody)?;
+    /// Reads one linkage identifier, deferring a `lid` that names a resource yet to be created.
+    fn target(
+        &self,
+        identifier: JsonApiIdentifier,
+        related: &str,
+    ) -> std::result::Result<LinkageTarget, Error> {
+        match identifier {
+            JsonApiIdentifier::New {
+                ref kind,
+                lid: Some(ref lid),
+            } if kind.as_str() == related
+                && matches!(self.locals.get(&(kind.clone(), lid.clone())), Some(None)) =>
+            {
+                Ok(LinkageTarget::Pending(lid.clone()))
+            }
+            identifier => self.id(identifier, related).map(LinkageTarget::Resolved),
+        }
+    }
 
-        match document.content {
-            PrimaryContent::Empty { .. } => Ok(Linkage::Empty),
-            PrimaryContent::Record { data } => Ok(Linkage::ToOne(Self::require_identifier(*data)?)),
-            PrimaryContent::Collection { data } => Ok(Linkage::ToMany(
+    /// Reads primary content as relationship linkage; absent content is no linkage at all. Each
+    /// resource object must be a bare identifier: carrying attributes, relationships, or links makes
+    /// it a resource rather than linkage and is rejected.
+    pub(crate) fn linkage(content: PrimaryContent) -> std::result::Result<Option<Linkage>, Error> {
+        match content {
+            PrimaryContent::Empty { .. } => Ok(Some(Linkage::Empty)),
+            PrimaryContent::Record { data } => {
+                Ok(Some(Linkage::ToOne(Self::linkage_identifier(*data)?)))
+            }
+            PrimaryContent::Collection { data } => Ok(Some(Linkage::ToMany(
                 data.into_iter()
-                    .map(Self::require_identifier)
+                    .map(Self::linkage_identifier)
                     .try_collect()?,
-            )),
+            ))),
             PrimaryContent::Errors { .. } => Err(Error::ErrorDocumentSubmitted),
+            PrimaryContent::Absent {} => Ok(None),
         }
     }
 
     /// Unwraps a resource object into its identifier, asserting it is a resource identifier object
     /// — no attributes, relationships, or links. Meta is permitted and discarded.
-    fn require_identifier(resource: Resource) -> std::result::Result<JsonApiIdentifier, Error> {
+    fn linkage_identifier(resource: Resource) -> std::result::Result<JsonApiIdentifier, Error> {
         if let Resource {
             identifier,
             attributes: None,
@@ -257,19 +647,31 @@ impl<'sch: 'req, 'req, Adapter: AdapterInterface + 'sch> ResourceContext<'sch, '
         }
     }
 
-    /// Resolves a request-supplied identifier into a typed primary key. As it validates client
-    /// input, every failure is a routing error: a `New` (`lid`) identifier has no id to resolve, a
-    /// mismatched type cannot name the expected resource, and a non-integer id cannot be parsed.
-    fn materialise_id(
+    /// Resolves an identifier of the resource type `schema` into a typed primary key: an `id` by
+    /// parsing it, a `lid` by the id its resource was created under. A mismatched type cannot name
+    /// the expected resource, a non-integer id cannot be parsed, and a bare `New` identifier names
+    /// nothing.
+    pub(crate) fn id(
         &self,
         identifier: JsonApiIdentifier,
         schema: &str,
     ) -> std::result::Result<Identifier, Error> {
-        let schema = self.context.manager.registry().schema(schema)?;
+        let schema = self.registry.schema(schema)?;
         let identifier = match identifier {
-            JsonApiIdentifier::Existing { kind, id } if kind.as_str() == schema.name() => id,
-            JsonApiIdentifier::New { .. } => return Err(Error::UnresolvableIdentifier),
-            JsonApiIdentifier::Existing { kind, .. } => {
+            JsonApiIdentifier::Existing { kind, id, .. } if kind.as_str() == schema.name() => id,
+            JsonApiIdentifier::New {
+                kind,
+                lid: Some(lid),
+            } if kind.as_str() == schema.name() => {
+                return self
+                    .locals
+                    .get(&(kind, lid.clone()))
+                    .cloned()
+                    .flatten()
+                    .ok_or(Error::UnknownLocalIdentifier { lid });
+            }
+            JsonApiIdentifier::New { lid: None, .. } => return Err(Error::UnresolvableIdentifier),
+            JsonApiIdentifier::Existing { kind, .. } | JsonApiIdentifier::New { kind, .. } => {
                 return Err(Error::IdentifierTypeMismatch {
                     expected: schema.name().to_string(),
                     actual: kind,
@@ -286,17 +688,6 @@ impl<'sch: 'req, 'req, Adapter: AdapterInterface + 'sch> ResourceContext<'sch, '
             }),
         }
     }
-
-    /// Resolves the endpoint's `:id` route parameter into a typed primary key.
-    pub fn require_id(&self) -> std::result::Result<Identifier, Error> {
-        let parameters = self.route_parameters();
-        let identifier = match self.schema.primary_key().kind {
-            IdentifierType::Text => Identifier::Text(parameters.require_as("id")?),
-            IdentifierType::Integer => Identifier::Integer(parameters.require_as("id")?),
-        };
-
-        Ok(identifier)
-    }
 }
 
 impl<'sch: 'req, 'req, Adapter: AdapterInterface + 'sch> Deref
@@ -318,16 +709,42 @@ impl<'sch: 'req, 'req, Adapter: AdapterInterface + 'sch> DerefMut
 }
 
 /// A controller's behaviour configuration: the knobs it exposes to shape how the framework serves
-/// its resource. Expands as new hooks are added; today it governs only client-generated ids.
+/// its resource. Expands as new hooks are added; today it governs client-generated ids, upserts and
+/// creation from another resource's `included`.
 #[derive(Default)]
 pub struct Configuration {
     /// Whether a create request may carry a client-generated id. When false the server assigns
     /// every id and a client-supplied id is refused with 403 Forbidden.
     pub accepts_client_ids: bool,
+    /// Whether a `PUT` to a member creates the record it names when none exists, rather than
+    /// updating it only. Creating under the request's id needs `accepts_client_ids` as well.
+    pub upserts: bool,
+    /// Whether a resource of this type may be created from the `included` member of another's
+    /// create document. The store creates it with the record including it, running none of this
+    /// controller's handlers or hooks, so it is refused with 403 Forbidden unless set.
+    pub accepts_inclusion: bool,
+}
+
+/// The change a relationship endpoint makes to a record's linkage.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LinkageChange {
+    /// A `POST` adding members to a to-many relationship.
+    Add,
+    /// A `DELETE` removing members from a to-many relationship.
+    Remove,
+    /// A `PATCH` replacing a relationship's linkage outright.
+    Replace,
 }
 
 /// The behaviour served at a resource's endpoints. Every method defaults to the framework's
 /// serving; an implementor overrides only the endpoints and configuration it customises.
+///
+/// The lifecycle hooks (`before_*` / `after_*`) run inside the write's transaction, so an error
+/// one returns aborts the write and rolls back everything made before it. A `before_*` hook may
+/// amend what is about to be written. They run wherever the default handlers write: a bulk create
+/// runs the create hooks for each member, a bulk update runs `before_update_many` on its patch and
+/// `after_update` for each record it changed, and a bulk delete the delete hooks for each record it
+/// removes. The Atomic Operations endpoint serves each operation through these handlers too.
 pub trait ResourceController<'sch, Adapter: AdapterInterface + 'sch> {
     /// This controller's behaviour configuration; override to opt out of the framework defaults.
     fn configuration(&self) -> Configuration {
@@ -368,17 +785,144 @@ pub trait ResourceController<'sch, Adapter: AdapterInterface + 'sch> {
             .collect()
     }
 
+    /// Runs before `record` is created; may amend it.
+    fn before_create<'req>(
+        &self,
+        _context: &ResourceContext<'sch, 'req, Adapter>,
+        _record: &mut Record<'sch>,
+    ) -> Result<(), CrateError>
+    where
+        'sch: 'req,
+    {
+        Ok(())
+    }
+
+    /// Runs once `record` has been created.
+    fn after_create<'req>(
+        &self,
+        _context: &ResourceContext<'sch, 'req, Adapter>,
+        _record: &Record<'sch>,
+    ) -> Result<(), CrateError>
+    where
+        'sch: 'req,
+    {
+        Ok(())
+    }
+
+    /// Runs before `record` — the members an update writes, under the id it updates — is written;
+    /// may amend it.
+    fn before_update<'req>(
+        &self,
+        _context: &ResourceContext<'sch, 'req, Adapter>,
+        _record: &mut Record<'sch>,
+    ) -> Result<(), CrateError>
+    where
+        'sch: 'req,
+    {
+        Ok(())
+    }
+
+    /// Runs once `record` has been updated.
+    fn after_update<'req>(
+        &self,
+        _context: &ResourceContext<'sch, 'req, Adapter>,
+        _record: &Record<'sch>,
+    ) -> Result<(), CrateError>
+    where
+        'sch: 'req,
+    {
+        Ok(())
+    }
+
+    /// Runs before a bulk update applies `patch` to the records its `filter` matches; may amend it.
+    fn before_update_many<'req>(
+        &self,
+        _context: &ResourceContext<'sch, 'req, Adapter>,
+        _patch: &mut RecordPatch<'sch>,
+    ) -> Result<(), CrateError>
+    where
+        'sch: 'req,
+    {
+        Ok(())
+    }
+
+    /// Runs before the record at `id` is deleted.
+    fn before_delete<'req>(
+        &self,
+        _context: &ResourceContext<'sch, 'req, Adapter>,
+        _id: &Identifier,
+    ) -> Result<(), CrateError>
+    where
+        'sch: 'req,
+    {
+        Ok(())
+    }
+
+    /// Runs once the record at `id` has been deleted.
+    fn after_delete<'req>(
+        &self,
+        _context: &ResourceContext<'sch, 'req, Adapter>,
+        _id: &Identifier,
+    ) -> Result<(), CrateError>
+    where
+        'sch: 'req,
+    {
+        Ok(())
+    }
+
+    /// Runs before a relationship endpoint applies `change` to the `relationship` of the record at
+    /// `id`, with `targets` the linkage it names; may amend them.
+    fn before_relationship_change<'req>(
+        &self,
+        _context: &ResourceContext<'sch, 'req, Adapter>,
+        _id: &Identifier,
+        _relationship: &'sch str,
+        _change: LinkageChange,
+        _targets: &mut Relationship,
+    ) -> Result<(), CrateError>
+    where
+        'sch: 'req,
+    {
+        Ok(())
+    }
+
+    /// Runs once a relationship endpoint has applied `change` to the `relationship` of the record at
+    /// `id`, with `targets` the linkage it applied.
+    fn after_relationship_change<'req>(
+        &self,
+        _context: &ResourceContext<'sch, 'req, Adapter>,
+        _id: &Identifier,
+        _relationship: &'sch str,
+        _change: LinkageChange,
+        _targets: &Relationship,
+    ) -> Result<(), CrateError>
+    where
+        'sch: 'req,
+    {
+        Ok(())
+    }
+
     fn index<'req>(&self, context: ResourceContext<'sch, 'req, Adapter>) -> ResourceResult
     where
         'sch: 'req,
     {
         let parameters = context.query_parameters()?;
+        if let Some(aggregate) = &parameters.aggregate {
+            let rows =
+                context
+                    .store()?
+                    .aggregate_collection(context.schema(), aggregate, parameters)?;
+            return respond(Some(to_aggregate_document(rows, aggregate, context.uri())));
+        }
+
         let Composite { content, included } = context
             .store()?
             .fetch_collection(context.schema(), parameters)?;
         let document = to_document(&content, included, context.uri(), &context.uri_generator())?;
 
-        respond(Some(document))
+        let mut response = respond(Some(document))?;
+        tag_collection(&mut response, context.schema(), &content)?;
+        Ok(response)
     }
 
     fn show<'req>(&self, context: ResourceContext<'sch, 'req, Adapter>) -> ResourceResult
@@ -393,14 +937,16 @@ pub trait ResourceController<'sch, Adapter: AdapterInterface + 'sch> {
                 .fetch_record(context.schema(), id, parameters)?;
         let document = to_document(&content, included, context.uri(), &context.uri_generator())?;
 
-        respond(Some(document))
+        let mut response = respond(Some(document))?;
+        tag_record(&mut response, &content)?;
+        Ok(response)
     }
 
     fn create<'req>(&self, mut context: ResourceContext<'sch, 'req, Adapter>) -> ResourceResult
     where
         'sch: 'req,
     {
-        let record = context.require_record()?;
+        let (mut record, included) = context.require_records()?;
 
         if record.id.is_some() && !self.configuration().accepts_client_ids {
             return Err(Error::ClientGeneratedIdNotSupported {
@@ -410,7 +956,14 @@ pub trait ResourceController<'sch, Adapter: AdapterInterface + 'sch> {
         }
 
         let parameters = context.query_parameters()?;
-        let Composite { content, included } = context.store()?.create_record(record, parameters)?;
+        let Composite { content, included } = context.connection()?.transaction_with(|| {
+            self.before_create(&context, &mut record)?;
+            let created = context
+                .store()?
+                .create_record(record, included, parameters)?;
+            self.after_create(&context, &created.content)?;
+            Ok::<_, CrateError>(created)
+        })?;
         let document = to_document(&content, included, context.uri(), &context.uri_generator())?;
 
         respond_with(StatusCode::CREATED, Some(document))
@@ -420,12 +973,88 @@ pub trait ResourceController<'sch, Adapter: AdapterInterface + 'sch> {
     where
         'sch: 'req,
     {
-        let record = context.require_record()?;
+        let mut record = context.require_record()?;
         let parameters = context.query_parameters()?;
-        let Composite { content, included } = context.store()?.update_record(record, parameters)?;
+        // An update made under `If-Match` is made against the version it matched, unless its
+        // document names a version of its own.
+        let Composite { content, included } = context.connection()?.transaction_with(|| {
+            if let Some(current) = context.require_precondition(record.require_id()?)?
+                && let Some(version) = context.schema().version()
+            {
+                record
+                    .attributes
+                    .entry(version)
+                    .or_insert(Attribute::Integer(current));
+            }
+            self.before_update(&context, &mut record)?;
+            let updated = context.store()?.update_record(record, parameters)?;
+            self.after_update(&context, &updated.content)?;
+            Ok::<_, CrateError>(updated)
+        })?;
         let document = to_document(&content, included, context.uri(), &context.uri_generator())?;
 
-        respond(Some(document))
+        let mut response = respond(Some(document))?;
+        tag_record(&mut response, &content)?;
+        Ok(response)
+    }
+
+    /// Serves a member's `PUT`: an update, or — with `upserts` configured — a create-or-update,
+    /// answered `201 Created` when it created the record and `200 OK` when it updated one.
+    fn upsert<'req>(&self, mut context: ResourceContext<'sch, 'req, Adapter>) -> ResourceResult
+    where
+        'sch: 'req,
+    {
+        let configuration = self.configuration();
+        if !configuration.upserts {
+            return self.update(context);
+        }
+
+        let mut record = context.require_record()?;
+        if !configuration.accepts_client_ids {
+            return Err(Error::ClientGeneratedIdNotSupported {
+                kind: record.schema.name().to_string(),
+            }
+            .into());
+        }
+
+        let parameters = context.query_parameters()?;
+        let (Composite { content, included }, created) =
+            context.connection()?.transaction_with(|| {
+                if let Some(current) = context.require_precondition(record.require_id()?)?
+                    && let Some(version) = context.schema().version()
+                {
+                    record
+                        .attributes
+                        .entry(version)
+                        .or_insert(Attribute::Integer(current));
+                }
+                let (upserted, created) =
+                    context
+                        .store()?
+                        .upsert_record_with(record, parameters, |record, exists| {
+                            if exists {
+                                self.before_update(&context, record)
+                            } else {
+                                self.before_create(&context, record)
+                            }
+                        })?;
+                if created {
+                    self.after_create(&context, &upserted.content)?;
+                } else {
+                    self.after_update(&context, &upserted.content)?;
+                }
+                Ok::<_, CrateError>((upserted, created))
+            })?;
+        let document = to_document(&content, included, context.uri(), &context.uri_generator())?;
+
+        let status = if created {
+            StatusCode::CREATED
+        } else {
+            StatusCode::OK
+        };
+        let mut response = respond_with(status, Some(document))?;
+        tag_record(&mut response, &content)?;
+        Ok(response)
     }
 
     fn delete<'req>(&self, context: ResourceContext<'sch, 'req, Adapter>) -> ResourceResult
@@ -433,7 +1062,137 @@ pub trait ResourceController<'sch, Adapter: AdapterInterface + 'sch> {
         'sch: 'req,
     {
         let id = context.require_id()?;
-        context.store()?.delete_record(context.schema(), id)?;
+        context.connection()?.transaction_with(|| {
+            context.require_precondition(&id)?;
+            self.before_delete(&context, &id)?;
+            context
+                .store()?
+                .delete_record(context.schema(), id.clone())?;
+            self.after_delete(&context, &id)?;
+            Ok::<_, CrateError>(())
+        })?;
+
+        no_content()
+    }
+
+    /// Serves a bulk create: every resource of the request's array is created, all or none of
+    /// them, and at most `limit` at once.
+    fn create_many<'req>(
+        &self,
+        mut context: ResourceContext<'sch, 'req, Adapter>,
+        limit: usize,
+    ) -> ResourceResult
+    where
+        'sch: 'req,
+    {
+        let mut records = context.require_collection()?;
+        if records.len() > limit {
+            return Err(Error::BatchTooLarge {
+                limit,
+                actual: records.len(),
+            }
+            .into());
+        }
+
+        if !self.configuration().accepts_client_ids
+            && let Some((index, record)) =
+                records.iter().find_position(|record| record.id.is_some())
+        {
+            return Err(within_data(index)(Error::ClientGeneratedIdNotSupported {
+                kind: record.schema.name().to_string(),
+            }));
+        }
+
+        // Each member is inserted on its own, so a failure the store raises names its member; the
+        // included resources are loaded for the whole collection once every member is in.
+        let parameters = context.query_parameters()?;
+        let members = QueryParameters {
+            include: IncludeParameters::new(),
+            ..parameters.clone()
+        };
+        let (content, included) = context.connection()?.transaction_with(|| {
+            for (index, record) in records.iter_mut().enumerate() {
+                self.before_create(&context, record)
+                    .map_err(within_data(index))?;
+            }
+            let store = context.store()?;
+            let mut created = Vec::with_capacity(records.len());
+            for (index, record) in records.into_iter().enumerate() {
+                let member = store
+                    .create_record(record, Vec::new(), &members)
+                    .map_err(within_data(index))?;
+                created.push(member.content);
+            }
+            let included = store.load_included(&mut created, parameters)?;
+            for (index, record) in created.iter().enumerate() {
+                self.after_create(&context, record)
+                    .map_err(within_data(index))?;
+            }
+            Ok::<_, CrateError>((created, included))
+        })?;
+        let document = to_document(&content, included, context.uri(), &context.uri_generator())?;
+
+        respond_with(StatusCode::CREATED, Some(document))
+    }
+
+    /// Serves a bulk update: the request's patch is applied to every record its `filter` matches,
+    /// all or none of them, and to at most `limit`.
+    fn update_many<'req>(
+        &self,
+        mut context: ResourceContext<'sch, 'req, Adapter>,
+        limit: usize,
+    ) -> ResourceResult
+    where
+        'sch: 'req,
+    {
+        let mut patch = context.require_patch()?;
+        let parameters = context.query_parameters()?;
+        require_scope(parameters)?;
+
+        // The scope is counted first, so an oversized one is refused before anything is written.
+        let scope = parameters.scope_of();
+        let Composite { content, included } = context.connection()?.transaction_with(|| {
+            scope_ids(&context, &scope, limit)?;
+            self.before_update_many(&context, &mut patch)?;
+            let updated = context.store()?.update_collection(patch, parameters)?;
+            for record in &updated.content {
+                self.after_update(&context, record)?;
+            }
+            Ok::<_, CrateError>(updated)
+        })?;
+        let document = to_document(&content, included, context.uri(), &context.uri_generator())?;
+
+        respond(Some(document))
+    }
+
+    /// Serves a bulk deletion: every record the request's `filter` matches is deleted, all or none
+    /// of them, and at most `limit`.
+    fn delete_many<'req>(
+        &self,
+        context: ResourceContext<'sch, 'req, Adapter>,
+        limit: usize,
+    ) -> ResourceResult
+    where
+        'sch: 'req,
+    {
+        let parameters = context.query_parameters()?;
+        require_scope(parameters)?;
+
+        // The ids in scope are read first, so each record's hooks run around the deletion.
+        let schema = context.schema();
+        let scope = parameters.scope_of();
+        context.connection()?.transaction_with(|| {
+            let ids = scope_ids(&context, &scope, limit)?;
+            let store = context.store()?;
+            for id in &ids {
+                self.before_delete(&context, id)?;
+            }
+            store.delete_collection(schema, &scope)?;
+            for id in &ids {
+                self.after_delete(&context, id)?;
+            }
+            Ok::<_, CrateError>(())
+        })?;
 
         no_content()
     }
@@ -465,20 +1224,36 @@ pub trait ResourceController<'sch, Adapter: AdapterInterface + 'sch> {
             .fetch_record(schema, id, &QueryParameters::new(schema))?
             .content;
 
+        // A to-many linkage is filtered, sorted and paged like the related collection, and links
+        // its neighbouring pages when paged.
+        let mut pagination = None;
         let content: Content<'sch, 'req> = match descriptor.kind {
-            RelationshipKind::HasMany => store
-                .peek_related_collection(&parent, relationship)?
-                .into_iter()
-                .map(|id| JsonApiIdentifier::from((id, related_schema)))
-                .collect::<Vec<_>>()
-                .into(),
+            RelationshipKind::HasMany => {
+                let parameters = context.context.parse_query(related_schema)?;
+                if let Some(page) = &parameters.page {
+                    let total =
+                        store.count_related_collection(&parent, relationship, &parameters)?;
+                    pagination = Some(pagination_links(context.uri(), page, total)?);
+                }
+
+                store
+                    .peek_related_collection_with(&parent, relationship, parameters)?
+                    .into_iter()
+                    .map(|id| JsonApiIdentifier::from((id, related_schema)))
+                    .collect::<Vec<_>>()
+                    .into()
+            }
             RelationshipKind::BelongsTo | RelationshipKind::HasOne => store
                 .peek_related_record(&parent, relationship)?
                 .map(|id| JsonApiIdentifier::from((id, related_schema)))
                 .into(),
         };
 
-        let document = to_document(content, Vec::new(), context.uri(), &context.uri_generator())?;
+        let mut document =
+            to_document(content, Vec::new(), context.uri(), &context.uri_generator())?;
+        if let Some(links) = &mut document.links {
+            links.pagination = pagination;
+        }
 
         respond(Some(document))
     }
@@ -511,7 +1286,7 @@ pub trait ResourceController<'sch, Adapter: AdapterInterface + 'sch> {
         let parent = store.fetch_record(schema, id, &parameters)?.content;
 
         let uri = context.uri();
-        let related_parameters = QueryParameters::parse(uri, related_schema, registry)?;
+        let related_parameters = context.context.parse_query(related_schema)?;
 
         let generator = context.uri_generator();
         let document = match descriptor.kind {
@@ -556,25 +1331,35 @@ pub trait ResourceController<'sch, Adapter: AdapterInterface + 'sch> {
 
         let id = context.require_id()?;
         let linkage = context.require_linkage()?;
-        let targets = match context.require_relationship(Some(linkage), descriptor)? {
-            Relationship::HasMany(identifiers) => identifiers,
-            Relationship::Empty => Vec::new(),
-            Relationship::BelongsTo(_) | Relationship::HasOne(_) => {
-                return Err(DatabaseError::MismatchedRelationshipKind {
-                    schema: schema.name().to_string(),
-                    relationship: relationship.to_string(),
+        let mut target = context.require_relationship(Some(linkage), descriptor)?;
+
+        let linked = context.connection()?.transaction_with(|| {
+            let change = LinkageChange::Add;
+            let version = context.require_precondition(&id)?;
+            context.store()?.bump_version(schema, id.clone(), version)?;
+            self.before_relationship_change(&context, &id, relationship, change, &mut target)?;
+            let targets = match &target {
+                Relationship::HasMany(identifiers) => identifiers.clone(),
+                Relationship::Empty => Vec::new(),
+                Relationship::BelongsTo(_) | Relationship::HasOne(_) => {
+                    return Err(DatabaseError::MismatchedRelationshipKind {
+                        schema: schema.name().to_string(),
+                        relationship: relationship.to_string(),
+                    }
+                    .into());
                 }
-                .into());
-            }
-        };
-
-        let store = context.store()?;
-        let parent = store
-            .fetch_record(schema, id, &QueryParameters::new(schema))?
-            .content;
+            };
+
+            let store = context.store()?;
+            let parent = store
+                .fetch_record(schema, id.clone(), &QueryParameters::new(schema))?
+                .content;
+            let linked = store.link_collection(parent, relationship, targets)?;
+            self.after_relationship_change(&context, &id, relationship, change, &target)?;
+            Ok::<_, CrateError>(linked)
+        })?;
 
-        let content: Content<'sch, 'req> = store
-            .link_collection(parent, relationship, targets)?
+        let content: Content<'sch, 'req> = linked
             .into_iter()
             .map(|id| JsonApiIdentifier::from((id, related_schema)))
             .collect::<Vec<_>>()
@@ -608,25 +1393,35 @@ pub trait ResourceController<'sch, Adapter: AdapterInterface + 'sch> {
 
         let id = context.require_id()?;
         let linkage = context.require_linkage()?;
-        let targets = match context.require_relationship(Some(linkage), descriptor)? {
-            Relationship::HasMany(identifiers) => identifiers,
-            Relationship::Empty => Vec::new(),
-            Relationship::BelongsTo(_) | Relationship::HasOne(_) => {
-                return Err(DatabaseError::MismatchedRelationshipKind {
-                    schema: schema.name().to_string(),
-                    relationship: relationship.to_string(),
+        let mut target = context.require_relationship(Some(linkage), descriptor)?;
+
+        let linked = context.connection()?.transaction_with(|| {
+            let change = LinkageChange::Remove;
+            let version = context.require_precondition(&id)?;
+            context.store()?.bump_version(schema, id.clone(), version)?;
+            self.before_relationship_change(&context, &id, relationship, change, &mut target)?;
+            let targets = match &target {
+                Relationship::HasMany(identifiers) => identifiers.clone(),
+                Relationship::Empty => Vec::new(),
+                Relationship::BelongsTo(_) | Relationship::HasOne(_) => {
+                    return Err(DatabaseError::MismatchedRelationshipKind {
+                        schema: schema.name().to_string(),
+                        relationship: relationship.to_string(),
+                    }
+                    .into());
                 }
-                .into());
-            }
-        };
-
-        let store = context.store()?;
-        let parent = store
-            .fetch_record(schema, id, &QueryParameters::new(schema))?
-            .content;
+            };
+
+            let store = context.store()?;
+            let parent = store
+                .fetch_record(schema, id.clone(), &QueryParameters::new(schema))?
+                .content;
+            let linked = store.unlink_collection(parent, relationship, targets)?;
+            self.after_relationship_change(&context, &id, relationship, change, &target)?;
+            Ok::<_, CrateError>(linked)
+        })?;
 
-        let content: Content<'sch, 'req> = store
-            .unlink_collection(parent, relationship, targets)?
+        let content: Content<'sch, 'req> = linked
             .into_iter()
             .map(|id| JsonApiIdentifier::from((id, related_schema)))
             .collect::<Vec<_>>()
@@ -660,37 +1455,45 @@ pub trait ResourceController<'sch, Adapter: AdapterInterface + 'sch> {
 
         let id = context.require_id()?;
         let linkage = context.require_linkage()?;
-        let target = context.require_relationship(Some(linkage), descriptor)?;
-
-        let store = context.store()?;
-        let parent = store
-            .fetch_record(schema, id, &QueryParameters::new(schema))?
-            .content;
-
-        let content: Content<'sch, 'req> = match target {
-            Relationship::BelongsTo(identifier) | Relationship::HasOne(identifier) => store
-                .relink_record(parent, relationship, identifier)?
-                .map(|id| JsonApiIdentifier::from((id, related_schema)))
-                .into(),
-            Relationship::HasMany(identifiers) => store
-                .relink_collection(parent, relationship, identifiers)?
-                .into_iter()
-                .map(|id| JsonApiIdentifier::from((id, related_schema)))
-                .collect::<Vec<_>>()
-                .into(),
-            Relationship::Empty => match descriptor.kind {
-                RelationshipKind::HasMany => store
-                    .relink_collection(parent, relationship, Vec::new())?
-                    .into_iter()
+        let mut target = context.require_relationship(Some(linkage), descriptor)?;
+
+        let content: Content<'sch, 'req> = context.connection()?.transaction_with(|| {
+            let change = LinkageChange::Replace;
+            let version = context.require_precondition(&id)?;
+            context.store()?.bump_version(schema, id.clone(), version)?;
+            self.before_relationship_change(&context, &id, relationship, change, &mut target)?;
+
+            let store = context.store()?;
+            let parent = store
+                .fetch_record(schema, id.clone(), &QueryParameters::new(schema))?
+                .content;
+            let content = match target.clone() {
+                Relationship::BelongsTo(identifier) | Relationship::HasOne(identifier) => store
+                    .relink_record(parent, relationship, identifier)?
                     .map(|id| JsonApiIdentifier::from((id, related_schema)))
-                    .collect::<Vec<_>>()
                     .into(),
-                RelationshipKind::BelongsTo | RelationshipKind::HasOne => store
-                    .unlink_record(parent, relationship)?
+                Relationship::HasMany(identifiers) => store
+                    .relink_collection(parent, relationship, identifiers)?
+                    .into_iter()
                     .map(|id| JsonApiIdentifier::from((id, related_schema)))
+                    .collect::<Vec<_>>()
                     .into(),
-            },
-        };
+                Relationship::Empty => match descriptor.kind {
+                    RelationshipKind::HasMany => store
+                        .relink_collection(parent, relationship, Vec::new())?
+                        .into_iter()
+                        .map(|id| JsonApiIdentifier::from((id, related_schema)))
+                        .collect::<Vec<_>>()
+                        .into(),
+                    RelationshipKind::BelongsTo | RelationshipKind::HasOne => store
+                        .unlink_record(parent, relationship)?
+                        .map(|id| JsonApiIdentifier::from((id, related_schema)))
+                        .into(),
+                },
+            };
+            self.after_relationship_change(&context, &id, relationship, change, &target)?;
+            Ok::<_, CrateError>(content)
+        })?;
 
         let document = to_document(content, Vec::new(), context.uri(), &context.uri_generator())?;
 
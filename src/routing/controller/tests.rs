@@ -1,11 +1,14 @@
-use super::{Configuration, ResourceContext, ResourceController};
+use super::{Configuration, LinkageChange, ResourceContext, ResourceController};
 use crate::database::adapters::SqliteAdapter;
 use crate::database::adapters::sqlite::Pool;
 use crate::database::attributes::{Attribute, Attributes, Identifier};
 use crate::database::connection_manager::ConnectionManager;
+use crate::database::query_parameters::{QueryLimits, QueryParameters};
 use crate::database::record::{Builder, Record};
 use crate::database::registry::Registry;
+use crate::database::relationships::Relationship;
 use crate::database::schema::{AttributeType, Related, Schema, SchemaBuilder};
+use crate::error::Error as CrateError;
 use crate::http_wrappers::{StatusCode, Uri};
 use crate::json_api::document::Document;
 use crate::routing::mount_table::{RelationshipMounts, ResourceMount};
@@ -14,6 +17,7 @@ use crate::serialisation::ByteStream;
 use http::HeaderMap;
 use serde_json::{Value, json};
 use std::borrow::Cow;
+use std::cell::RefCell;
 use std::collections::HashMap;
 use std::error::Error as StdError;
 use std::io::Cursor;
@@ -157,8 +161,7 @@ fn test_index_returns_collection() -> TestResult {
         request,
     );
 
-    let response =
-        Authors::default().index(ResourceContext::new(schema(&manager, "authors"), context))?;
+    let response = Authors.index(ResourceContext::new(schema(&manager, "authors"), context))?;
 
     assert_eq!(response.status(), StatusCode::OK);
     assert_eq!(data_ids(&response), vec![json!("1"), json!("2")]);
@@ -183,8 +186,7 @@ fn test_show_returns_record() -> TestResult {
     let context =
         PrimaryContext::from_request(&manager, &base, &mounts, &uri, route_id("1"), request);
 
-    let response =
-        Books::default().show(ResourceContext::new(schema(&manager, "books"), context))?;
+    let response = Books.show(ResourceContext::new(schema(&manager, "books"), context))?;
 
     assert_eq!(response.status(), StatusCode::OK);
     assert_eq!(body(&response)["data"]["type"], json!("books"));
@@ -204,7 +206,7 @@ fn test_show_missing_is_not_found() -> TestResult {
     let context =
         PrimaryContext::from_request(&manager, &base, &mounts, &uri, route_id("999"), request);
 
-    match Books::default().show(ResourceContext::new(schema(&manager, "books"), context)) {
+    match Books.show(ResourceContext::new(schema(&manager, "books"), context)) {
         Ok(_) => Err("a missing record must error".into()),
         Err(error) => {
             assert_eq!(error.status, StatusCode::NOT_FOUND);
@@ -233,8 +235,7 @@ fn test_create_persists_record() -> TestResult {
         request,
     );
 
-    let created =
-        Books::default().create(ResourceContext::new(schema(&manager, "books"), context))?;
+    let created = Books.create(ResourceContext::new(schema(&manager, "books"), context))?;
 
     assert_eq!(created.status(), StatusCode::CREATED);
     assert_eq!(body(&created)["data"]["type"], json!("books"));
@@ -252,8 +253,7 @@ fn test_create_persists_record() -> TestResult {
     let context =
         PrimaryContext::from_request(&manager, &base, &mounts, &uri, route_id(&id), request);
 
-    let fetched =
-        Books::default().show(ResourceContext::new(schema(&manager, "books"), context))?;
+    let fetched = Books.show(ResourceContext::new(schema(&manager, "books"), context))?;
     assert_eq!(body(&fetched)["data"]["attributes"]["title"], json!("Four"));
 
     Ok(())
@@ -266,6 +266,7 @@ impl<'sch> ResourceController<'sch, SqliteAdapter> for ClientIdBooks {
     fn configuration(&self) -> Configuration {
         Configuration {
             accepts_client_ids: true,
+            ..Configuration::default()
         }
     }
 }
@@ -290,7 +291,7 @@ fn test_create_refuses_unaccepted_client_generated_id() -> TestResult {
         request,
     );
 
-    match Books::default().create(ResourceContext::new(schema(&manager, "books"), context)) {
+    match Books.create(ResourceContext::new(schema(&manager, "books"), context)) {
         Ok(_) => Err("an unaccepted client-generated id must be refused".into()),
         Err(error) => {
             assert_eq!(error.status, StatusCode::FORBIDDEN);
@@ -319,8 +320,7 @@ fn test_create_honours_accepted_client_generated_id() -> TestResult {
         request,
     );
 
-    let created = ClientIdBooks::default()
-        .create(ResourceContext::new(schema(&manager, "books"), context))?;
+    let created = ClientIdBooks.create(ResourceContext::new(schema(&manager, "books"), context))?;
 
     assert_eq!(created.status(), StatusCode::CREATED);
     assert_eq!(body(&created)["data"]["id"], json!("42"));
@@ -332,8 +332,7 @@ fn test_create_honours_accepted_client_generated_id() -> TestResult {
     let mounts = mount_table();
     let context =
         PrimaryContext::from_request(&manager, &base, &mounts, &uri, route_id("42"), request);
-    let fetched =
-        Books::default().show(ResourceContext::new(schema(&manager, "books"), context))?;
+    let fetched = Books.show(ResourceContext::new(schema(&manager, "books"), context))?;
 
     assert_eq!(fetched.status(), StatusCode::OK);
     assert_eq!(body(&fetched)["data"]["attributes"]["title"], json!("Four"));
@@ -367,8 +366,7 @@ fn test_create_with_belongs_to_relationship() -> TestResult {
         request,
     );
 
-    let created =
-        Books::default().create(ResourceContext::new(schema(&manager, "books"), context))?;
+    let created = Books.create(ResourceContext::new(schema(&manager, "books"), context))?;
 
     assert_eq!(created.status(), StatusCode::CREATED);
     assert_eq!(
@@ -399,7 +397,7 @@ fn test_create_rejects_type_mismatch() -> TestResult {
         request,
     );
 
-    match Books::default().create(ResourceContext::new(schema(&manager, "books"), context)) {
+    match Books.create(ResourceContext::new(schema(&manager, "books"), context)) {
         Ok(_) => Err("a type mismatch must error".into()),
         Err(error) => {
             assert_eq!(error.status, StatusCode::CONFLICT);
@@ -428,7 +426,7 @@ fn test_create_rejects_unknown_attribute() -> TestResult {
         request,
     );
 
-    match Books::default().create(ResourceContext::new(schema(&manager, "books"), context)) {
+    match Books.create(ResourceContext::new(schema(&manager, "books"), context)) {
         Ok(_) => Err("an unknown attribute must error".into()),
         Err(error) => {
             assert_eq!(error.status, StatusCode::UNPROCESSABLE_ENTITY);
@@ -454,7 +452,7 @@ fn test_create_rejects_non_resource_document() -> TestResult {
         request,
     );
 
-    match Books::default().create(ResourceContext::new(schema(&manager, "books"), context)) {
+    match Books.create(ResourceContext::new(schema(&manager, "books"), context)) {
         Ok(_) => Err("a non-resource document must error".into()),
         Err(error) => {
             assert_eq!(error.status, StatusCode::UNPROCESSABLE_ENTITY);
@@ -479,8 +477,7 @@ fn test_update_changes_attributes() -> TestResult {
     let context =
         PrimaryContext::from_request(&manager, &base, &mounts, &uri, route_id("1"), request);
 
-    let response =
-        Books::default().update(ResourceContext::new(schema(&manager, "books"), context))?;
+    let response = Books.update(ResourceContext::new(schema(&manager, "books"), context))?;
 
     assert_eq!(response.status(), StatusCode::OK);
     assert_eq!(
@@ -505,7 +502,7 @@ fn test_update_missing_is_not_found() -> TestResult {
     let context =
         PrimaryContext::from_request(&manager, &base, &mounts, &uri, route_id("999"), request);
 
-    match Books::default().update(ResourceContext::new(schema(&manager, "books"), context)) {
+    match Books.update(ResourceContext::new(schema(&manager, "books"), context)) {
         Ok(_) => Err("a missing record must error".into()),
         Err(error) => {
             assert_eq!(error.status, StatusCode::NOT_FOUND);
@@ -534,8 +531,7 @@ fn test_update_patches_belongs_to_relationship() -> TestResult {
     let context =
         PrimaryContext::from_request(&manager, &base, &mounts, &uri, route_id("1"), request);
 
-    let response =
-        Books::default().update(ResourceContext::new(schema(&manager, "books"), context))?;
+    let response = Books.update(ResourceContext::new(schema(&manager, "books"), context))?;
 
     assert_eq!(response.status(), StatusCode::OK);
     assert_eq!(
@@ -560,7 +556,7 @@ fn test_update_rejects_type_mismatch() -> TestResult {
     let context =
         PrimaryContext::from_request(&manager, &base, &mounts, &uri, route_id("1"), request);
 
-    match Books::default().update(ResourceContext::new(schema(&manager, "books"), context)) {
+    match Books.update(ResourceContext::new(schema(&manager, "books"), context)) {
         Ok(_) => Err("a type mismatch must error".into()),
         Err(error) => {
             assert_eq!(error.status, StatusCode::CONFLICT);
@@ -583,7 +579,7 @@ fn test_update_rejects_id_mismatch() -> TestResult {
     let context =
         PrimaryContext::from_request(&manager, &base, &mounts, &uri, route_id("1"), request);
 
-    match Books::default().update(ResourceContext::new(schema(&manager, "books"), context)) {
+    match Books.update(ResourceContext::new(schema(&manager, "books"), context)) {
         Ok(_) => Err("an id mismatch must error".into()),
         Err(error) => {
             assert_eq!(error.status, StatusCode::CONFLICT);
@@ -602,8 +598,7 @@ fn test_delete_removes_record() -> TestResult {
     let context =
         PrimaryContext::from_request(&manager, &base, &mounts, &uri, route_id("2"), request);
 
-    let deleted =
-        Books::default().delete(ResourceContext::new(schema(&manager, "books"), context))?;
+    let deleted = Books.delete(ResourceContext::new(schema(&manager, "books"), context))?;
     assert_eq!(deleted.status(), StatusCode::NO_CONTENT);
 
     let request = build_request("GET", "/books/2", Value::Null)?;
@@ -613,7 +608,7 @@ fn test_delete_removes_record() -> TestResult {
     let context =
         PrimaryContext::from_request(&manager, &base, &mounts, &uri, route_id("2"), request);
 
-    match Books::default().show(ResourceContext::new(schema(&manager, "books"), context)) {
+    match Books.show(ResourceContext::new(schema(&manager, "books"), context)) {
         Ok(_) => Err("a deleted record must be gone".into()),
         Err(error) => {
             assert_eq!(error.status, StatusCode::NOT_FOUND);
@@ -632,7 +627,7 @@ fn test_linkage_to_many() -> TestResult {
     let context =
         PrimaryContext::from_request(&manager, &base, &mounts, &uri, route_id("1"), request);
 
-    let response = Authors::default().linkage(
+    let response = Authors.linkage(
         ResourceContext::new(schema(&manager, "authors"), context),
         "books",
     )?;
@@ -660,7 +655,7 @@ fn test_linkage_to_one() -> TestResult {
     let context =
         PrimaryContext::from_request(&manager, &base, &mounts, &uri, route_id("1"), request);
 
-    let response = Books::default().linkage(
+    let response = Books.linkage(
         ResourceContext::new(schema(&manager, "books"), context),
         "author",
     )?;
@@ -682,7 +677,7 @@ fn test_linkage_empty_to_one() -> TestResult {
     let context =
         PrimaryContext::from_request(&manager, &base, &mounts, &uri, route_id("3"), request);
 
-    let response = Books::default().linkage(
+    let response = Books.linkage(
         ResourceContext::new(schema(&manager, "books"), context),
         "author",
     )?;
@@ -703,7 +698,7 @@ fn test_linkage_has_one() -> TestResult {
     let context =
         PrimaryContext::from_request(&manager, &base, &mounts, &uri, route_id("1"), request);
 
-    let response = Authors::default().linkage(
+    let response = Authors.linkage(
         ResourceContext::new(schema(&manager, "authors"), context),
         "bio",
     )?;
@@ -725,7 +720,7 @@ fn test_linkage_unknown_relationship_is_internal_error() -> TestResult {
     let context =
         PrimaryContext::from_request(&manager, &base, &mounts, &uri, route_id("1"), request);
 
-    match Authors::default().linkage(
+    match Authors.linkage(
         ResourceContext::new(schema(&manager, "authors"), context),
         "ghost",
     ) {
@@ -751,7 +746,7 @@ fn test_link_adds_to_collection() -> TestResult {
     let context =
         PrimaryContext::from_request(&manager, &base, &mounts, &uri, route_id("2"), request);
 
-    let response = Authors::default().link(
+    let response = Authors.link(
         ResourceContext::new(schema(&manager, "authors"), context),
         "books",
     )?;
@@ -776,7 +771,7 @@ fn test_relink_replaces_collection() -> TestResult {
     let context =
         PrimaryContext::from_request(&manager, &base, &mounts, &uri, route_id("1"), request);
 
-    let response = Authors::default().relink(
+    let response = Authors.relink(
         ResourceContext::new(schema(&manager, "authors"), context),
         "books",
     )?;
@@ -801,7 +796,7 @@ fn test_unlink_removes_from_collection() -> TestResult {
     let context =
         PrimaryContext::from_request(&manager, &base, &mounts, &uri, route_id("1"), request);
 
-    let response = Authors::default().unlink(
+    let response = Authors.unlink(
         ResourceContext::new(schema(&manager, "authors"), context),
         "books",
     )?;
@@ -826,7 +821,7 @@ fn test_relink_sets_belongs_to_target() -> TestResult {
     let context =
         PrimaryContext::from_request(&manager, &base, &mounts, &uri, route_id("1"), request);
 
-    let response = Books::default().relink(
+    let response = Books.relink(
         ResourceContext::new(schema(&manager, "books"), context),
         "author",
     )?;
@@ -852,7 +847,7 @@ fn test_relink_null_clears_to_one() -> TestResult {
     let context =
         PrimaryContext::from_request(&manager, &base, &mounts, &uri, route_id("1"), request);
 
-    let response = Books::default().relink(
+    let response = Books.relink(
         ResourceContext::new(schema(&manager, "books"), context),
         "author",
     )?;
@@ -877,7 +872,7 @@ fn test_relink_sets_has_one_target() -> TestResult {
     let context =
         PrimaryContext::from_request(&manager, &base, &mounts, &uri, route_id("2"), request);
 
-    let response = Authors::default().relink(
+    let response = Authors.relink(
         ResourceContext::new(schema(&manager, "authors"), context),
         "bio",
     )?;
@@ -903,7 +898,7 @@ fn test_relink_missing_target_is_not_found() -> TestResult {
     let context =
         PrimaryContext::from_request(&manager, &base, &mounts, &uri, route_id("1"), request);
 
-    match Books::default().relink(
+    match Books.relink(
         ResourceContext::new(schema(&manager, "books"), context),
         "author",
     ) {
@@ -929,7 +924,7 @@ fn test_link_rejects_to_one_linkage() -> TestResult {
     let context =
         PrimaryContext::from_request(&manager, &base, &mounts, &uri, route_id("1"), request);
 
-    match Authors::default().link(
+    match Authors.link(
         ResourceContext::new(schema(&manager, "authors"), context),
         "books",
     ) {
@@ -955,7 +950,7 @@ fn test_link_missing_target_is_not_found() -> TestResult {
     let context =
         PrimaryContext::from_request(&manager, &base, &mounts, &uri, route_id("1"), request);
 
-    match Authors::default().link(
+    match Authors.link(
         ResourceContext::new(schema(&manager, "authors"), context),
         "books",
     ) {
@@ -981,7 +976,7 @@ fn test_relink_missing_parent_is_not_found() -> TestResult {
     let context =
         PrimaryContext::from_request(&manager, &base, &mounts, &uri, route_id("999"), request);
 
-    match Books::default().relink(
+    match Books.relink(
         ResourceContext::new(schema(&manager, "books"), context),
         "author",
     ) {
@@ -1007,7 +1002,7 @@ fn test_relink_unknown_relationship_is_internal_error() -> TestResult {
     let context =
         PrimaryContext::from_request(&manager, &base, &mounts, &uri, route_id("1"), request);
 
-    match Books::default().relink(
+    match Books.relink(
         ResourceContext::new(schema(&manager, "books"), context),
         "ghost",
     ) {
@@ -1029,7 +1024,7 @@ fn test_relink_without_body_is_unprocessable() -> TestResult {
     let context =
         PrimaryContext::from_request(&manager, &base, &mounts, &uri, route_id("1"), request);
 
-    match Books::default().relink(
+    match Books.relink(
         ResourceContext::new(schema(&manager, "books"), context),
         "author",
     ) {
@@ -1055,7 +1050,7 @@ fn test_link_on_to_one_is_kind_mismatch() -> TestResult {
     let context =
         PrimaryContext::from_request(&manager, &base, &mounts, &uri, route_id("1"), request);
 
-    match Authors::default().link(
+    match Authors.link(
         ResourceContext::new(schema(&manager, "authors"), context),
         "bio",
     ) {
@@ -1081,7 +1076,7 @@ fn test_unlink_on_to_one_is_kind_mismatch() -> TestResult {
     let context =
         PrimaryContext::from_request(&manager, &base, &mounts, &uri, route_id("1"), request);
 
-    match Authors::default().unlink(
+    match Authors.unlink(
         ResourceContext::new(schema(&manager, "authors"), context),
         "bio",
     ) {
@@ -1133,10 +1128,13 @@ where
                             Cow::Borrowed(":id"),
                             Cow::Borrowed(name),
                         ]),
+                        writable: true,
                     },
                 )
             })
             .collect(),
+        query_limits: QueryLimits::default(),
+        read_only: false,
     }
 }
 
@@ -1171,8 +1169,7 @@ fn test_parameters_for_route_resolves_id_and_echoes_others() -> TestResult {
     route.insert("tenant", "acme");
     let headers = HeaderMap::new();
 
-    let resolved =
-        Authors::default().parameters_for_route(&record, &route, &headers, &["id", "tenant"]);
+    let resolved = Authors.parameters_for_route(&record, &route, &headers, &["id", "tenant"]);
 
     // `:id` resolves from the record's identifier; every other parameter echoes the request.
     assert_eq!(resolved["id"], Cow::Borrowed("7"));
@@ -1224,8 +1221,7 @@ fn test_parameters_for_route_override_resolves_from_record() -> TestResult {
     let route = RouteParameters::new();
     let headers = HeaderMap::new();
 
-    let resolved =
-        SluggedBooks::default().parameters_for_route(&record, &route, &headers, &["title"]);
+    let resolved = SluggedBooks.parameters_for_route(&record, &route, &headers, &["title"]);
 
     assert_eq!(resolved["title"], Cow::Borrowed("One"));
     Ok(())
@@ -1241,7 +1237,7 @@ fn test_related_to_many_serves_collection() -> TestResult {
     let context =
         PrimaryContext::from_request(&manager, &base, &mounts, &uri, route_id("1"), request);
 
-    let response = Authors::default().related(
+    let response = Authors.related(
         ResourceContext::new(schema(&manager, "authors"), context),
         "books",
     )?;
@@ -1270,7 +1266,7 @@ fn test_related_to_one_serves_record() -> TestResult {
     let context =
         PrimaryContext::from_request(&manager, &base, &mounts, &uri, route_id("1"), request);
 
-    let response = Books::default().related(
+    let response = Books.related(
         ResourceContext::new(schema(&manager, "books"), context),
         "author",
     )?;
@@ -1293,7 +1289,7 @@ fn test_related_empty_to_one_is_null() -> TestResult {
     let context =
         PrimaryContext::from_request(&manager, &base, &mounts, &uri, route_id("3"), request);
 
-    let response = Books::default().related(
+    let response = Books.related(
         ResourceContext::new(schema(&manager, "books"), context),
         "author",
     )?;
@@ -1314,7 +1310,7 @@ fn test_related_has_one_serves_record() -> TestResult {
     let context =
         PrimaryContext::from_request(&manager, &base, &mounts, &uri, route_id("1"), request);
 
-    let response = Authors::default().related(
+    let response = Authors.related(
         ResourceContext::new(schema(&manager, "authors"), context),
         "bio",
     )?;
@@ -1336,7 +1332,7 @@ fn test_related_supports_primary_content_include() -> TestResult {
     let context =
         PrimaryContext::from_request(&manager, &base, &mounts, &uri, route_id("1"), request);
 
-    let response = Authors::default().related(
+    let response = Authors.related(
         ResourceContext::new(schema(&manager, "authors"), context),
         "books",
     )?;
@@ -1363,7 +1359,7 @@ fn test_related_unknown_relationship_is_internal_error() -> TestResult {
     let context =
         PrimaryContext::from_request(&manager, &base, &mounts, &uri, route_id("1"), request);
 
-    match Authors::default().related(
+    match Authors.related(
         ResourceContext::new(schema(&manager, "authors"), context),
         "ghost",
     ) {
@@ -1374,3 +1370,380 @@ fn test_related_unknown_relationship_is_internal_error() -> TestResult {
         }
     }
 }
+
+// An embedder's own failure, joining the funnel through `From` as any downstream error does.
+#[derive(Debug)]
+struct Refusal;
+
+impl From<Refusal> for CrateError {
+    fn from(_: Refusal) -> Self {
+        CrateError {
+            status: StatusCode::UNPROCESSABLE_ENTITY,
+            code: Cow::Borrowed("Refused"),
+            title: Cow::Borrowed("The hook refused the write"),
+            detail: "The hook refused the write".to_string(),
+            source: None,
+            meta: None,
+        }
+    }
+}
+
+// A controller whose hooks trim every title it writes, record what they saw, and refuse whatever
+// is titled "Refused" — before a delete, or once a create has been made.
+#[derive(Default)]
+struct HookedBooks {
+    seen: RefCell<Vec<String>>,
+}
+
+impl HookedBooks {
+    fn trim_title(record: &mut Record<'_>) {
+        if let Some(Attribute::Text(title)) = record.attributes.get_mut("title") {
+            *title = title.trim().to_string();
+        }
+    }
+
+    fn title(record: &Record<'_>) -> Option<String> {
+        match record.attributes.get("title") {
+            Some(Attribute::Text(title)) => Some(title.clone()),
+            _ => None,
+        }
+    }
+}
+
+impl<'sch> ResourceController<'sch, SqliteAdapter> for HookedBooks {
+    fn before_create<'req>(
+        &self,
+        _context: &ResourceContext<'sch, 'req, SqliteAdapter>,
+        record: &mut Record<'sch>,
+    ) -> Result<(), CrateError>
+    where
+        'sch: 'req,
+    {
+        Self::trim_title(record);
+        Ok(())
+    }
+
+    fn after_create<'req>(
+        &self,
+        _context: &ResourceContext<'sch, 'req, SqliteAdapter>,
+        record: &Record<'sch>,
+    ) -> Result<(), CrateError>
+    where
+        'sch: 'req,
+    {
+        if Self::title(record).as_deref() == Some("Refused") {
+            return Err(Refusal.into());
+        }
+        self.seen
+            .borrow_mut()
+            .extend(record.get_id().map(|id| format!("created {id}")));
+        Ok(())
+    }
+
+    fn before_update<'req>(
+        &self,
+        _context: &ResourceContext<'sch, 'req, SqliteAdapter>,
+        record: &mut Record<'sch>,
+    ) -> Result<(), CrateError>
+    where
+        'sch: 'req,
+    {
+        Self::trim_title(record);
+        Ok(())
+    }
+
+    fn before_delete<'req>(
+        &self,
+        context: &ResourceContext<'sch, 'req, SqliteAdapter>,
+        id: &Identifier,
+    ) -> Result<(), CrateError>
+    where
+        'sch: 'req,
+    {
+        let parameters = QueryParameters::new(context.schema());
+        let record = context
+            .store()?
+            .fetch_record(context.schema(), id.clone(), &parameters)?
+            .content;
+        if Self::title(&record).as_deref() == Some("Refused") {
+            return Err(Refusal.into());
+        }
+        Ok(())
+    }
+
+    fn after_delete<'req>(
+        &self,
+        _context: &ResourceContext<'sch, 'req, SqliteAdapter>,
+        id: &Identifier,
+    ) -> Result<(), CrateError>
+    where
+        'sch: 'req,
+    {
+        self.seen.borrow_mut().push(format!("deleted {id}"));
+        Ok(())
+    }
+}
+
+// A controller whose relationship hooks refuse every removal, add book 3 to every addition, and
+// record each change they let through.
+#[derive(Default)]
+struct HookedAuthors {
+    seen: RefCell<Vec<(LinkageChange, Relationship)>>,
+}
+
+impl<'sch> ResourceController<'sch, SqliteAdapter> for HookedAuthors {
+    fn before_relationship_change<'req>(
+        &self,
+        _context: &ResourceContext<'sch, 'req, SqliteAdapter>,
+        _id: &Identifier,
+        _relationship: &'sch str,
+        change: LinkageChange,
+        targets: &mut Relationship,
+    ) -> Result<(), CrateError>
+    where
+        'sch: 'req,
+    {
+        match (change, targets) {
+            (LinkageChange::Remove, _) => Err(Refusal.into()),
+            (LinkageChange::Add, Relationship::HasMany(identifiers)) => {
+                identifiers.push(Identifier::Integer(3));
+                Ok(())
+            }
+            _ => Ok(()),
+        }
+    }
+
+    fn after_relationship_change<'req>(
+        &self,
+        _context: &ResourceContext<'sch, 'req, SqliteAdapter>,
+        _id: &Identifier,
+        _relationship: &'sch str,
+        change: LinkageChange,
+        targets: &Relationship,
+    ) -> Result<(), CrateError>
+    where
+        'sch: 'req,
+    {
+        self.seen.borrow_mut().push((change, targets.clone()));
+        Ok(())
+    }
+}
+
+#[test]
+fn test_before_create_amends_the_record() -> TestResult {
+    let manager = manager()?;
+    let request = build_request(
+        "POST",
+        "/books",
+        json!({ "data": { "type": "books", "attributes": { "title": "  Four " } } }),
+    )?;
+    let uri: Uri = request.uri().clone().into();
+    let base = base_uri();
+    let mounts = mount_table();
+    let context = PrimaryContext::from_request(
+        &manager,
+        &base,
+        &mounts,
+        &uri,
+        RouteParameters::new(),
+        request,
+    );
+
+    let books = HookedBooks::default();
+    let created = books.create(ResourceContext::new(schema(&manager, "books"), context))?;
+
+    assert_eq!(created.status(), StatusCode::CREATED);
+    assert_eq!(body(&created)["data"]["attributes"]["title"], json!("Four"));
+    assert_eq!(books.seen.take(), vec!["created 4".to_string()]);
+
+    Ok(())
+}
+
+#[test]
+fn test_after_create_error_rolls_the_create_back() -> TestResult {
+    let manager = manager()?;
+    let request = build_request(
+        "POST",
+        "/books",
+        json!({ "data": { "type": "books", "attributes": { "title": "Refused" } } }),
+    )?;
+    let uri: Uri = request.uri().clone().into();
+    let base = base_uri();
+    let mounts = mount_table();
+    let context = PrimaryContext::from_request(
+        &manager,
+        &base,
+        &mounts,
+        &uri,
+        RouteParameters::new(),
+        request,
+    );
+
+    match HookedBooks::default().create(ResourceContext::new(schema(&manager, "books"), context)) {
+        Ok(_) => return Err("a refused create must fail".into()),
+        Err(error) => assert_eq!(error, CrateError::from(Refusal)),
+    }
+
+    let request = build_request("GET", "/books", Value::Null)?;
+    let uri: Uri = request.uri().clone().into();
+    let context = PrimaryContext::from_request(
+        &manager,
+        &base,
+        &mounts,
+        &uri,
+        RouteParameters::new(),
+        request,
+    );
+    let books = Books.index(ResourceContext::new(schema(&manager, "books"), context))?;
+    assert_eq!(data_ids(&books), vec![json!("1"), json!("2"), json!("3")]);
+
+    Ok(())
+}
+
+#[test]
+fn test_before_update_amends_the_record() -> TestResult {
+    let manager = manager()?;
+    let request = build_request(
+        "PATCH",
+        "/books/1",
+        json!({
+            "data": { "type": "books", "id": "1", "attributes": { "title": " Renamed  " } }
+        }),
+    )?;
+    let uri: Uri = request.uri().clone().into();
+    let base = base_uri();
+    let mounts = mount_table();
+    let context =
+        PrimaryContext::from_request(&manager, &base, &mounts, &uri, route_id("1"), request);
+
+    let response =
+        HookedBooks::default().update(ResourceContext::new(schema(&manager, "books"), context))?;
+
+    assert_eq!(
+        body(&response)["data"]["attributes"]["title"],
+        json!("Renamed")
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_before_delete_error_keeps_the_record() -> TestResult {
+    let manager = manager()?;
+    manager
+        .acquire()?
+        .execute_batch("UPDATE books SET title = 'Refused' WHERE id = 2;")?;
+    let request = build_request("DELETE", "/books/2", Value::Null)?;
+    let uri: Uri = request.uri().clone().into();
+    let base = base_uri();
+    let mounts = mount_table();
+    let context =
+        PrimaryContext::from_request(&manager, &base, &mounts, &uri, route_id("2"), request);
+
+    let books = HookedBooks::default();
+    match books.delete(ResourceContext::new(schema(&manager, "books"), context)) {
+        Ok(_) => return Err("a refused delete must fail".into()),
+        Err(error) => assert_eq!(error, CrateError::from(Refusal)),
+    }
+    assert!(books.seen.take().is_empty());
+
+    let request = build_request("GET", "/books/2", Value::Null)?;
+    let uri: Uri = request.uri().clone().into();
+    let context =
+        PrimaryContext::from_request(&manager, &base, &mounts, &uri, route_id("2"), request);
+    let fetched = Books.show(ResourceContext::new(schema(&manager, "books"), context))?;
+    assert_eq!(
+        body(&fetched)["data"]["attributes"]["title"],
+        json!("Refused")
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_after_delete_sees_the_deleted_id() -> TestResult {
+    let manager = manager()?;
+    let request = build_request("DELETE", "/books/2", Value::Null)?;
+    let uri: Uri = request.uri().clone().into();
+    let base = base_uri();
+    let mounts = mount_table();
+    let context =
+        PrimaryContext::from_request(&manager, &base, &mounts, &uri, route_id("2"), request);
+
+    let books = HookedBooks::default();
+    let deleted = books.delete(ResourceContext::new(schema(&manager, "books"), context))?;
+
+    assert_eq!(deleted.status(), StatusCode::NO_CONTENT);
+    assert_eq!(books.seen.take(), vec!["deleted 2".to_string()]);
+
+    Ok(())
+}
+
+#[test]
+fn test_before_relationship_change_amends_the_targets() -> TestResult {
+    let manager = manager()?;
+    let request = build_request(
+        "POST",
+        "/authors/2/relationships/books",
+        json!({ "data": [{ "type": "books", "id": "2" }] }),
+    )?;
+    let uri: Uri = request.uri().clone().into();
+    let base = base_uri();
+    let mounts = mount_table();
+    let context =
+        PrimaryContext::from_request(&manager, &base, &mounts, &uri, route_id("2"), request);
+
+    let authors = HookedAuthors::default();
+    let response = authors.link(
+        ResourceContext::new(schema(&manager, "authors"), context),
+        "books",
+    )?;
+
+    assert_eq!(data_ids(&response), vec![json!("2"), json!("3")]);
+    assert_eq!(
+        authors.seen.take(),
+        vec![(
+            LinkageChange::Add,
+            Relationship::HasMany(vec![Identifier::Integer(2), Identifier::Integer(3)])
+        )]
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_before_relationship_change_error_keeps_the_linkage() -> TestResult {
+    let manager = manager()?;
+    let request = build_request(
+        "DELETE",
+        "/authors/1/relationships/books",
+        json!({ "data": [{ "type": "books", "id": "2" }] }),
+    )?;
+    let uri: Uri = request.uri().clone().into();
+    let base = base_uri();
+    let mounts = mount_table();
+    let context =
+        PrimaryContext::from_request(&manager, &base, &mounts, &uri, route_id("1"), request);
+
+    let authors = HookedAuthors::default();
+    match authors.unlink(
+        ResourceContext::new(schema(&manager, "authors"), context),
+        "books",
+    ) {
+        Ok(_) => return Err("a refused unlink must fail".into()),
+        Err(error) => assert_eq!(error, CrateError::from(Refusal)),
+    }
+    assert!(authors.seen.take().is_empty());
+
+    let request = build_request("GET", "/authors/1/relationships/books", Value::Null)?;
+    let uri: Uri = request.uri().clone().into();
+    let context =
+        PrimaryContext::from_request(&manager, &base, &mounts, &uri, route_id("1"), request);
+    let linkage = Authors.linkage(
+        ResourceContext::new(schema(&manager, "authors"), context),
+        "books",
+    )?;
+    assert_eq!(data_ids(&linkage), vec![json!("1"), json!("2")]);
+
+    Ok(())
+}
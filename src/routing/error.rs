@@ -33,6 +33,8 @@ pub enum Error {
     MissingLinkageBody,
     /// The body parsed, but its primary data is not a single resource object.
     PrimaryDataIsNotAResource,
+    /// The body parsed, but its primary data is not an array of resource objects.
+    PrimaryDataIsNotACollection,
     /// The body is an errors document, which carries no primary data at all.
     ErrorDocumentSubmitted,
     MalformedRequestBody {
@@ -81,6 +83,28 @@ pub enum Error {
     InvalidIntegerIdentifier {
         id: String,
     },
+    /// A `lid` names no resource created earlier in the request.
+    UnknownLocalIdentifier {
+        lid: String,
+    },
+    /// Two resources created in one request claim the same `lid`.
+    DuplicateLocalIdentifier {
+        lid: String,
+    },
+    /// A resource included in a create request is not a new resource named by a `lid`.
+    IncludedResourceNotNew {
+        kind: String,
+    },
+    /// A resource included in a create request is of a type whose controller does not accept its
+    /// resources being created so.
+    InclusionNotAccepted {
+        kind: String,
+    },
+    /// A resource included in a create request is of a type created behind middleware the request
+    /// does not run.
+    IncludedResourceBehindMiddleware {
+        kind: String,
+    },
 
     InvalidHeaderValue {
         header: String,
@@ -94,6 +118,10 @@ pub enum Error {
     UnsupportedJsonApiExtension {
         extensions: Vec<String>,
     },
+    /// The endpoint only reads documents of a JSON:API extension the `Content-Type` does not apply.
+    MissingJsonApiExtension {
+        extension: String,
+    },
     InvalidAcceptHeader,
     NoAcceptableMediaType,
     /// Every JSON:API media type offered carries a parameter that rules it out.
@@ -120,6 +148,48 @@ pub enum Error {
         message: String,
     },
 
+    /// An atomic operation carries no `data` where it needs some.
+    MissingOperationData,
+    /// An atomic operation removing a resource or writing a relationship carries no `ref`.
+    MissingOperationReference,
+    /// An atomic operation creating a resource carries a `ref`, which only relationship writes take.
+    UnexpectedOperationReference,
+    /// An atomic operation is targeted by `href`, which the server does not resolve.
+    OperationHrefNotSupported,
+    /// An atomic operation targets a resource type the router does not serve.
+    UnservedResourceType {
+        kind: String,
+    },
+    /// An atomic operation targets a resource served behind schema-less middleware the operations
+    /// endpoint does not share, which an operation cannot run.
+    OperationBehindMiddleware {
+        kind: String,
+    },
+    /// The route serving an atomic operation answered with a response other than a success.
+    OperationRefused {
+        status: StatusCode,
+    },
+
+    /// The resource is not at a version the request's `If-Match` precondition names.
+    PreconditionFailed,
+
+    /// A bulk create carries more resources than the endpoint takes at once.
+    BatchTooLarge {
+        limit: usize,
+        actual: usize,
+    },
+    /// A bulk update or deletion carries no `filter` scoping the records it writes.
+    UnscopedBulkWrite,
+    /// A bulk update or deletion matches more records than the endpoint writes at once.
+    BulkScopeTooLarge {
+        limit: usize,
+        actual: usize,
+    },
+    /// A bulk update names a record by id, where its `filter` alone names the records it changes.
+    UnexpectedResourceId {
+        id: String,
+    },
+
     /// The mount serves no handler for this operation.
     UnsupportedOperation,
     /// A schema-less middleware appears inside the schema-bound chain. The builder rejects this at
@@ -141,30 +211,54 @@ impl Error {
             | FailedToParseRouteParameter { .. }
             | MalformedRequestBody { .. }
             | RequestBodyPeekFailed { .. }
-            | InvalidHeaderValue { .. } => StatusCode::BAD_REQUEST,
+            | InvalidHeaderValue { .. }
+            | UnscopedBulkWrite
+            | BulkScopeTooLarge { .. } => StatusCode::BAD_REQUEST,
 
             MissingResourceBody
             | MissingLinkageBody
             | PrimaryDataIsNotAResource
+            | PrimaryDataIsNotACollection
             | ErrorDocumentSubmitted
             | InvalidRequestBodyContent { .. }
             | UnknownAttribute { .. }
+            | UnexpectedResourceId { .. }
             | InvalidLinkage
             | UnresolvableIdentifier
             | IdentifierTypeMismatch { .. }
-            | InvalidIntegerIdentifier { .. } => StatusCode::UNPROCESSABLE_ENTITY,
+            | InvalidIntegerIdentifier { .. }
+            | UnknownLocalIdentifier { .. }
+            | DuplicateLocalIdentifier { .. }
+            | IncludedResourceNotNew { .. }
+            | MissingOperationData
+            | MissingOperationReference
+            | UnexpectedOperationReference
+            | OperationHrefNotSupported => StatusCode::UNPROCESSABLE_ENTITY,
+
+            UnservedResourceType { .. } => StatusCode::NOT_FOUND,
+
+            OperationRefused { status } => status.clone(),
+
+            PreconditionFailed => StatusCode::PRECONDITION_FAILED,
+
+            BatchTooLarge { .. } => StatusCode::PAYLOAD_TOO_LARGE,
 
             ResourceTypeMismatch { .. } | ResourceIdMismatch { .. } | ResourceIdMissing { .. } => {
                 StatusCode::CONFLICT
             }
 
-            ClientGeneratedIdNotSupported { .. } | UnsupportedOperation => StatusCode::FORBIDDEN,
+            ClientGeneratedIdNotSupported { .. }
+            | UnsupportedOperation
+            | OperationBehindMiddleware { .. }
+            | InclusionNotAccepted { .. }
+            | IncludedResourceBehindMiddleware { .. } => StatusCode::FORBIDDEN,
 
             MissingContentType
             | UnsupportedContentType
             | InvalidContentType
             | ContentTypeCarriesQuality
             | UnsupportedJsonApiExtension { .. }
+            | MissingJsonApiExtension { .. }
             | UnsupportedMediaTypeParameter { .. }
             | UnsupportedQualityValue => StatusCode::UNSUPPORTED_MEDIA_TYPE,
 
@@ -194,6 +288,7 @@ impl Error {
             MissingResourceBody => "MissingResourceBody",
             MissingLinkageBody => "MissingLinkageBody",
             PrimaryDataIsNotAResource => "PrimaryDataIsNotAResource",
+            PrimaryDataIsNotACollection => "PrimaryDataIsNotACollection",
             ErrorDocumentSubmitted => "ErrorDocumentSubmitted",
             MalformedRequestBody { .. } => "MalformedRequestBody",
             InvalidRequestBodyContent { .. } => "InvalidRequestBodyContent",
@@ -208,12 +303,18 @@ impl Error {
             UnresolvableIdentifier => "UnresolvableIdentifier",
             IdentifierTypeMismatch { .. } => "IdentifierTypeMismatch",
             InvalidIntegerIdentifier { .. } => "InvalidIntegerIdentifier",
+            UnknownLocalIdentifier { .. } => "UnknownLocalIdentifier",
+            DuplicateLocalIdentifier { .. } => "DuplicateLocalIdentifier",
+            IncludedResourceNotNew { .. } => "IncludedResourceNotNew",
+            InclusionNotAccepted { .. } => "InclusionNotAccepted",
+            IncludedResourceBehindMiddleware { .. } => "IncludedResourceBehindMiddleware",
             InvalidHeaderValue { .. } => "InvalidHeaderValue",
             MissingContentType => "MissingContentType",
             UnsupportedContentType => "UnsupportedContentType",
             InvalidContentType => "InvalidContentType",
             ContentTypeCarriesQuality => "ContentTypeCarriesQuality",
             UnsupportedJsonApiExtension { .. } => "UnsupportedJsonApiExtension",
+            MissingJsonApiExtension { .. } => "MissingJsonApiExtension",
             InvalidAcceptHeader => "InvalidAcceptHeader",
             NoAcceptableMediaType => "NoAcceptableMediaType",
             UnusableAcceptMediaTypes => "UnusableAcceptMediaTypes",
@@ -223,6 +324,18 @@ impl Error {
             NotAJsonApiMediaType { .. } => "NotAJsonApiMediaType",
             ResponseConstructionFailed { .. } => "ResponseConstructionFailed",
             GeneratedInvalidHeader { .. } => "GeneratedInvalidHeader",
+            MissingOperationData => "MissingOperationData",
+            MissingOperationReference => "MissingOperationReference",
+            UnexpectedOperationReference => "UnexpectedOperationReference",
+            OperationHrefNotSupported => "OperationHrefNotSupported",
+            UnservedResourceType { .. } => "UnservedResourceType",
+            OperationBehindMiddleware { .. } => "OperationBehindMiddleware",
+            OperationRefused { .. } => "OperationRefused",
+            PreconditionFailed => "PreconditionFailed",
+            BatchTooLarge { .. } => "BatchTooLarge",
+            UnscopedBulkWrite => "UnscopedBulkWrite",
+            BulkScopeTooLarge { .. } => "BulkScopeTooLarge",
+            UnexpectedResourceId { .. } => "UnexpectedResourceId",
             UnsupportedOperation => "UnsupportedOperation",
             MisorderedMiddleware => "MisorderedMiddleware",
             Database(error) => error.code(),
@@ -240,7 +353,9 @@ impl Error {
             FailedToParseRouteParameter { .. } => "A route parameter could not be parsed",
             MissingResourceBody => "This request requires a body carrying a resource object",
             MissingLinkageBody => "This request requires a body carrying relationship linkage",
-            PrimaryDataIsNotAResource => "The request document has an unexpected shape",
+            PrimaryDataIsNotAResource | PrimaryDataIsNotACollection => {
+                "The request document has an unexpected shape"
+            }
             ErrorDocumentSubmitted => "The request document carries no primary data",
             MalformedRequestBody { .. } => "The request body is not valid JSON",
             InvalidRequestBodyContent { .. } => "The request body is not a valid JSON:API document",
@@ -257,6 +372,13 @@ impl Error {
             UnresolvableIdentifier => "This identifier does not reference an existing resource",
             IdentifierTypeMismatch { .. } => "This identifier references the wrong resource type",
             InvalidIntegerIdentifier { .. } => "The identifier is not a valid integer",
+            UnknownLocalIdentifier { .. } => "This local identifier references no created resource",
+            DuplicateLocalIdentifier { .. } => "This local identifier is already in use",
+            IncludedResourceNotNew { .. } => "An included resource is not a new resource",
+            InclusionNotAccepted { .. } => "The included resource's type cannot be created so",
+            IncludedResourceBehindMiddleware { .. } => {
+                "The included resource is created behind middleware this request does not run"
+            }
             InvalidHeaderValue { .. } => "A request header could not be read",
             MissingContentType => "A 'Content-Type' header is required",
             UnsupportedContentType => "This endpoint does not accept the provided 'Content-Type'",
@@ -265,6 +387,9 @@ impl Error {
             UnsupportedJsonApiExtension { .. } => {
                 "The request applies a JSON:API extension the server does not support"
             }
+            MissingJsonApiExtension { .. } => {
+                "This endpoint requires a JSON:API extension the request does not apply"
+            }
             InvalidAcceptHeader => "The 'Accept' header is invalid",
             NoAcceptableMediaType => {
                 "This endpoint cannot produce a response in any accepted media type"
@@ -280,6 +405,20 @@ impl Error {
             NotAJsonApiMediaType { .. } => "The media type is not a JSON:API media type",
             ResponseConstructionFailed { .. } => "The response could not be constructed",
             GeneratedInvalidHeader { .. } => "The server generated an invalid header",
+            MissingOperationData => "The operation carries no data",
+            MissingOperationReference => "The operation does not name its target",
+            UnexpectedOperationReference => "The operation names a target it cannot take",
+            OperationHrefNotSupported => "Operations cannot be targeted by 'href'",
+            UnservedResourceType { .. } => "The resource type is not served",
+            OperationBehindMiddleware { .. } => {
+                "The resource is served behind middleware an operation cannot pass"
+            }
+            OperationRefused { .. } => "The operation was refused",
+            PreconditionFailed => "The request's precondition failed",
+            BatchTooLarge { .. } => "The request carries too many resources",
+            UnscopedBulkWrite => "A bulk write must be scoped by a filter or search",
+            BulkScopeTooLarge { .. } => "The filter matches too many resources",
+            UnexpectedResourceId { .. } => "A bulk update cannot name a resource by id",
             UnsupportedOperation => "This endpoint does not support the requested operation",
             MisorderedMiddleware => "The middleware chain is misordered",
             Database(error) => error.title(),
@@ -294,15 +433,23 @@ impl Error {
 
         match self {
             PrimaryDataIsNotAResource
+            | PrimaryDataIsNotACollection
+            | BatchTooLarge { .. }
             | InvalidLinkage
             | UnresolvableIdentifier
             | IdentifierTypeMismatch { .. }
             | InvalidIntegerIdentifier { .. } => Some(pointer::for_primary_data()),
             UnknownAttribute { attribute, .. } => Some(pointer::for_attribute(attribute)),
-            ResourceTypeMismatch { .. } => Some(pointer::for_member("type")),
+            ResourceTypeMismatch { .. }
+            | InclusionNotAccepted { .. }
+            | IncludedResourceBehindMiddleware { .. } => Some(pointer::for_member("type")),
             ResourceIdMismatch { .. }
             | ResourceIdMissing { .. }
-            | ClientGeneratedIdNotSupported { .. } => Some(pointer::for_member("id")),
+            | ClientGeneratedIdNotSupported { .. }
+            | UnexpectedResourceId { .. } => Some(pointer::for_member("id")),
+            DuplicateLocalIdentifier { .. } | IncludedResourceNotNew { .. } => {
+                Some(pointer::for_member("lid"))
+            }
             InvalidHeaderValue { header, .. } | GeneratedInvalidHeader { header, .. } => {
                 Some(Source::Header(header.clone()))
             }
@@ -311,12 +458,18 @@ impl Error {
             | InvalidContentType
             | ContentTypeCarriesQuality
             | UnsupportedJsonApiExtension { .. }
+            | MissingJsonApiExtension { .. }
             | UnsupportedMediaTypeParameter { .. }
             | UnsupportedQualityValue => Some(Source::Header("Content-Type".to_string())),
             InvalidAcceptHeader
             | NoAcceptableMediaType
             | UnusableAcceptMediaTypes
             | UnsatisfiableJsonApiExtension { .. } => Some(Source::Header("Accept".to_string())),
+            PreconditionFailed => Some(Source::Header("If-Match".to_string())),
+            UnscopedBulkWrite | BulkScopeTooLarge { .. } => {
+                Some(Source::Parameter("filter".to_string()))
+            }
+            Database(error) => error.source(),
 
             RequiredRouteParameterMissing { .. }
             | FailedToParseRouteParameter { .. }
@@ -329,9 +482,16 @@ impl Error {
             | RequestBodyPeekFailed { .. }
             | NotAJsonApiMediaType { .. }
             | ResponseConstructionFailed { .. }
+            | UnknownLocalIdentifier { .. }
+            | MissingOperationData
+            | MissingOperationReference
+            | UnexpectedOperationReference
+            | OperationHrefNotSupported
+            | UnservedResourceType { .. }
+            | OperationBehindMiddleware { .. }
+            | OperationRefused { .. }
             | UnsupportedOperation
             | MisorderedMiddleware
-            | Database(_)
             | Serialisation(_) => None,
         }
     }
@@ -374,6 +534,10 @@ impl Display for Error {
                 f,
                 "The request body must contain a single resource object as its primary data"
             ),
+            PrimaryDataIsNotACollection => write!(
+                f,
+                "The request body must contain an array of resource objects as its primary data"
+            ),
             ErrorDocumentSubmitted => write!(
                 f,
                 "The request body is an errors document, which carries no primary data"
@@ -433,6 +597,26 @@ impl Display for Error {
             InvalidIntegerIdentifier { id } => {
                 write!(f, "The id '{id}' is not a valid integer identifier")
             }
+            UnknownLocalIdentifier { lid } => write!(
+                f,
+                "The local identifier '{lid}' references no resource created earlier in this request"
+            ),
+            DuplicateLocalIdentifier { lid } => write!(
+                f,
+                "The local identifier '{lid}' is claimed by more than one resource in this request"
+            ),
+            IncludedResourceNotNew { kind } => write!(
+                f,
+                "The included '{kind}' resource must be a new resource named by a 'lid' to be created with the primary resource"
+            ),
+            InclusionNotAccepted { kind } => write!(
+                f,
+                "Resources of type '{kind}' cannot be created from another resource's 'included' member"
+            ),
+            IncludedResourceBehindMiddleware { kind } => write!(
+                f,
+                "Resources of type '{kind}' are created behind middleware this request does not run"
+            ),
             InvalidHeaderValue { header, message } => write!(
                 f,
                 "The '{header}' header contains invalid characters and could not be parsed: {message}"
@@ -455,6 +639,10 @@ impl Display for Error {
                 "The 'Content-Type' header applies JSON:API extensions the server does not support: {}",
                 extensions.join(", ")
             ),
+            MissingJsonApiExtension { extension } => write!(
+                f,
+                "This endpoint requires a 'Content-Type' header applying the JSON:API extension {extension}"
+            ),
             InvalidAcceptHeader => write!(
                 f,
                 "The 'Accept' header provided contains an invalid value that cannot be parsed"
@@ -490,6 +678,49 @@ impl Display for Error {
                 f,
                 "The server generated an invalid '{header}' header: {message}"
             ),
+            MissingOperationData => write!(f, "This operation requires a 'data' member"),
+            MissingOperationReference => write!(
+                f,
+                "This operation requires a 'ref' naming the resource or relationship it targets"
+            ),
+            UnexpectedOperationReference => write!(
+                f,
+                "An operation adding a resource cannot carry a 'ref' unless it names a relationship"
+            ),
+            OperationHrefNotSupported => write!(
+                f,
+                "Operations targeted by 'href' are not supported; name the target with 'ref' instead"
+            ),
+            UnservedResourceType { kind } => {
+                write!(f, "The resource type '{kind}' is not served by this API")
+            }
+            OperationBehindMiddleware { kind } => write!(
+                f,
+                "Resources of type '{kind}' are served behind middleware the operations endpoint does not run"
+            ),
+            OperationRefused { status } => {
+                write!(f, "The operation was refused with status {status}")
+            }
+            PreconditionFailed => write!(
+                f,
+                "The resource is not at a version named by the 'If-Match' header"
+            ),
+            BatchTooLarge { limit, actual } => write!(
+                f,
+                "The request carries {actual} resources, but at most {limit} are taken at once"
+            ),
+            UnscopedBulkWrite => write!(
+                f,
+                "A bulk update or deletion must carry a 'filter' or 'search' naming the resources it writes"
+            ),
+            BulkScopeTooLarge { limit, actual } => write!(
+                f,
+                "The filter matches {actual} resources, but at most {limit} are written at once"
+            ),
+            UnexpectedResourceId { id } => write!(
+                f,
+                "A bulk update changes the resources its filter matches and cannot name the id '{id}'"
+            ),
             UnsupportedOperation => {
                 write!(f, "This endpoint does not support the requested operation")
             }
@@ -662,6 +893,21 @@ mod tests {
         assert_eq!(error.source(), None);
     }
 
+    /// A query parameter that fails to parse is named as the error's source.
+    #[test]
+    fn a_parameter_parse_failure_names_the_parameter() {
+        let error = Error::from(DatabaseError::ParseParameterFailure {
+            parameter: "filter[scope]".to_string(),
+            message: "Unknown filter scope: 'archived'".to_string(),
+        });
+
+        assert_eq!(error.status(), StatusCode::BAD_REQUEST);
+        assert_eq!(
+            error.source(),
+            Some(Source::Parameter("filter[scope]".to_string()))
+        );
+    }
+
     #[test]
     fn a_nested_serialisation_failure_answers_for_itself() {
         let nested = SerialisationError::LinkGenerationError {
@@ -0,0 +1,174 @@
+//! Conditional reads: validators stamped on a successful `GET`, and the `304 Not Modified` answering
+//! a request that already holds the representation they validate.
+
+use super::{ResourceHandler, ResourceMiddleware};
+use crate::database::adapters::Adapter as AdapterInterface;
+use crate::json_api::{document::Document, primary_content::PrimaryContent, resource::Resource};
+use crate::routing::{
+    Error as RoutingError, ResourceResult,
+    controller::ResourceContext,
+    preconditions::{EntityTag, EntityTags, digest, entity_tag},
+};
+use chrono::{DateTime, SubsecRound, Utc};
+use http::{
+    HeaderMap, HeaderName, HeaderValue, Method, StatusCode,
+    header::{ETAG, IF_MODIFIED_SINCE, IF_NONE_MATCH, LAST_MODIFIED},
+};
+
+/// The format of an HTTP date, always in GMT.
+const HTTP_DATE: &str = "%a, %d %b %Y %H:%M:%S GMT";
+
+/// Answers conditional reads of the routes it wraps. A successful `GET` carries an `ETag` — the
+/// handler's own, such as a versioned record's, extended over the document when the query shapes it
+/// or it carries included resources or meta, or else a strong one over the document — and, given a
+/// timestamp attribute, a `Last-Modified` of the latest timestamp among the primary data. A request
+/// whose `If-None-Match` lists that tag, or, sending none, whose `If-Modified-Since` is no earlier
+/// than that time, is answered `304 Not Modified` with no body.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct ConditionalGet<'sch> {
+    modified_at: Option<&'sch str>,
+}
+
+impl<'sch> ConditionalGet<'sch> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Derives `Last-Modified` from the date-time attribute `attribute`, such as `updated_at`.
+    pub fn last_modified(mut self, attribute: &'sch str) -> Self {
+        self.modified_at = Some(attribute);
+        self
+    }
+
+    /// The latest value of the timestamp attribute among the document's primary data; none when any
+    /// resource lacks one, or the data is no resource at all.
+    fn modified_at(&self, document: &Document) -> Option<DateTime<Utc>> {
+        let attribute = self.modified_at?;
+        let timestamp = |resource: &Resource| {
+            let value = resource.attributes.as_ref()?.get(attribute)?.as_str()?;
+            DateTime::parse_from_rfc3339(value)
+                .ok()
+                .map(|timestamp| timestamp.with_timezone(&Utc).trunc_subsecs(0))
+        };
+
+        match &document.content {
+            PrimaryContent::Record { data } => timestamp(data),
+            PrimaryContent::Collection { data } => data
+                .iter()
+                .map(timestamp)
+                .collect::<Option<Vec<_>>>()?
+                .into_iter()
+                .max(),
+            _ => None,
+        }
+    }
+}
+
+impl<'sch, Adapter: AdapterInterface + 'sch> ResourceMiddleware<'sch, Adapter>
+    for ConditionalGet<'sch>
+{
+    fn handle<'req>(
+        &self,
+        context: ResourceContext<'sch, 'req, Adapter>,
+        next: &ResourceHandler<'sch, 'req, Adapter>,
+    ) -> ResourceResult
+    where
+        'sch: 'req,
+    {
+        if *context.method() != Method::GET {
+            return next(context);
+        }
+
+        let none_match = EntityTags::parse(context.headers(), IF_NONE_MATCH)?;
+        let modified_since = modified_since(context.headers());
+        let queried = context.uri().query().is_some();
+        let mut response = next(context)?;
+        let Some(document) = response
+            .body()
+            .as_ref()
+            .filter(|_| response.status() == StatusCode::OK)
+        else {
+            return Ok(response);
+        };
+
+        let modified_at = self.modified_at(document);
+        // A handler's tag validates the resource's plain representation; one the query shapes, or
+        // carrying more than the resource, is validated by the document's digest as well.
+        let shaped = queried
+            || document
+                .included
+                .as_ref()
+                .is_some_and(|included| !included.is_empty())
+            || document.meta.is_some();
+        let tag = match response.headers().get(ETAG).and_then(entity_tag) {
+            Some(tag) if shaped => fold(tag, digest(document)?),
+            Some(tag) => tag,
+            None => document_tag(digest(document)?),
+        };
+
+        let headers = response.headers_mut();
+        headers.insert(ETAG, header_value(ETAG, render(&tag))?);
+        if let Some(modified_at) = modified_at {
+            headers.insert(
+                LAST_MODIFIED,
+                header_value(LAST_MODIFIED, modified_at.format(HTTP_DATE).to_string())?,
+            );
+        }
+
+        let unmodified = match (none_match, modified_since) {
+            (Some(none_match), _) => none_match.lists(&tag),
+            (None, Some(since)) => modified_at.is_some_and(|modified_at| modified_at <= since),
+            (None, None) => false,
+        };
+        if unmodified {
+            *response.status_mut() = StatusCode::NOT_MODIFIED;
+            *response.body_mut() = None;
+        }
+
+        Ok(response)
+    }
+}
+
+/// A strong entity tag over the document's content.
+fn document_tag(digest: u64) -> EntityTag {
+    EntityTag {
+        weak: false,
+        opaque: format!("{digest:016x}"),
+    }
+}
+
+/// Extends `tag` to validate the document `digest` was taken over too, unless it already does.
+fn fold(tag: EntityTag, digest: u64) -> EntityTag {
+    let digest = format!("-{digest:016x}");
+    if tag.opaque.ends_with(&digest) {
+        return tag;
+    }
+    EntityTag {
+        opaque: tag.opaque + &digest,
+        ..tag
+    }
+}
+
+/// An entity tag as an `ETag` header carries it.
+fn render(tag: &EntityTag) -> String {
+    if tag.weak {
+        format!("W/\"{}\"", tag.opaque)
+    } else {
+        format!("\"{}\"", tag.opaque)
+    }
+}
+
+/// The time an `If-Modified-Since` header names; an unparseable date is ignored, as though absent.
+fn modified_since(headers: &HeaderMap) -> Option<DateTime<Utc>> {
+    let value = headers.get(IF_MODIFIED_SINCE)?.to_str().ok()?;
+    DateTime::parse_from_rfc2822(value)
+        .ok()
+        .map(|since| since.with_timezone(&Utc))
+}
+
+fn header_value(header: HeaderName, value: String) -> Result<HeaderValue, RoutingError> {
+    HeaderValue::try_from(value).map_err(|error| RoutingError::GeneratedInvalidHeader {
+        header: header.to_string(),
+        message: error.to_string(),
+    })
+}
@@ -4,18 +4,18 @@ use itertools::Itertools;
 use std::fmt::Display;
 
 /// The JSON:API media type, sans parameters.
-pub(super) const JSONAPI_MEDIA_TYPE: &str = "application/vnd.api+json";
+pub(crate) const JSONAPI_MEDIA_TYPE: &str = "application/vnd.api+json";
 
 /// The set of media types accepted by the server as matches for JSON:API — the literal type and the
 /// wildcards that subsume it.
-pub(super) const ACCEPTED_MEDIA_TYPES: [&str; 3] = [JSONAPI_MEDIA_TYPE, "application/*", "*/*"];
+pub(crate) const ACCEPTED_MEDIA_TYPES: [&str; 3] = [JSONAPI_MEDIA_TYPE, "application/*", "*/*"];
 
 /// A parsed JSON:API media type: the profiles and extensions applied to it, and its quality weight.
 #[derive(Debug, Default, PartialEq)]
-pub(super) struct JsonApiMediaType<'a> {
-    pub(super) profiles: Vec<&'a str>,
-    pub(super) extensions: Vec<&'a str>,
-    pub(super) quality: Option<f64>,
+pub(crate) struct JsonApiMediaType<'a> {
+    pub(crate) profiles: Vec<&'a str>,
+    pub(crate) extensions: Vec<&'a str>,
+    pub(crate) quality: Option<f64>,
 }
 
 impl<'a> JsonApiMediaType<'a> {
@@ -24,7 +24,7 @@ impl<'a> JsonApiMediaType<'a> {
     /// and the `q` weight; profile and extension URIs are collected verbatim, to be matched by
     /// equality. A media type that is not a JSON:API match is a caller invariant violation (callers
     /// pre-filter), hence the 500.
-    pub(super) fn try_new(media_type: MediaType<'a>) -> Result<Self, Error> {
+    pub(crate) fn try_new(media_type: MediaType<'a>) -> Result<Self, Error> {
         ACCEPTED_MEDIA_TYPES
             .iter()
             .any(|value| media_type.essence.eq_ignore_ascii_case(value))
@@ -1,5 +1,5 @@
-mod media_type;
-mod negotiation;
+pub(crate) mod media_type;
+pub(crate) mod negotiation;
 
 #[cfg(test)]
 mod tests;
@@ -7,15 +7,15 @@ mod tests;
 use super::{ResourceHandler, ResourceMiddleware};
 use crate::database::adapters::Adapter as AdapterInterface;
 use crate::error::Error;
-use crate::http_wrappers::StatusCode;
+use crate::http_wrappers::{StatusCode, Uri};
 use crate::json_api::error::Error as JsonApiError;
 use crate::json_api::primary_content::PrimaryContent;
 use crate::routing::controller::ResourceContext;
-use crate::routing::{Error as RoutingError, ResourceResult, respond_with};
+use crate::routing::{Error as RoutingError, ResourceResult, RouteParameters, respond_with};
 use crate::serialisation::factories::to_document;
 use crate::serialisation::uri_generator::NullUriGenerator;
-use http::HeaderValue;
 use http::header::{CONTENT_TYPE, LOCATION};
+use http::{HeaderMap, HeaderValue};
 use log::error;
 use media_type::{JSONAPI_MEDIA_TYPE, JsonApiMediaType};
 use negotiation::ContentNegotiator;
@@ -25,11 +25,12 @@ use std::borrow::Cow;
 /// TODO: Spec and publish those profiles.
 const PAGINATION_PROFILE: &str = "https://example.com/profiles/pagination";
 const FILTER_PROFILE: &str = "https://example.com/profiles/filter";
+const FILTER_EXPRESSION_PROFILE: &str = "https://example.com/profiles/filter-expression";
 
 /// The framework boundary at every resourceful route: the outermost resource middleware, seeded at
-/// registration. It negotiates content (`415`/`406`), catches a resource-tier error and renders it
-/// into an error document, and stamps the JSON:API `Content-Type` on the response coming back — so
-/// the tier is JSON:API whether it succeeds or fails. A stateless ZST, shared across every route.
+/// registration. It negotiates content (`415`/`406`) against the extensions its endpoint supports,
+/// catches a resource-tier error and renders it into an error document, and stamps the JSON:API
+/// `Content-Type` on the response coming back — so the tier is JSON:API whether it succeeds or fails. A stateless ZST, shared across every route.
 #[derive(Default)]
 pub(crate) struct JsonApi;
 
@@ -43,21 +44,35 @@ impl<'sch, Adapter: AdapterInterface + 'sch> ResourceMiddleware<'sch, Adapter> f
         'sch: 'req,
     {
         let uri = context.uri();
+        let extensions = context.extensions();
 
-        let uses_filter_profile = context
+        let (uses_filter_profile, uses_filter_expression_profile) = context
             .query_parameters()
-            .map(|parameters| parameters.filter.is_some());
+            .map(|parameters| {
+                (
+                    parameters.filter.is_some() || parameters.relationship_filter.is_some(),
+                    parameters.filter_expression.is_some(),
+                )
+            })
+            .unwrap_or_default();
 
-        ContentNegotiator::negotiate(&mut context)
+        ContentNegotiator::negotiate(&mut context, extensions)
             .map_err(Error::from)
             .and_then(|()| next(context))
             .and_then(|mut response| {
-                let mut content_type = JsonApiMediaType::default();
+                let mut content_type = JsonApiMediaType {
+                    extensions: extensions.to_vec(),
+                    ..JsonApiMediaType::default()
+                };
 
-                if uses_filter_profile.is_ok_and(|value| value) {
+                if uses_filter_profile {
                     content_type.profiles.push(FILTER_PROFILE);
                 }
 
+                if uses_filter_expression_profile {
+                    content_type.profiles.push(FILTER_EXPRESSION_PROFILE);
+                }
+
                 if let Some(document) = response.body() {
                     if let Some(ref links) = document.links
                         && let Some(ref pagination) = links.pagination
@@ -98,36 +113,50 @@ impl<'sch, Adapter: AdapterInterface + 'sch> ResourceMiddleware<'sch, Adapter> f
                 response.headers_mut().insert(CONTENT_TYPE, content_type);
                 Ok(response)
             })
-            .or_else(|mut error| {
-                // Render any resource-tier error into an error document. A 5xx is logged whole and
-                // then stripped, so the detail reaches the operator and never the client.
-                let status = error.status.clone();
+            .or_else(|error| render_error(error, uri))
+    }
+}
 
-                if status.is_server_error() {
-                    error!("{uri} failed: {error:?}");
+/// A route guard matching only a request whose `Content-Type` applies the extension it names, so an
+/// endpoint of that extension shares its path with the plain endpoint beside it.
+pub(crate) struct RequiresExtension(pub(crate) &'static str);
 
-                    let is_development = cfg!(debug_assertions);
-                    if !is_development {
-                        redact_error(&mut error);
-                    }
-                }
+impl<'sch, Adapter: AdapterInterface + 'sch> ResourceMiddleware<'sch, Adapter>
+    for RequiresExtension
+{
+    fn matches(&self, headers: &HeaderMap, _uri: &Uri, _route: &RouteParameters) -> bool {
+        ContentNegotiator::require_extension(headers, self.0).is_ok()
+    }
+}
 
-                // An errors document renders no per-record links, so it needs no request-bound
-                // generator: the null generator refuses any link, asserting exactly that.
-                let document = to_document(
-                    vec![JsonApiError::from(error)],
-                    Vec::new(),
-                    uri,
-                    &NullUriGenerator,
-                )?;
-                respond_with(status, Some(document)).map(|mut response| {
-                    response
-                        .headers_mut()
-                        .insert(CONTENT_TYPE, HeaderValue::from_static(JSONAPI_MEDIA_TYPE));
-                    response
-                })
-            })
+/// Renders an error into an error document response. A 5xx is logged whole and then stripped, so
+/// the detail reaches the operator and never the client.
+pub(crate) fn render_error(mut error: Error, uri: &Uri) -> ResourceResult {
+    let status = error.status.clone();
+
+    if status.is_server_error() {
+        error!("{uri} failed: {error:?}");
+
+        let is_development = cfg!(debug_assertions);
+        if !is_development {
+            redact_error(&mut error);
+        }
     }
+
+    // An errors document renders no per-record links, so it needs no request-bound generator: the
+    // null generator refuses any link, asserting exactly that.
+    let document = to_document(
+        vec![JsonApiError::from(error)],
+        Vec::new(),
+        uri,
+        &NullUriGenerator,
+    )?;
+    respond_with(status, Some(document)).map(|mut response| {
+        response
+            .headers_mut()
+            .insert(CONTENT_TYPE, HeaderValue::from_static(JSONAPI_MEDIA_TYPE));
+        response
+    })
 }
 
 /// Strips an error bound for a client down to its status. A `5xx` names broken internals — schema
@@ -1,20 +1,14 @@
 use super::media_type::{ACCEPTED_MEDIA_TYPES, JSONAPI_MEDIA_TYPE, JsonApiMediaType};
 use crate::database::adapters::Adapter as AdapterInterface;
-use crate::routing::Error;
-use crate::routing::controller::ResourceContext;
+use crate::routing::{Error, PrimaryContext};
 use crate::utils::MediaType;
 use http::header::{ACCEPT, CONTENT_TYPE};
 use http::{HeaderMap, HeaderName};
 use itertools::Itertools;
-use std::collections::HashSet;
-use std::sync::LazyLock;
-
-/// The JSON:API extensions the server supports — none, so any `ext` is unsupported.
-static SUPPORTED_EXTENSIONS: LazyLock<HashSet<&str>> = LazyLock::new(HashSet::new);
 
 /// Aggregates behaviour for inspecting and validating the `Content-Type` and `Accept` headers of a
 /// request.
-pub(super) struct ContentNegotiator;
+pub(crate) struct ContentNegotiator;
 
 impl ContentNegotiator {
     /// Checks the request's `Accept` and `Content-Type` for standard conformity.
@@ -29,10 +23,12 @@ impl ContentNegotiator {
     /// The header is never mandatory, but if provided, it must carry at least one valid instance
     /// of the JSON:API media type.
     ///
-    /// On validation, a media type's extensions are matched against a list of supported extensions,
-    /// raising an error naming the offenders on failure. Profiles are informative and ignored.
-    pub(super) fn negotiate<'sch: 'req, 'req, Adapter: AdapterInterface>(
-        context: &mut ResourceContext<'sch, 'req, Adapter>,
+    /// On validation, a media type's extensions are matched against the extensions the endpoint
+    /// `supported`, raising an error naming the offenders on failure. Profiles are informative and
+    /// ignored.
+    pub(crate) fn negotiate<'sch: 'req, 'req, Adapter: AdapterInterface>(
+        context: &mut PrimaryContext<'sch, 'req, Adapter>,
+        supported: &[&str],
     ) -> Result<(), Error> {
         let content_type_required = context.contains_body()?;
         if let Some(extensions) =
@@ -41,7 +37,7 @@ impl ContentNegotiator {
                     content_type
                         .extensions
                         .iter()
-                        .filter(|extension| !SUPPORTED_EXTENSIONS.contains(*extension))
+                        .filter(|extension| !supported.contains(*extension))
                         .map(|extension| extension.to_string())
                         .collect_vec()
                 })
@@ -55,14 +51,14 @@ impl ContentNegotiator {
                 accept
                     .extensions
                     .iter()
-                    .any(|extension| !SUPPORTED_EXTENSIONS.contains(extension))
+                    .any(|extension| !supported.contains(extension))
             })
         {
             return Err(Error::UnsatisfiableJsonApiExtension {
                 extensions: media_types
                     .iter()
                     .flat_map(|accept| accept.extensions.iter())
-                    .filter(|extension| !SUPPORTED_EXTENSIONS.contains(*extension))
+                    .filter(|extension| !supported.contains(*extension))
                     .map(|extension| extension.to_string())
                     .unique()
                     .collect(),
@@ -72,6 +68,17 @@ impl ContentNegotiator {
         Ok(())
     }
 
+    /// Checks that the request's `Content-Type` applies `extension`, which an endpoint serving only
+    /// documents of that extension requires.
+    pub(crate) fn require_extension(headers: &HeaderMap, extension: &str) -> Result<(), Error> {
+        Self::extract_json_api_content_type(headers, true)?
+            .filter(|content_type| content_type.extensions.contains(&extension))
+            .map(|_| ())
+            .ok_or_else(|| Error::MissingJsonApiExtension {
+                extension: extension.to_string(),
+            })
+    }
+
     /// Attempts to read a header value from the supplied header map.
     /// If the header is absent, returns `Ok(None)` and if its value is not valid ASCII, returns an
     /// error.
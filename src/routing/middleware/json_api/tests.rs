@@ -1,4 +1,4 @@
-use super::{FILTER_PROFILE, JsonApi, PAGINATION_PROFILE, redact_error};
+use super::{FILTER_EXPRESSION_PROFILE, FILTER_PROFILE, JsonApi, PAGINATION_PROFILE, redact_error};
 use crate::database::adapters::SqliteAdapter;
 use crate::database::adapters::sqlite::Pool;
 use crate::database::connection_manager::ConnectionManager;
@@ -217,6 +217,31 @@ fn stamps_the_filter_profile_when_the_request_filters() -> TestResult {
     Ok(())
 }
 
+#[test]
+fn stamps_the_filter_expression_profile_when_the_request_uses_an_expression() -> TestResult {
+    let manager = manager()?;
+    let response = handle(
+        &manager,
+        request(
+            "GET",
+            "/articles?filter=or(eq(title,Rust),eq(title,Go))",
+            "",
+            &[],
+        )?,
+        |_context| Ok(Response::new(None)),
+    )?;
+
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(
+        content_type(&response),
+        Some(format!(
+            "application/vnd.api+json;profile=\"{FILTER_EXPRESSION_PROFILE}\""
+        ))
+    );
+
+    Ok(())
+}
+
 #[test]
 fn stamps_the_pagination_profile_when_the_response_paginates() -> TestResult {
     let manager = manager()?;
@@ -1,3 +1,4 @@
+pub mod conditional;
 pub mod json_api;
 
 use super::context::PrimaryContext;
@@ -9,6 +10,8 @@ use crate::http_wrappers::Uri;
 use http::HeaderMap;
 use std::sync::Arc;
 
+pub use conditional::ConditionalGet;
+
 /// Any primary-tier handler: a request handler on the raw byte tier, and the `next` continuation a
 /// `PrimaryMiddleware` calls to run the rest of the chain. The `'req` bound ties the erased handler
 /// to the request, so the recursive continuation need not be `'static`.
@@ -94,6 +97,15 @@ impl<'sch, Adapter: AdapterInterface + 'sch> Middleware<'sch, Adapter> {
         }
     }
 
+    /// Whether `other` is this very middleware, shared between chains, rather than an equal one.
+    pub(crate) fn is(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Middleware::Primary(this), Middleware::Primary(other)) => Arc::ptr_eq(this, other),
+            (Middleware::Resource(this), Middleware::Resource(other)) => Arc::ptr_eq(this, other),
+            _ => false,
+        }
+    }
+
     /// Whether this is a resource-tier middleware — the primaries/resources partition check.
     pub(crate) fn is_resource(&self) -> bool {
         matches!(self, Middleware::Resource(_))
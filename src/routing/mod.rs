@@ -8,6 +8,8 @@ pub mod controller;
 pub mod error;
 pub mod middleware;
 pub mod mount_table;
+mod operations;
+pub mod preconditions;
 pub mod request;
 pub mod responder;
 pub mod result;
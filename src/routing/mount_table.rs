@@ -1,4 +1,5 @@
 use crate::database::adapters::Adapter as AdapterInterface;
+use crate::database::query_parameters::QueryLimits;
 use crate::routing::controller::ResourceController;
 use indexmap::IndexMap;
 use std::borrow::Cow;
@@ -10,33 +11,55 @@ pub(crate) type ControllerFactory<'sch, Adapter> =
 
 /// The two canonical link templates of one mounted relationship, each present only when its
 /// endpoint was mounted. The segments mirror the mounted route's path, so a link rendered from a
-/// template always lands on a real route.
+/// template always lands on a real route. `writable` records whether the linkage was mounted with
+/// its write endpoints.
 #[derive(Default)]
 pub(crate) struct RelationshipMounts<'sch> {
     pub linkage: Option<Vec<Cow<'sch, str>>>,
     pub related: Option<Vec<Cow<'sch, str>>>,
+    pub writable: bool,
 }
 
-/// The canonical mount of one resource: its kind, its controller factory, and the path templates
-/// its links are rendered from — the `base` (collection) prefix, from which the resource path is
-/// `base` + `:id`, and each mounted relationship.
+/// The canonical mount of one resource: its kind, its controller factory, the path templates its
+/// links are rendered from — the `base` (collection) prefix, from which the resource path is
+/// `base` + `:id`, and each mounted relationship — the query limits set on the resource, and whether
+/// it was mounted read-only.
 pub(crate) struct ResourceMount<'sch, Adapter: AdapterInterface> {
     pub kind: &'sch str,
     pub factory: ControllerFactory<'sch, Adapter>,
     pub base: Vec<Cow<'sch, str>>,
     pub relationships: IndexMap<&'sch str, RelationshipMounts<'sch>>,
+    pub query_limits: QueryLimits,
+    pub read_only: bool,
 }
 
 /// Resolves a resource kind to its mount: the controller factory and the link templates the router
 /// captured for it. A kind with no mounted resource resolves to `DefaultController` and no links.
+/// Also carries the router-wide query limits, which a mount's own limits override.
 pub(crate) struct MountTable<'sch, Adapter: AdapterInterface> {
     mounts: IndexMap<&'sch str, ResourceMount<'sch, Adapter>>,
+    query_limits: QueryLimits,
 }
 
 impl<'sch, Adapter: AdapterInterface> MountTable<'sch, Adapter> {
     /// Wraps an already-deduplicated set of mounts, keyed by kind — the router's own assembly path.
     pub(crate) fn new(mounts: IndexMap<&'sch str, ResourceMount<'sch, Adapter>>) -> Self {
-        Self { mounts }
+        Self {
+            mounts,
+            query_limits: QueryLimits::default(),
+        }
+    }
+
+    pub(crate) fn set_query_limits(&mut self, limits: QueryLimits) {
+        self.query_limits = limits;
+    }
+
+    /// The limits a query against `kind` is held to: the resource's own, then the router-wide ones.
+    pub(crate) fn query_limits(&self, kind: &str) -> QueryLimits {
+        match self.mounts.get(kind) {
+            Some(mount) => mount.query_limits.or(self.query_limits),
+            None => self.query_limits,
+        }
     }
 }
 
@@ -44,6 +67,7 @@ impl<'sch, Adapter: AdapterInterface> Default for MountTable<'sch, Adapter> {
     fn default() -> Self {
         Self {
             mounts: IndexMap::new(),
+            query_limits: QueryLimits::default(),
         }
     }
 }
@@ -64,6 +88,7 @@ impl<'sch, Adapter: AdapterInterface> FromIterator<ResourceMount<'sch, Adapter>>
     fn from_iter<I: IntoIterator<Item = ResourceMount<'sch, Adapter>>>(iter: I) -> Self {
         Self {
             mounts: iter.into_iter().map(|mount| (mount.kind, mount)).collect(),
+            query_limits: QueryLimits::default(),
         }
     }
 }
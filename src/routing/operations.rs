@@ -0,0 +1,369 @@
+use super::{
+    Error as RoutingError, PrimaryContext, PrimaryResult,
+    controller::{DocumentReader, LocalIdentifiers},
+    middleware::json_api::{
+        media_type::JsonApiMediaType, negotiation::ContentNegotiator, render_error,
+    },
+    respond, respond_with,
+    router::{serve_operation, stream_document},
+};
+use crate::{
+    database::{
+        adapters::Adapter as AdapterInterface, attributes::Identifier,
+        connection::Connection as ConnectionInterface, error::Error as DatabaseError,
+    },
+    error::{Error, pointer},
+    http_wrappers::StatusCode,
+    json_api::{
+        atomic::{
+            ATOMIC_EXTENSION, Operation, OperationCode, OperationResult, OperationsDocument,
+            Reference, ResultsDocument,
+        },
+        identifier::Identifier as JsonApiIdentifier,
+        primary_content::PrimaryContent,
+        resource::Resource,
+    },
+    serialisation::{ByteStream, factories::to_results_document},
+};
+use http::{HeaderValue, Method, Response, header::CONTENT_TYPE};
+use std::borrow::Cow;
+use std::io::{self, Cursor};
+
+/// The Atomic Operations endpoint: performs every operation of the request document in order,
+/// inside one transaction, so that either all of them take effect or none does. Each runs through
+/// the route a request making it would reach, its middleware and its controller's handler. Served on the raw
+/// byte tier, as the document it reads is not a JSON:API document of any one resource; it renders
+/// its own errors, each pointing at the operation that raised it.
+pub(crate) fn serve_operations<'sch: 'req, 'req, Adapter: AdapterInterface + 'sch>(
+    mut context: PrimaryContext<'sch, 'req, Adapter>,
+) -> PrimaryResult {
+    let uri = context.uri;
+    match perform(&mut context) {
+        Ok(response) => stream_document(response),
+        Err(error) => stream_document(render_error(error, uri)?),
+    }
+}
+
+fn perform<'sch: 'req, 'req, Adapter: AdapterInterface + 'sch>(
+    context: &mut PrimaryContext<'sch, 'req, Adapter>,
+) -> Result<Response<Option<ResultsDocument>>, Error> {
+    ContentNegotiator::negotiate(context, &[ATOMIC_EXTENSION])?;
+    ContentNegotiator::require_extension(context.headers(), ATOMIC_EXTENSION)?;
+
+    let body = context.require_body()?;
+    let document: OperationsDocument = serde_json::from_reader(body).map_err(RoutingError::from)?;
+
+    let context = &*context;
+    let operations = Operations { context };
+    let results = context.connection()?.transaction_with(|| {
+        let mut locals = LocalIdentifiers::new();
+        document
+            .operations
+            .into_iter()
+            .enumerate()
+            .map(|(index, operation)| {
+                operations
+                    .perform(index, operation, &mut locals)
+                    .map_err(|mut error| {
+                        let source = error.source.take().map(|source| *source);
+                        error.source = Some(Box::new(pointer::within_operation(index, source)));
+                        error
+                    })
+            })
+            .collect::<Result<Vec<_>, Error>>()
+    })?;
+
+    // A request whose every operation yields no data answers with no document at all.
+    let mut response = if results
+        .iter()
+        .all(|result| matches!(result.content, PrimaryContent::Absent {}))
+    {
+        respond_with(StatusCode::NO_CONTENT, None)?
+    } else {
+        respond(Some(to_results_document(results)?))?
+    };
+
+    let content_type = JsonApiMediaType {
+        extensions: vec![ATOMIC_EXTENSION],
+        ..JsonApiMediaType::default()
+    };
+    let content_type = HeaderValue::try_from(content_type.to_string()).map_err(|error| {
+        RoutingError::GeneratedInvalidHeader {
+            header: CONTENT_TYPE.to_string(),
+            message: error.to_string(),
+        }
+    })?;
+    response.headers_mut().insert(CONTENT_TYPE, content_type);
+    Ok(response)
+}
+
+/// The request every operation runs within.
+struct Operations<'sch: 'req, 'req, 'a, Adapter: AdapterInterface + 'sch> {
+    context: &'a PrimaryContext<'sch, 'req, Adapter>,
+}
+
+impl<'sch: 'req, 'req, 'a, Adapter: AdapterInterface + 'sch> Operations<'sch, 'req, 'a, Adapter> {
+    /// Performs one operation, registering the `lid` of any resource it creates for the operations
+    /// after it to reference.
+    fn perform(
+        &self,
+        index: usize,
+        operation: Operation,
+        locals: &mut LocalIdentifiers,
+    ) -> Result<OperationResult, Error> {
+        let Operation {
+            op,
+            reference,
+            href,
+            content,
+            ..
+        } = operation;
+
+        if href.is_some() {
+            return Err(RoutingError::OperationHrefNotSupported.into());
+        }
+
+        let content = match (op, reference) {
+            (
+                _,
+                Some(Reference {
+                    identifier,
+                    relationship: Some(relationship),
+                }),
+            ) => self.write_relationship(index, op, identifier, &relationship, content, locals)?,
+            (OperationCode::Add, None) => self.add(content, locals)?,
+            (OperationCode::Add, Some(_)) => {
+                return Err(RoutingError::UnexpectedOperationReference.into());
+            }
+            (OperationCode::Update, reference) => self.update(index, reference, content, locals)?,
+            (OperationCode::Remove, Some(reference)) => {
+                self.remove(index, reference.identifier, locals)?
+            }
+            (OperationCode::Remove, None) => {
+                return Err(RoutingError::MissingOperationReference.into());
+            }
+        };
+
+        Ok(OperationResult {
+            content,
+            meta: None,
+        })
+    }
+
+    /// Creates the resource `data` carries, as a `POST` to its collection would.
+    fn add(
+        &self,
+        content: PrimaryContent,
+        locals: &mut LocalIdentifiers,
+    ) -> Result<PrimaryContent, Error> {
+        let resource = require_resource(content)?;
+        let kind = resource.identifier.kind().to_string();
+        let (mount, _) = self.context.writable_mount(&kind)?;
+
+        let local = match &resource.identifier {
+            JsonApiIdentifier::New {
+                kind,
+                lid: Some(lid),
+            } => {
+                let local = (kind.clone(), lid.clone());
+                if locals.contains_key(&local) {
+                    return Err(RoutingError::DuplicateLocalIdentifier { lid: lid.clone() }.into());
+                }
+                Some(local)
+            }
+            JsonApiIdentifier::New { lid: None, .. } | JsonApiIdentifier::Existing { .. } => None,
+        };
+
+        let content = self.serve(
+            Method::POST,
+            mount.base.clone(),
+            None,
+            Some(PrimaryContent::Record {
+                data: Box::new(resource),
+            }),
+            locals,
+        )?;
+        if let Some(local) = local
+            && let PrimaryContent::Record { data } = &content
+        {
+            let id = self.reader(locals).id(data.identifier.clone(), &kind)?;
+            locals.insert(local, Some(id));
+        }
+
+        Ok(content)
+    }
+
+    /// Updates the resource `data` names, which must be the one a `ref`, if given, names too, as a
+    /// `PATCH` to it would.
+    fn update(
+        &self,
+        index: usize,
+        reference: Option<Reference>,
+        content: PrimaryContent,
+        locals: &LocalIdentifiers,
+    ) -> Result<PrimaryContent, Error> {
+        let mut resource = require_resource(content)?;
+        let kind = resource.identifier.kind().to_string();
+        let (mount, _) = self.context.writable_mount(&kind)?;
+        let reader = self.reader(locals);
+        let id = reader.id(resource.identifier.clone(), &kind)?;
+
+        if let Some(Reference { identifier, .. }) = reference {
+            let expected = at_reference(index, reader.id(identifier, &kind))?;
+            if expected != id {
+                return Err(RoutingError::ResourceIdMismatch {
+                    expected: format!("{expected}"),
+                    actual: format!("{id}"),
+                }
+                .into());
+            }
+        }
+
+        // The route names the resource by its id, so `data` must too, even when it was named by
+        // the `lid` it was created under.
+        if let JsonApiIdentifier::New { kind, lid } = resource.identifier {
+            resource.identifier = JsonApiIdentifier::Existing {
+                kind,
+                id: format!("{id}"),
+                lid,
+            };
+        }
+
+        self.serve(
+            Method::PATCH,
+            member(&mount.base),
+            Some(&id),
+            Some(PrimaryContent::Record {
+                data: Box::new(resource),
+            }),
+            locals,
+        )
+    }
+
+    /// Removes the resource the `ref` names, as a `DELETE` to it would.
+    fn remove(
+        &self,
+        index: usize,
+        identifier: JsonApiIdentifier,
+        locals: &LocalIdentifiers,
+    ) -> Result<PrimaryContent, Error> {
+        let (id, mount) = at_reference(index, {
+            let kind = identifier.kind().to_string();
+            self.context
+                .writable_mount(&kind)
+                .and_then(|(mount, _)| Ok((self.reader(locals).id(identifier, &kind)?, mount)))
+        })?;
+        self.serve(Method::DELETE, member(&mount.base), Some(&id), None, locals)?;
+
+        Ok(PrimaryContent::Absent {})
+    }
+
+    /// Adds members to, removes members from, or replaces a relationship's linkage, as a `POST`, a
+    /// `DELETE` or a `PATCH` to its relationship endpoint would.
+    fn write_relationship(
+        &self,
+        index: usize,
+        op: OperationCode,
+        identifier: JsonApiIdentifier,
+        relationship: &str,
+        content: PrimaryContent,
+        locals: &LocalIdentifiers,
+    ) -> Result<PrimaryContent, Error> {
+        let (template, id) = at_reference(index, {
+            let kind = identifier.kind().to_string();
+            let (mount, schema) = self.context.mount(&kind)?;
+            let descriptor = schema.relationship(relationship).ok_or_else(|| {
+                DatabaseError::InvalidRelationshipAccess {
+                    schema: kind.clone(),
+                    relationship: relationship.to_string(),
+                }
+            })?;
+            let template = mount
+                .relationships
+                .get(descriptor.name)
+                .filter(|mounts| mounts.writable)
+                .and_then(|mounts| mounts.linkage.clone())
+                .ok_or(RoutingError::UnsupportedOperation)?;
+
+            self.reader(locals)
+                .id(identifier, &kind)
+                .map(|id| (template, id))
+                .map_err(Error::from)
+        })?;
+
+        if let PrimaryContent::Absent {} = content {
+            return Err(RoutingError::MissingOperationData.into());
+        }
+        let method = match op {
+            OperationCode::Add => Method::POST,
+            OperationCode::Update => Method::PATCH,
+            OperationCode::Remove => Method::DELETE,
+        };
+        self.serve(method, template, Some(&id), Some(content), locals)?;
+
+        Ok(PrimaryContent::Absent {})
+    }
+
+    /// Serves an operation as a `method` request to the route mounted at `path` would be served,
+    /// the route's `:id` the record at `id`, and its body `content`. Yields the primary data the
+    /// route answers with.
+    fn serve(
+        &self,
+        method: Method,
+        path: Vec<Cow<'sch, str>>,
+        id: Option<&Identifier>,
+        content: Option<PrimaryContent>,
+        locals: &LocalIdentifiers,
+    ) -> Result<PrimaryContent, Error> {
+        let mut route = self.context.route_parameters().clone();
+        if let Some(id) = id {
+            route.insert("id", format!("{id}"));
+        }
+        let body: ByteStream = match content {
+            Some(content) => Box::new(Cursor::new(
+                serde_json::to_vec(&content).map_err(RoutingError::from)?,
+            )),
+            None => Box::new(io::empty()),
+        };
+
+        let response = serve_operation(
+            self.context.for_operation(method, route, body),
+            &path,
+            locals.clone(),
+        )?;
+        Ok(response
+            .into_body()
+            .map(|document| document.content)
+            .unwrap_or(PrimaryContent::Absent {}))
+    }
+
+    fn reader<'b>(&self, locals: &'b LocalIdentifiers) -> DocumentReader<'sch, 'b> {
+        DocumentReader::new(self.context.manager.registry(), locals)
+    }
+}
+
+/// The path template of a resource's member, under its collection's `base`.
+fn member<'sch>(base: &[Cow<'sch, str>]) -> Vec<Cow<'sch, str>> {
+    base.iter().cloned().chain([Cow::Borrowed(":id")]).collect()
+}
+
+/// Takes an operation's `data` as the single resource object a resource operation carries.
+fn require_resource(content: PrimaryContent) -> Result<Resource, Error> {
+    match content {
+        PrimaryContent::Record { data } => Ok(*data),
+        PrimaryContent::Absent {} => Err(RoutingError::MissingOperationData.into()),
+        PrimaryContent::Errors { .. } => Err(RoutingError::ErrorDocumentSubmitted.into()),
+        PrimaryContent::Collection { .. } | PrimaryContent::Empty { .. } => {
+            Err(RoutingError::PrimaryDataIsNotAResource.into())
+        }
+    }
+}
+
+/// Points a failure to resolve an operation's `ref` at the `ref`.
+fn at_reference<T>(index: usize, result: Result<T, impl Into<Error>>) -> Result<T, Error> {
+    result.map_err(|error| {
+        let mut error = error.into();
+        error.source = Some(Box::new(pointer::for_operation_member(index, "ref")));
+        error
+    })
+}
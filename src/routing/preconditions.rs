@@ -0,0 +1,321 @@
+//! Entity tags derived from a record's lock version, and the `If-Match` and `If-None-Match`
+//! preconditions compared against them.
+
+use super::Error;
+use crate::database::{attributes::Attribute, record::Record, schema::Schema};
+use crate::json_api::document::Document;
+use http::{HeaderMap, HeaderName, HeaderValue, Response, header::ETAG};
+use serde_json::Value;
+use std::hash::{DefaultHasher, Hash, Hasher};
+
+/// The version `record` is at, when its schema is versioned and the version was read.
+pub fn version_of(record: &Record) -> Option<i64> {
+    match record.attributes.get(record.schema.version()?) {
+        Some(&Attribute::Integer(version)) => Some(version),
+        _ => None,
+    }
+}
+
+/// Stamps a strong entity tag of the version `record` is at onto `response`, followed by a digest
+/// of the document it carries: what is included, the fieldset, the linkage and meta shape the
+/// representation without moving the version. An unversioned record, or one read without its
+/// version, is not tagged.
+pub fn tag_record(response: &mut Response<Option<Document>>, record: &Record) -> Result<(), Error> {
+    let Some(version) = version_of(record) else {
+        return Ok(());
+    };
+    let tag = match response.body() {
+        Some(document) => format!("\"{version}-{:016x}\"", digest(document)?),
+        None => format!("\"{version}\""),
+    };
+    stamp(response, tag)
+}
+
+/// Stamps a weak entity tag over the id and version of each of `records`, and the document
+/// carrying them, onto `response`. It changes whenever a record is added, removed or updated, or
+/// anything else the document renders does. A collection of an unversioned schema, or read without
+/// its versions, is not tagged.
+pub fn tag_collection(
+    response: &mut Response<Option<Document>>,
+    schema: &Schema,
+    records: &[Record],
+) -> Result<(), Error> {
+    if schema.version().is_none() {
+        return Ok(());
+    }
+
+    let mut hasher = DefaultHasher::new();
+    for record in records {
+        let Some(version) = version_of(record) else {
+            return Ok(());
+        };
+        (&record.id, version).hash(&mut hasher);
+    }
+    if let Some(document) = response.body() {
+        digest(document)?.hash(&mut hasher);
+    }
+    stamp(response, format!("W/\"{:016x}\"", hasher.finish()))
+}
+
+/// A digest of `document`'s content. Its members are hashed in sorted order, so equal documents
+/// digest alike however their maps happen to be ordered.
+pub(crate) fn digest(document: &Document) -> Result<u64, Error> {
+    let value = serde_json::to_value(document)?;
+    let mut hasher = DefaultHasher::new();
+    hash_value(&value, &mut hasher);
+    Ok(hasher.finish())
+}
+
+fn hash_value(value: &Value, hasher: &mut DefaultHasher) {
+    match value {
+        Value::Object(members) => {
+            let mut members: Vec<_> = members.iter().collect();
+            members.sort_by_key(|(name, _)| *name);
+            for (name, member) in members {
+                name.hash(hasher);
+                hash_value(member, hasher);
+            }
+        }
+        Value::Array(elements) => elements
+            .iter()
+            .for_each(|element| hash_value(element, hasher)),
+        scalar => scalar.to_string().hash(hasher),
+    }
+}
+
+fn stamp<T>(response: &mut Response<T>, tag: String) -> Result<(), Error> {
+    let tag = HeaderValue::try_from(tag).map_err(|error| Error::GeneratedInvalidHeader {
+        header: ETAG.to_string(),
+        message: error.to_string(),
+    })?;
+    response.headers_mut().insert(ETAG, tag);
+    Ok(())
+}
+
+/// An entity tag: its opaque value, and whether it is weak.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EntityTag {
+    pub weak: bool,
+    pub opaque: String,
+}
+
+impl EntityTag {
+    /// Whether the two tags name the same representation: their values agree and, compared
+    /// strongly, neither is weak.
+    fn agrees(&self, other: &EntityTag, strongly: bool) -> bool {
+        self.opaque == other.opaque && !(strongly && (self.weak || other.weak))
+    }
+
+    /// The version a record's tag names: its value up to the digest of the representation it was
+    /// stamped on, if any.
+    fn version(&self) -> &str {
+        self.opaque
+            .split_once('-')
+            .map_or(self.opaque.as_str(), |(version, _)| version)
+    }
+}
+
+/// The entity tags a precondition header lists: any current representation, or one of those tags.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EntityTags {
+    Any,
+    Tags(Vec<EntityTag>),
+}
+
+impl EntityTags {
+    /// Reads the entity tags listed across every `header` of a request; none when it sends none.
+    pub fn parse(headers: &HeaderMap, header: HeaderName) -> Result<Option<Self>, Error> {
+        let malformed = |message: String| Error::InvalidHeaderValue {
+            header: header.to_string(),
+            message,
+        };
+
+        let mut values = headers.get_all(&header).iter().peekable();
+        if values.peek().is_none() {
+            return Ok(None);
+        }
+
+        let (mut any, mut tags) = (false, Vec::new());
+        for value in values {
+            let value = value
+                .to_str()
+                .map_err(|error| malformed(error.to_string()))?;
+            let (listed_any, listed) = list(value).map_err(|message| malformed(message.into()))?;
+            any |= listed_any;
+            tags.extend(listed);
+        }
+
+        match (any, tags.is_empty()) {
+            (true, _) => Ok(Some(EntityTags::Any)),
+            (false, true) => Err(malformed("The header names no entity tag".to_string())),
+            (false, false) => Ok(Some(EntityTags::Tags(tags))),
+        }
+    }
+
+    /// Whether a resource at `version` satisfies an `If-Match` precondition: a listed tag names
+    /// that version, of whichever representation it was stamped on. An unversioned resource, with
+    /// none, satisfies only `*`. Tags compare strongly, so a weak tag never matches.
+    pub fn matches(&self, version: Option<i64>) -> bool {
+        match (self, version) {
+            (EntityTags::Any, _) => true,
+            (EntityTags::Tags(tags), Some(version)) => {
+                let version = version.to_string();
+                tags.iter().any(|tag| !tag.weak && tag.version() == version)
+            }
+            (EntityTags::Tags(_), None) => false,
+        }
+    }
+
+    /// Whether a representation tagged `current` is one an `If-None-Match` precondition lists,
+    /// comparing weakly.
+    pub fn lists(&self, current: &EntityTag) -> bool {
+        match self {
+            EntityTags::Any => true,
+            EntityTags::Tags(tags) => tags.iter().any(|tag| tag.agrees(current, false)),
+        }
+    }
+}
+
+/// Reads the entity tag of an `ETag` header value.
+pub fn entity_tag(value: &HeaderValue) -> Option<EntityTag> {
+    match list(value.to_str().ok()?) {
+        Ok((false, mut tags)) if tags.len() == 1 => tags.pop(),
+        _ => None,
+    }
+}
+
+/// Splits a comma-separated list of entity tags, noting whether it lists `*`.
+fn list(mut rest: &str) -> Result<(bool, Vec<EntityTag>), &'static str> {
+    let (mut any, mut tags) = (false, Vec::new());
+    loop {
+        rest = rest.trim_start_matches([' ', '\t', ',']);
+        if rest.is_empty() {
+            return Ok((any, tags));
+        }
+        if let Some(after) = rest.strip_prefix('*') {
+            any = true;
+            rest = after;
+            continue;
+        }
+
+        let (weak, quoted) = match rest.strip_prefix("W/") {
+            Some(quoted) => (true, quoted),
+            None => (false, rest),
+        };
+        let quoted = quoted
+            .strip_prefix('"')
+            .ok_or("An entity tag must be quoted")?;
+        let end = quoted.find('"').ok_or("An entity tag is not closed")?;
+        tags.push(EntityTag {
+            weak,
+            opaque: quoted[..end].to_string(),
+        });
+
+        rest = quoted[end + 1..].trim_start_matches([' ', '\t']);
+        if !rest.is_empty() && !rest.starts_with(',') {
+            return Err("Entity tags must be separated by commas");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use http::header::IF_MATCH;
+
+    fn if_match(values: &[&str]) -> Result<Option<EntityTags>, Error> {
+        let mut headers = HeaderMap::new();
+        for value in values {
+            headers.append(
+                IF_MATCH,
+                HeaderValue::from_str(value).expect("a header value"),
+            );
+        }
+        EntityTags::parse(&headers, IF_MATCH)
+    }
+
+    #[test]
+    fn test_no_header_sets_no_precondition() {
+        assert_eq!(if_match(&[]), Ok(None));
+    }
+
+    #[test]
+    fn test_tags_are_read_across_headers() {
+        let condition = if_match(&["\"1\", W/\"2\"", "\"3\""]);
+
+        assert_eq!(
+            condition,
+            Ok(Some(EntityTags::Tags(vec![
+                EntityTag {
+                    weak: false,
+                    opaque: "1".to_string()
+                },
+                EntityTag {
+                    weak: true,
+                    opaque: "2".to_string()
+                },
+                EntityTag {
+                    weak: false,
+                    opaque: "3".to_string()
+                },
+            ])))
+        );
+    }
+
+    #[test]
+    fn test_only_a_strong_tag_of_the_version_matches() -> Result<(), Error> {
+        let condition = if_match(&["W/\"4\", \"5\""])?.expect("a precondition");
+
+        assert!(condition.matches(Some(5)));
+        assert!(!condition.matches(Some(4)));
+        assert!(!condition.matches(None));
+        Ok(())
+    }
+
+    #[test]
+    fn test_a_tag_of_a_representation_matches_its_version() -> Result<(), Error> {
+        let condition = if_match(&["\"5-00ff00ff00ff00ff\""])?.expect("a precondition");
+
+        assert!(condition.matches(Some(5)));
+        assert!(!condition.matches(Some(50)));
+        Ok(())
+    }
+
+    #[test]
+    fn test_a_wildcard_matches_any_resource() -> Result<(), Error> {
+        let condition = if_match(&["*"])?.expect("a precondition");
+
+        assert_eq!(condition, EntityTags::Any);
+        assert!(condition.matches(None));
+        Ok(())
+    }
+
+    #[test]
+    fn test_an_unquoted_tag_is_malformed() {
+        assert_eq!(
+            if_match(&["5"]),
+            Err(Error::InvalidHeaderValue {
+                header: "if-match".to_string(),
+                message: "An entity tag must be quoted".to_string(),
+            })
+        );
+    }
+
+    #[test]
+    fn test_a_weak_tag_is_listed_under_weak_comparison() -> Result<(), Error> {
+        let condition = if_match(&["W/\"4\""])?.expect("a precondition");
+
+        assert!(condition.lists(&EntityTag {
+            weak: false,
+            opaque: "4".to_string()
+        }));
+        assert_eq!(
+            entity_tag(&HeaderValue::from_static("W/\"4\"")),
+            Some(EntityTag {
+                weak: true,
+                opaque: "4".to_string()
+            })
+        );
+        Ok(())
+    }
+}
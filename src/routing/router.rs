@@ -1,14 +1,14 @@
 use super::{
     BaseUri, Error, PrimaryContext, PrimaryRequest, PrimaryResult, ResourceResult, RouteParameters,
     builders::{PrimaryRouteBuilder, RouteBuilder},
-    controller::ResourceContext,
+    controller::{LocalIdentifiers, ResourceContext},
     middleware::Middleware,
     respond_with,
 };
 use crate::{
     database::{
         adapters::Adapter as AdapterInterface, connection_manager::ConnectionManager,
-        schema::Schema,
+        query_parameters::QueryLimits, schema::Schema,
     },
     http_wrappers::{StatusCode, Uri},
     routing::mount_table::{MountTable, ResourceMount},
@@ -18,6 +18,7 @@ use http::{HeaderMap, Method, Response};
 use indexmap::IndexMap;
 use itertools::Itertools;
 use log::{debug, error};
+use serde::Serialize;
 use std::borrow::Cow;
 use std::fmt::{self, Display};
 use std::io::Cursor;
@@ -58,6 +59,7 @@ pub enum EndpointHandler<'sch, Adapter: AdapterInterface> {
     Primary(Box<dyn PrimaryEndpointHandler<'sch, Adapter>>),
     Resource {
         schema: &'sch Schema<'sch>,
+        extensions: &'static [&'static str],
         handler: Box<dyn ResourceEndpointHandler<'sch, Adapter>>,
     },
 }
@@ -73,9 +75,20 @@ impl<'sch, Adapter: AdapterInterface + 'sch> EndpointHandler<'sch, Adapter> {
     pub(crate) fn resource(
         schema: &'sch Schema<'sch>,
         handler: impl ResourceEndpointHandler<'sch, Adapter>,
+    ) -> Self {
+        Self::extended(schema, &[], handler)
+    }
+
+    /// A schema-bound handler serving documents of the JSON:API `extensions` it names, which the
+    /// boundary then negotiates.
+    pub(crate) fn extended(
+        schema: &'sch Schema<'sch>,
+        extensions: &'static [&'static str],
+        handler: impl ResourceEndpointHandler<'sch, Adapter>,
     ) -> Self {
         EndpointHandler::Resource {
             schema,
+            extensions,
             handler: Box::new(handler),
         }
     }
@@ -106,6 +119,43 @@ impl<'sch, Adapter: AdapterInterface + 'sch> Route<'sch, Adapter> {
         }
     }
 
+    /// This route's middleware either side of its JSON:API boundary: the schema-less ones before
+    /// it, and the schema-bound ones after it.
+    fn around_boundary(&self) -> (&[Middleware<'sch, Adapter>], &[Middleware<'sch, Adapter>]) {
+        let boundary = self
+            .middleware
+            .iter()
+            .position(Middleware::is_resource)
+            .unwrap_or(self.middleware.len());
+        let (primaries, resources) = self.middleware.split_at(boundary);
+        (primaries, resources.get(1..).unwrap_or_default())
+    }
+
+    /// Whether every middleware in `middleware` also wraps the request `context` serves, so that it
+    /// has run already.
+    fn runs_within<'req>(
+        middleware: &[Middleware<'sch, Adapter>],
+        context: &PrimaryContext<'sch, 'req, Adapter>,
+    ) -> bool
+    where
+        'sch: 'req,
+    {
+        middleware
+            .iter()
+            .all(|middleware| context.middleware().iter().any(|own| own.is(middleware)))
+    }
+
+    /// Whether every middleware of this route, its JSON:API boundary aside, wraps the request
+    /// `context` serves too, so that a write this route makes can be made within that request
+    /// without passing any of them over.
+    pub(crate) fn wrapped_by<'req>(&self, context: &PrimaryContext<'sch, 'req, Adapter>) -> bool
+    where
+        'sch: 'req,
+    {
+        let (primaries, resources) = self.around_boundary();
+        Self::runs_within(primaries, context) && Self::runs_within(resources, context)
+    }
+
     /// Matches the request line against this route's method and path template, capturing dynamic
     /// segments. Does not consult middleware.
     ///
@@ -336,6 +386,12 @@ impl<'sch, Adapter: AdapterInterface + 'sch> MaterialisedRoutes<'sch, Adapter> {
         self.mounts.extend(other.mounts);
     }
 
+    /// Folds `other` in ahead of the routes already built, so that its routes are matched first.
+    pub(crate) fn absorb_ahead(&mut self, other: MaterialisedRoutes<'sch, Adapter>) {
+        self.routes.splice(0..0, other.routes);
+        self.mounts.extend(other.mounts);
+    }
+
     /// Unwraps every route (surfacing the first build fault), then folds the mounts by kind
     /// (surfacing a duplicate resource) into the finished routes and controller lookup.
     fn resolve(
@@ -375,23 +431,32 @@ where
             primary.handle(context, &|context| serve(rest, handler, context))
         }
         (_, EndpointHandler::Primary(handler)) => handler(context),
-        (_, EndpointHandler::Resource { schema, handler }) => {
-            let response = serve_resource(
-                middleware,
-                &**handler,
-                ResourceContext::new(schema, context),
-            )?;
-            let (parts, body) = response.into_parts();
-            let body = body
-                .map(|document| serde_json::to_vec(&document))
-                .transpose()?
-                .map(|bytes| Box::new(Cursor::new(bytes)) as ByteStream);
-
-            Ok(Response::from_parts(parts, body))
-        }
+        (
+            _,
+            EndpointHandler::Resource {
+                schema,
+                extensions,
+                handler,
+            },
+        ) => stream_document(serve_resource(
+            middleware,
+            &**handler,
+            ResourceContext::new(schema, context).supporting(extensions),
+        )?),
     }
 }
 
+/// Serialises a response's document to the byte stream the primary tier returns.
+pub(crate) fn stream_document(response: Response<Option<impl Serialize>>) -> PrimaryResult {
+    let (parts, body) = response.into_parts();
+    let body = body
+        .map(|document| serde_json::to_vec(&document))
+        .transpose()?
+        .map(|bytes| Box::new(Cursor::new(bytes)) as ByteStream);
+
+    Ok(Response::from_parts(parts, body))
+}
+
 /// Runs the schema-bound middleware wrapping a resource handler — each wraps the recursive call that
 /// runs the rest — then the handler. Any schema-less middleware left in the slice has already run
 /// against the `PrimaryContext`, so it is passed over.
@@ -415,6 +480,76 @@ where
     }
 }
 
+/// The route mounted at the path template `path` that a `method` request in `context` would reach,
+/// its guards met by the request's head.
+pub(crate) fn route_at<'sch, 'req, Adapter>(
+    context: &PrimaryContext<'sch, 'req, Adapter>,
+    method: &Method,
+    path: &[Cow<'sch, str>],
+) -> Option<&'req Route<'sch, Adapter>>
+where
+    'sch: 'req,
+    Adapter: AdapterInterface + 'sch,
+{
+    context.routes().iter().find(|route| {
+        route.method == method
+            && route.path == path
+            && route.middleware.iter().all(|middleware| {
+                middleware.matches(context.headers(), context.uri, context.route_parameters())
+            })
+    })
+}
+
+/// Runs one operation of an Atomic Operations request through the route mounted at `path` for the
+/// context's method, as a request to that route would run: the route must match, its schema-bound
+/// middleware wraps its handler, and the handler writes on the request's connection. The JSON:API
+/// boundary is passed over, as the operations endpoint negotiates and renders for the whole request.
+/// A schema-less middleware runs on bytes no operation has, so the route's must all wrap the
+/// operations endpoint too, having run already; a route behind any other is refused. `locals`
+/// resolves the `lid`s the operation's document names.
+pub(crate) fn serve_operation<'sch, 'req, Adapter>(
+    context: PrimaryContext<'sch, 'req, Adapter>,
+    path: &[Cow<'sch, str>],
+    locals: LocalIdentifiers,
+) -> ResourceResult
+where
+    'sch: 'req,
+    Adapter: AdapterInterface + 'sch,
+{
+    let route = route_at(&context, context.method(), path).ok_or(Error::UnsupportedOperation)?;
+    let EndpointHandler::Resource {
+        schema,
+        extensions,
+        handler,
+    } = &route.handler
+    else {
+        return Err(Error::UnsupportedOperation.into());
+    };
+
+    let (primaries, resources) = route.around_boundary();
+    if !Route::runs_within(primaries, &context) {
+        return Err(Error::OperationBehindMiddleware {
+            kind: schema.name().to_string(),
+        }
+        .into());
+    }
+
+    let response = serve_resource(
+        resources,
+        &**handler,
+        ResourceContext::new(schema, context)
+            .supporting(extensions)
+            .resolving(locals),
+    )?;
+    if !response.status().is_success() {
+        return Err(Error::OperationRefused {
+            status: response.status().into(),
+        }
+        .into());
+    }
+    Ok(response)
+}
+
 /// A schema-aware router: the base its links are rooted at, the mounted routes it dispatches to,
 /// and the mount table its handlers resolve controllers and link templates through.
 pub struct Router<'sch, Adapter: AdapterInterface> {
@@ -440,6 +575,13 @@ impl<'sch, Adapter: AdapterInterface + 'sch> Router<'sch, Adapter> {
         })
     }
 
+    /// Bounds the complexity of every query the router parses. A resource's own limits, set on
+    /// its builder, take precedence over these.
+    pub fn with_query_limits(mut self, limits: QueryLimits) -> Self {
+        self.mount_table.set_query_limits(limits);
+        self
+    }
+
     /// Dispatches a request. An unmatched route yields a bare bodyless 404; a matched one runs its
     /// middleware and handler. The result is fallible to the embedder: expected JSON:API errors are
     /// rendered into documents by the resource middleware, so an `Err` here is exceptional.
@@ -468,7 +610,8 @@ impl<'sch, Adapter: AdapterInterface + 'sch> Router<'sch, Adapter> {
                     &uri,
                     parameters,
                     request,
-                );
+                )
+                .serving(&self.routes, &route.middleware);
                 serve(&route.middleware, &route.handler, context)
             })
             .unwrap_or_else(|| {
@@ -1,12 +1,18 @@
 use crate::database::adapters::SqliteAdapter;
 use crate::database::adapters::sqlite::Pool;
+use crate::database::attributes::Attribute;
+use crate::database::audit::{AUDIT_LOG, audit_log};
 use crate::database::connection_manager::ConnectionManager;
+use crate::database::query_parameters::QueryLimits;
+use crate::database::record::Record;
 use crate::database::registry::Registry as DatabaseRegistry;
 use crate::database::schema::{AttributeType, Related, SchemaBuilder};
+use crate::database::table::Table;
+use crate::error::Error as CrateError;
 use crate::http_wrappers::Uri;
 use crate::routing::builders::RouteBuilder;
-use crate::routing::controller::{ResourceContext, ResourceController};
-use crate::routing::middleware::{PrimaryMiddleware, ResourceMiddleware};
+use crate::routing::controller::{Configuration, ResourceContext, ResourceController};
+use crate::routing::middleware::{ConditionalGet, PrimaryMiddleware, ResourceMiddleware};
 use crate::routing::responder::respond_with;
 use crate::routing::{
     BaseUri, PrimaryContext, PrimaryHandler, PrimaryResult, ResourceHandler,
@@ -45,6 +51,7 @@ fn articles_schema() -> SchemaBuilder<'static> {
                 .pointing_related("article_id")
                 .to_own("id"),
         )
+        .indexed(["title", "body"])
 }
 
 fn comments_schema() -> SchemaBuilder<'static> {
@@ -62,6 +69,7 @@ fn comments_schema() -> SchemaBuilder<'static> {
 fn drafts_schema() -> SchemaBuilder<'static> {
     SchemaBuilder::table("drafts")
         .attribute("title", AttributeType::Text)
+        .attribute("revision", AttributeType::Integer)
         .foreign_key("article_id", AttributeType::Integer)
         .belongs_to(
             "article",
@@ -69,11 +77,13 @@ fn drafts_schema() -> SchemaBuilder<'static> {
                 .pointing_own("article_id")
                 .to_related("id"),
         )
+        .versioned("revision")
 }
 
 fn summaries_schema() -> SchemaBuilder<'static> {
     SchemaBuilder::table("summaries")
         .attribute("abstract", AttributeType::Text)
+        .attribute("revised_at", AttributeType::DateTime)
         .foreign_key("article_id", AttributeType::Integer)
         .belongs_to(
             "article",
@@ -94,7 +104,14 @@ fn schemas() -> [SchemaBuilder<'static>; 4] {
 
 #[derive(Default)]
 struct Articles;
-impl<'sch> ResourceController<'sch, SqliteAdapter> for Articles {}
+impl<'sch> ResourceController<'sch, SqliteAdapter> for Articles {
+    fn configuration(&self) -> Configuration {
+        Configuration {
+            accepts_inclusion: true,
+            ..Configuration::default()
+        }
+    }
+}
 
 #[derive(Default)]
 struct Comments;
@@ -102,15 +119,29 @@ impl<'sch> ResourceController<'sch, SqliteAdapter> for Comments {}
 
 #[derive(Default)]
 struct Drafts;
-impl<'sch> ResourceController<'sch, SqliteAdapter> for Drafts {}
+impl<'sch> ResourceController<'sch, SqliteAdapter> for Drafts {
+    fn configuration(&self) -> Configuration {
+        Configuration {
+            accepts_inclusion: true,
+            ..Configuration::default()
+        }
+    }
+}
 
 #[derive(Default)]
 struct Summaries;
 impl<'sch> ResourceController<'sch, SqliteAdapter> for Summaries {}
 
+#[derive(Default)]
+struct AuditLog;
+impl<'sch> ResourceController<'sch, SqliteAdapter> for AuditLog {}
+
 fn manager() -> Result<Manager, Box<dyn StdError>> {
-    let manager: Manager =
-        ConnectionManager::new(DatabaseRegistry::try_new(schemas())?, Pool::memory()?);
+    manager_with(DatabaseRegistry::try_new(schemas())?)
+}
+
+fn manager_with(registry: DatabaseRegistry<'static>) -> Result<Manager, Box<dyn StdError>> {
+    let manager: Manager = ConnectionManager::new(registry, Pool::memory()?);
 
     manager.acquire()?.execute_batch(
         "CREATE TABLE articles (id INTEGER PRIMARY KEY, title TEXT NOT NULL, body TEXT); \
@@ -124,22 +155,36 @@ fn manager() -> Result<Manager, Box<dyn StdError>> {
            id INTEGER PRIMARY KEY, \
            article_id INTEGER, \
            title TEXT NOT NULL, \
+           revision INTEGER NOT NULL DEFAULT 1, \
            FOREIGN KEY(article_id) REFERENCES articles(id) \
          ); \
          CREATE TABLE summaries ( \
            id INTEGER PRIMARY KEY, \
            article_id INTEGER NOT NULL UNIQUE, \
            abstract TEXT NOT NULL, \
+           revised_at TEXT, \
            FOREIGN KEY(article_id) REFERENCES articles(id) \
          ); \
+         CREATE TABLE audit_log ( \
+           id INTEGER PRIMARY KEY, \
+           resource TEXT NOT NULL, \
+           record_id TEXT NOT NULL, \
+           operation TEXT NOT NULL, \
+           changes TEXT NOT NULL, \
+           actor TEXT, \
+           recorded_at TEXT NOT NULL \
+         ); \
          INSERT INTO articles (id, title, body) \
            VALUES (1, 'First', 'Body one'), (2, 'Second', 'Body two'); \
          INSERT INTO comments (id, article_id, content) \
            VALUES (1, 1, 'Nice'), (2, 1, 'Agreed'); \
          INSERT INTO drafts (id, article_id, title) \
            VALUES (1, 1, 'Draft A'), (2, 1, 'Draft B'); \
-         INSERT INTO summaries (id, article_id, abstract) \
-           VALUES (1, 1, 'About first'), (2, 2, 'About second');",
+         INSERT INTO summaries (id, article_id, abstract, revised_at) \
+           VALUES (1, 1, 'About first', '2024-03-01T09:30:00.250+00:00'), \
+                  (2, 2, 'About second', '2024-05-17T16:00:00+02:00'); \
+         CREATE VIRTUAL TABLE articles_fts USING fts5(title, body); \
+         INSERT INTO articles_fts (rowid, title, body) SELECT id, title, body FROM articles;",
     )?;
 
     Ok(manager)
@@ -366,6 +411,224 @@ fn test_create_yields_record() -> TestResult {
     Ok(())
 }
 
+#[test]
+fn test_create_makes_included_resources_named_by_lid() -> TestResult {
+    let manager = manager()?;
+    let response = serve(
+        &manager,
+        "POST",
+        "/articles",
+        json!({
+            "data": {
+                "type": "articles",
+                "attributes": { "title": "Third", "body": "Three" },
+                "relationships": {
+                    "drafts": { "data": [
+                        { "type": "drafts", "lid": "d1" },
+                        { "type": "drafts", "lid": "d2" }
+                    ] }
+                }
+            },
+            "included": [
+                { "type": "drafts", "lid": "d1", "attributes": { "title": "One" } },
+                { "type": "drafts", "lid": "d2", "attributes": { "title": "Two" } }
+            ]
+        }),
+    )?;
+
+    assert_eq!(response.status(), StatusCode::CREATED);
+    let document = body(&response);
+    let included = document["included"].as_array().expect("an included array");
+    assert_eq!(included.len(), 2);
+    for draft in included {
+        assert!(draft["id"].is_string());
+    }
+    assert_eq!(included[0]["lid"], json!("d1"));
+    assert_eq!(included[1]["lid"], json!("d2"));
+    assert_eq!(
+        document["data"]["relationships"]["drafts"]["data"],
+        json!([
+            { "type": "drafts", "id": included[0]["id"] },
+            { "type": "drafts", "id": included[1]["id"] }
+        ])
+    );
+
+    let article = document["data"]["id"].as_str().expect("an article id");
+    let drafts = serve(
+        &manager,
+        "GET",
+        &format!("/articles/{article}/drafts"),
+        Value::Null,
+    )?;
+    assert_eq!(
+        data_ids(&drafts),
+        vec![included[0]["id"].clone(), included[1]["id"].clone()]
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_create_links_to_one_to_an_included_resource() -> TestResult {
+    let manager = manager()?;
+    let response = serve(
+        &manager,
+        "POST",
+        "/comments",
+        json!({
+            "data": {
+                "type": "comments",
+                "attributes": { "content": "First!" },
+                "relationships": {
+                    "article": { "data": { "type": "articles", "lid": "a1" } }
+                }
+            },
+            "included": [
+                { "type": "articles", "lid": "a1", "attributes": { "title": "New", "body": "Fresh" } }
+            ]
+        }),
+    )?;
+
+    assert_eq!(response.status(), StatusCode::CREATED);
+    let document = body(&response);
+    let article = &document["included"][0];
+    assert_eq!(article["lid"], json!("a1"));
+    assert_eq!(
+        document["data"]["relationships"]["article"]["data"],
+        json!({ "type": "articles", "id": article["id"] })
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_create_refuses_an_included_resource_without_lid() -> TestResult {
+    let manager = manager()?;
+    let response = serve(
+        &manager,
+        "POST",
+        "/comments",
+        json!({
+            "data": {
+                "type": "comments",
+                "attributes": { "content": "First!" },
+                "relationships": { "article": { "data": { "type": "articles", "id": "1" } } }
+            },
+            "included": [{ "type": "articles", "id": "2", "attributes": { "title": "Old" } }]
+        }),
+    )?;
+
+    assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+    let error = &body(&response)["errors"][0];
+    assert_eq!(error["code"], json!("IncludedResourceNotNew"));
+    assert_eq!(error["source"]["pointer"], json!("/included/0/lid"));
+    assert_eq!(
+        data_ids(&serve(&manager, "GET", "/comments", Value::Null)?).len(),
+        2
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_create_refuses_an_included_type_not_accepting_inclusion() -> TestResult {
+    let manager = manager()?;
+    let response = serve(
+        &manager,
+        "POST",
+        "/articles",
+        json!({
+            "data": {
+                "type": "articles",
+                "attributes": { "title": "Third", "body": "Three" },
+                "relationships": {
+                    "comments": { "data": [{ "type": "comments", "lid": "c1" }] }
+                }
+            },
+            "included": [{ "type": "comments", "lid": "c1", "attributes": { "content": "Hi" } }]
+        }),
+    )?;
+
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    let error = &body(&response)["errors"][0];
+    assert_eq!(error["code"], json!("InclusionNotAccepted"));
+    assert_eq!(error["source"]["pointer"], json!("/included/0/type"));
+    assert_eq!(
+        data_ids(&serve(&manager, "GET", "/articles", Value::Null)?).len(),
+        2
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_create_refuses_an_included_type_behind_unrun_middleware() -> TestResult {
+    let manager = manager()?;
+    let articles = manager.registry().schema("articles")?;
+    let drafts = manager.registry().schema("drafts")?;
+    let router = Router::try_new(BaseUri::Relative, |root| {
+        root.resource::<Articles>("articles", articles)
+            .resource_with::<Drafts>("drafts", drafts, |drafts| {
+                drafts.middleware(DenyResource, |drafts| drafts.default_endpoints())
+            })
+    })?;
+
+    let response = send(
+        &manager,
+        &router,
+        "POST",
+        "/articles",
+        json!({
+            "data": {
+                "type": "articles",
+                "attributes": { "title": "Third", "body": "Three" },
+                "relationships": {
+                    "drafts": { "data": [{ "type": "drafts", "lid": "d1" }] }
+                }
+            },
+            "included": [{ "type": "drafts", "lid": "d1", "attributes": { "title": "One" } }]
+        }),
+        &[],
+    )?;
+
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    assert_eq!(
+        body(&response)["errors"][0]["code"],
+        json!("IncludedResourceBehindMiddleware")
+    );
+    assert_eq!(
+        data_ids(&serve(&manager, "GET", "/articles", Value::Null)?).len(),
+        2
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_create_refuses_a_lid_nothing_includes() -> TestResult {
+    let manager = manager()?;
+    let response = serve(
+        &manager,
+        "POST",
+        "/comments",
+        json!({
+            "data": {
+                "type": "comments",
+                "attributes": { "content": "First!" },
+                "relationships": { "article": { "data": { "type": "articles", "lid": "a1" } } }
+            }
+        }),
+    )?;
+
+    assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+    assert_eq!(
+        body(&response)["errors"][0]["code"],
+        json!("UnknownLocalIdentifier")
+    );
+
+    Ok(())
+}
+
 #[test]
 fn test_update_yields_record() -> TestResult {
     let manager = manager()?;
@@ -456,6 +719,145 @@ fn test_computed_owned_static_segment_matches_and_renders() -> TestResult {
     Ok(())
 }
 
+// A counted relationship carries its count in `meta` beside its linkage.
+#[test]
+fn test_counts_render_into_relationship_meta() -> TestResult {
+    let manager = manager()?;
+    let response = serve(
+        &manager,
+        "GET",
+        "/articles?fields[articles]=title,comments&meta[counts]=comments",
+        Value::Null,
+    )?;
+
+    assert_eq!(response.status(), StatusCode::OK);
+    let data = &body(&response)["data"];
+    assert_eq!(
+        data[0]["relationships"]["comments"]["meta"],
+        json!({ "count": 2 })
+    );
+    assert_eq!(
+        data[1]["relationships"]["comments"],
+        json!({
+            "data": [],
+            "links": {
+                "self": "/articles/2/relationships/comments",
+                "related": "/articles/2/comments",
+            },
+            "meta": { "count": 0 },
+        })
+    );
+    Ok(())
+}
+
+// A counted relationship left out of the fieldset is not presented, count included.
+#[test]
+fn test_counts_respect_the_fieldset() -> TestResult {
+    let manager = manager()?;
+    let response = serve(
+        &manager,
+        "GET",
+        "/articles?fields[articles]=title&meta[counts]=comments",
+        Value::Null,
+    )?;
+
+    assert_eq!(response.status(), StatusCode::OK);
+    let data = &body(&response)["data"];
+    assert_eq!(data[0]["relationships"].get("comments"), None);
+    Ok(())
+}
+
+// A limited include keeps the first related records in the requested order, and marks a linkage
+// it cut short as truncated beside the full count.
+#[test]
+fn test_limited_include_marks_truncated_linkage() -> TestResult {
+    let manager = manager()?;
+    let response = serve(
+        &manager,
+        "GET",
+        "/articles?include=comments&sort[comments]=content&page[comments][limit]=1",
+        Value::Null,
+    )?;
+
+    assert_eq!(response.status(), StatusCode::OK);
+    let document = body(&response);
+    let data = &document["data"];
+    assert_eq!(
+        data[0]["relationships"]["comments"]["data"],
+        json!([{ "type": "comments", "id": "2" }])
+    );
+    assert_eq!(
+        data[0]["relationships"]["comments"]["meta"],
+        json!({ "count": 2, "truncated": true })
+    );
+    assert_eq!(
+        data[1]["relationships"]["comments"]["meta"],
+        json!({ "count": 0 })
+    );
+    assert_eq!(document["included"].as_array().map(Vec::len), Some(1));
+    Ok(())
+}
+
+#[test]
+fn test_search_excerpts_render_into_resource_meta() -> TestResult {
+    let manager = manager()?;
+    let response = serve(
+        &manager,
+        "GET",
+        "/articles?search=two&sort=-relevance&meta[snippets]=body&meta[highlights]=title",
+        Value::Null,
+    )?;
+
+    assert_eq!(response.status(), StatusCode::OK);
+    let data = &body(&response)["data"];
+    assert_eq!(data.as_array().map(Vec::len), Some(1));
+    assert_eq!(
+        data[0]["meta"],
+        json!({
+            "snippets": { "body": "Body <mark>two</mark>" },
+            "highlights": { "title": "Second" },
+        })
+    );
+    Ok(())
+}
+
+#[test]
+fn test_aggregate_renders_a_meta_document() -> TestResult {
+    let manager = manager()?;
+    manager
+        .acquire()?
+        .execute_batch("INSERT INTO comments (id, article_id, content) VALUES (3, 2, 'Late')")?;
+    let response = serve(
+        &manager,
+        "GET",
+        "/comments?aggregate[count]=id&aggregate[max]=content&group=article_id",
+        Value::Null,
+    )?;
+
+    assert_eq!(response.status(), StatusCode::OK);
+    let document = body(&response);
+    assert!(document.get("data").is_none());
+    assert_eq!(
+        document["meta"]["aggregates"],
+        json!([
+            { "group": { "article_id": 1 }, "count": { "id": 2 }, "max": { "content": "Nice" } },
+            { "group": { "article_id": 2 }, "count": { "id": 1 }, "max": { "content": "Late" } },
+        ])
+    );
+
+    let response = serve(
+        &manager,
+        "GET",
+        "/comments?aggregate[count]=id&filter[article_id]=eq:2",
+        Value::Null,
+    )?;
+    assert_eq!(
+        body(&response)["meta"]["aggregates"],
+        json!([{ "count": { "id": 1 } }])
+    );
+    Ok(())
+}
+
 // --- resourceful routes: relationship endpoints ----------------------------
 
 #[test]
@@ -481,6 +883,76 @@ fn test_linkage_self_link_yields_identifiers() -> TestResult {
     Ok(())
 }
 
+#[test]
+fn test_linkage_is_filtered_sorted_and_paged() -> TestResult {
+    let manager = manager()?;
+    manager.acquire()?.execute_batch(
+        "INSERT INTO comments (id, article_id, content) \
+           VALUES (3, 1, 'Third'), (4, 1, 'Fourth'), (5, 1, 'Fifth'), (6, 2, 'Elsewhere');",
+    )?;
+
+    let path = "/articles/1/relationships/comments";
+    let query = "filter[content]=nin:Agreed&sort=-content";
+    let response = serve(
+        &manager,
+        "GET",
+        &format!("{path}?{query}&page[size]=2&page[number]=2"),
+        Value::Null,
+    )?;
+
+    // In document order, unlike `data_ids`.
+    let page = |response: &Response<Vec<u8>>| body(response)["data"].clone();
+
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(
+        page(&response),
+        json!([{"type": "comments", "id": "4"}, {"type": "comments", "id": "5"}])
+    );
+    let links = &body(&response)["links"];
+    assert_eq!(
+        links["first"],
+        json!(format!("{path}?{query}&page[number]=1&page[size]=2"))
+    );
+    assert_eq!(links["prev"], links["first"]);
+    assert_eq!(
+        links["last"],
+        json!(format!("{path}?{query}&page[number]=2&page[size]=2"))
+    );
+    assert_eq!(links["next"], Value::Null);
+
+    let response = serve(
+        &manager,
+        "GET",
+        &format!("{path}?{query}&page[size]=2"),
+        Value::Null,
+    )?;
+    assert_eq!(
+        page(&response),
+        json!([{"type": "comments", "id": "3"}, {"type": "comments", "id": "1"}])
+    );
+    assert_eq!(
+        body(&response)["links"]["next"],
+        json!(format!("{path}?{query}&page[number]=2&page[size]=2"))
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_linkage_rejects_parameters_invalid_for_the_related_schema() -> TestResult {
+    let manager = manager()?;
+    let response = serve(
+        &manager,
+        "GET",
+        "/articles/1/relationships/comments?sort=title",
+        Value::Null,
+    )?;
+
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+    Ok(())
+}
+
 #[test]
 fn test_related_link_yields_primary_collection() -> TestResult {
     let manager = manager()?;
@@ -654,7 +1126,1019 @@ fn test_read_only_relationship_write_is_forbidden() -> TestResult {
         json!({ "data": { "type": "articles", "id": "2" } }),
     )?;
 
-    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+    Ok(())
+}
+
+// --- optimistic concurrency ----------------------------------------------
+
+fn edit_draft(revision: Option<i64>) -> Value {
+    let mut attributes = json!({ "title": "Edited" });
+    if let Some(revision) = revision {
+        attributes["revision"] = json!(revision);
+    }
+    json!({ "data": { "type": "drafts", "id": "1", "attributes": attributes } })
+}
+
+fn send_if_match(
+    manager: &Manager,
+    method: &str,
+    uri: &str,
+    body: Value,
+    tag: &str,
+) -> Result<Response<Vec<u8>>, Box<dyn StdError>> {
+    let router = standard_router(manager)?;
+    send(manager, &router, method, uri, body, &[("if-match", tag)])
+}
+
+#[test]
+fn test_show_tags_a_versioned_record_with_its_version() -> TestResult {
+    let manager = manager()?;
+    let response = serve(&manager, "GET", "/drafts/1", Value::Null)?;
+
+    assert!(response.headers()["etag"].to_str()?.starts_with("\"1-"));
+    let unversioned = serve(&manager, "GET", "/articles/1", Value::Null)?;
+    assert!(unversioned.headers().get("etag").is_none());
+
+    Ok(())
+}
+
+#[test]
+fn test_show_tags_each_representation_of_a_version_apart() -> TestResult {
+    let manager = manager()?;
+    let full = serve(&manager, "GET", "/drafts/1", Value::Null)?;
+    let sparse = serve(
+        &manager,
+        "GET",
+        "/drafts/1?fields[drafts]=revision",
+        Value::Null,
+    )?;
+    let included = serve(&manager, "GET", "/drafts/1?include=article", Value::Null)?;
+
+    let full = full.headers()["etag"].to_str()?;
+    let sparse = sparse.headers()["etag"].to_str()?;
+    assert_ne!(full, sparse);
+    assert_ne!(full, included.headers()["etag"].to_str()?);
+
+    // Each names the version, so any of them makes an update's precondition.
+    let response = send_if_match(&manager, "PATCH", "/drafts/1", edit_draft(None), sparse)?;
+    assert_eq!(response.status(), StatusCode::OK);
+
+    Ok(())
+}
+
+#[test]
+fn test_index_tags_a_versioned_collection_weakly() -> TestResult {
+    let manager = manager()?;
+    let before = serve(&manager, "GET", "/drafts", Value::Null)?;
+    serve(&manager, "PATCH", "/drafts/1", edit_draft(Some(1)))?;
+    let after = serve(&manager, "GET", "/drafts", Value::Null)?;
+
+    let before = before.headers()["etag"].to_str()?;
+    assert!(before.starts_with("W/\""));
+    assert_ne!(before, after.headers()["etag"].to_str()?);
+
+    Ok(())
+}
+
+#[test]
+fn test_update_bumps_the_version() -> TestResult {
+    let manager = manager()?;
+    let response = serve(&manager, "PATCH", "/drafts/1", edit_draft(Some(1)))?;
+
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(body(&response)["data"]["attributes"]["revision"], json!(2));
+    assert!(response.headers()["etag"].to_str()?.starts_with("\"2-"));
+
+    Ok(())
+}
+
+#[test]
+fn test_update_against_a_stale_version_conflicts() -> TestResult {
+    let manager = manager()?;
+    serve(&manager, "PATCH", "/drafts/1", edit_draft(Some(1)))?;
+    let response = serve(&manager, "PATCH", "/drafts/1", edit_draft(Some(1)))?;
+
+    assert_eq!(response.status(), StatusCode::CONFLICT);
+    assert_eq!(
+        body(&response)["errors"][0]["code"],
+        json!("LockVersionMismatch")
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_update_naming_no_version_requires_a_precondition() -> TestResult {
+    let manager = manager()?;
+    let response = serve(&manager, "PATCH", "/drafts/1", edit_draft(None))?;
+
+    assert_eq!(response.status(), StatusCode::PRECONDITION_REQUIRED);
+    assert_eq!(
+        body(&response)["errors"][0]["code"],
+        json!("MissingLockVersion")
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_update_under_if_match_is_made_against_the_matched_version() -> TestResult {
+    let manager = manager()?;
+    let response = send_if_match(&manager, "PATCH", "/drafts/1", edit_draft(None), "\"1\"")?;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(body(&response)["data"]["attributes"]["revision"], json!(2));
+
+    let stale = send_if_match(&manager, "PATCH", "/drafts/1", edit_draft(None), "\"1\"")?;
+    assert_eq!(stale.status(), StatusCode::PRECONDITION_FAILED);
+    let error = &body(&stale)["errors"][0];
+    assert_eq!(error["code"], json!("PreconditionFailed"));
+    assert_eq!(error["source"]["header"], json!("If-Match"));
+
+    Ok(())
+}
+
+#[test]
+fn test_delete_honours_if_match() -> TestResult {
+    let manager = manager()?;
+    let stale = send_if_match(&manager, "DELETE", "/drafts/1", Value::Null, "\"7\"")?;
+    assert_eq!(stale.status(), StatusCode::PRECONDITION_FAILED);
+    assert_eq!(
+        serve(&manager, "GET", "/drafts/1", Value::Null)?.status(),
+        StatusCode::OK
+    );
+
+    let current = send_if_match(&manager, "DELETE", "/drafts/1", Value::Null, "\"1\"")?;
+    assert_eq!(current.status(), StatusCode::NO_CONTENT);
+
+    Ok(())
+}
+
+#[test]
+fn test_relationship_write_requires_and_bumps_the_version() -> TestResult {
+    let manager = manager()?;
+    let relink = || json!({ "data": { "type": "articles", "id": "2" } });
+
+    let unconditional = serve(
+        &manager,
+        "PATCH",
+        "/drafts/1/relationships/article",
+        relink(),
+    )?;
+    assert_eq!(unconditional.status(), StatusCode::PRECONDITION_REQUIRED);
+    assert_eq!(
+        body(&unconditional)["errors"][0]["code"],
+        json!("MissingLockVersion")
+    );
+
+    let current = send_if_match(
+        &manager,
+        "PATCH",
+        "/drafts/1/relationships/article",
+        relink(),
+        "\"1\"",
+    )?;
+    assert_eq!(current.status(), StatusCode::OK);
+    let draft = serve(&manager, "GET", "/drafts/1", Value::Null)?;
+    assert_eq!(body(&draft)["data"]["attributes"]["revision"], json!(2));
+
+    let stale = send_if_match(
+        &manager,
+        "PATCH",
+        "/drafts/1/relationships/article",
+        relink(),
+        "\"1\"",
+    )?;
+    assert_eq!(stale.status(), StatusCode::PRECONDITION_FAILED);
+
+    Ok(())
+}
+
+#[test]
+fn test_if_match_on_an_unversioned_resource_matches_only_a_wildcard() -> TestResult {
+    let manager = manager()?;
+    let edit =
+        json!({ "data": { "type": "articles", "id": "2", "attributes": { "title": "New" } } });
+
+    let tagged = send_if_match(&manager, "PATCH", "/articles/2", edit.clone(), "\"1\"")?;
+    assert_eq!(tagged.status(), StatusCode::PRECONDITION_FAILED);
+    let wildcard = send_if_match(&manager, "PATCH", "/articles/2", edit, "*")?;
+    assert_eq!(wildcard.status(), StatusCode::OK);
+
+    Ok(())
+}
+
+// --- conditional reads ---------------------------------------------------
+
+fn conditional_router(manager: &Manager) -> Result<Router<'_, SqliteAdapter>, Box<dyn StdError>> {
+    let articles = manager.registry().schema("articles")?;
+    let drafts = manager.registry().schema("drafts")?;
+    let summaries = manager.registry().schema("summaries")?;
+    Ok(Router::try_new(BaseUri::Relative, |root| {
+        root.resource_with::<Articles>("articles", articles, |articles| {
+            articles.middleware(ConditionalGet::new(), |wrapped| wrapped.default_endpoints())
+        })
+        .resource_with::<Drafts>("drafts", drafts, |drafts| {
+            drafts.middleware(ConditionalGet::new(), |wrapped| wrapped.default_endpoints())
+        })
+        .read_only_resource_with::<Summaries>("summaries", summaries, |summaries| {
+            summaries.middleware(
+                ConditionalGet::new().last_modified("revised_at"),
+                |wrapped| wrapped.default_endpoints(),
+            )
+        })
+    })?)
+}
+
+fn conditional_get(
+    manager: &Manager,
+    uri: &str,
+    headers: &[(&str, &str)],
+) -> Result<Response<Vec<u8>>, Box<dyn StdError>> {
+    send(
+        manager,
+        &conditional_router(manager)?,
+        "GET",
+        uri,
+        Value::Null,
+        headers,
+    )
+}
+
+#[test]
+fn test_conditional_get_tags_the_document_strongly() -> TestResult {
+    let manager = manager()?;
+    let first = conditional_get(&manager, "/articles/1", &[])?;
+    let again = conditional_get(&manager, "/articles/1", &[])?;
+    let other = conditional_get(&manager, "/articles/2", &[])?;
+
+    let tag = first.headers()["etag"].to_str()?;
+    assert!(tag.starts_with('"'));
+    assert_eq!(again.headers()["etag"], tag);
+    assert_ne!(other.headers()["etag"], tag);
+
+    Ok(())
+}
+
+#[test]
+fn test_conditional_get_answers_a_listed_tag_not_modified() -> TestResult {
+    let manager = manager()?;
+    let tag = conditional_get(&manager, "/articles", &[])?.headers()["etag"]
+        .to_str()?
+        .to_string();
+
+    let response = conditional_get(&manager, "/articles", &[("if-none-match", &tag)])?;
+    assert_eq!(response.status(), StatusCode::NOT_MODIFIED);
+    assert_eq!(response.headers()["etag"], tag.as_str());
+    assert!(response.body().is_empty());
+
+    let listed = format!("\"stale\", {tag}");
+    let response = conditional_get(&manager, "/articles", &[("if-none-match", &listed)])?;
+    assert_eq!(response.status(), StatusCode::NOT_MODIFIED);
+
+    Ok(())
+}
+
+#[test]
+fn test_conditional_get_serves_a_changed_resource() -> TestResult {
+    let manager = manager()?;
+    let tag = conditional_get(&manager, "/articles/1", &[])?.headers()["etag"]
+        .to_str()?
+        .to_string();
+    serve(
+        &manager,
+        "PATCH",
+        "/articles/1",
+        json!({ "data": { "type": "articles", "id": "1", "attributes": { "title": "Retitled" } } }),
+    )?;
+
+    let response = conditional_get(&manager, "/articles/1", &[("if-none-match", &tag)])?;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_ne!(response.headers()["etag"], tag.as_str());
+    assert_eq!(body(&response)["data"]["attributes"]["title"], "Retitled");
+
+    Ok(())
+}
+
+#[test]
+fn test_conditional_get_keeps_a_versioned_tag_and_compares_weakly() -> TestResult {
+    let manager = manager()?;
+    let tag = conditional_get(&manager, "/drafts/1", &[])?.headers()["etag"]
+        .to_str()?
+        .to_string();
+    assert!(tag.starts_with("\"1-"));
+
+    let weak = format!("W/{tag}");
+    let response = conditional_get(&manager, "/drafts/1", &[("if-none-match", &weak)])?;
+    assert_eq!(response.status(), StatusCode::NOT_MODIFIED);
+    assert_eq!(response.headers()["etag"], tag.as_str());
+
+    Ok(())
+}
+
+/// A resource around-filter tagging every response alike, as a handler tagging only the resource
+/// it serves would, whatever the query made of its document.
+struct FixedTag;
+impl<'sch> ResourceMiddleware<'sch, SqliteAdapter> for FixedTag {
+    fn handle<'req>(
+        &self,
+        context: ResourceContext<'sch, 'req, SqliteAdapter>,
+        next: &ResourceHandler<'sch, 'req, SqliteAdapter>,
+    ) -> RouteResult
+    where
+        'sch: 'req,
+    {
+        let mut response = next(context)?;
+        response
+            .headers_mut()
+            .insert("etag", HeaderValue::from_static("\"fixed\""));
+        Ok(response)
+    }
+}
+
+#[test]
+fn test_conditional_get_extends_a_handler_tag_over_a_shaped_document() -> TestResult {
+    let manager = manager()?;
+    let articles = manager.registry().schema("articles")?;
+    let router = Router::try_new(BaseUri::Relative, |root| {
+        root.resource_with::<Articles>("articles", articles, |articles| {
+            articles.middleware(ConditionalGet::new(), |conditional| {
+                conditional.middleware(FixedTag, |tagged| tagged.default_endpoints())
+            })
+        })
+    })?;
+    let get = |uri: &str, tag: &str| {
+        send(
+            &manager,
+            &router,
+            "GET",
+            uri,
+            Value::Null,
+            &[("if-none-match", tag)],
+        )
+    };
+
+    let plain = get("/articles/1", "\"fixed\"")?;
+    assert_eq!(plain.status(), StatusCode::NOT_MODIFIED);
+
+    let included = get("/articles/1?include=comments", "\"fixed\"")?;
+    assert_eq!(included.status(), StatusCode::OK);
+    let tag = included.headers()["etag"].to_str()?.to_string();
+    assert!(tag.starts_with("\"fixed-"));
+
+    serve(
+        &manager,
+        "PATCH",
+        "/comments/2",
+        json!({ "data": { "type": "comments", "id": "2", "attributes": { "content": "Edited" } } }),
+    )?;
+    let changed = get("/articles/1?include=comments", &tag)?;
+    assert_eq!(changed.status(), StatusCode::OK);
+    assert_ne!(changed.headers()["etag"], tag.as_str());
+
+    Ok(())
+}
+
+#[test]
+fn test_conditional_get_any_tag_matches() -> TestResult {
+    let manager = manager()?;
+    let response = conditional_get(&manager, "/articles/1", &[("if-none-match", "*")])?;
+
+    assert_eq!(response.status(), StatusCode::NOT_MODIFIED);
+
+    Ok(())
+}
+
+#[test]
+fn test_conditional_get_stamps_last_modified() -> TestResult {
+    let manager = manager()?;
+    let record = conditional_get(&manager, "/summaries/1", &[])?;
+    let collection = conditional_get(&manager, "/summaries", &[])?;
+
+    assert_eq!(
+        record.headers()["last-modified"],
+        "Fri, 01 Mar 2024 09:30:00 GMT"
+    );
+    assert_eq!(
+        collection.headers()["last-modified"],
+        "Fri, 17 May 2024 14:00:00 GMT"
+    );
+    let untimed = conditional_get(&manager, "/articles/1", &[])?;
+    assert!(untimed.headers().get("last-modified").is_none());
+
+    Ok(())
+}
+
+#[test]
+fn test_conditional_get_compares_if_modified_since() -> TestResult {
+    let manager = manager()?;
+    let unchanged = conditional_get(
+        &manager,
+        "/summaries/1",
+        &[("if-modified-since", "Fri, 01 Mar 2024 09:30:00 GMT")],
+    )?;
+    let changed = conditional_get(
+        &manager,
+        "/summaries/1",
+        &[("if-modified-since", "Fri, 01 Mar 2024 09:29:59 GMT")],
+    )?;
+    let unreadable = conditional_get(
+        &manager,
+        "/summaries/1",
+        &[("if-modified-since", "yesterday")],
+    )?;
+
+    assert_eq!(unchanged.status(), StatusCode::NOT_MODIFIED);
+    assert_eq!(changed.status(), StatusCode::OK);
+    assert_eq!(unreadable.status(), StatusCode::OK);
+
+    Ok(())
+}
+
+#[test]
+fn test_conditional_get_prefers_if_none_match() -> TestResult {
+    let manager = manager()?;
+    let response = conditional_get(
+        &manager,
+        "/summaries/1",
+        &[
+            ("if-none-match", "\"stale\""),
+            ("if-modified-since", "Fri, 01 Mar 2024 09:30:00 GMT"),
+        ],
+    )?;
+
+    assert_eq!(response.status(), StatusCode::OK);
+
+    Ok(())
+}
+
+// --- upserts ---------------------------------------------------------------
+
+#[derive(Default)]
+struct SyncedArticles;
+impl<'sch> ResourceController<'sch, SqliteAdapter> for SyncedArticles {
+    fn configuration(&self) -> Configuration {
+        Configuration {
+            accepts_client_ids: true,
+            upserts: true,
+            ..Configuration::default()
+        }
+    }
+}
+
+#[derive(Default)]
+struct ServerKeyedArticles;
+impl<'sch> ResourceController<'sch, SqliteAdapter> for ServerKeyedArticles {
+    fn configuration(&self) -> Configuration {
+        Configuration {
+            upserts: true,
+            ..Configuration::default()
+        }
+    }
+}
+
+fn put_article(id: &str, title: &str) -> Value {
+    json!({ "data": { "type": "articles", "id": id, "attributes": { "title": title } } })
+}
+
+fn upsert_router(manager: &Manager) -> Result<Router<'_, SqliteAdapter>, Box<dyn StdError>> {
+    let articles = manager.registry().schema("articles")?;
+    Ok(Router::try_new(BaseUri::Relative, |root| {
+        root.resource::<SyncedArticles>("articles", articles)
+    })?)
+}
+
+#[test]
+fn test_put_creates_a_missing_record() -> TestResult {
+    let manager = manager()?;
+    let router = upsert_router(&manager)?;
+    let response = send(
+        &manager,
+        &router,
+        "PUT",
+        "/articles/7",
+        put_article("7", "Seventh"),
+        &[],
+    )?;
+
+    assert_eq!(response.status(), StatusCode::CREATED);
+    assert_eq!(response.headers()["location"], "/articles/7");
+    assert_eq!(body(&response)["data"]["id"], "7");
+
+    let fetched = send(&manager, &router, "GET", "/articles/7", Value::Null, &[])?;
+    assert_eq!(body(&fetched)["data"]["attributes"]["title"], "Seventh");
+
+    Ok(())
+}
+
+#[test]
+fn test_put_updates_an_existing_record() -> TestResult {
+    let manager = manager()?;
+    let router = upsert_router(&manager)?;
+    let response = send(
+        &manager,
+        &router,
+        "PUT",
+        "/articles/1",
+        put_article("1", "Replaced"),
+        &[],
+    )?;
+
+    assert_eq!(response.status(), StatusCode::OK);
+    assert!(response.headers().get("location").is_none());
+    assert_eq!(body(&response)["data"]["attributes"]["title"], "Replaced");
+    assert_eq!(body(&response)["data"]["attributes"]["body"], Value::Null);
+
+    Ok(())
+}
+
+#[test]
+fn test_put_without_upserts_only_updates() -> TestResult {
+    let manager = manager()?;
+    let response = serve(&manager, "PUT", "/articles/7", put_article("7", "Seventh"))?;
+
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+    Ok(())
+}
+
+#[test]
+fn test_put_upsert_needs_client_ids() -> TestResult {
+    let manager = manager()?;
+    let articles = manager.registry().schema("articles")?;
+    let router = Router::try_new(BaseUri::Relative, |root| {
+        root.resource::<ServerKeyedArticles>("articles", articles)
+    })?;
+    let response = send(
+        &manager,
+        &router,
+        "PUT",
+        "/articles/7",
+        put_article("7", "Seventh"),
+        &[],
+    )?;
+
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    assert_eq!(
+        body(&response)["errors"][0]["code"],
+        "ClientGeneratedIdNotSupported"
+    );
+
+    Ok(())
+}
+
+// --- bulk writes -----------------------------------------------------------
+
+const BULK_CONTENT_TYPE: &str = "application/vnd.api+json;ext=\"https://example.com/ext/bulk\"";
+
+fn bulk_router(manager: &Manager) -> Result<Router<'_, SqliteAdapter>, Box<dyn StdError>> {
+    let articles = manager.registry().schema("articles")?;
+    Ok(Router::try_new(BaseUri::Relative, |root| {
+        root.resource_with::<Articles>("articles", articles, |articles| {
+            articles.default_endpoints().bulk_endpoints(2)
+        })
+    })?)
+}
+
+fn bulk(
+    manager: &Manager,
+    method: &str,
+    uri: &str,
+    body: Value,
+) -> Result<Response<Vec<u8>>, Box<dyn StdError>> {
+    send(
+        manager,
+        &bulk_router(manager)?,
+        method,
+        uri,
+        body,
+        &[("Content-Type", BULK_CONTENT_TYPE)],
+    )
+}
+
+fn titles(manager: &Manager) -> Result<Vec<Value>, Box<dyn StdError>> {
+    let response = serve(manager, "GET", "/articles", Value::Null)?;
+    Ok(body(&response)["data"]
+        .as_array()
+        .map(|members| {
+            members
+                .iter()
+                .map(|member| member["attributes"]["title"].clone())
+                .collect()
+        })
+        .unwrap_or_default())
+}
+
+#[test]
+fn test_bulk_post_creates_every_member() -> TestResult {
+    let manager = manager()?;
+    let response = bulk(
+        &manager,
+        "POST",
+        "/articles",
+        json!({ "data": [
+            { "type": "articles", "attributes": { "title": "Third" } },
+            { "type": "articles", "attributes": { "title": "Fourth" } }
+        ] }),
+    )?;
+
+    assert_eq!(response.status(), StatusCode::CREATED);
+    assert_eq!(response.headers()["content-type"], BULK_CONTENT_TYPE);
+    assert_eq!(data_ids(&response), vec![json!("3"), json!("4")]);
+    assert_eq!(
+        titles(&manager)?,
+        vec![
+            json!("First"),
+            json!("Second"),
+            json!("Third"),
+            json!("Fourth")
+        ]
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_bulk_post_points_an_error_at_its_member() -> TestResult {
+    let manager = manager()?;
+    let response = bulk(
+        &manager,
+        "POST",
+        "/articles",
+        json!({ "data": [
+            { "type": "articles", "attributes": { "title": "Third" } },
+            { "type": "articles", "attributes": { "headline": "Fourth" } }
+        ] }),
+    )?;
+
+    assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+    assert_eq!(
+        body(&response)["errors"][0]["source"]["pointer"],
+        "/data/1/attributes/headline"
+    );
+    assert_eq!(titles(&manager)?, vec![json!("First"), json!("Second")]);
+
+    Ok(())
+}
+
+#[test]
+fn test_bulk_post_points_a_constraint_violation_at_its_member() -> TestResult {
+    let manager = manager()?;
+    let response = bulk(
+        &manager,
+        "POST",
+        "/articles",
+        json!({ "data": [
+            { "type": "articles", "attributes": { "title": "Third" } },
+            { "type": "articles", "attributes": { "title": null } }
+        ] }),
+    )?;
+
+    assert_eq!(response.status(), StatusCode::CONFLICT);
+    assert_eq!(body(&response)["errors"][0]["code"], "ConstraintViolation");
+    assert_eq!(body(&response)["errors"][0]["source"]["pointer"], "/data/1");
+    assert_eq!(titles(&manager)?, vec![json!("First"), json!("Second")]);
+
+    Ok(())
+}
+
+#[test]
+fn test_bulk_post_refuses_a_batch_over_the_limit() -> TestResult {
+    let manager = manager()?;
+    let member = json!({ "type": "articles", "attributes": { "title": "More" } });
+    let response = bulk(
+        &manager,
+        "POST",
+        "/articles",
+        json!({ "data": [member.clone(), member.clone(), member] }),
+    )?;
+
+    assert_eq!(response.status(), StatusCode::PAYLOAD_TOO_LARGE);
+    assert_eq!(body(&response)["errors"][0]["code"], "BatchTooLarge");
+    assert_eq!(titles(&manager)?, vec![json!("First"), json!("Second")]);
+
+    Ok(())
+}
+
+#[test]
+fn test_bulk_patch_updates_the_filtered_records() -> TestResult {
+    let manager = manager()?;
+    let response = bulk(
+        &manager,
+        "PATCH",
+        "/articles?filter[title]=eq:First",
+        json!({ "data": { "type": "articles", "attributes": { "body": "Rewritten" } } }),
+    )?;
+
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(data_ids(&response), vec![json!("1")]);
+
+    let first = serve(&manager, "GET", "/articles/1", Value::Null)?;
+    let second = serve(&manager, "GET", "/articles/2", Value::Null)?;
+    assert_eq!(body(&first)["data"]["attributes"]["body"], "Rewritten");
+    assert_eq!(body(&second)["data"]["attributes"]["body"], "Body two");
+
+    Ok(())
+}
+
+#[test]
+fn test_bulk_patch_refuses_an_unfiltered_request() -> TestResult {
+    let manager = manager()?;
+    let response = bulk(
+        &manager,
+        "PATCH",
+        "/articles",
+        json!({ "data": { "type": "articles", "attributes": { "body": "Rewritten" } } }),
+    )?;
+
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    assert_eq!(body(&response)["errors"][0]["code"], "UnscopedBulkWrite");
+    assert_eq!(
+        body(&response)["errors"][0]["source"]["parameter"],
+        "filter"
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_bulk_patch_refuses_an_identified_resource() -> TestResult {
+    let manager = manager()?;
+    let response = bulk(
+        &manager,
+        "PATCH",
+        "/articles?filter[title]=eq:First",
+        json!({ "data": { "type": "articles", "id": "1", "attributes": { "body": "Rewritten" } } }),
+    )?;
+
+    assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+    assert_eq!(body(&response)["errors"][0]["code"], "UnexpectedResourceId");
+
+    Ok(())
+}
+
+#[test]
+fn test_bulk_delete_over_the_limit_rolls_back() -> TestResult {
+    let manager = manager()?;
+    for title in ["Third", "Fourth", "Fifth"] {
+        bulk(
+            &manager,
+            "POST",
+            "/articles",
+            json!({ "data": [{ "type": "articles", "attributes": { "title": title } }] }),
+        )?;
+    }
+    let response = bulk(&manager, "DELETE", "/articles?filter[id]=gt:2", Value::Null)?;
+
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    assert_eq!(body(&response)["errors"][0]["code"], "BulkScopeTooLarge");
+    assert_eq!(titles(&manager)?.len(), 5);
+
+    Ok(())
+}
+
+#[test]
+fn test_bulk_patch_over_the_limit_is_refused_before_writing() -> TestResult {
+    let manager = manager()?;
+    bulk(
+        &manager,
+        "POST",
+        "/articles",
+        json!({ "data": [{ "type": "articles", "attributes": { "title": "Third" } }] }),
+    )?;
+    // Writing the null title would violate its constraint, so only a refusal made before the
+    // write reports the scope.
+    let response = bulk(
+        &manager,
+        "PATCH",
+        "/articles?filter[id]=gt:0",
+        json!({ "data": { "type": "articles", "attributes": { "title": null } } }),
+    )?;
+
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    assert_eq!(body(&response)["errors"][0]["code"], "BulkScopeTooLarge");
+    assert_eq!(titles(&manager)?, ["First", "Second", "Third"]);
+
+    Ok(())
+}
+
+#[test]
+fn test_bulk_delete_removes_the_filtered_records() -> TestResult {
+    let manager = manager()?;
+    bulk(
+        &manager,
+        "POST",
+        "/articles",
+        json!({ "data": [
+            { "type": "articles", "attributes": { "title": "Third" } },
+            { "type": "articles", "attributes": { "title": "Fourth" } }
+        ] }),
+    )?;
+    let response = bulk(&manager, "DELETE", "/articles?filter[id]=gt:2", Value::Null)?;
+
+    assert_eq!(response.status(), StatusCode::NO_CONTENT);
+    assert_eq!(titles(&manager)?, vec![json!("First"), json!("Second")]);
+
+    Ok(())
+}
+
+#[test]
+fn test_plain_post_beside_bulk_endpoints_creates_one_record() -> TestResult {
+    let manager = manager()?;
+    let response = send(
+        &manager,
+        &bulk_router(&manager)?,
+        "POST",
+        "/articles",
+        json!({ "data": { "type": "articles", "attributes": { "title": "Third" } } }),
+        &[],
+    )?;
+
+    assert_eq!(response.status(), StatusCode::CREATED);
+    assert_eq!(
+        response.headers()["content-type"],
+        "application/vnd.api+json"
+    );
+    assert_eq!(body(&response)["data"]["id"], "3");
+
+    Ok(())
+}
+
+#[test]
+fn test_bulk_extension_without_bulk_endpoints_is_unsupported() -> TestResult {
+    let manager = manager()?;
+    let response = send(
+        &manager,
+        &standard_router(&manager)?,
+        "POST",
+        "/articles",
+        json!({ "data": [{ "type": "articles", "attributes": { "title": "Third" } }] }),
+        &[("Content-Type", BULK_CONTENT_TYPE)],
+    )?;
+
+    assert_eq!(response.status(), StatusCode::UNSUPPORTED_MEDIA_TYPE);
+
+    Ok(())
+}
+
+// --- audit log -------------------------------------------------------------
+
+/// A primary middleware naming the actor of every request through it, as authentication would.
+struct ActAs(&'static str);
+impl<'sch> PrimaryMiddleware<'sch, SqliteAdapter> for ActAs {
+    fn handle<'req>(
+        &self,
+        mut context: PrimaryContext<'sch, 'req, SqliteAdapter>,
+        next: &PrimaryHandler<'sch, 'req, SqliteAdapter>,
+    ) -> PrimaryResult
+    where
+        'sch: 'req,
+    {
+        context.act_as(self.0);
+        next(context)
+    }
+}
+
+fn audited_manager() -> Result<Manager, Box<dyn StdError>> {
+    manager_with(DatabaseRegistry::try_new([
+        articles_schema().audited(),
+        comments_schema(),
+        drafts_schema(),
+        summaries_schema(),
+        audit_log(),
+    ])?)
+}
+
+fn audit_router(manager: &Manager) -> Result<Router<'_, SqliteAdapter>, Box<dyn StdError>> {
+    let articles = manager.registry().schema("articles")?;
+    let audit_log = manager.registry().schema(AUDIT_LOG)?;
+    Ok(Router::try_new(BaseUri::Relative, |root| {
+        root.middleware(ActAs("ada"), |root| {
+            root.resource::<Articles>("articles", articles)
+                .read_only_resource::<AuditLog>("audit-log", audit_log)
+        })
+    })?)
+}
+
+#[test]
+fn test_an_audited_write_is_served_from_the_audit_log() -> TestResult {
+    let manager = audited_manager()?;
+    let router = audit_router(&manager)?;
+    let response = send(
+        &manager,
+        &router,
+        "PATCH",
+        "/articles/1",
+        json!({ "data": { "type": "articles", "id": "1", "attributes": { "title": "Renamed" } } }),
+        &[],
+    )?;
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let response = send(&manager, &router, "GET", "/audit-log", Value::Null, &[])?;
+    assert_eq!(response.status(), StatusCode::OK);
+    let entries = body(&response)["data"].clone();
+    assert_eq!(entries.as_array().map(Vec::len), Some(1));
+    let attributes = &entries[0]["attributes"];
+    assert_eq!(attributes["resource"], "articles");
+    assert_eq!(attributes["record_id"], "1");
+    assert_eq!(attributes["operation"], "update");
+    assert_eq!(attributes["actor"], "ada");
+    assert_eq!(
+        attributes["changes"],
+        json!({ "title": { "old": "First", "new": "Renamed" } })
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_the_audit_log_refuses_writes() -> TestResult {
+    let manager = audited_manager()?;
+    let response = send(
+        &manager,
+        &audit_router(&manager)?,
+        "POST",
+        "/audit-log",
+        json!({ "data": { "type": "audit_log", "attributes": { "operation": "delete" } } }),
+        &[],
+    )?;
+
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+    Ok(())
+}
+
+// --- history -----------------------------------------------------------------
+
+fn historied_manager() -> Result<Manager, Box<dyn StdError>> {
+    let manager = manager_with(DatabaseRegistry::try_new([
+        articles_schema().keeps_history(),
+        comments_schema(),
+        drafts_schema(),
+        summaries_schema(),
+    ])?)?;
+    manager
+        .table("articles", &manager.acquire()?)?
+        .create_history()?;
+
+    Ok(manager)
+}
+
+#[test]
+fn test_a_historied_resource_is_read_as_of_an_instant() -> TestResult {
+    let manager = historied_manager()?;
+    let articles = manager.registry().schema("articles")?;
+    let router = Router::try_new(BaseUri::Relative, |root| {
+        root.resource::<Articles>("articles", articles)
+    })?;
+    // The versions held before the writes are stamped with a fixed instant, so the read before
+    // them is pinned rather than timed.
+    manager.acquire()?.execute_batch(
+        "UPDATE articles_history SET valid_from = '2001-01-01T00:00:00.000+00:00'",
+    )?;
+    let before = "2001-01-01T12:00:00Z";
+
+    let renamed =
+        json!({ "data": { "type": "articles", "id": "1", "attributes": { "title": "Renamed" } } });
+    let created = json!({ "data": { "type": "articles", "attributes": { "title": "Third" } } });
+    assert_eq!(
+        send(&manager, &router, "PATCH", "/articles/1", renamed, &[])?.status(),
+        StatusCode::OK
+    );
+    assert_eq!(
+        send(&manager, &router, "POST", "/articles", created, &[])?.status(),
+        StatusCode::CREATED
+    );
+
+    let get = |uri: &str| send(&manager, &router, "GET", uri, Value::Null, &[]);
+    let titles = |response: &Response<Vec<u8>>| {
+        let mut titles: Vec<_> = body(response)["data"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .filter_map(|article| article["attributes"]["title"].as_str().map(str::to_string))
+            .collect();
+        titles.sort();
+        titles
+    };
+
+    assert_eq!(
+        titles(&get(&format!("/articles?filter[as_of]={before}"))?),
+        ["First", "Second"]
+    );
+    assert_eq!(titles(&get("/articles")?), ["Renamed", "Second", "Third"]);
+    let response = get(&format!("/articles/1?filter[as_of]={before}"))?;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(body(&response)["data"]["attributes"]["title"], "First");
+    assert_eq!(
+        get(&format!("/articles/3?filter[as_of]={before}"))?.status(),
+        StatusCode::NOT_FOUND
+    );
+    assert_eq!(
+        get("/articles?filter[as_of]=yesterday")?.status(),
+        StatusCode::BAD_REQUEST
+    );
 
     Ok(())
 }
@@ -1980,3 +3464,421 @@ fn test_named_segment_with_invalid_encoding_does_not_match() -> TestResult {
     );
     Ok(())
 }
+
+// --- query limits ----------------------------------------------------------
+
+fn limited_router(
+    manager: &Manager,
+    resource_limits: QueryLimits,
+) -> Result<Router<'_, SqliteAdapter>, Box<dyn StdError>> {
+    let articles = manager.registry().schema("articles")?;
+    Ok(Router::try_new(BaseUri::Relative, |root| {
+        root.resource_with::<Articles>("articles", articles, |resource| {
+            resource
+                .query_limits(resource_limits)
+                .default_endpoints()
+                .all_relationships()
+        })
+    })?
+    .with_query_limits(QueryLimits::new().sort_fields(1).include_depth(1)))
+}
+
+#[test]
+fn test_router_query_limit_rejects_naming_the_parameter() -> TestResult {
+    let manager = manager()?;
+    let router = limited_router(&manager, QueryLimits::new())?;
+
+    let response = send(
+        &manager,
+        &router,
+        "GET",
+        "/articles?sort=title,body",
+        Value::Null,
+        &[],
+    )?;
+
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    assert_eq!(
+        body(&response)["errors"][0]["source"],
+        json!({ "parameter": "sort" })
+    );
+    Ok(())
+}
+
+#[test]
+fn test_resource_query_limits_override_the_router() -> TestResult {
+    let manager = manager()?;
+    let router = limited_router(&manager, QueryLimits::new().sort_fields(2))?;
+
+    // The resource's own sort bound relaxes the router's; its include bound is left standing.
+    let sorted = send(
+        &manager,
+        &router,
+        "GET",
+        "/articles?sort=title,body",
+        Value::Null,
+        &[],
+    )?;
+    assert_eq!(sorted.status(), StatusCode::OK);
+
+    let included = send(
+        &manager,
+        &router,
+        "GET",
+        "/articles?include=comments.article",
+        Value::Null,
+        &[],
+    )?;
+    assert_eq!(included.status(), StatusCode::BAD_REQUEST);
+    assert_eq!(
+        body(&included)["errors"][0]["source"],
+        json!({ "parameter": "include" })
+    );
+    Ok(())
+}
+
+// --- atomic operations -----------------------------------------------------
+
+const ATOMIC_CONTENT_TYPE: &str = "application/vnd.api+json;ext=\"https://jsonapi.org/ext/atomic\"";
+
+fn operations_router(manager: &Manager) -> Result<Router<'_, SqliteAdapter>, Box<dyn StdError>> {
+    let articles = manager.registry().schema("articles")?;
+    let comments = manager.registry().schema("comments")?;
+    let drafts = manager.registry().schema("drafts")?;
+    let summaries = manager.registry().schema("summaries")?;
+    Ok(Router::try_new(BaseUri::Relative, |root| {
+        root.resource::<Articles>("articles", articles)
+            .resource::<Comments>("comments", comments)
+            .resource::<Drafts>("drafts", drafts)
+            .read_only_resource::<Summaries>("summaries", summaries)
+            .operations("operations")
+    })?)
+}
+
+fn operate(manager: &Manager, operations: Value) -> Result<Response<Vec<u8>>, Box<dyn StdError>> {
+    send(
+        manager,
+        &operations_router(manager)?,
+        "POST",
+        "/operations",
+        json!({ "atomic:operations": operations }),
+        &[("Content-Type", ATOMIC_CONTENT_TYPE)],
+    )
+}
+
+#[test]
+fn test_operations_resolve_local_identifiers_between_operations() -> TestResult {
+    let manager = manager()?;
+    let response = operate(
+        &manager,
+        json!([
+            {
+                "op": "add",
+                "data": {
+                    "type": "articles",
+                    "lid": "new-article",
+                    "attributes": { "title": "Third", "body": "Body three" }
+                }
+            },
+            {
+                "op": "add",
+                "data": {
+                    "type": "comments",
+                    "attributes": { "content": "First!" },
+                    "relationships": {
+                        "article": { "data": { "type": "articles", "lid": "new-article" } }
+                    }
+                }
+            },
+            {
+                "op": "add",
+                "ref": { "type": "articles", "lid": "new-article", "relationship": "drafts" },
+                "data": [{ "type": "drafts", "id": "2" }]
+            }
+        ]),
+    )?;
+
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(
+        response.headers()["content-type"],
+        "application/vnd.api+json;ext=\"https://jsonapi.org/ext/atomic\""
+    );
+    let document = body(&response);
+    assert_eq!(
+        document["jsonapi"]["ext"],
+        json!(["https://jsonapi.org/ext/atomic"])
+    );
+    let results = &document["atomic:results"];
+    assert_eq!(results[0]["data"]["id"], json!("3"));
+    assert_eq!(results[1]["data"]["id"], json!("3"));
+    assert_eq!(results[2], json!({}));
+
+    let comments = serve(
+        &manager,
+        "GET",
+        "/articles/3/relationships/comments",
+        Value::Null,
+    )?;
+    assert_eq!(data_ids(&comments), vec![json!("3")]);
+    let drafts = serve(
+        &manager,
+        "GET",
+        "/articles/3/relationships/drafts",
+        Value::Null,
+    )?;
+    assert_eq!(data_ids(&drafts), vec![json!("2")]);
+    Ok(())
+}
+
+#[test]
+fn test_operations_roll_back_when_one_fails() -> TestResult {
+    let manager = manager()?;
+    let response = operate(
+        &manager,
+        json!([
+            {
+                "op": "add",
+                "data": { "type": "articles", "attributes": { "title": "Third", "body": "Body" } }
+            },
+            {
+                "op": "update",
+                "data": { "type": "articles", "id": "1", "attributes": { "subtitle": "None" } }
+            }
+        ]),
+    )?;
+
+    assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+    let error = &body(&response)["errors"][0];
+    assert_eq!(error["code"], json!("UnknownAttribute"));
+    assert_eq!(
+        error["source"],
+        json!({ "pointer": "/atomic:operations/1/data/attributes/subtitle" })
+    );
+
+    let articles = serve(&manager, "GET", "/articles", Value::Null)?;
+    assert_eq!(data_ids(&articles), vec![json!("1"), json!("2")]);
+    Ok(())
+}
+
+#[test]
+fn test_operations_refuse_an_unknown_local_identifier() -> TestResult {
+    let manager = manager()?;
+    let response = operate(
+        &manager,
+        json!([{
+            "op": "remove",
+            "ref": { "type": "articles", "lid": "never-created" }
+        }]),
+    )?;
+
+    assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+    let error = &body(&response)["errors"][0];
+    assert_eq!(error["code"], json!("UnknownLocalIdentifier"));
+    assert_eq!(
+        error["source"],
+        json!({ "pointer": "/atomic:operations/0/ref" })
+    );
+    Ok(())
+}
+
+#[test]
+fn test_operations_refuse_a_read_only_resource() -> TestResult {
+    let manager = manager()?;
+    let response = operate(
+        &manager,
+        json!([{ "op": "remove", "ref": { "type": "summaries", "id": "1" } }]),
+    )?;
+
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    assert_eq!(
+        body(&response)["errors"][0]["code"],
+        json!("UnsupportedOperation")
+    );
+
+    let summary = send(
+        &manager,
+        &operations_router(&manager)?,
+        "GET",
+        "/summaries/1",
+        Value::Null,
+        &[],
+    )?;
+    assert_eq!(summary.status(), StatusCode::OK);
+    Ok(())
+}
+
+#[test]
+fn test_operations_yielding_no_data_answer_no_content() -> TestResult {
+    let manager = manager()?;
+    let response = operate(
+        &manager,
+        json!([{ "op": "remove", "ref": { "type": "drafts", "id": "1" } }]),
+    )?;
+
+    assert_eq!(response.status(), StatusCode::NO_CONTENT);
+    assert_eq!(body(&response), Value::Null);
+
+    let draft = serve(&manager, "GET", "/drafts/1", Value::Null)?;
+    assert_eq!(draft.status(), StatusCode::NOT_FOUND);
+    Ok(())
+}
+
+/// An articles controller whose create hook stamps the body, so a response shows the hook ran.
+#[derive(Default)]
+struct ReviewedArticles;
+impl<'sch> ResourceController<'sch, SqliteAdapter> for ReviewedArticles {
+    fn before_create<'req>(
+        &self,
+        _context: &ResourceContext<'sch, 'req, SqliteAdapter>,
+        record: &mut Record<'sch>,
+    ) -> Result<(), CrateError>
+    where
+        'sch: 'req,
+    {
+        record
+            .attributes
+            .insert("body", Attribute::Text("Reviewed".to_string()));
+        Ok(())
+    }
+}
+
+#[test]
+fn test_operations_run_the_controller_hooks() -> TestResult {
+    let manager = manager()?;
+    let articles = manager.registry().schema("articles")?;
+    let router = Router::try_new(BaseUri::Relative, |root| {
+        root.resource::<ReviewedArticles>("articles", articles)
+            .operations("operations")
+    })?;
+
+    let response = send(
+        &manager,
+        &router,
+        "POST",
+        "/operations",
+        json!({ "atomic:operations": [{
+            "op": "add",
+            "data": { "type": "articles", "attributes": { "title": "Third", "body": "Draft" } }
+        }] }),
+        &[("Content-Type", ATOMIC_CONTENT_TYPE)],
+    )?;
+
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(
+        body(&response)["atomic:results"][0]["data"]["attributes"]["body"],
+        json!("Reviewed")
+    );
+    Ok(())
+}
+
+#[test]
+fn test_operations_run_the_resource_middleware() -> TestResult {
+    let manager = manager()?;
+    let articles = manager.registry().schema("articles")?;
+    let drafts = manager.registry().schema("drafts")?;
+    let router = Router::try_new(BaseUri::Relative, |root| {
+        root.resource::<Articles>("articles", articles)
+            .resource_with::<Drafts>("drafts", drafts, |drafts| {
+                drafts.middleware(DenyResource, |drafts| drafts.default_endpoints())
+            })
+            .operations("operations")
+    })?;
+
+    let response = send(
+        &manager,
+        &router,
+        "POST",
+        "/operations",
+        json!({ "atomic:operations": [
+            {
+                "op": "add",
+                "data": { "type": "articles", "attributes": { "title": "Third", "body": "Body" } }
+            },
+            { "op": "remove", "ref": { "type": "drafts", "id": "1" } }
+        ] }),
+        &[("Content-Type", ATOMIC_CONTENT_TYPE)],
+    )?;
+
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    assert_eq!(
+        body(&response)["errors"][0]["code"],
+        json!("OperationRefused")
+    );
+
+    let articles = serve(&manager, "GET", "/articles", Value::Null)?;
+    assert_eq!(data_ids(&articles), vec![json!("1"), json!("2")]);
+    Ok(())
+}
+
+#[test]
+fn test_operations_refuse_a_resource_behind_unshared_middleware() -> TestResult {
+    let manager = manager()?;
+    let drafts = manager.registry().schema("drafts")?;
+    let router = Router::try_new(BaseUri::Relative, |root| {
+        root.middleware(StampResponse, |root| {
+            root.resource::<Drafts>("drafts", drafts)
+        })
+        .operations("operations")
+    })?;
+
+    let response = send(
+        &manager,
+        &router,
+        "POST",
+        "/operations",
+        json!({ "atomic:operations": [
+            { "op": "remove", "ref": { "type": "drafts", "id": "1" } }
+        ] }),
+        &[("Content-Type", ATOMIC_CONTENT_TYPE)],
+    )?;
+
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    assert_eq!(
+        body(&response)["errors"][0]["code"],
+        json!("OperationBehindMiddleware")
+    );
+
+    let draft = serve(&manager, "GET", "/drafts/1", Value::Null)?;
+    assert_eq!(draft.status(), StatusCode::OK);
+    Ok(())
+}
+
+#[test]
+fn test_operations_require_the_atomic_extension() -> TestResult {
+    let manager = manager()?;
+    let response = send(
+        &manager,
+        &operations_router(&manager)?,
+        "POST",
+        "/operations",
+        json!({ "atomic:operations": [] }),
+        &[],
+    )?;
+
+    assert_eq!(response.status(), StatusCode::UNSUPPORTED_MEDIA_TYPE);
+    assert_eq!(
+        body(&response)["errors"][0]["code"],
+        json!("MissingJsonApiExtension")
+    );
+    Ok(())
+}
+
+#[test]
+fn test_resource_routes_still_refuse_the_atomic_extension() -> TestResult {
+    let manager = manager()?;
+    let response = send(
+        &manager,
+        &operations_router(&manager)?,
+        "POST",
+        "/articles",
+        json!({ "data": { "type": "articles", "attributes": { "title": "X", "body": "Y" } } }),
+        &[("Content-Type", ATOMIC_CONTENT_TYPE)],
+    )?;
+
+    assert_eq!(response.status(), StatusCode::UNSUPPORTED_MEDIA_TYPE);
+    assert_eq!(
+        body(&response)["errors"][0]["code"],
+        json!("UnsupportedJsonApiExtension")
+    );
+    Ok(())
+}
@@ -4,12 +4,14 @@ use crate::{
     database::{
         attributes::Identifier as DatabaseIdentifier,
         error::Error as DatabaseError,
+        query_parameters::{AggregateParameters, AggregateRow, PageParameters},
         record::Record,
         relationships::Relationship as DatabaseRelationship,
         schema::{IdentifierType, RelationshipKind as SchemaRelationship, Schema},
     },
     http_wrappers::Uri,
     json_api::{
+        atomic::{ATOMIC_EXTENSION, OperationResult, ResultsDocument},
         document::{self, Document, ImplementationInfo},
         error::Error as JsonApiError,
         identifier::Identifier,
@@ -19,7 +21,7 @@ use crate::{
         resource::{self, Resource},
     },
 };
-use serde_json::Value;
+use serde_json::{Value, json};
 use std::collections::HashMap;
 
 pub enum Content<'sch: 'req, 'req> {
@@ -77,6 +79,7 @@ impl<'sch> From<(DatabaseIdentifier, &'sch Schema<'sch>)> for Identifier {
                 DatabaseIdentifier::Integer(value) => value.to_string(),
                 DatabaseIdentifier::Text(value) => value,
             },
+            lid: None,
         }
     }
 }
@@ -89,7 +92,7 @@ impl<'sch> TryFrom<(Identifier, &'sch Schema<'sch>)> for DatabaseIdentifier {
 
         let id = match identifier {
             Identifier::New { kind, .. } => Err(DatabaseError::MissingRecordId { schema: kind })?,
-            Identifier::Existing { kind, id } if kind == schema.name() => {
+            Identifier::Existing { kind, id, .. } if kind == schema.name() => {
                 match schema.primary_key().kind {
                     IdentifierType::Integer => {
                         DatabaseIdentifier::Integer(id.parse().map_err(|_error| {
@@ -124,6 +127,28 @@ pub(crate) fn make_record_resource<'sch>(
         .map(|(name, value)| (name.to_string(), Value::from(value.clone())))
         .collect();
 
+    let relationship_links = |relationship: &str| -> Result<_, Error> {
+        Ok(
+            match (
+                generator.uri_for_linkage(record, relationship)?,
+                generator.uri_for_related(record, relationship)?,
+            ) {
+                (None, None) => None,
+                (this, related) => Some(relationship::Links { this, related }),
+            },
+        )
+    };
+    // A to-many linkage holding fewer records than were counted was limited when included.
+    let relationship_meta = |relationship: &str, linked: Option<usize>| {
+        record.counts.get(relationship).map(|&count| {
+            if linked.is_some_and(|linked| (linked as u64) < count) {
+                json!({ "count": count, "truncated": true })
+            } else {
+                json!({ "count": count })
+            }
+        })
+    };
+
     let relationships = record.relationships
         .iter()
         .map(|(relationship, value)| -> Result<_, Error> {
@@ -138,14 +163,16 @@ pub(crate) fn make_record_resource<'sch>(
                 (SchemaRelationship::HasOne, DatabaseRelationship::HasOne(id)) =>
                     Linkage::ToOne(Identifier::Existing {
                         kind: related.resource.to_string(),
-                        id: id.to_string()
+                        id: id.to_string(),
+                        lid: None,
                     }),
                 (SchemaRelationship::HasMany, DatabaseRelationship::HasMany(ids)) =>
                     Linkage::ToMany(ids
                         .iter()
                         .map(|id| Identifier::Existing {
                             kind: related.resource.to_string(),
-                            id: id.to_string()
+                            id: id.to_string(),
+                            lid: None,
                         })
                         .collect()
                     ),
@@ -160,18 +187,15 @@ pub(crate) fn make_record_resource<'sch>(
                 })?
             };
 
-            let links = match (
-                generator.uri_for_linkage(record, relationship)?,
-                generator.uri_for_related(record, relationship)?,
-            ) {
-                (None, None) => None,
-                (this, related) => Some(relationship::Links { this, related }),
+            let linked = match &linkage {
+                Linkage::ToMany(identifiers) => Some(identifiers.len()),
+                _ => None,
             };
 
             Ok((relationship.to_string(), Relationship {
                 data: Some(linkage),
-                links,
-                meta: None
+                links: relationship_links(relationship)?,
+                meta: relationship_meta(relationship, linked),
             }))
         })
         .collect::<Result<HashMap<_, _>, _>>()?;
@@ -180,12 +204,25 @@ pub(crate) fn make_record_resource<'sch>(
         .uri_for_resource(record)?
         .map(|this| resource::Links { this });
 
+    // Search excerpts go under `meta`, each kind present only when requested.
+    let excerpts = &record.excerpts;
+    let meta = (!excerpts.is_empty()).then(|| {
+        let mut meta = HashMap::new();
+        if !excerpts.snippets.is_empty() {
+            meta.insert("snippets".to_string(), json!(excerpts.snippets));
+        }
+        if !excerpts.highlights.is_empty() {
+            meta.insert("highlights".to_string(), json!(excerpts.highlights));
+        }
+        meta
+    });
+
     Ok(Resource {
         identifier,
         attributes: Some(attributes),
         relationships: Some(relationships),
         links,
-        meta: None,
+        meta,
     })
 }
 
@@ -199,6 +236,25 @@ pub fn make_linkage_resource(identifier: Identifier) -> Resource {
     }
 }
 
+/// Builds the response document of an atomic request, declaring the extension it applies.
+pub(crate) fn to_results_document(results: Vec<OperationResult>) -> Result<ResultsDocument, Error> {
+    let extension = ATOMIC_EXTENSION
+        .parse()
+        .map_err(
+            |error: http::uri::InvalidUri| Error::DocumentSerialisationError {
+                message: error.to_string(),
+            },
+        )?;
+
+    Ok(ResultsDocument {
+        results,
+        jsonapi: Some(ImplementationInfo {
+            ext: Some(vec![extension]),
+            ..implementation_info()
+        }),
+    })
+}
+
 fn implementation_info() -> ImplementationInfo {
     ImplementationInfo {
         version: Some("1.1".to_string()),
@@ -258,3 +314,98 @@ pub(crate) fn to_document<'sch: 'req, 'req>(
         included: carries_data.then_some(included),
     })
 }
+
+/// Renders the links to the first, last, previous and next pages of a collection of `total`
+/// records paged by `page`, each a copy of `uri` with its `page` parameters replaced. A page past
+/// either end is left out.
+pub(crate) fn pagination_links(
+    uri: &Uri,
+    page: &PageParameters,
+    total: u64,
+) -> Result<document::Pagination, Error> {
+    let query = uri
+        .query()
+        .unwrap_or_default()
+        .split('&')
+        .filter(|entry| {
+            let name = entry.split_once('=').map_or(*entry, |(name, _)| name);
+            !urlencoding::decode(name).is_ok_and(|name| name.starts_with("page["))
+        })
+        .filter(|entry| !entry.is_empty())
+        .collect::<Vec<_>>();
+    let link = |number: u64| -> Result<Option<Link>, Error> {
+        let mut entries = query.clone();
+        let number = format!("page[number]={number}");
+        let size = format!("page[size]={}", page.size);
+        entries.extend([number.as_str(), size.as_str()]);
+
+        format!("{}?{}", uri.path(), entries.join("&"))
+            .parse::<Uri>()
+            .map(|uri| Some(Link::Uri(uri)))
+            .map_err(|error| Error::LinkGenerationError {
+                message: error.to_string(),
+            })
+    };
+
+    let number = u64::from(page.number);
+    let last = total.div_ceil(u64::from(page.size)).max(1);
+
+    Ok(document::Pagination {
+        first: link(1)?,
+        last: link(last)?,
+        prev: if number > 1 {
+            link((number - 1).min(last))?
+        } else {
+            None
+        },
+        next: if number < last {
+            link(number + 1)?
+        } else {
+            None
+        },
+    })
+}
+
+/// Renders the results of an aggregation as a document with no primary data, the groups listed
+/// under `meta.aggregates`. Each group holds its `group` values by column, as `created_at:month`
+/// for a bucketed one, and each computed value by function and column, as `sum.price`.
+pub(crate) fn to_aggregate_document(
+    rows: Vec<AggregateRow>,
+    aggregate: &AggregateParameters,
+    uri: &Uri,
+) -> Document {
+    let groups = rows
+        .into_iter()
+        .map(|row| {
+            let mut group = serde_json::Map::new();
+            if !aggregate.group.is_empty() {
+                let values = aggregate
+                    .group
+                    .iter()
+                    .zip(row.group)
+                    .map(|(key, value)| (key.label(), Value::from(value)))
+                    .collect();
+                group.insert("group".to_string(), Value::Object(values));
+            }
+
+            for (aggregate, value) in aggregate.aggregates.iter().zip(row.values) {
+                if let Value::Object(values) = group
+                    .entry(aggregate.function.name())
+                    .or_insert_with(|| Value::Object(Default::default()))
+                {
+                    values.insert(aggregate.column.to_string(), Value::from(value));
+                }
+            }
+
+            Value::Object(group)
+        })
+        .collect::<Vec<_>>();
+
+    Document {
+        content: PrimaryContent::Absent {},
+        meta: Some(json!({ "aggregates": groups })),
+        jsonapi: Some(implementation_info()),
+        links: Some(document_links(uri)),
+        included: None,
+    }
+}